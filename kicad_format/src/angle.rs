@@ -0,0 +1,162 @@
+//! Rotation angles in degrees.
+//!
+//! KiCad writes angles as decimal degrees, so an [`Angle`] keeps fractional
+//! degrees as they were read. Items are not normalized when parsed, so that
+//! files round trip unchanged; use [`Angle::normalized`] or
+//! [`Angle::normalized_180`] to compare orientations the way KiCad does.
+//!
+//! ```
+//! use kicad_format::angle::Angle;
+//!
+//! let angle = Angle::from_degrees(-90.0);
+//!
+//! assert_eq!(angle.normalized(), Angle::from_degrees(270.0));
+//! assert_eq!((angle + Angle::from_degrees(360.0)).normalized_180(), angle);
+//! assert_eq!(Angle::from_degrees(22.5).to_string(), "22.5°");
+//! ```
+
+use std::{
+    f64::consts::PI,
+    fmt::{self, Display},
+    ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign},
+};
+
+use kicad_sexpr::Number;
+
+/// The number of significant digits KiCad writes angles with.
+const SIGNIFICANT_DIGITS: usize = 10;
+
+/// A rotation angle, stored in degrees.
+///
+/// Positive angles rotate counterclockwise as seen on the screen, matching
+/// KiCad.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+#[derive(Debug, PartialEq, PartialOrd, Default, Clone, Copy)]
+pub struct Angle(f64);
+
+impl Angle {
+    pub const ZERO: Self = Self(0.0);
+
+    pub const fn from_degrees(degrees: f64) -> Self {
+        Self(degrees)
+    }
+
+    pub fn from_radians(radians: f64) -> Self {
+        Self(radians * 180.0 / PI)
+    }
+
+    pub const fn degrees(self) -> f64 {
+        self.0
+    }
+
+    pub fn radians(self) -> f64 {
+        self.0 * PI / 180.0
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0 == 0.0
+    }
+
+    /// Returns the angle in the range `[0, 360)`, as KiCad normalizes text
+    /// and pad orientations.
+    pub fn normalized(self) -> Self {
+        let degrees = self.0.rem_euclid(360.0);
+
+        // `rem_euclid` can round tiny negative angles up to 360
+        Self(if degrees >= 360.0 { 0.0 } else { degrees })
+    }
+
+    /// Returns the angle in the range `(-180, 180]`, as KiCad normalizes
+    /// footprint orientations.
+    pub fn normalized_180(self) -> Self {
+        let degrees = self.normalized().0;
+
+        Self(if degrees > 180.0 {
+            degrees - 360.0
+        } else {
+            degrees
+        })
+    }
+}
+
+impl Display for Angle {
+    /// Formats the angle in degrees, like `22.5°`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match f.precision() {
+            Some(precision) => write!(f, "{:.precision$}°", self.0),
+            None => write!(f, "{}°", Number::from(*self)),
+        }
+    }
+}
+
+/// Angles are written to files in degrees with at most ten significant
+/// digits, and without a fraction when they are whole.
+impl From<Angle> for Number {
+    fn from(angle: Angle) -> Self {
+        let degrees = angle.0;
+
+        if degrees.fract() == 0.0 && degrees.abs() < i64::MAX as f64 {
+            return Number::Integer(degrees as i64);
+        }
+
+        // Rounds to significant digits through the exponent notation, which
+        // the shortest representation of the result then matches
+        let rounded = format!("{degrees:.*e}", SIGNIFICANT_DIGITS - 1)
+            .parse()
+            .unwrap_or(degrees);
+
+        Number::Float(rounded, None)
+    }
+}
+
+/// Numbers in files are degrees.
+impl From<Number> for Angle {
+    fn from(number: Number) -> Self {
+        Self(number.to_f64())
+    }
+}
+
+impl Add for Angle {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self(self.0 + rhs.0)
+    }
+}
+
+impl AddAssign for Angle {
+    fn add_assign(&mut self, rhs: Self) {
+        self.0 += rhs.0;
+    }
+}
+
+impl Sub for Angle {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self(self.0 - rhs.0)
+    }
+}
+
+impl SubAssign for Angle {
+    fn sub_assign(&mut self, rhs: Self) {
+        self.0 -= rhs.0;
+    }
+}
+
+impl Neg for Angle {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self(-self.0)
+    }
+}
+
+impl Mul<f64> for Angle {
+    type Output = Self;
+
+    fn mul(self, rhs: f64) -> Self {
+        Self(self.0 * rhs)
+    }
+}
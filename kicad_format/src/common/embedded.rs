@@ -0,0 +1,158 @@
+//! Files embedded in boards, footprints, schematics and symbols, since
+//! KiCad 9.
+
+use crate::{
+    convert::{writes, Extra, FromSexpr, ListBuilder, Parser, SexprListExt, ToSexpr},
+    simple_maybe_from_sexpr, simple_to_from_string,
+    version::Syntax,
+    KiCadParseError,
+};
+
+/// The number of characters KiCad writes the data of a file in per line.
+const DATA_LINE_LENGTH: usize = 76;
+
+/// A file stored inside another file, such as a font used by the board's
+/// text or a datasheet.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct EmbeddedFile {
+    pub name: String,
+    pub kind: EmbeddedFileKind,
+    /// The contents of the file, compressed with zstd and encoded with
+    /// base64.
+    pub data: Option<String>,
+    /// A hash of the uncompressed contents, used to detect files that
+    /// changed.
+    pub checksum: Option<String>,
+    pub extra: Extra,
+}
+
+impl FromSexpr for EmbeddedFile {
+    fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
+        parser.expect_symbol_matching("file")?;
+
+        let mut parser = parser.keyed();
+
+        let name = parser.expect_string_with_name("name")?;
+        let kind = parser
+            .expect_symbol_with_name("type")?
+            .parse::<EmbeddedFileKind>()?;
+        // The data is written between bars over several lines, like
+        // `|KLUv...` up to `...AAA|`, which reads as one symbol per line
+        let data = parser.maybe_list_with_name("data", |p| {
+            let lines = p.expect_many_symbols()?;
+
+            Ok(lines.concat().trim_matches('|').to_string())
+        })?;
+        let checksum = parser.maybe_string_with_name("checksum")?;
+
+        let extra = parser.expect_end_with_extra()?;
+
+        Ok(Self {
+            name,
+            kind,
+            data,
+            checksum,
+            extra,
+        })
+    }
+}
+
+simple_maybe_from_sexpr!(EmbeddedFile, file);
+
+impl ToSexpr for EmbeddedFile {
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("file", &self.extra, |s| {
+            s.append_string_with_name("name", &self.name);
+            s.append_symbol_with_name("type", self.kind);
+            if let Some(data) = &self.data {
+                s.append_list("data", |s| {
+                    let data = format!("|{data}|");
+                    let mut rest = data.as_str();
+
+                    // The opening bar does not count towards the first line
+                    let mut line_length = DATA_LINE_LENGTH + 1;
+
+                    while !rest.is_empty() {
+                        let end = rest
+                            .char_indices()
+                            .nth(line_length)
+                            .map_or(rest.len(), |(index, _)| index);
+                        let (line, remaining) = rest.split_at(end);
+
+                        // Keep the closing bar on the last line
+                        if remaining == "|" {
+                            s.append_symbol(rest);
+                            break;
+                        }
+
+                        s.append_symbol(line);
+                        rest = remaining;
+                        line_length = DATA_LINE_LENGTH;
+                    }
+                });
+            }
+            s.append_maybe_string_with_name("checksum", self.checksum.as_ref());
+        });
+    }
+}
+
+/// What an [`EmbeddedFile`] is used for.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum EmbeddedFileKind {
+    Font,
+    Model,
+    Datasheet,
+    Worksheet,
+    Other,
+}
+
+simple_to_from_string! {
+    EmbeddedFileKind,
+    font <-> Font,
+    model <-> Model,
+    datasheet <-> Datasheet,
+    worksheet <-> Worksheet,
+    other <-> Other,
+}
+
+/// Reads the `embedded_files` list of a file, which holds the files that are
+/// embedded in it.
+pub(crate) fn parse_embedded_files(
+    parser: &mut Parser,
+) -> Result<Option<Vec<EmbeddedFile>>, KiCadParseError> {
+    parser.maybe_since(Syntax::EmbeddedFiles, "embedded_files", |parser| {
+        parser.maybe_list_with_name("embedded_files", |p| {
+            let files = p.expect_many::<EmbeddedFile>()?;
+
+            Ok(files)
+        })
+    })
+}
+
+pub(crate) fn write_embedded_files(s: &mut ListBuilder, files: &Option<Vec<EmbeddedFile>>) {
+    if !writes(Syntax::EmbeddedFiles) {
+        return;
+    }
+
+    if let Some(files) = files {
+        s.append_list("embedded_files", |s| s.append_many(files));
+    }
+}
+
+/// Reads the `embedded_fonts` setting of a file, which says whether the fonts
+/// it uses are embedded in it.
+pub(crate) fn parse_embedded_fonts(parser: &mut Parser) -> Result<Option<bool>, KiCadParseError> {
+    parser.maybe_since(Syntax::EmbeddedFiles, "embedded_fonts", |parser| {
+        parser.maybe_bool_with_name("embedded_fonts")
+    })
+}
+
+pub(crate) fn write_embedded_fonts(s: &mut ListBuilder, embedded_fonts: Option<bool>) {
+    if writes(Syntax::EmbeddedFiles) {
+        s.append_maybe_bool_with_name("embedded_fonts", embedded_fonts);
+    }
+}
@@ -2,21 +2,22 @@
 //!
 //! Used both in footprint library files and board files.
 
-use kicad_sexpr::{Sexpr, SexprList};
+use kicad_sexpr::SexprList;
 
 use self::{
     shape::FootprintShape,
-    text::{FootprintText, FootprintTextBox},
+    text::{FootprintProperty, FootprintText, FootprintTextBox},
 };
 use super::{
-    pad::Pad, symbol::LibraryId, zone::Zone, Group, Image, LayerId, Position, Property, Uuid, Vec3D,
+    embedded::EmbeddedFile, pad::Pad, symbol::LibraryId, zone::Zone, Group, Image, LayerId,
+    Position, Rotation3D, Uuid, Vec3D,
 };
 use crate::{
     convert::{
-        FromSexpr, MaybeFromSexpr, Parser, SexprListExt, ToSexpr, ToSexprWithName,
-        VecToMaybeSexprVec,
+        Extra, FieldOrder, FromSexpr, ListBuilder, MaybeFromSexpr, Parser, SexprListExt, ToSexpr,
     },
     footprint_library::FootprintLibraryFile,
+    length::Length,
     simple_maybe_from_sexpr, KiCadParseError, SexprKind,
 };
 
@@ -28,31 +29,74 @@ pub mod text;
 /// TODO: move to pcb module
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, FromSexpr, ToSexpr)]
+#[sexpr(name = "footprint")]
 pub struct FootprintInlined {
+    #[sexpr(positional, string)]
     pub library_link: LibraryId,
+    #[sexpr(locked)]
     pub locked: bool,
+    #[sexpr(versioned_flag)]
     pub placed: bool,
+    #[sexpr(string)]
     pub layer: LayerId,
+    #[sexpr(id)]
     pub tstamp: Uuid,
+    #[sexpr(name = "at")]
     pub position: Position,
+    #[sexpr(name = "descr")]
     pub description: Option<String>,
     pub tags: Option<String>,
-    pub properties: Vec<Property>,
+    pub properties: Vec<FootprintProperty>,
+    /// The component classes assigned to the footprint by its symbol, since
+    /// KiCad 9.
+    #[sexpr(with = "component_classes", since = "ComponentClasses")]
+    pub component_classes: Option<Vec<String>>,
     pub path: Option<String>,
-    pub solder_mask_margin: Option<f32>,
-    pub solder_paste_margin: Option<f32>,
-    pub solder_paste_ratio: Option<f32>,
-    pub clearance: Option<f32>,
+    /// The name of the schematic sheet the footprint's symbol is on. Before
+    /// KiCad 8, this is the `Sheetname` property instead.
+    #[sexpr(name = "sheetname")]
+    pub sheet_name: Option<String>,
+    /// The file of the schematic sheet the footprint's symbol is on. Before
+    /// KiCad 8, this is the `Sheetfile` property instead.
+    #[sexpr(name = "sheetfile")]
+    pub sheet_file: Option<String>,
+    pub solder_mask_margin: Option<Length>,
+    pub solder_paste_margin: Option<Length>,
+    pub solder_paste_ratio: Option<f64>,
+    pub clearance: Option<Length>,
+    #[sexpr(try_from = "u8")]
     pub zone_connect: Option<ZoneConnectKind>,
     pub attributes: Option<FootprintAttributes>,
+    #[sexpr(string)]
     pub private_layers: Option<Vec<LayerId>>,
+    #[sexpr(with = "net_tie_pad_groups")]
     pub net_tie_pad_groups: Option<Vec<Vec<String>>>,
+    /// Whether pads sharing a number are connected by a jumper, since KiCad
+    /// 9.
+    #[sexpr(since = "Jumpers")]
+    pub duplicate_pad_numbers_are_jumpers: Option<bool>,
+    /// The groups of pads connected by a jumper, since KiCad 9.
+    #[sexpr(with = "jumper_groups", since = "Jumpers")]
+    pub jumper_pad_groups: Option<Vec<Vec<String>>>,
     pub graphics_items: Vec<FootprintGraphicsItem>,
     pub pads: Vec<Pad>,
     pub keep_out_zones: Vec<Zone>,
     pub groups: Vec<Group>,
+    /// Whether the fonts used by the footprint are embedded in it, since
+    /// KiCad 9.
+    #[sexpr(since = "EmbeddedFiles")]
+    pub embedded_fonts: Option<bool>,
+    #[sexpr(since = "EmbeddedFiles")]
+    pub embedded_files: Option<Vec<EmbeddedFile>>,
     pub models: Vec<Model>,
+    /// The order the fields were read in, so that the footprint is written
+    /// back the same way. Empty for footprints that were not parsed.
+    #[cfg_attr(feature = "serde", serde(default))]
+    #[sexpr(field_order)]
+    pub field_order: FieldOrder,
+    #[sexpr(extra)]
+    pub extra: Extra,
 }
 
 impl FootprintInlined {
@@ -72,6 +116,9 @@ impl FootprintInlined {
         self.attributes = footprint_library_file.attributes.clone();
         self.private_layers = footprint_library_file.private_layers.clone();
         self.net_tie_pad_groups = footprint_library_file.net_tie_pad_groups.clone();
+        self.duplicate_pad_numbers_are_jumpers =
+            footprint_library_file.duplicate_pad_numbers_are_jumpers;
+        self.jumper_pad_groups = footprint_library_file.jumper_pad_groups.clone();
 
         // Remove shapes and images
         self.graphics_items.retain(|g| {
@@ -135,6 +182,8 @@ impl FootprintInlined {
         self.pads = new_pads;
         self.keep_out_zones = footprint_library_file.keep_out_zones.clone();
         self.groups = footprint_library_file.groups.clone();
+        self.embedded_fonts = footprint_library_file.embedded_fonts;
+        self.embedded_files = footprint_library_file.embedded_files.clone();
         self.models = footprint_library_file.models.clone();
     }
 
@@ -149,152 +198,109 @@ impl FootprintInlined {
     }
 }
 
-impl FromSexpr for FootprintInlined {
-    fn from_sexpr(mut parser: Parser) -> Result<Self, crate::KiCadParseError> {
-        parser.expect_symbol_matching("footprint")?;
-
-        let library_link = parser.expect_string()?.parse::<LibraryId>()?;
-        let locked = parser.maybe_symbol_matching("locked");
-        let placed = parser.maybe_symbol_matching("placed");
-        let layer = parser.expect_string_with_name("layer")?.parse()?;
-        let tstamp = parser.expect_with_name::<Uuid>("tstamp")?;
-        let position = parser.expect::<Position>()?;
-        let description = parser.maybe_string_with_name("descr")?;
-        let tags = parser.maybe_string_with_name("tags")?;
-        let properties = parser.expect_many::<Property>()?;
-        let path = parser.maybe_string_with_name("path")?;
-        let solder_mask_margin = parser.maybe_number_with_name("solder_mask_margin")?;
-        let solder_paste_margin = parser.maybe_number_with_name("solder_paste_margin")?;
-        let solder_paste_ratio = parser.maybe_number_with_name("solder_paste_ratio")?;
-        let clearance = parser.maybe_number_with_name("clearance")?;
-        let zone_connect = parser
-            .maybe_number_with_name("zone_connect")?
-            .map(|n| n as u8)
-            .map(ZoneConnectKind::try_from)
-            .transpose()?;
-        let attributes = parser.maybe::<FootprintAttributes>()?;
-        let private_layers = parser
-            .maybe_list_with_name("private_layers")
-            .map(|mut a| {
-                a.expect_many_strings()?
-                    .into_iter()
-                    .map(|s| s.parse())
-                    .collect()
-            })
-            .transpose()?;
-        let net_tie_pad_groups = parser
-            .maybe_list_with_name("net_tie_pad_groups")
-            .map(|mut a| {
-                Ok::<_, KiCadParseError>(
-                    a.expect_many_strings()?
-                        .into_iter()
-                        .map(|s| s.split(',').map(|s| s.to_owned()).collect::<Vec<String>>())
-                        .collect(),
-                )
-            })
-            .transpose()?;
-        let graphics_items = parser.expect_many::<FootprintGraphicsItem>()?;
-        let pads = parser.expect_many::<Pad>()?;
-        let keep_out_zones = parser.expect_many::<Zone>()?;
-        let groups = parser.expect_many::<Group>()?;
-        let models = parser.expect_many::<Model>()?;
-
-        parser.expect_end()?;
+/// The component classes of a footprint are written as
+/// `(component_classes (class "A") (class "B"))`.
+mod component_classes {
+    use crate::{
+        convert::{ListBuilder, Parser},
+        KiCadParseError,
+    };
+
+    pub fn parse(parser: &mut Parser, name: &str) -> Result<Option<Vec<String>>, KiCadParseError> {
+        parser.maybe_list_with_name(name, |a| {
+            let mut classes = Vec::new();
+            while let Some(class) = a.maybe_string_with_name("class")? {
+                classes.push(class);
+            }
 
-        Ok(Self {
-            library_link,
-            locked,
-            placed,
-            layer,
-            tstamp,
-            position,
-            description,
-            tags,
-            properties,
-            path,
-            solder_mask_margin,
-            solder_paste_margin,
-            solder_paste_ratio,
-            clearance,
-            zone_connect,
-            attributes,
-            private_layers,
-            net_tie_pad_groups,
-            graphics_items,
-            pads,
-            keep_out_zones,
-            groups,
-            models,
+            Ok(classes)
         })
     }
+
+    pub fn write(s: &mut ListBuilder, name: &str, classes: &Option<Vec<String>>) {
+        if let Some(classes) = classes {
+            s.append_list(name, |s| {
+                for class in classes {
+                    s.append_string_with_name("class", class);
+                }
+            });
+        }
+    }
+}
+
+/// The groups of pads a net tie footprint connects. KiCad writes each group
+/// as one string of pad numbers, like `"1, 2"`.
+pub(crate) mod net_tie_pad_groups {
+    use crate::{
+        convert::{ListBuilder, Parser},
+        KiCadParseError,
+    };
+
+    pub fn parse(
+        parser: &mut Parser,
+        name: &str,
+    ) -> Result<Option<Vec<Vec<String>>>, KiCadParseError> {
+        parser.maybe_list_with_name(name, |a| {
+            Ok(a.expect_many_strings()?
+                .into_iter()
+                .map(|group| {
+                    group
+                        .split(|c: char| c == ',' || c.is_whitespace())
+                        .filter(|number| !number.is_empty())
+                        .map(|number| number.to_owned())
+                        .collect()
+                })
+                .collect())
+        })
+    }
+
+    pub fn write(s: &mut ListBuilder, name: &str, groups: &Option<Vec<Vec<String>>>) {
+        if let Some(groups) = groups {
+            s.append_list(name, |s| {
+                for group in groups {
+                    s.append_string(group.join(", "));
+                }
+            });
+        }
+    }
 }
 
-simple_maybe_from_sexpr!(FootprintInlined, footprint);
-
-impl ToSexpr for FootprintInlined {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "footprint",
-            [
-                vec![
-                    Some(self.library_link.to_sexpr()),
-                    self.locked.then(|| Sexpr::symbol("locked")),
-                    self.placed.then(|| Sexpr::symbol("placed")),
-                    Some(Sexpr::string_with_name("layer", self.layer)),
-                    Some(self.tstamp.to_sexpr_with_name("tstamp")),
-                    Some(self.position.to_sexpr()),
-                    self.description
-                        .as_ref()
-                        .map(|s| Sexpr::string_with_name("descr", s)),
-                    self.tags
-                        .as_ref()
-                        .map(|s| Sexpr::string_with_name("tags", s)),
-                ],
-                self.properties.into_sexpr_vec(),
-                vec![
-                    self.path
-                        .as_ref()
-                        .map(|s| Sexpr::string_with_name("path", s)),
-                    self.solder_mask_margin
-                        .map(|n| Sexpr::number_with_name("solder_mask_margin", n)),
-                    self.solder_paste_margin
-                        .map(|n| Sexpr::number_with_name("solder_paste_margin", n)),
-                    self.solder_paste_ratio
-                        .map(|n| Sexpr::number_with_name("solder_paste_ratio", n)),
-                    self.clearance
-                        .map(|n| Sexpr::number_with_name("clearance", n)),
-                    self.zone_connect
-                        .map(|n| Sexpr::number_with_name("zone_connect", n as u8 as f32)),
-                    self.attributes.as_ref().map(ToSexpr::to_sexpr),
-                    self.private_layers.as_ref().map(|l| {
-                        Sexpr::list_with_name(
-                            "private_layers",
-                            l.iter()
-                                .map(Clone::clone)
-                                .map(Sexpr::string)
-                                .map(Option::Some)
-                                .collect::<Vec<_>>(),
-                        )
-                    }),
-                    self.net_tie_pad_groups.as_ref().map(|a| {
-                        Sexpr::list_with_name(
-                            "net_tie_pad_groups",
-                            a.iter()
-                                .map(|group| group.join(","))
-                                .map(Sexpr::string)
-                                .map(Option::Some)
-                                .collect::<Vec<_>>(),
-                        )
-                    }),
-                ],
-                self.graphics_items.into_sexpr_vec(),
-                self.pads.into_sexpr_vec(),
-                self.keep_out_zones.into_sexpr_vec(),
-                self.groups.into_sexpr_vec(),
-                self.models.into_sexpr_vec(),
-            ]
-            .concat(),
-        )
+/// The groups of pads or pins connected by a jumper, such as
+/// `jumper_pad_groups`. Unlike net tie groups, KiCad writes each group as an
+/// unnamed list of numbers, like `("1" "2")`.
+pub(crate) mod jumper_groups {
+    use crate::{
+        convert::{ListBuilder, Parser},
+        KiCadParseError,
+    };
+
+    pub fn parse(
+        parser: &mut Parser,
+        name: &str,
+    ) -> Result<Option<Vec<Vec<String>>>, KiCadParseError> {
+        parser.maybe_list_with_name(name, |a| {
+            let mut groups = Vec::new();
+            while let Some(mut group) = a.maybe_list() {
+                groups.push(group.expect_many_strings()?);
+                group.expect_end()?;
+            }
+
+            Ok(groups)
+        })
+    }
+
+    pub fn write(s: &mut ListBuilder, name: &str, groups: &Option<Vec<Vec<String>>>) {
+        if let Some(groups) = groups {
+            s.append_list(name, |s| {
+                for group in groups {
+                    s.append_unnamed_list(|s| {
+                        for number in group {
+                            s.append_string(number);
+                        }
+                    });
+                }
+            });
+        }
     }
 }
 
@@ -332,6 +338,12 @@ impl TryFrom<u8> for ZoneConnectKind {
     }
 }
 
+impl From<ZoneConnectKind> for u8 {
+    fn from(value: ZoneConnectKind) -> Self {
+        value as u8
+    }
+}
+
 // ############################################################################
 
 /// Attributes of the footprint (ex. SMD, through-hole, included in BOM, etc.)
@@ -346,12 +358,15 @@ pub struct FootprintAttributes {
     pub exclude_from_bom: bool,
     pub allow_missing_courtyard: bool,
     pub allow_solder_mask_bridges: bool,
+    pub extra: Extra,
 }
 
 impl FromSexpr for FootprintAttributes {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("attr")?;
 
+        let mut parser = parser.keyed();
+
         let smd = parser.maybe_symbol_matching("smd");
         let through_hole = parser.maybe_symbol_matching("through_hole");
         let board_only = parser.maybe_symbol_matching("board_only");
@@ -360,7 +375,7 @@ impl FromSexpr for FootprintAttributes {
         let allow_missing_courtyard = parser.maybe_symbol_matching("allow_missing_courtyard");
         let allow_solder_mask_bridges = parser.maybe_symbol_matching("allow_soldermask_bridges");
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             smd,
@@ -370,6 +385,7 @@ impl FromSexpr for FootprintAttributes {
             exclude_from_bom,
             allow_missing_courtyard,
             allow_solder_mask_bridges,
+            extra,
         })
     }
 }
@@ -377,23 +393,16 @@ impl FromSexpr for FootprintAttributes {
 simple_maybe_from_sexpr!(FootprintAttributes, attr);
 
 impl ToSexpr for FootprintAttributes {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "attr",
-            [
-                self.smd.then(|| Sexpr::symbol("smd")),
-                self.through_hole.then(|| Sexpr::symbol("through_hole")),
-                self.board_only.then(|| Sexpr::symbol("board_only")),
-                self.exclude_from_pos_files
-                    .then(|| Sexpr::symbol("exclude_from_pos_files")),
-                self.exclude_from_bom
-                    .then(|| Sexpr::symbol("exclude_from_bom")),
-                self.allow_missing_courtyard
-                    .then(|| Sexpr::symbol("allow_missing_courtyard")),
-                self.allow_solder_mask_bridges
-                    .then(|| Sexpr::symbol("allow_soldermask_bridges")),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("attr", &self.extra, |s| {
+            s.append_symbol_if("smd", self.smd);
+            s.append_symbol_if("through_hole", self.through_hole);
+            s.append_symbol_if("board_only", self.board_only);
+            s.append_symbol_if("exclude_from_pos_files", self.exclude_from_pos_files);
+            s.append_symbol_if("exclude_from_bom", self.exclude_from_bom);
+            s.append_symbol_if("allow_missing_courtyard", self.allow_missing_courtyard);
+            s.append_symbol_if("allow_soldermask_bridges", self.allow_solder_mask_bridges);
+        });
     }
 }
 
@@ -408,7 +417,7 @@ impl ToSexpr for FootprintAttributes {
 pub enum FootprintGraphicsItem {
     Image(Image),
     Text(FootprintText),
-    TextBox(FootprintTextBox),
+    TextBox(Box<FootprintTextBox>),
     Shape(FootprintShape),
 }
 
@@ -423,9 +432,8 @@ impl FromSexpr for FootprintGraphicsItem {
         match symbol {
             "image" => Image::from_sexpr(parser).map(FootprintGraphicsItem::Image),
             "fp_text" => FootprintText::from_sexpr(parser).map(FootprintGraphicsItem::Text),
-            "fp_text_box" => {
-                FootprintTextBox::from_sexpr(parser).map(FootprintGraphicsItem::TextBox)
-            }
+            "fp_text_box" => FootprintTextBox::from_sexpr(parser)
+                .map(|text_box| FootprintGraphicsItem::TextBox(Box::new(text_box))),
             "fp_line" => FootprintShape::from_sexpr(parser).map(FootprintGraphicsItem::Shape),
             "fp_rect" => FootprintShape::from_sexpr(parser).map(FootprintGraphicsItem::Shape),
             "fp_circle" => FootprintShape::from_sexpr(parser).map(FootprintGraphicsItem::Shape),
@@ -458,12 +466,12 @@ impl MaybeFromSexpr for FootprintGraphicsItem {
 }
 
 impl ToSexpr for FootprintGraphicsItem {
-    fn to_sexpr(&self) -> Sexpr {
+    fn write_sexpr(&self, s: &mut ListBuilder) {
         match self {
-            FootprintGraphicsItem::Image(image) => image.to_sexpr(),
-            FootprintGraphicsItem::Text(text) => text.to_sexpr(),
-            FootprintGraphicsItem::TextBox(text_box) => text_box.to_sexpr(),
-            FootprintGraphicsItem::Shape(shape) => shape.to_sexpr(),
+            FootprintGraphicsItem::Image(image) => s.append(image),
+            FootprintGraphicsItem::Text(text) => s.append(text),
+            FootprintGraphicsItem::TextBox(text_box) => s.append(text_box.as_ref()),
+            FootprintGraphicsItem::Shape(shape) => s.append(shape),
         }
     }
 }
@@ -477,24 +485,27 @@ impl ToSexpr for FootprintGraphicsItem {
 pub struct Model {
     pub file: String,
     pub hide: bool,
-    pub opacity: Option<f32>,
+    pub opacity: Option<f64>,
     pub offset: Vec3D,
     pub scale: Vec3D,
-    pub rotate: Vec3D,
+    pub rotate: Rotation3D,
+    pub extra: Extra,
 }
 
 impl FromSexpr for Model {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("model")?;
 
+        let mut parser = parser.keyed();
+
         let file = parser.expect_string()?;
         let hide = parser.maybe_symbol_matching("hide");
         let opacity = parser.maybe_number_with_name("opacity")?;
-        let offset = parser.expect_list_with_name("offset")?.expect::<Vec3D>()?;
-        let scale = parser.expect_list_with_name("scale")?.expect::<Vec3D>()?;
-        let rotate = parser.expect_list_with_name("rotate")?.expect::<Vec3D>()?;
+        let offset = parser.expect_list_with_name("offset", |p| p.expect::<Vec3D>())?;
+        let scale = parser.expect_list_with_name("scale", |p| p.expect::<Vec3D>())?;
+        let rotate = parser.expect_list_with_name("rotate", |p| p.expect::<Rotation3D>())?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             file,
@@ -503,6 +514,7 @@ impl FromSexpr for Model {
             offset,
             scale,
             rotate,
+            extra,
         })
     }
 }
@@ -510,26 +522,14 @@ impl FromSexpr for Model {
 simple_maybe_from_sexpr!(Model, model);
 
 impl ToSexpr for Model {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "model",
-            [
-                Some(Sexpr::string(&self.file)),
-                self.hide.then(|| Sexpr::symbol("hide")),
-                self.opacity.map(|o| Sexpr::number_with_name("opacity", o)),
-                Some(Sexpr::list_with_name(
-                    "offset",
-                    [Some(self.offset.to_sexpr())],
-                )),
-                Some(Sexpr::list_with_name(
-                    "scale",
-                    [Some(self.scale.to_sexpr())],
-                )),
-                Some(Sexpr::list_with_name(
-                    "rotate",
-                    [Some(self.rotate.to_sexpr())],
-                )),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("model", &self.extra, |s| {
+            s.append_string(&self.file);
+            s.append_symbol_if("hide", self.hide);
+            s.append_maybe_number_with_name("opacity", self.opacity);
+            s.append_list("offset", |s| s.append(&self.offset));
+            s.append_list("scale", |s| s.append(&self.scale));
+            s.append_list("rotate", |s| s.append(&self.rotate));
+        });
     }
 }
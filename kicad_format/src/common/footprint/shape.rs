@@ -1,10 +1,12 @@
 //! All the shape types allowed within a footprint.
 
-use kicad_sexpr::{Sexpr, SexprList};
+use kicad_sexpr::SexprList;
 
 use crate::{
     common::{CoordinatePointList, LayerId, SimpleFillMode, Stroke, Uuid, Vec2D},
-    convert::{FromSexpr, MaybeFromSexpr, Parser, SexprListExt, ToSexpr, ToSexprWithName},
+    convert::{
+        Extra, FieldOrder, FromSexpr, ListBuilder, MaybeFromSexpr, Parser, SexprListExt, ToSexpr,
+    },
     KiCadParseError,
 };
 
@@ -18,6 +20,8 @@ pub struct FootprintShape {
     pub stroke: Stroke,
     pub layer: LayerId,
     pub tstamp: Uuid,
+    pub field_order: FieldOrder,
+    pub extra: Extra,
 }
 
 /// All the different types of shapes allowed within a footprint.
@@ -45,7 +49,10 @@ impl FromSexpr for FootprintShape {
             "fp_curve",
         ])?;
 
-        let locked = parser.maybe_symbol_matching("locked");
+        let mut parser = parser.keyed();
+        let field_order = parser.field_order();
+
+        let locked = parser.maybe_locked()?;
 
         let (kind, stroke) = match name.as_str() {
             "fp_line" => {
@@ -100,7 +107,7 @@ impl FromSexpr for FootprintShape {
                 )
             }
             "fp_poly" => {
-                let points = parser.expect::<CoordinatePointList>()?;
+                let points = parser.expect_points()?;
                 let stroke = parser.expect::<Stroke>()?;
                 let fill = parser
                     .expect_symbol_with_name("fill")?
@@ -112,7 +119,7 @@ impl FromSexpr for FootprintShape {
                 )
             }
             "fp_curve" => {
-                let points = parser.expect::<CoordinatePointList>().and_then(|v| {
+                let points = parser.expect_points().and_then(|v| {
                     v.try_into()
                         .map_err(|v: Vec<_>| KiCadParseError::IncorrectNumberOfPoints {
                             expected: 4,
@@ -130,7 +137,9 @@ impl FromSexpr for FootprintShape {
         };
 
         let layer = parser.expect_string_with_name("layer")?.parse()?;
-        let tstamp = parser.expect_with_name::<Uuid>("tstamp")?;
+        let tstamp = parser.expect_id()?;
+
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             locked,
@@ -138,6 +147,8 @@ impl FromSexpr for FootprintShape {
             stroke,
             layer,
             tstamp,
+            field_order,
+            extra,
         })
     }
 }
@@ -160,69 +171,44 @@ impl MaybeFromSexpr for FootprintShape {
 }
 
 impl ToSexpr for FootprintShape {
-    fn to_sexpr(&self) -> Sexpr {
-        let (name, elements, fill) = match &self.kind {
-            FootprintShapeKind::Line(line) => (
-                "fp_line",
-                vec![
-                    line.start.to_sexpr_with_name("start"),
-                    line.end.to_sexpr_with_name("end"),
-                ],
-                None,
-            ),
-            FootprintShapeKind::Rectangle(rect) => (
-                "fp_rect",
-                vec![
-                    rect.start.to_sexpr_with_name("start"),
-                    rect.end.to_sexpr_with_name("end"),
-                ],
-                Some(rect.fill),
-            ),
-            FootprintShapeKind::Circle(circle) => (
-                "fp_circle",
-                vec![
-                    circle.center.to_sexpr_with_name("center"),
-                    circle.end.to_sexpr_with_name("end"),
-                ],
-                Some(circle.fill),
-            ),
-            FootprintShapeKind::Arc(arc) => (
-                "fp_arc",
-                vec![
-                    arc.start.to_sexpr_with_name("start"),
-                    arc.midpoint.to_sexpr_with_name("mid"),
-                    arc.end.to_sexpr_with_name("end"),
-                ],
-                None,
-            ),
-            FootprintShapeKind::Polygon(ploy) => {
-                ("fp_poly", vec![ploy.points.to_sexpr()], Some(ploy.fill))
-            }
-            FootprintShapeKind::Curve(bezier) => (
-                "fp_curve",
-                bezier
-                    .points
-                    .iter()
-                    .map(|p| p.to_sexpr())
-                    .collect::<Vec<_>>(),
-                None,
-            ),
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        let (name, fill) = match &self.kind {
+            FootprintShapeKind::Line(_) => ("fp_line", None),
+            FootprintShapeKind::Rectangle(rect) => ("fp_rect", Some(rect.fill)),
+            FootprintShapeKind::Circle(circle) => ("fp_circle", Some(circle.fill)),
+            FootprintShapeKind::Arc(_) => ("fp_arc", None),
+            FootprintShapeKind::Polygon(poly) => ("fp_poly", Some(poly.fill)),
+            FootprintShapeKind::Curve(_) => ("fp_curve", None),
         };
 
-        Sexpr::list_with_name(
-            name,
-            [
-                &[self.locked.then(|| Sexpr::symbol("locked"))][..],
-                &elements.into_iter().map(Option::Some).collect::<Vec<_>>(),
-                &[
-                    Some(self.stroke.to_sexpr()),
-                    fill.map(|f| Sexpr::symbol_with_name("fill", f)),
-                    Some(Sexpr::string_with_name("layer", self.layer)),
-                    Some(self.tstamp.to_sexpr_with_name("tstamp")),
-                ][..],
-            ]
-            .concat(),
-        )
+        s.append_list_in_order(name, &self.field_order, &self.extra, |s| {
+            s.append_locked(self.locked);
+            match &self.kind {
+                FootprintShapeKind::Line(line) => {
+                    s.append_with_name("start", &line.start);
+                    s.append_with_name("end", &line.end);
+                }
+                FootprintShapeKind::Rectangle(rect) => {
+                    s.append_with_name("start", &rect.start);
+                    s.append_with_name("end", &rect.end);
+                }
+                FootprintShapeKind::Circle(circle) => {
+                    s.append_with_name("center", &circle.center);
+                    s.append_with_name("end", &circle.end);
+                }
+                FootprintShapeKind::Arc(arc) => {
+                    s.append_with_name("start", &arc.start);
+                    s.append_with_name("mid", &arc.midpoint);
+                    s.append_with_name("end", &arc.end);
+                }
+                FootprintShapeKind::Polygon(poly) => s.append(&poly.points),
+                FootprintShapeKind::Curve(bezier) => s.append_many(&bezier.points),
+            }
+            s.append(&self.stroke);
+            s.append_maybe_symbol_with_name("fill", fill);
+            s.append_string_with_name("layer", self.layer);
+            s.append_id(&self.tstamp);
+        });
     }
 }
 
@@ -1,13 +1,125 @@
 //! Footprint text and text box definitions.
 
-use kicad_sexpr::Sexpr;
-
 use crate::{
-    common::{CoordinatePointList, LayerId, Stroke, TextEffects, Uuid, Vec2D},
-    convert::{FromSexpr, Parser, SexprListExt, ToSexpr, ToSexprWithName},
-    simple_to_from_string, KiCadParseError,
+    angle::Angle,
+    common::{LayerId, Stroke, TextEffects, TextRenderCache, Uuid, Vec2D},
+    convert::{writes, Extra, FieldOrder, FromSexpr, ListBuilder, Parser, SexprListExt, ToSexpr},
+    length::Length,
+    simple_maybe_from_sexpr, simple_to_from_string,
+    version::Syntax,
+    KiCadParseError,
 };
 
+/// A property of a footprint, such as its reference or value.
+///
+/// Before KiCad 8, footprint properties are plain key-value pairs and the
+/// reference and value are [`FootprintText`] items. Since KiCad 8 every
+/// property is a text item of its own, with a position, layer and effects.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct FootprintProperty {
+    pub key: String,
+    pub value: String,
+    pub position: Option<FootprintTextPosition>,
+    pub layer: Option<LayerId>,
+    pub knockout: bool,
+    pub hide: bool,
+    pub tstamp: Option<Uuid>,
+    pub effects: Option<TextEffects>,
+    pub render_cache: Option<TextRenderCache>,
+    pub field_order: FieldOrder,
+    pub extra: Extra,
+}
+
+impl FootprintProperty {
+    /// Creates a property that is a plain key-value pair.
+    pub fn new(key: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            value: value.into(),
+            position: None,
+            layer: None,
+            knockout: false,
+            hide: false,
+            tstamp: None,
+            effects: None,
+            render_cache: None,
+            field_order: FieldOrder::default(),
+            extra: Extra::default(),
+        }
+    }
+}
+
+impl FromSexpr for FootprintProperty {
+    fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
+        parser.expect_symbol_matching("property")?;
+
+        let mut parser = parser.keyed();
+        let field_order = parser.field_order();
+
+        let key = parser.expect_string()?;
+        let value = parser.expect_string()?;
+        let mut position = parser.maybe::<FootprintTextPosition>()?;
+        if let Some(position) = &mut position {
+            position.unlocked |= parser.maybe_flag("unlocked")?;
+        }
+        let (layer, knockout) = parser
+            .maybe_list_with_name("layer", |list| {
+                let layer = list.expect_string()?.parse()?;
+                let knockout = list.maybe_symbol_matching("knockout");
+                Ok((Some(layer), knockout))
+            })?
+            .unwrap_or_default();
+        let hide = parser.maybe_flag("hide")?;
+        let tstamp = parser.maybe_id()?;
+        let effects = parser.maybe::<TextEffects>()?;
+        let render_cache = parser.maybe::<TextRenderCache>()?;
+
+        let extra = parser.expect_end_with_extra()?;
+
+        Ok(Self {
+            key,
+            value,
+            position,
+            layer,
+            knockout,
+            hide,
+            tstamp,
+            effects,
+            render_cache,
+            field_order,
+            extra,
+        })
+    }
+}
+
+simple_maybe_from_sexpr!(FootprintProperty, property);
+
+impl ToSexpr for FootprintProperty {
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_in_order("property", &self.field_order, &self.extra, |s| {
+            s.append_string(&self.key);
+            s.append_string(&self.value);
+            if let Some(position) = &self.position {
+                position.write_sexpr_with_flag(s);
+            }
+            if let Some(layer) = self.layer {
+                s.append_list("layer", |s| {
+                    s.append_string(layer);
+                    s.append_symbol_if("knockout", self.knockout);
+                });
+            }
+            s.append_flag("hide", self.hide);
+            if let Some(tstamp) = &self.tstamp {
+                s.append_id(tstamp);
+            }
+            s.append_maybe(&self.effects);
+            s.append_maybe(&self.render_cache);
+        });
+    }
+}
+
 /// A footprint text element.
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
@@ -22,24 +134,36 @@ pub struct FootprintText {
     pub hide: bool,
     pub effects: TextEffects,
     pub tstamp: Uuid,
+    pub render_cache: Option<TextRenderCache>,
+    pub field_order: FieldOrder,
+    pub extra: Extra,
 }
 
 impl FromSexpr for FootprintText {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("fp_text")?;
 
+        let mut parser = parser.keyed();
+        let field_order = parser.field_order();
+
         let kind = parser.expect_symbol()?.parse()?;
-        let locked = parser.maybe_symbol_matching("locked");
+        let locked = parser.maybe_locked()?;
         let text = parser.expect_string()?;
-        let position = parser.expect::<FootprintTextPosition>()?;
-        let (layer, knockout) = parser.expect_list_with_name("layer").and_then(|mut list| {
+        let mut position = parser.expect::<FootprintTextPosition>()?;
+        position.unlocked |= parser.maybe_flag("unlocked")?;
+        let (layer, knockout) = parser.expect_list_with_name("layer", |list| {
             let layer = list.expect_string()?.parse()?;
             let knockout = list.maybe_symbol_matching("knockout");
             Ok((layer, knockout))
         })?;
-        let hide = parser.maybe_symbol_matching("hide");
-        let effects = parser.expect::<TextEffects>()?;
-        let tstamp = parser.expect_with_name::<Uuid>("tstamp")?;
+        let hide = parser.maybe_flag("hide")?;
+        let effects = parser
+            .maybe::<TextEffects>()?
+            .ok_or_else(|| KiCadParseError::MissingField("effects".to_string()))?;
+        let tstamp = parser.expect_id()?;
+        let render_cache = parser.maybe::<TextRenderCache>()?;
+
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             kind,
@@ -51,31 +175,38 @@ impl FromSexpr for FootprintText {
             hide,
             effects,
             tstamp,
+            render_cache,
+            field_order,
+            extra,
         })
     }
 }
 
 impl ToSexpr for FootprintText {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "fp_text",
-            [
-                Some(Sexpr::symbol(self.kind)),
-                self.locked.then(|| Sexpr::symbol("locked")),
-                Some(Sexpr::string(&self.text)),
-                Some(self.position.to_sexpr()),
-                Some(Sexpr::list_with_name(
-                    "layer",
-                    [
-                        Some(Sexpr::string(self.layer)),
-                        self.knockout.then(|| Sexpr::symbol("knockout")),
-                    ],
-                )),
-                self.hide.then(|| Sexpr::symbol("hide")),
-                Some(self.effects.to_sexpr()),
-                Some(self.tstamp.to_sexpr_with_name("tstamp")),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_in_order("fp_text", &self.field_order, &self.extra, |s| {
+            s.append_symbol(self.kind);
+            s.append_locked(self.locked);
+            s.append_string(&self.text);
+            self.position.write_sexpr_with_flag(s);
+            s.append_list("layer", |s| {
+                s.append_string(self.layer);
+                s.append_symbol_if("knockout", self.knockout);
+            });
+            s.append_flag("hide", self.hide);
+
+            // KiCad 8 moved the identifier in front of the effects and the
+            // render cache
+            if writes(Syntax::Uuid) {
+                s.append_id(&self.tstamp);
+                s.append(&self.effects);
+                s.append_maybe(&self.render_cache);
+            } else {
+                s.append(&self.effects);
+                s.append_maybe(&self.render_cache);
+                s.append_id(&self.tstamp);
+            }
+        });
     }
 }
 
@@ -101,13 +232,16 @@ simple_to_from_string! {
 
 /// An extension of the normal [`Position`](crate::common::Position) struct that includes an `unlocked`
 /// field
+///
+/// Since KiCad 8 `unlocked` is written as an `(unlocked yes)` flag after the
+/// position rather than inside it.
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 #[derive(Debug, PartialEq, Clone)]
 pub struct FootprintTextPosition {
-    pub x: f32,
-    pub y: f32,
-    pub angle: Option<f32>,
+    pub x: Length,
+    pub y: Length,
+    pub angle: Option<Angle>,
     pub unlocked: bool,
 }
 
@@ -115,10 +249,11 @@ impl FromSexpr for FootprintTextPosition {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("at")?;
 
-        let x = parser.expect_number()?;
-        let y = parser.expect_number()?;
-        let angle = parser.maybe_number();
-        let unlocked = parser.maybe_symbol_matching("unlocked");
+        let x = parser.expect_length()?;
+        let y = parser.expect_length()?;
+        let angle = parser.maybe_angle();
+        let unlocked = parser.reads(Syntax::YesNoFlags) != Some(true)
+            && parser.maybe_symbol_matching("unlocked");
 
         Ok(Self {
             x,
@@ -130,16 +265,28 @@ impl FromSexpr for FootprintTextPosition {
 }
 
 impl ToSexpr for FootprintTextPosition {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "at",
-            [
-                Some(Sexpr::number(self.x)),
-                Some(Sexpr::number(self.y)),
-                self.angle.map(Sexpr::number),
-                self.unlocked.then(|| Sexpr::symbol("unlocked")),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list("at", |s| {
+            s.append_number(self.x);
+            s.append_number(self.y);
+            s.append_maybe_number(self.angle);
+            if !writes(Syntax::YesNoFlags) {
+                s.append_symbol_if("unlocked", self.unlocked);
+            }
+        });
+    }
+}
+
+simple_maybe_from_sexpr!(FootprintTextPosition, at);
+
+impl FootprintTextPosition {
+    /// Writes the position, followed by the `unlocked` flag in the versions
+    /// that write it separately.
+    fn write_sexpr_with_flag(&self, s: &mut ListBuilder) {
+        s.append(self);
+        if writes(Syntax::YesNoFlags) {
+            s.append_flag("unlocked", self.unlocked);
+        }
     }
 }
 
@@ -155,23 +302,29 @@ pub struct FootprintTextBox {
     pub start: Option<Vec2D>,
     pub end: Option<Vec2D>,
     pub points: Option<[Vec2D; 4]>,
-    pub angle: Option<f32>,
+    pub angle: Option<Angle>,
     pub layer: LayerId,
     pub tstamp: Uuid,
     pub effects: TextEffects,
     pub stroke: Option<Stroke>,
+    pub render_cache: Option<TextRenderCache>,
+    pub field_order: FieldOrder,
+    pub extra: Extra,
 }
 
 impl FromSexpr for FootprintTextBox {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("fp_text_box")?;
 
-        let locked = parser.maybe_symbol_matching("locked");
+        let mut parser = parser.keyed();
+        let field_order = parser.field_order();
+
+        let locked = parser.maybe_locked()?;
         let text = parser.expect_string()?;
         let start = parser.maybe_with_name::<Vec2D>("start")?;
         let end = parser.maybe_with_name::<Vec2D>("end")?;
         let points = parser
-            .maybe::<CoordinatePointList>()?
+            .maybe_points()?
             .map(|v| {
                 v.try_into()
                     .map_err(|v: Vec<_>| KiCadParseError::IncorrectNumberOfPoints {
@@ -180,23 +333,14 @@ impl FromSexpr for FootprintTextBox {
                     })
             })
             .transpose()?;
-        let angle = parser.maybe_number_with_name("angle")?;
+        let angle = parser.maybe_angle_with_name("angle")?;
         let layer = parser.expect_string_with_name("layer")?.parse()?;
-        let tstamp = parser.expect_with_name::<Uuid>("tstamp")?;
+        let tstamp = parser.expect_id()?;
         let effects = parser.expect::<TextEffects>()?;
         let stroke = parser.maybe::<Stroke>()?;
+        let render_cache = parser.maybe::<TextRenderCache>()?;
 
-        if parser
-            .peek_list()
-            .is_some_and(|l| l.first_symbol().is_some_and(|s| s == "render_cache"))
-        {
-            unimplemented!(
-                "Found render_cache list in FootprintTextBox: {:?}",
-                parser.peek_next().unwrap()
-            );
-        };
-
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             locked,
@@ -209,26 +353,29 @@ impl FromSexpr for FootprintTextBox {
             tstamp,
             effects,
             stroke,
+            render_cache,
+            field_order,
+            extra,
         })
     }
 }
 
 impl ToSexpr for FootprintTextBox {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "fp_text_box",
-            [
-                self.locked.then(|| Sexpr::symbol("locked")),
-                Some(Sexpr::string(&self.text)),
-                self.start.as_ref().map(|v| v.to_sexpr_with_name("start")),
-                self.end.as_ref().map(|v| v.to_sexpr_with_name("end")),
-                self.points.as_ref().map(|v| v.to_vec().to_sexpr()),
-                self.angle.map(|n| Sexpr::number_with_name("angle", n)),
-                Some(Sexpr::string_with_name("layer", self.layer)),
-                Some(self.tstamp.to_sexpr_with_name("tstamp")),
-                Some(self.effects.to_sexpr()),
-                self.stroke.as_ref().map(ToSexpr::to_sexpr),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_in_order("fp_text_box", &self.field_order, &self.extra, |s| {
+            s.append_locked(self.locked);
+            s.append_string(&self.text);
+            s.append_maybe_with_name("start", &self.start);
+            s.append_maybe_with_name("end", &self.end);
+            if let Some(points) = &self.points {
+                s.append(&points.to_vec());
+            }
+            s.append_maybe_number_with_name("angle", self.angle);
+            s.append_string_with_name("layer", self.layer);
+            s.append_id(&self.tstamp);
+            s.append(&self.effects);
+            s.append_maybe(&self.stroke);
+            s.append_maybe(&self.render_cache);
+        });
     }
 }
@@ -1,21 +1,30 @@
 //! Common (shared) types within the KiCad Sexpr file format.
 
-use std::{ops::Deref, str::FromStr};
+use std::{
+    ops::{Add, Deref, Sub},
+    str::FromStr,
+};
 
-use kicad_sexpr::{Sexpr, SexprList};
+use kicad_sexpr::{Number, SexprList};
 
 use crate::{
+    angle::Angle,
     convert::{
-        FromSexpr, FromSexprWithName, MaybeFromSexpr, MaybeFromSexprWithName, Parser, SexprListExt,
-        ToSexpr, ToSexprWithName, VecToMaybeSexprVec,
+        writes, Extra, FromSexpr, FromSexprWithName, ListBuilder, MaybeFromSexpr,
+        MaybeFromSexprWithName, Parser, SexprListExt, ToSexpr, ToSexprWithName,
     },
-    simple_maybe_from_sexpr, simple_to_from_string, KiCadParseError,
+    length::Length,
+    simple_maybe_from_sexpr, simple_to_from_string,
+    version::Syntax,
+    KiCadParseError,
 };
 
+pub mod embedded;
 pub mod footprint;
 pub mod pad;
 pub mod shape;
 pub mod symbol;
+pub mod table;
 pub mod zone;
 
 /// Generic position type used in many parts of the format.
@@ -24,42 +33,46 @@ pub mod zone;
 #[derive(Debug, PartialEq, Clone)]
 pub struct Position {
     /// The `X` attribute defines the horizontal position of the object.
-    pub x: f32,
+    pub x: Length,
     /// The `Y` attribute defines the vertical position of the object.
-    pub y: f32,
+    pub y: Length,
     /// The optional `ANGLE` attribute defines the rotational angle of the object.
     /// Not all objects have rotational position definitions.
-    pub angle: Option<i16>,
+    pub angle: Option<Angle>,
 }
 
 impl Position {
-    pub fn new(x: f32, y: f32, angle: Option<i16>) -> Self {
+    pub fn new(x: Length, y: Length, angle: Option<Angle>) -> Self {
         Self { x, y, angle }
     }
+
+    /// The position without its angle.
+    pub fn point(&self) -> Vec2D {
+        Vec2D::new(self.x, self.y)
+    }
 }
 
 impl FromSexpr for Position {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("at")?;
 
-        let x = parser.expect_number()?;
-        let y = parser.expect_number()?;
-        let angle = parser.maybe_number().map(|n| n as i16);
+        let x = parser.expect_length()?;
+        let y = parser.expect_length()?;
+        let angle = parser.maybe_angle();
 
         Ok(Self { x, y, angle })
     }
 }
 
+simple_maybe_from_sexpr!(Position, at);
+
 impl ToSexpr for Position {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "at",
-            [
-                Some(Sexpr::number(self.x)),
-                Some(Sexpr::number(self.y)),
-                self.angle.map(|a| Sexpr::number(a as f32)),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list("at", |s| {
+            s.append_number(self.x);
+            s.append_number(self.y);
+            s.append_maybe_number(self.angle);
+        });
     }
 }
 
@@ -83,24 +96,44 @@ impl FromSexpr for CoordinatePointList {
 simple_maybe_from_sexpr!(CoordinatePointList, pts);
 
 impl ToSexpr for CoordinatePointList {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name("pts", self.into_sexpr_vec())
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list("pts", |s| s.append_many(self));
     }
 }
 
 /// A single coordinate pair
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Default, Clone, Copy)]
 pub struct Vec2D {
-    pub x: f32,
-    pub y: f32,
+    pub x: Length,
+    pub y: Length,
 }
 
 impl Vec2D {
-    pub fn new(x: f32, y: f32) -> Self {
+    pub fn new(x: Length, y: Length) -> Self {
         Self { x, y }
     }
+
+    pub fn from_mm(x: f64, y: f64) -> Self {
+        Self::new(Length::from_mm(x), Length::from_mm(y))
+    }
+}
+
+impl Add for Vec2D {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.x + rhs.x, self.y + rhs.y)
+    }
+}
+
+impl Sub for Vec2D {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.x - rhs.x, self.y - rhs.y)
+    }
 }
 
 impl FromSexpr for Vec2D {
@@ -119,8 +152,8 @@ impl FromSexprWithName for Vec2D {
     fn from_sexpr_with_name(mut parser: Parser, name: &str) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching(name)?;
 
-        let x = parser.expect_number()?;
-        let y = parser.expect_number()?;
+        let x = parser.expect_length()?;
+        let y = parser.expect_length()?;
 
         parser.expect_end()?;
 
@@ -131,17 +164,17 @@ impl FromSexprWithName for Vec2D {
 impl MaybeFromSexprWithName for Vec2D {}
 
 impl ToSexpr for Vec2D {
-    fn to_sexpr(&self) -> Sexpr {
-        self.to_sexpr_with_name("xy")
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        self.write_sexpr_with_name(s, "xy");
     }
 }
 
 impl ToSexprWithName for Vec2D {
-    fn to_sexpr_with_name(&self, name: &str) -> Sexpr {
-        Sexpr::list_with_name(
-            name,
-            [Some(Sexpr::number(self.x)), Some(Sexpr::number(self.y))],
-        )
+    fn write_sexpr_with_name(&self, s: &mut ListBuilder, name: &str) {
+        s.append_list(name, |s| {
+            s.append_number(self.x);
+            s.append_number(self.y);
+        });
     }
 }
 
@@ -152,13 +185,13 @@ impl ToSexprWithName for Vec2D {
 #[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 #[derive(Debug, PartialEq, Clone)]
 pub struct Vec3D {
-    pub x: f32,
-    pub y: f32,
-    pub z: f32,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
 }
 
 impl Vec3D {
-    pub fn new(x: f32, y: f32, z: f32) -> Self {
+    pub fn new(x: f64, y: f64, z: f64) -> Self {
         Self { x, y, z }
     }
 }
@@ -177,22 +210,65 @@ impl FromSexpr for Vec3D {
     }
 }
 
+simple_maybe_from_sexpr!(Vec3D, xyz);
+
 impl ToSexpr for Vec3D {
-    fn to_sexpr(&self) -> Sexpr {
-        self.to_sexpr_with_name("xyz")
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        self.write_sexpr_with_name(s, "xyz");
     }
 }
 
 impl ToSexprWithName for Vec3D {
-    fn to_sexpr_with_name(&self, name: &str) -> Sexpr {
-        Sexpr::list_with_name(
-            name,
-            [
-                Some(Sexpr::number(self.x)),
-                Some(Sexpr::number(self.y)),
-                Some(Sexpr::number(self.z)),
-            ],
-        )
+    fn write_sexpr_with_name(&self, s: &mut ListBuilder, name: &str) {
+        s.append_list(name, |s| {
+            s.append_number(self.x);
+            s.append_number(self.y);
+            s.append_number(self.z);
+        });
+    }
+}
+
+// ############################################################################
+
+/// A rotation around each of the three axes, like the `rotate` of a 3D model.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Default, Clone, Copy)]
+pub struct Rotation3D {
+    pub x: Angle,
+    pub y: Angle,
+    pub z: Angle,
+}
+
+impl Rotation3D {
+    pub fn new(x: Angle, y: Angle, z: Angle) -> Self {
+        Self { x, y, z }
+    }
+}
+
+impl FromSexpr for Rotation3D {
+    fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
+        parser.expect_symbol_matching("xyz")?;
+
+        let x = parser.expect_angle()?;
+        let y = parser.expect_angle()?;
+        let z = parser.expect_angle()?;
+
+        parser.expect_end()?;
+
+        Ok(Self { x, y, z })
+    }
+}
+
+simple_maybe_from_sexpr!(Rotation3D, xyz);
+
+impl ToSexpr for Rotation3D {
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list("xyz", |s| {
+            s.append_number(self.x);
+            s.append_number(self.y);
+            s.append_number(self.z);
+        });
     }
 }
 
@@ -207,7 +283,7 @@ impl ToSexprWithName for Vec3D {
 #[derive(Debug, PartialEq, Default, Clone)]
 pub struct Stroke {
     /// The `width` token attribute defines the line width of the graphic object.
-    pub width: f32,
+    pub width: Length,
     /// The type token attribute defines the line style of the graphic object. Valid stroke line styles are:
     ///  - dash
     ///  - dash_dot
@@ -218,22 +294,25 @@ pub struct Stroke {
     pub kind: StrokeKind,
     /// The `color` token attributes define the line red, green, blue, and alpha color settings.
     pub color: Option<Color>,
+    pub extra: Extra,
 }
 
 impl Stroke {
-    pub fn new(width: f32, kind: StrokeKind) -> Self {
+    pub fn new(width: Length, kind: StrokeKind) -> Self {
         Self {
             width,
             kind,
             color: None,
+            extra: Extra::default(),
         }
     }
 
-    pub fn with_color(width: f32, kind: StrokeKind, color: Color) -> Self {
+    pub fn with_color(width: Length, kind: StrokeKind, color: Color) -> Self {
         Self {
             width,
             kind,
             color: Some(color),
+            extra: Extra::default(),
         }
     }
 }
@@ -242,28 +321,32 @@ impl FromSexpr for Stroke {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("stroke")?;
 
-        let width = parser.expect_number_with_name("width")?;
+        let mut parser = parser.keyed();
+
+        let width = parser.expect_length_with_name("width")?;
         let kind = parser.expect_symbol_with_name("type")?.parse()?;
         let color = parser.maybe::<Color>()?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
-        Ok(Self { width, kind, color })
+        Ok(Self {
+            width,
+            kind,
+            color,
+            extra,
+        })
     }
 }
 
 simple_maybe_from_sexpr!(Stroke, stroke);
 
 impl ToSexpr for Stroke {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "stroke",
-            [
-                Some(Sexpr::number_with_name("width", self.width)),
-                Some(Sexpr::symbol_with_name("type", self.kind)),
-                self.color.as_ref().map(ToSexpr::to_sexpr),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("stroke", &self.extra, |s| {
+            s.append_number_with_name("width", self.width);
+            s.append_symbol_with_name("type", self.kind);
+            s.append_maybe(&self.color);
+        });
     }
 }
 
@@ -299,17 +382,19 @@ pub struct Color {
     pub red: u8,
     pub green: u8,
     pub blue: u8,
-    pub alpha: u8,
+    /// The opacity, from 0 to 1. KiCad writes it with a fixed number of
+    /// decimals in some places, which the [`Number`] keeps.
+    pub alpha: Number,
 }
 
 impl FromSexpr for Color {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("color")?;
 
-        let red = parser.expect_number()? as u8;
-        let green = parser.expect_number()? as u8;
-        let blue = parser.expect_number()? as u8;
-        let alpha = parser.expect_number()? as u8;
+        let red = parser.expect_integer::<u8>()?;
+        let green = parser.expect_integer::<u8>()?;
+        let blue = parser.expect_integer::<u8>()?;
+        let alpha = parser.expect_raw_number()?;
 
         parser.expect_end()?;
 
@@ -325,16 +410,13 @@ impl FromSexpr for Color {
 simple_maybe_from_sexpr!(Color, color);
 
 impl ToSexpr for Color {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "color",
-            [
-                Some(Sexpr::number(self.red as f32)),
-                Some(Sexpr::number(self.green as f32)),
-                Some(Sexpr::number(self.blue as f32)),
-                Some(Sexpr::number(self.alpha as f32)),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list("color", |s| {
+            s.append_number(self.red);
+            s.append_number(self.green);
+            s.append_number(self.blue);
+            s.append_number(self.alpha);
+        });
     }
 }
 
@@ -355,14 +437,13 @@ pub struct TextEffects {
     pub justify: Option<Justify>,
     /// The optional `hide` token defines if the text is hidden.
     pub hide: bool,
-    /// Whether the hide was in legacy format (just "hide") vs new format ("hide yes/no")
-    pub hide_legacy_format: bool,
     /// UNDOCUMENTED: The `href` token specifies the hyperlink of the text.
     pub href: Option<String>,
+    pub extra: Extra,
 }
 
 impl TextEffects {
-    pub fn from_size(x: f32, y: f32) -> Self {
+    pub fn from_size(x: Length, y: Length) -> Self {
         Self {
             font: Font {
                 face: None,
@@ -370,19 +451,18 @@ impl TextEffects {
                 line_spacing: None,
                 thickness: None,
                 bold: false,
-                bold_legacy_format: false,
                 italic: false,
-                italic_legacy_format: false,
                 color: None,
+                extra: Extra::default(),
             },
             justify: None,
             hide: false,
-            hide_legacy_format: false,
             href: None,
+            extra: Extra::default(),
         }
     }
 
-    pub fn with_size(self, x: f32, y: f32) -> Self {
+    pub fn with_size(self, x: Length, y: Length) -> Self {
         Self {
             font: Font {
                 size: Vec2D { x, y },
@@ -395,64 +475,41 @@ impl TextEffects {
     pub fn with_hide(self, hide: bool) -> Self {
         Self { hide, ..self }
     }
-
-    pub fn with_hide_legacy(self, hide: bool) -> Self {
-        Self {
-            hide,
-            hide_legacy_format: true,
-            ..self
-        }
-    }
 }
 
 impl FromSexpr for TextEffects {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("effects")?;
 
+        let mut parser = parser.keyed();
+
         let font = parser.expect::<Font>()?;
         let justify = parser.maybe::<Justify>()?;
-        // Handle both old format (hide) and new format (hide yes/no)
-        let (hide, hide_legacy_format) = if parser.maybe_symbol_matching("hide") {
-            (true, true)
-        } else if let Some(hide_value) = parser.maybe_bool_with_name("hide")? {
-            (hide_value, false)
-        } else {
-            (false, false)
-        };
+        let hide = parser.maybe_flag("hide")?;
         let href = parser.maybe_string_with_name("href")?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             font,
             justify,
             hide,
-            hide_legacy_format,
             href,
+            extra,
         })
     }
 }
 
+simple_maybe_from_sexpr!(TextEffects, effects);
+
 impl ToSexpr for TextEffects {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "effects",
-            [
-                Some(self.font.to_sexpr()),
-                self.justify.as_ref().map(ToSexpr::to_sexpr),
-                // Preserve original format: legacy uses just "hide", newer uses "hide yes/no"
-                if self.hide && self.hide_legacy_format {
-                    Some(Sexpr::symbol("hide"))
-                } else if self.hide {
-                    Some(Sexpr::bool_with_name("hide", true))
-                } else {
-                    None
-                },
-                self.href
-                    .as_ref()
-                    .map(|h| Sexpr::string_with_name("href", h)),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("effects", &self.extra, |s| {
+            s.append(&self.font);
+            s.append_maybe(&self.justify);
+            s.append_flag("hide", self.hide);
+            s.append_maybe_string_with_name("href", self.href.as_ref());
+        });
     }
 }
 
@@ -469,48 +526,33 @@ pub struct Font {
     pub size: Vec2D,
     /// The `line_spacing` token specifies the spacing between lines as a ratio
     /// of standard line-spacing. (Not yet supported)
-    pub line_spacing: Option<f32>,
+    pub line_spacing: Option<f64>,
     /// The `thickness` token attribute defines the line thickness of the font.
-    pub thickness: Option<f32>,
+    pub thickness: Option<Length>,
     /// The `bold` token specifies if the font should be bold.
     pub bold: bool,
-    /// Whether the bold was in legacy format (just "bold") vs new format ("bold yes/no")
-    pub bold_legacy_format: bool,
     /// The `italic` token specifies if the font should be italicized.
     pub italic: bool,
-    /// Whether the italic was in legacy format (just "italic") vs new format ("italic yes/no")
-    pub italic_legacy_format: bool,
     /// UNDOCUMENTED: The `color` token specifies the color of the text.
     pub color: Option<Color>,
+    pub extra: Extra,
 }
 
 impl FromSexpr for Font {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("font")?;
 
+        let mut parser = parser.keyed();
+
         let face = parser.maybe_string_with_name("face")?;
         let size = parser.expect_with_name::<Vec2D>("size")?;
         let line_spacing = parser.maybe_number_with_name("line_spacing")?;
-        let thickness = parser.maybe_number_with_name("thickness")?;
-        // Handle both old format (bold) and new format (bold yes/no)
-        let (bold, bold_legacy_format) = if parser.maybe_symbol_matching("bold") {
-            (true, true)
-        } else if let Some(bold_value) = parser.maybe_bool_with_name("bold")? {
-            (bold_value, false)
-        } else {
-            (false, false)
-        };
-        // Handle both old format (italic) and new format (italic yes/no)
-        let (italic, italic_legacy_format) = if parser.maybe_symbol_matching("italic") {
-            (true, true)
-        } else if let Some(italic_value) = parser.maybe_bool_with_name("italic")? {
-            (italic_value, false)
-        } else {
-            (false, false)
-        };
+        let thickness = parser.maybe_length_with_name("thickness")?;
+        let bold = parser.maybe_flag("bold")?;
+        let italic = parser.maybe_flag("italic")?;
         let color = parser.maybe::<Color>()?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             face,
@@ -518,46 +560,26 @@ impl FromSexpr for Font {
             line_spacing,
             thickness,
             bold,
-            bold_legacy_format,
             italic,
-            italic_legacy_format,
             color,
+            extra,
         })
     }
 }
 
+simple_maybe_from_sexpr!(Font, font);
+
 impl ToSexpr for Font {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "font",
-            [
-                self.face
-                    .as_ref()
-                    .map(|f| Sexpr::string_with_name("face", f)),
-                Some(self.size.to_sexpr_with_name("size")),
-                self.line_spacing
-                    .map(|l| Sexpr::number_with_name("line_spacing", l)),
-                self.thickness
-                    .map(|t| Sexpr::number_with_name("thickness", t)),
-                // Preserve original format: legacy uses just "bold", newer uses "bold yes/no"
-                if self.bold && self.bold_legacy_format {
-                    Some(Sexpr::symbol("bold"))
-                } else if self.bold {
-                    Some(Sexpr::bool_with_name("bold", true))
-                } else {
-                    None
-                },
-                // Preserve original format: legacy uses just "italic", newer uses "italic yes/no"
-                if self.italic && self.italic_legacy_format {
-                    Some(Sexpr::symbol("italic"))
-                } else if self.italic {
-                    Some(Sexpr::bool_with_name("italic", true))
-                } else {
-                    None
-                },
-                self.color.as_ref().map(ToSexpr::to_sexpr),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("font", &self.extra, |s| {
+            s.append_maybe_string_with_name("face", self.face.as_ref());
+            s.append_with_name("size", &self.size);
+            s.append_maybe_number_with_name("line_spacing", self.line_spacing);
+            s.append_maybe_number_with_name("thickness", self.thickness);
+            s.append_flag("bold", self.bold);
+            s.append_flag("italic", self.italic);
+            s.append_maybe(&self.color);
+        });
     }
 }
 
@@ -597,15 +619,12 @@ impl FromSexpr for Justify {
 simple_maybe_from_sexpr!(Justify, justify);
 
 impl ToSexpr for Justify {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "justify",
-            [
-                self.horizontal_direction.map(Sexpr::symbol),
-                self.vertical_direction.map(Sexpr::symbol),
-                self.mirror.then(|| Sexpr::symbol("mirror")),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list("justify", |s| {
+            s.append_maybe_symbol(self.horizontal_direction);
+            s.append_maybe_symbol(self.vertical_direction);
+            s.append_symbol_if("mirror", self.mirror);
+        });
     }
 }
 
@@ -639,6 +658,102 @@ simple_to_from_string! {
     bottom <-> Bottom
 }
 
+/// The glyph outlines KiCad caches for text drawn with an outline (TrueType)
+/// font, so that the text can be drawn without the font installed. The font
+/// itself is named by the `face` of the text's [`Font`].
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct TextRenderCache {
+    /// The text the outlines were rendered from, with any text variables
+    /// resolved.
+    pub text: String,
+    /// The angle the text was drawn at.
+    pub angle: Angle,
+    /// The filled outlines of the glyphs, in board coordinates.
+    pub polygons: Vec<RenderCachePolygon>,
+    pub extra: Extra,
+}
+
+impl FromSexpr for TextRenderCache {
+    fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
+        parser.expect_symbol_matching("render_cache")?;
+
+        let text = parser.expect_string()?;
+        let angle = parser.expect_angle()?;
+
+        let mut parser = parser.keyed();
+
+        let polygons = parser.expect_many::<RenderCachePolygon>()?;
+
+        let extra = parser.expect_end_with_extra()?;
+
+        Ok(Self {
+            text,
+            angle,
+            polygons,
+            extra,
+        })
+    }
+}
+
+simple_maybe_from_sexpr!(TextRenderCache, render_cache);
+
+impl ToSexpr for TextRenderCache {
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("render_cache", &self.extra, |s| {
+            s.append_string(&self.text);
+            s.append_number(self.angle);
+            s.append_many(&self.polygons);
+        });
+    }
+}
+
+/// A filled polygon of a [`TextRenderCache`], such as the outline of a glyph
+/// together with the holes cut out of it.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct RenderCachePolygon {
+    pub outline: CoordinatePointList,
+    pub holes: Vec<CoordinatePointList>,
+    pub extra: Extra,
+}
+
+impl FromSexpr for RenderCachePolygon {
+    fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
+        parser.expect_symbol_matching("polygon")?;
+
+        let mut parser = parser.keyed();
+
+        // The first `pts` list is the outline and any others are holes
+        let outline = parser.expect_points()?;
+        let mut holes = Vec::new();
+        while let Some(hole) = parser.maybe_points()? {
+            holes.push(hole);
+        }
+
+        let extra = parser.expect_end_with_extra()?;
+
+        Ok(Self {
+            outline,
+            holes,
+            extra,
+        })
+    }
+}
+
+simple_maybe_from_sexpr!(RenderCachePolygon, polygon);
+
+impl ToSexpr for RenderCachePolygon {
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("polygon", &self.extra, |s| {
+            s.append(&self.outline);
+            s.append_many(&self.holes);
+        });
+    }
+}
+
 // ############################################################################
 
 /// Defines the drawing page size and orientation.
@@ -650,18 +765,21 @@ pub struct PageSettings {
     /// The portrait token defines if the page is shown in the portrait mode.
     /// If not defined, the landscape page layout mode is used.
     pub portrait: bool,
+    pub extra: Extra,
 }
 
 impl FromSexpr for PageSettings {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("paper")?;
 
+        let mut parser = parser.keyed();
+
         let size_string = parser.expect_string()?;
 
         let size = match size_string.as_str() {
             "User" => {
-                let width = parser.expect_number()?;
-                let height = parser.expect_number()?;
+                let width = parser.expect_length()?;
+                let height = parser.expect_length()?;
 
                 PageSize::Custom(CustomPageSize { width, height })
             }
@@ -670,29 +788,31 @@ impl FromSexpr for PageSettings {
 
         let portrait = parser.maybe_symbol_matching("portrait");
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
-        Ok(Self { size, portrait })
+        Ok(Self {
+            size,
+            portrait,
+            extra,
+        })
     }
 }
 
+simple_maybe_from_sexpr!(PageSettings, paper);
+
 impl ToSexpr for PageSettings {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "paper",
-            [
-                match &self.size {
-                    PageSize::Standard(s) => vec![Some(Sexpr::string(s.to_string()))],
-                    PageSize::Custom(c) => vec![
-                        Some(Sexpr::string("User")),
-                        Some(Sexpr::number(c.width)),
-                        Some(Sexpr::number(c.height)),
-                    ],
-                },
-                vec![self.portrait.then(|| Sexpr::symbol("portrait"))],
-            ]
-            .concat(),
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("paper", &self.extra, |s| {
+            match &self.size {
+                PageSize::Standard(size) => s.append_string(size.to_string()),
+                PageSize::Custom(c) => {
+                    s.append_string("User");
+                    s.append_number(c.width);
+                    s.append_number(c.height);
+                }
+            }
+            s.append_symbol_if("portrait", self.portrait);
+        });
     }
 }
 
@@ -752,8 +872,8 @@ simple_to_from_string! {
 #[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 #[derive(Debug, PartialEq, Clone)]
 pub struct CustomPageSize {
-    pub width: f32,
-    pub height: f32,
+    pub width: Length,
+    pub height: Length,
 }
 
 // ############################################################################
@@ -778,19 +898,22 @@ pub struct TitleBlock {
     /// The `comment` token attributes define the document comments where N is
     /// a number from 1 to 9 and COMMENT is a quoted string.
     pub comments: Vec<Comment>,
+    pub extra: Extra,
 }
 
 impl FromSexpr for TitleBlock {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("title_block")?;
 
+        let mut parser = parser.keyed();
+
         let title = parser.maybe_string_with_name("title")?;
         let date = parser.maybe_string_with_name("date")?;
         let revision = parser.maybe_string_with_name("rev")?;
         let company = parser.maybe_string_with_name("company")?;
         let comments = parser.expect_many::<Comment>()?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             title,
@@ -798,6 +921,7 @@ impl FromSexpr for TitleBlock {
             revision,
             company,
             comments,
+            extra,
         })
     }
 }
@@ -805,28 +929,14 @@ impl FromSexpr for TitleBlock {
 simple_maybe_from_sexpr!(TitleBlock, title_block);
 
 impl ToSexpr for TitleBlock {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "title_block",
-            [
-                &[
-                    self.title
-                        .as_ref()
-                        .map(|t| Sexpr::string_with_name("title", t)),
-                    self.date
-                        .as_ref()
-                        .map(|d| Sexpr::string_with_name("date", d)),
-                    self.revision
-                        .as_ref()
-                        .map(|r| Sexpr::string_with_name("rev", r)),
-                    self.company
-                        .as_ref()
-                        .map(|c| Sexpr::string_with_name("company", c)),
-                ][..],
-                &self.comments.into_sexpr_vec(),
-            ]
-            .concat(),
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("title_block", &self.extra, |s| {
+            s.append_maybe_string_with_name("title", self.title.as_ref());
+            s.append_maybe_string_with_name("date", self.date.as_ref());
+            s.append_maybe_string_with_name("rev", self.revision.as_ref());
+            s.append_maybe_string_with_name("company", self.company.as_ref());
+            s.append_many(&self.comments);
+        });
     }
 }
 
@@ -843,7 +953,7 @@ impl FromSexpr for Comment {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("comment")?;
 
-        let index = parser.expect_number()? as u8;
+        let index = parser.expect_integer::<u8>()?;
         let text = parser.expect_string()?;
 
         parser.expect_end()?;
@@ -855,14 +965,11 @@ impl FromSexpr for Comment {
 simple_maybe_from_sexpr!(Comment, comment);
 
 impl ToSexpr for Comment {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "comment",
-            [
-                Some(Sexpr::number(self.index as f32)),
-                Some(Sexpr::string(&self.text)),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list("comment", |s| {
+            s.append_number(self.index);
+            s.append_string(&self.text);
+        });
     }
 }
 
@@ -895,14 +1002,11 @@ impl FromSexpr for Property {
 simple_maybe_from_sexpr!(Property, property);
 
 impl ToSexpr for Property {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "property",
-            [
-                Some(Sexpr::string(&self.key)),
-                Some(Sexpr::string(&self.value)),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list("property", |s| {
+            s.append_string(&self.key);
+            s.append_string(&self.value);
+        });
     }
 }
 
@@ -914,7 +1018,7 @@ impl ToSexpr for Property {
 /// TODO: replace with just uuid::Uuid
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct Uuid(
     /// The UUID attribute is a Version 4 (random) UUID that should be globally
     /// unique. KiCad UUIDs are generated using the
@@ -950,7 +1054,7 @@ impl FromSexprWithName for Uuid {
     fn from_sexpr_with_name(mut parser: Parser, name: &str) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching(name)?;
 
-        let uuid = parser.expect_symbol()?.parse()?;
+        let uuid = parser.expect_string_or_symbol()?.parse()?;
 
         Ok(Self(uuid))
     }
@@ -959,14 +1063,18 @@ impl FromSexprWithName for Uuid {
 impl MaybeFromSexprWithName for Uuid {}
 
 impl ToSexpr for Uuid {
-    fn to_sexpr(&self) -> Sexpr {
-        self.to_sexpr_with_name("uuid")
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        self.write_sexpr_with_name(s, "uuid");
     }
 }
 
 impl ToSexprWithName for Uuid {
-    fn to_sexpr_with_name(&self, name: &str) -> Sexpr {
-        Sexpr::list_with_name(name, [Some(Sexpr::symbol(self.to_string()))])
+    fn write_sexpr_with_name(&self, s: &mut ListBuilder, name: &str) {
+        if writes(Syntax::QuotedUuids) {
+            s.append_string_with_name(name, self.to_string());
+        } else {
+            s.append_symbol_with_name(name, self.to_string());
+        }
     }
 }
 
@@ -990,6 +1098,12 @@ impl From<Uuid> for uuid::Uuid {
     }
 }
 
+impl std::fmt::Display for Uuid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
 // ############################################################################
 
 /// An embedded bitmap image stored in Base64 encoded PNG format.
@@ -1009,19 +1123,22 @@ pub struct Image {
     pub layer: Option<LayerId>,
     /// The optional `scale` token attribute defines the SCALE_FACTOR of the
     /// image.
-    pub scale: Option<f32>,
+    pub scale: Option<f64>,
     /// The UNIQUE_IDENTIFIER defines the universally unique identifier for the
     /// image.
     pub unique_id: Option<Uuid>,
     /// The `data` token attribute defines the image data in the portable
     /// network graphics format (PNG) encoded with MIME type base64.
     pub data: String,
+    pub extra: Extra,
 }
 
 impl FromSexpr for Image {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("image")?;
 
+        let mut parser = parser.keyed();
+
         let position = parser.expect::<Position>()?;
         let layer = parser
             .maybe_string_with_name("layer")?
@@ -1031,7 +1148,7 @@ impl FromSexpr for Image {
         let unique_id = parser.maybe::<Uuid>()?;
         let data = parser.expect_string()?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             position,
@@ -1039,6 +1156,7 @@ impl FromSexpr for Image {
             layer,
             unique_id,
             data,
+            extra,
         })
     }
 }
@@ -1046,17 +1164,14 @@ impl FromSexpr for Image {
 simple_maybe_from_sexpr!(Image, image);
 
 impl ToSexpr for Image {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "image",
-            [
-                Some(self.position.to_sexpr()),
-                self.layer.map(|l| Sexpr::string_with_name("layer", l)),
-                self.scale.map(|s| Sexpr::number_with_name("scale", s)),
-                self.unique_id.as_ref().map(ToSexpr::to_sexpr),
-                Some(Sexpr::string(&self.data)),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("image", &self.extra, |s| {
+            s.append(&self.position);
+            s.append_maybe_string_with_name("layer", self.layer);
+            s.append_maybe_number_with_name("scale", self.scale);
+            s.append_maybe(&self.unique_id);
+            s.append_string(&self.data);
+        });
     }
 }
 
@@ -1075,7 +1190,7 @@ impl ToSexpr for Image {
 #[repr(u8)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum LayerId {
     FCu,
     In1Cu,
@@ -1339,6 +1454,12 @@ impl From<LayerId> for String {
     }
 }
 
+impl std::fmt::Display for LayerId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&String::from(*self))
+    }
+}
+
 // ############################################################################
 
 /// A group of items represented by a list of unique identifiers.
@@ -1351,41 +1472,48 @@ pub struct Group {
     /// UNDOCUMENTED: The locked token specifies if the group is locked.
     pub locked: bool,
     /// The `id` token attribute defines the unique identifier of the group.
+    /// Written as `uuid` since KiCad 8.
     pub id: Uuid,
     /// The `members` token attributes define a list of unique identifiers of
     /// the objects belonging to the group.
     pub members: Vec<Uuid>,
+    pub extra: Extra,
 }
 
 impl FromSexpr for Group {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("group")?;
 
+        let mut parser = parser.keyed();
+
         let name = parser.expect_string()?;
-        let locked = parser.maybe_symbol_matching("locked");
-        let id = parser.expect_with_name::<Uuid>("id")?;
-        let members = parser.expect_list_with_name("members").and_then(|mut p| {
-            let members = p
-                .expect_many_symbols()?
-                .into_iter()
-                .map(|s| s.parse::<uuid::Uuid>())
-                .collect::<Result<Vec<_>, _>>()?
-                .into_iter()
-                .map(Uuid::from)
-                .collect();
-
-            p.expect_end()?;
+        let locked = parser.maybe_locked()?;
+        let id = match parser.reads(Syntax::Uuid) {
+            Some(true) => parser.expect_with_name::<Uuid>("uuid")?,
+            Some(false) => parser.expect_with_name::<Uuid>("id")?,
+            None => match parser.maybe_with_name::<Uuid>("id")? {
+                Some(id) => id,
+                None => parser.expect_with_name::<Uuid>("uuid")?,
+            },
+        };
+        let members = parser.expect_list_with_name("members", |p| {
+            let mut members = Vec::new();
+
+            while p.peek_next().is_some() {
+                members.push(Uuid(p.expect_string_or_symbol()?.parse()?));
+            }
 
             Ok(members)
         })?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             name,
             locked,
             id,
             members,
+            extra,
         })
     }
 }
@@ -1393,24 +1521,21 @@ impl FromSexpr for Group {
 simple_maybe_from_sexpr!(Group, group);
 
 impl ToSexpr for Group {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "group",
-            [
-                Some(Sexpr::string(&self.name)),
-                self.locked.then(|| Sexpr::symbol("locked")),
-                Some(self.id.to_sexpr_with_name("id")),
-                Some(Sexpr::list_with_name(
-                    "members",
-                    self.members
-                        .iter()
-                        .map(|u| u.0)
-                        .map(|u: uuid::Uuid| Sexpr::symbol(u.to_string()))
-                        .map(Option::Some)
-                        .collect::<Vec<_>>(),
-                )),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("group", &self.extra, |s| {
+            s.append_string(&self.name);
+            s.append_locked(self.locked);
+            s.append_with_name(if writes(Syntax::Uuid) { "uuid" } else { "id" }, &self.id);
+            s.append_list("members", |s| {
+                for member in &self.members {
+                    if writes(Syntax::QuotedUuids) {
+                        s.append_string(member.to_string());
+                    } else {
+                        s.append_symbol(member.to_string());
+                    }
+                }
+            });
+        });
     }
 }
 
@@ -1,54 +1,86 @@
 //! Common structures related to footprint pads
 
-use kicad_sexpr::Sexpr;
-
-use self::primitive::PadGraphicsPrimitive;
+use self::{padstack::Padstack, primitive::PadGraphicsPrimitive};
 
 use super::footprint::ZoneConnectKind;
 use crate::{
+    angle::Angle,
     common::{LayerId, Position, Uuid, Vec2D},
-    convert::{FromSexpr, Parser, SexprListExt, ToSexpr, ToSexprWithName},
+    convert::{Extra, FieldOrder, FromSexpr, ListBuilder, Parser, SexprListExt, ToSexpr},
+    length::Length,
     simple_maybe_from_sexpr, simple_to_from_string, KiCadParseError,
 };
 
+pub mod padstack;
 pub mod primitive;
 
 /// A footprint pad
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, FromSexpr, ToSexpr)]
+#[sexpr(name = "pad")]
 pub struct Pad {
+    #[sexpr(positional)]
     pub index: String,
+    #[sexpr(positional, symbol)]
     pub kind: PadKind,
+    #[sexpr(positional, symbol)]
     pub shape: PadShape,
+    #[sexpr(locked)]
     pub locked: bool,
+    #[sexpr(name = "at")]
     pub position: Position,
+    #[sexpr(with_name)]
     pub size: Vec2D,
+    #[sexpr(with_name)]
     pub rect_delta: Option<Vec2D>,
     pub drill: Option<Drill>,
+    #[sexpr(symbol)]
     pub property: Option<PadProperty>,
+    #[sexpr(string)]
     pub layers: Vec<LayerId>,
-    pub remove_unused_layer: bool,
-    pub keep_end_layers: bool,
+    /// Whether the pad is left out of copper layers it has no connection on.
+    /// `None` if the file does not say, as KiCad 8 only writes it for some
+    /// pads and older versions only when it is set.
+    #[sexpr(list_flag)]
+    pub remove_unused_layers: Option<bool>,
+    /// Whether the outer layers are kept when unused layers are removed.
+    #[sexpr(list_flag)]
+    pub keep_end_layers: Option<bool>,
+    #[sexpr(string)]
     pub zone_layer_connections: Option<Vec<LayerId>>,
-    pub round_rect_radius_ratio: Option<f32>,
-    pub chamfer_ratio: Option<f32>,
+    #[sexpr(name = "roundrect_rratio")]
+    pub round_rect_radius_ratio: Option<f64>,
+    pub chamfer_ratio: Option<f64>,
     pub chamfer: Option<Chamfer>,
     pub net: Option<Net>,
+    #[sexpr(name = "pinfunction")]
     pub pin_function: Option<String>,
+    #[sexpr(name = "pintype")]
     pub pin_type: Option<String>,
-    pub die_length: Option<f32>,
-    pub solder_mask_margin: Option<f32>,
-    pub solder_paste_margin: Option<f32>,
-    pub solder_paste_margin_ratio: Option<f32>,
-    pub clearance: Option<f32>,
+    pub die_length: Option<Length>,
+    pub solder_mask_margin: Option<Length>,
+    pub solder_paste_margin: Option<Length>,
+    pub solder_paste_margin_ratio: Option<f64>,
+    pub clearance: Option<Length>,
+    #[sexpr(try_from = "u8")]
     pub zone_connect: Option<ZoneConnectKind>,
-    pub thermal_bridge_width: Option<f32>,
-    pub thermal_bridge_angle: Option<f32>,
-    pub thermal_gap: Option<f32>,
+    pub thermal_bridge_width: Option<Length>,
+    pub thermal_bridge_angle: Option<Angle>,
+    pub thermal_gap: Option<Length>,
     pub custom_pad_options: Option<CustomPadOptions>,
+    #[sexpr(name = "primitives")]
     pub custom_pad_primitives: Option<Vec<PadGraphicsPrimitive>>,
+    #[sexpr(id)]
     pub tstamp: Uuid,
+    /// The shapes of the pad on other copper layers, since KiCad 9. `None`
+    /// if the pad has the same shape on all layers.
+    #[sexpr(since = "Padstacks")]
+    pub padstack: Option<Padstack>,
+    #[sexpr(field_order)]
+    pub field_order: FieldOrder,
+    #[sexpr(extra)]
+    pub extra: Extra,
 }
 
 impl Pad {
@@ -58,206 +90,6 @@ impl Pad {
     }
 }
 
-impl FromSexpr for Pad {
-    fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
-        parser.expect_symbol_matching("pad")?;
-
-        let index = parser.expect_string()?;
-        let kind = parser.expect_symbol()?.parse::<PadKind>()?;
-        let shape = parser.expect_symbol()?.parse::<PadShape>()?;
-        let locked = parser.maybe_symbol_matching("locked");
-        let position = parser.expect::<Position>()?;
-        let size = parser.expect_with_name::<Vec2D>("size")?;
-        let rect_delta = parser.maybe_with_name::<Vec2D>("rect_delta")?;
-        let drill = parser.maybe::<Drill>()?;
-        let property = parser
-            .maybe_list_with_name("property")
-            .map(|mut p| {
-                let property = p.expect_symbol()?.parse::<PadProperty>()?;
-                p.expect_end()?;
-                Ok::<_, KiCadParseError>(property)
-            })
-            .transpose()?;
-        let layers = parser.expect_list_with_name("layers").and_then(|mut p| {
-            let layers = p
-                .expect_many_strings()?
-                .into_iter()
-                .map(|s| s.parse::<LayerId>())
-                .collect::<Result<Vec<_>, _>>()?;
-            p.expect_end()?;
-            Ok::<_, KiCadParseError>(layers)
-        })?;
-        let remove_unused_layer = parser.maybe_empty_list_with_name("remove_unused_layer")?;
-        let keep_end_layers = parser.maybe_empty_list_with_name("keep_end_layers")?;
-        let zone_layer_connections = parser
-            .maybe_list_with_name("zone_layer_connections")
-            .map(|mut p| {
-                let layers = p
-                    .expect_many_strings()?
-                    .into_iter()
-                    .map(|s| s.parse::<LayerId>())
-                    .collect::<Result<Vec<_>, _>>()?;
-                p.expect_end()?;
-                Ok::<_, KiCadParseError>(layers)
-            })
-            .transpose()?;
-        let round_rect_radius_ratio = parser.maybe_number_with_name("roundrect_rratio")?;
-        let chamfer_ratio = parser.maybe_number_with_name("chamfer_ratio")?;
-        let chamfer = parser.maybe::<Chamfer>()?;
-        let net = parser.maybe::<Net>()?;
-        let pin_function = parser.maybe_string_with_name("pinfunction")?;
-        let pin_type = parser.maybe_string_with_name("pintype")?;
-        let die_length = parser.maybe_number_with_name("die_length")?;
-        let solder_mask_margin = parser.maybe_number_with_name("solder_mask_margin")?;
-        let solder_paste_margin = parser.maybe_number_with_name("solder_paste_margin")?;
-        let solder_paste_margin_ratio =
-            parser.maybe_number_with_name("solder_paste_margin_ratio")?;
-        let clearance = parser.maybe_number_with_name("clearance")?;
-        let zone_connect = parser
-            .maybe_number_with_name("zone_connect")?
-            .map(|n| n as u8)
-            .map(ZoneConnectKind::try_from)
-            .transpose()?;
-        let thermal_bridge_width = parser.maybe_number_with_name("thermal_bridge_width")?;
-        let thermal_bridge_angle = parser.maybe_number_with_name("thermal_bridge_angle")?;
-        let thermal_gap = parser.maybe_number_with_name("thermal_gap")?;
-        let custom_pad_options = parser.maybe::<CustomPadOptions>()?;
-        let custom_pad_primitives = parser
-            .maybe_list_with_name("primitives")
-            .map(|mut p| {
-                let primitives = p.expect_many::<PadGraphicsPrimitive>()?;
-                p.expect_end()?;
-                Ok::<_, KiCadParseError>(primitives)
-            })
-            .transpose()?;
-        let tstamp = parser.expect_with_name::<Uuid>("tstamp")?;
-
-        parser.expect_end()?;
-
-        Ok(Self {
-            index,
-            kind,
-            shape,
-            locked,
-            position,
-            size,
-            rect_delta,
-            drill,
-            property,
-            layers,
-            remove_unused_layer,
-            keep_end_layers,
-            zone_layer_connections,
-            round_rect_radius_ratio,
-            chamfer_ratio,
-            chamfer,
-            net,
-            pin_function,
-            pin_type,
-            die_length,
-            solder_mask_margin,
-            solder_paste_margin,
-            solder_paste_margin_ratio,
-            clearance,
-            zone_connect,
-            thermal_bridge_width,
-            thermal_bridge_angle,
-            thermal_gap,
-            custom_pad_options,
-            custom_pad_primitives,
-            tstamp,
-        })
-    }
-}
-
-simple_maybe_from_sexpr!(Pad, pad);
-
-impl ToSexpr for Pad {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "pad",
-            [
-                Some(Sexpr::string(&self.index)),
-                Some(Sexpr::symbol(self.kind)),
-                Some(Sexpr::symbol(self.shape)),
-                self.locked.then(|| Sexpr::symbol("locked")),
-                Some(self.position.to_sexpr()),
-                Some(self.size.to_sexpr_with_name("size")),
-                self.rect_delta
-                    .as_ref()
-                    .map(|r| r.to_sexpr_with_name("rect_delta")),
-                self.drill.as_ref().map(ToSexpr::to_sexpr),
-                self.property
-                    .map(|p| Sexpr::symbol_with_name("property", p)),
-                Some(Sexpr::list_with_name(
-                    "layers",
-                    self.layers
-                        .iter()
-                        .map(Clone::clone)
-                        .map(Sexpr::string)
-                        .map(Option::Some)
-                        .collect::<Vec<_>>(),
-                )),
-                self.remove_unused_layer
-                    .then(|| Sexpr::list_with_name("remove_unused_layer", [])),
-                self.keep_end_layers
-                    .then(|| Sexpr::list_with_name("keep_end_layers", [])),
-                self.zone_layer_connections.as_ref().map(|l| {
-                    Sexpr::list_with_name(
-                        "zone_layer_connections",
-                        l.iter()
-                            .map(Clone::clone)
-                            .map(Sexpr::string)
-                            .map(Option::Some)
-                            .collect::<Vec<_>>(),
-                    )
-                }),
-                self.round_rect_radius_ratio
-                    .map(|n| Sexpr::number_with_name("roundrect_rratio", n)),
-                self.chamfer_ratio
-                    .map(|n| Sexpr::number_with_name("chamfer_ratio", n)),
-                self.chamfer.as_ref().map(ToSexpr::to_sexpr),
-                self.net.as_ref().map(ToSexpr::to_sexpr),
-                self.pin_function
-                    .as_ref()
-                    .map(|s| Sexpr::string_with_name("pinfunction", s)),
-                self.pin_type
-                    .as_ref()
-                    .map(|s| Sexpr::string_with_name("pintype", s)),
-                self.die_length
-                    .map(|n| Sexpr::number_with_name("die_length", n)),
-                self.solder_mask_margin
-                    .map(|n| Sexpr::number_with_name("solder_mask_margin", n)),
-                self.solder_paste_margin
-                    .map(|n| Sexpr::number_with_name("solder_paste_margin", n)),
-                self.solder_paste_margin_ratio
-                    .map(|n| Sexpr::number_with_name("solder_paste_margin_ratio", n)),
-                self.clearance
-                    .map(|n| Sexpr::number_with_name("clearance", n)),
-                self.zone_connect
-                    .map(|z| Sexpr::number_with_name("zone_connect", z as u8 as f32)),
-                self.thermal_bridge_width
-                    .map(|n| Sexpr::number_with_name("thermal_bridge_width", n)),
-                self.thermal_bridge_angle
-                    .map(|n| Sexpr::number_with_name("thermal_bridge_angle", n)),
-                self.thermal_gap
-                    .map(|n| Sexpr::number_with_name("thermal_gap", n)),
-                self.custom_pad_options.as_ref().map(ToSexpr::to_sexpr),
-                self.custom_pad_primitives.as_ref().map(|p| {
-                    Sexpr::list_with_name(
-                        "primitives",
-                        p.iter()
-                            .map(ToSexpr::to_sexpr)
-                            .map(Option::Some)
-                            .collect::<Vec<_>>(),
-                    )
-                }),
-                Some(self.tstamp.to_sexpr_with_name("tstamp")),
-            ],
-        )
-    }
-}
-
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -302,10 +134,11 @@ simple_to_from_string! {
 #[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 #[derive(Debug, PartialEq, Clone)]
 pub struct Drill {
-    pub diameter: f32,
+    pub diameter: Length,
     /// Present if the drill is oval
-    pub width: Option<f32>,
+    pub width: Option<Length>,
     pub offset: Option<Vec2D>,
+    pub extra: Extra,
 }
 
 impl Drill {
@@ -318,18 +151,21 @@ impl FromSexpr for Drill {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("drill")?;
 
+        let mut parser = parser.keyed();
+
         let oval = parser.maybe_symbol_matching("oval");
 
-        let diameter = parser.expect_number()?;
-        let width = oval.then(|| parser.expect_number()).transpose()?;
+        let diameter = parser.expect_length()?;
+        let width = oval.then(|| parser.expect_length()).transpose()?;
         let offset = parser.maybe_with_name::<Vec2D>("offset")?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             diameter,
             width,
             offset,
+            extra,
         })
     }
 }
@@ -337,16 +173,13 @@ impl FromSexpr for Drill {
 simple_maybe_from_sexpr!(Drill, drill);
 
 impl ToSexpr for Drill {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "drill",
-            [
-                self.is_oval().then(|| Sexpr::symbol("oval")),
-                Some(Sexpr::number(self.diameter)),
-                self.width.map(Sexpr::number),
-                self.offset.as_ref().map(|o| o.to_sexpr_with_name("offset")),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("drill", &self.extra, |s| {
+            s.append_symbol_if("oval", self.is_oval());
+            s.append_number(self.diameter);
+            s.append_maybe_number(self.width);
+            s.append_maybe_with_name("offset", &self.offset);
+        });
     }
 }
 
@@ -380,24 +213,28 @@ pub struct Chamfer {
     pub top_right: bool,
     pub bottom_left: bool,
     pub bottom_right: bool,
+    pub extra: Extra,
 }
 
 impl FromSexpr for Chamfer {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("chamfer")?;
 
+        let mut parser = parser.keyed();
+
         let top_left = parser.maybe_symbol_matching("top_left");
         let top_right = parser.maybe_symbol_matching("top_right");
         let bottom_left = parser.maybe_symbol_matching("bottom_left");
         let bottom_right = parser.maybe_symbol_matching("bottom_right");
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             top_left,
             top_right,
             bottom_left,
             bottom_right,
+            extra,
         })
     }
 }
@@ -405,16 +242,13 @@ impl FromSexpr for Chamfer {
 simple_maybe_from_sexpr!(Chamfer, chamfer);
 
 impl ToSexpr for Chamfer {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "chamfer",
-            [
-                self.top_left.then(|| Sexpr::symbol("top_left")),
-                self.top_right.then(|| Sexpr::symbol("top_right")),
-                self.bottom_left.then(|| Sexpr::symbol("bottom_left")),
-                self.bottom_right.then(|| Sexpr::symbol("bottom_right")),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("chamfer", &self.extra, |s| {
+            s.append_symbol_if("top_left", self.top_left);
+            s.append_symbol_if("top_right", self.top_right);
+            s.append_symbol_if("bottom_left", self.bottom_left);
+            s.append_symbol_if("bottom_right", self.bottom_right);
+        });
     }
 }
 
@@ -430,7 +264,7 @@ impl FromSexpr for Net {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("net")?;
 
-        let code = parser.expect_number()? as i32;
+        let code = parser.expect_integer::<i32>()?;
         let name = parser.expect_string()?;
 
         parser.expect_end()?;
@@ -442,14 +276,11 @@ impl FromSexpr for Net {
 simple_maybe_from_sexpr!(Net, net);
 
 impl ToSexpr for Net {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "net",
-            [
-                Some(Sexpr::number(self.code as f32)),
-                Some(Sexpr::string(&self.name)),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list("net", |s| {
+            s.append_number(self.code);
+            s.append_string(&self.name);
+        });
     }
 }
 
@@ -459,12 +290,15 @@ impl ToSexpr for Net {
 pub struct CustomPadOptions {
     pub clearance: CustomPadClearanceKind,
     pub anchor: CustomPadAnchorShape,
+    pub extra: Extra,
 }
 
 impl FromSexpr for CustomPadOptions {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("options")?;
 
+        let mut parser = parser.keyed();
+
         let clearance = parser
             .expect_symbol_with_name("clearance")?
             .parse::<CustomPadClearanceKind>()?;
@@ -472,23 +306,24 @@ impl FromSexpr for CustomPadOptions {
             .expect_symbol_with_name("anchor")?
             .parse::<CustomPadAnchorShape>()?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
-        Ok(Self { clearance, anchor })
+        Ok(Self {
+            clearance,
+            anchor,
+            extra,
+        })
     }
 }
 
 simple_maybe_from_sexpr!(CustomPadOptions, options);
 
 impl ToSexpr for CustomPadOptions {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "options",
-            [
-                Some(Sexpr::symbol_with_name("clearance", self.clearance)),
-                Some(Sexpr::symbol_with_name("anchor", self.anchor)),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("options", &self.extra, |s| {
+            s.append_symbol_with_name("clearance", self.clearance);
+            s.append_symbol_with_name("anchor", self.anchor);
+        });
     }
 }
 
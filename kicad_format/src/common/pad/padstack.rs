@@ -0,0 +1,208 @@
+//! Padstacks, which give a pad a different shape on some copper layers, since
+//! KiCad 9.
+
+use std::str::FromStr;
+
+use super::{primitive::PadGraphicsPrimitive, Chamfer, CustomPadOptions, PadShape};
+use crate::{
+    angle::Angle,
+    common::{footprint::ZoneConnectKind, LayerId, Vec2D},
+    convert::{Extra, FromSexpr, ListBuilder, Parser, SexprListExt, ToSexpr},
+    length::Length,
+    simple_maybe_from_sexpr, simple_to_from_string, KiCadParseError,
+};
+
+/// The shapes of a pad on the layers where it differs from the shape given
+/// by the pad itself, which is the shape on the front copper layer.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct Padstack {
+    pub mode: PadstackMode,
+    pub layers: Vec<PadstackLayer>,
+    pub extra: Extra,
+}
+
+impl FromSexpr for Padstack {
+    fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
+        parser.expect_symbol_matching("padstack")?;
+
+        let mut parser = parser.keyed();
+
+        let mode = parser
+            .expect_symbol_with_name("mode")?
+            .parse::<PadstackMode>()?;
+        let layers = parser.expect_many::<PadstackLayer>()?;
+
+        let extra = parser.expect_end_with_extra()?;
+
+        Ok(Self {
+            mode,
+            layers,
+            extra,
+        })
+    }
+}
+
+simple_maybe_from_sexpr!(Padstack, padstack);
+
+impl ToSexpr for Padstack {
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("padstack", &self.extra, |s| {
+            s.append_symbol_with_name("mode", self.mode);
+            s.append_many(&self.layers);
+        });
+    }
+}
+
+/// Which layers of a [`Padstack`] can have their own shape.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PadstackMode {
+    /// The same shape on all layers. KiCad does not write a padstack for
+    /// these pads.
+    Normal,
+    /// One shape for the front layer, one for all inner layers and one for
+    /// the back layer.
+    FrontInnerBack,
+    /// A shape for every copper layer.
+    Custom,
+}
+
+simple_to_from_string! {
+    PadstackMode,
+    normal <-> Normal,
+    front_inner_back <-> FrontInnerBack,
+    custom <-> Custom,
+}
+
+/// The layer a [`PadstackLayer`] gives the shape on.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PadstackLayerId {
+    /// All inner copper layers, in [`PadstackMode::FrontInnerBack`] mode.
+    Inner,
+    Layer(LayerId),
+}
+
+impl FromStr for PadstackLayerId {
+    type Err = KiCadParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Inner" => Ok(Self::Inner),
+            _ => s.parse().map(Self::Layer),
+        }
+    }
+}
+
+impl From<PadstackLayerId> for String {
+    fn from(layer: PadstackLayerId) -> Self {
+        match layer {
+            PadstackLayerId::Inner => "Inner".to_string(),
+            PadstackLayerId::Layer(layer) => layer.into(),
+        }
+    }
+}
+
+/// The shape of a pad on one layer of a [`Padstack`].
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct PadstackLayer {
+    pub layer: PadstackLayerId,
+    pub shape: Option<PadShape>,
+    pub offset: Option<Vec2D>,
+    pub size: Option<Vec2D>,
+    pub rect_delta: Option<Vec2D>,
+    pub round_rect_radius_ratio: Option<f64>,
+    pub chamfer_ratio: Option<f64>,
+    pub chamfer: Option<Chamfer>,
+    pub thermal_bridge_width: Option<Length>,
+    pub thermal_gap: Option<Length>,
+    pub thermal_bridge_angle: Option<Angle>,
+    pub zone_connect: Option<ZoneConnectKind>,
+    pub custom_pad_options: Option<CustomPadOptions>,
+    pub custom_pad_primitives: Option<Vec<PadGraphicsPrimitive>>,
+    pub extra: Extra,
+}
+
+impl FromSexpr for PadstackLayer {
+    fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
+        parser.expect_symbol_matching("layer")?;
+
+        let mut parser = parser.keyed();
+
+        let layer = parser.expect_string()?.parse::<PadstackLayerId>()?;
+        let shape = parser
+            .maybe_symbol_with_name("shape")?
+            .map(|s| s.parse::<PadShape>())
+            .transpose()?;
+        let offset = parser.maybe_with_name::<Vec2D>("offset")?;
+        let size = parser.maybe_with_name::<Vec2D>("size")?;
+        let rect_delta = parser.maybe_with_name::<Vec2D>("rect_delta")?;
+        let round_rect_radius_ratio = parser.maybe_number_with_name("roundrect_rratio")?;
+        let chamfer_ratio = parser.maybe_number_with_name("chamfer_ratio")?;
+        let chamfer = parser.maybe::<Chamfer>()?;
+        let thermal_bridge_width = parser.maybe_length_with_name("thermal_bridge_width")?;
+        let thermal_gap = parser.maybe_length_with_name("thermal_gap")?;
+        let thermal_bridge_angle = parser.maybe_angle_with_name("thermal_bridge_angle")?;
+        let zone_connect = parser
+            .maybe_integer_with_name::<u8>("zone_connect")?
+            .map(ZoneConnectKind::try_from)
+            .transpose()?;
+        let custom_pad_options = parser.maybe::<CustomPadOptions>()?;
+        let custom_pad_primitives = parser.maybe_list_with_name("primitives", |p| {
+            let primitives = p.expect_many::<PadGraphicsPrimitive>()?;
+
+            Ok(primitives)
+        })?;
+
+        let extra = parser.expect_end_with_extra()?;
+
+        Ok(Self {
+            layer,
+            shape,
+            offset,
+            size,
+            rect_delta,
+            round_rect_radius_ratio,
+            chamfer_ratio,
+            chamfer,
+            thermal_bridge_width,
+            thermal_gap,
+            thermal_bridge_angle,
+            zone_connect,
+            custom_pad_options,
+            custom_pad_primitives,
+            extra,
+        })
+    }
+}
+
+simple_maybe_from_sexpr!(PadstackLayer, layer);
+
+impl ToSexpr for PadstackLayer {
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("layer", &self.extra, |s| {
+            s.append_string(self.layer);
+            s.append_maybe_symbol_with_name("shape", self.shape);
+            s.append_maybe_with_name("offset", &self.offset);
+            s.append_maybe_with_name("size", &self.size);
+            s.append_maybe_with_name("rect_delta", &self.rect_delta);
+            s.append_maybe_number_with_name("roundrect_rratio", self.round_rect_radius_ratio);
+            s.append_maybe_number_with_name("chamfer_ratio", self.chamfer_ratio);
+            s.append_maybe(&self.chamfer);
+            s.append_maybe_number_with_name("thermal_bridge_width", self.thermal_bridge_width);
+            s.append_maybe_number_with_name("thermal_gap", self.thermal_gap);
+            s.append_maybe_number_with_name("thermal_bridge_angle", self.thermal_bridge_angle);
+            s.append_maybe_number_with_name("zone_connect", self.zone_connect.map(|z| z as u8));
+            s.append_maybe(&self.custom_pad_options);
+            if let Some(primitives) = &self.custom_pad_primitives {
+                s.append_list("primitives", |s| s.append_many(primitives));
+            }
+        });
+    }
+}
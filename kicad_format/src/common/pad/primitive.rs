@@ -1,10 +1,11 @@
 // TODO gr_bbox: https://dev-docs.kicad.org/en/file-formats/sexpr-intro/#_annotation_bounding_box
 
-use kicad_sexpr::{Sexpr, SexprList};
+use kicad_sexpr::SexprList;
 
 use crate::{
     common::{CoordinatePointList, Vec2D},
-    convert::{FromSexpr, MaybeFromSexpr, Parser, SexprListExt, ToSexpr, ToSexprWithName},
+    convert::{Extra, FromSexpr, ListBuilder, MaybeFromSexpr, Parser, SexprListExt, ToSexpr},
+    length::Length,
     simple_to_from_string, KiCadParseError,
 };
 
@@ -13,7 +14,8 @@ use crate::{
 #[derive(Debug, PartialEq, Clone)]
 pub struct PadGraphicsPrimitive {
     pub kind: PadGraphicsPrimitiveKind,
-    pub width: f32,
+    pub width: Length,
+    pub extra: Extra,
 }
 
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -42,11 +44,13 @@ impl FromSexpr for PadGraphicsPrimitive {
             "gr_poly",
         ])?;
 
+        let mut parser = parser.keyed();
+
         let (kind, width) = match name.as_str() {
             "gr_line" => {
                 let start = parser.expect_with_name::<Vec2D>("start")?;
                 let end = parser.expect_with_name::<Vec2D>("end")?;
-                let width = parser.expect_number_with_name("width")?;
+                let width = parser.expect_length_with_name("width")?;
 
                 (
                     PadGraphicsPrimitiveKind::Line(PadLine { start, end }),
@@ -56,7 +60,7 @@ impl FromSexpr for PadGraphicsPrimitive {
             "gr_bbox" => {
                 let start = parser.expect_with_name::<Vec2D>("start")?;
                 let end = parser.expect_with_name::<Vec2D>("end")?;
-                let width = parser.expect_number_with_name("width")?;
+                let width = parser.expect_length_with_name("width")?;
 
                 let fill = parser
                     .expect_symbol_with_name("fill")?
@@ -74,7 +78,7 @@ impl FromSexpr for PadGraphicsPrimitive {
             "gr_rect" => {
                 let start = parser.expect_with_name::<Vec2D>("start")?;
                 let end = parser.expect_with_name::<Vec2D>("end")?;
-                let width = parser.expect_number_with_name("width")?;
+                let width = parser.expect_length_with_name("width")?;
 
                 let fill = parser
                     .expect_symbol_with_name("fill")?
@@ -89,7 +93,7 @@ impl FromSexpr for PadGraphicsPrimitive {
                 let start = parser.expect_with_name::<Vec2D>("start")?;
                 let midpoint = parser.expect_with_name::<Vec2D>("mid")?;
                 let end = parser.expect_with_name::<Vec2D>("end")?;
-                let width = parser.expect_number_with_name("width")?;
+                let width = parser.expect_length_with_name("width")?;
 
                 (
                     PadGraphicsPrimitiveKind::Arc(PadArc {
@@ -103,7 +107,7 @@ impl FromSexpr for PadGraphicsPrimitive {
             "gr_circle" => {
                 let center = parser.expect_with_name::<Vec2D>("center")?;
                 let end = parser.expect_with_name::<Vec2D>("end")?;
-                let width = parser.expect_number_with_name("width")?;
+                let width = parser.expect_length_with_name("width")?;
                 let fill = parser
                     .expect_symbol_with_name("fill")?
                     .parse::<PrimitiveFillMode>()?;
@@ -114,20 +118,20 @@ impl FromSexpr for PadGraphicsPrimitive {
                 )
             }
             "gr_curve" => {
-                let points = parser.expect::<CoordinatePointList>().and_then(|v| {
+                let points = parser.expect_points().and_then(|v| {
                     v.try_into()
                         .map_err(|v: Vec<_>| KiCadParseError::IncorrectNumberOfPoints {
                             expected: 4,
                             found: v.len(),
                         })
                 })?;
-                let width = parser.expect_number_with_name("width")?;
+                let width = parser.expect_length_with_name("width")?;
 
                 (PadGraphicsPrimitiveKind::Curve(PadBezier { points }), width)
             }
             "gr_poly" => {
-                let points = parser.expect::<CoordinatePointList>()?;
-                let width = parser.expect_number_with_name("width")?;
+                let points = parser.expect_points()?;
+                let width = parser.expect_length_with_name("width")?;
                 let fill = parser
                     .expect_symbol_with_name("fill")?
                     .parse::<PrimitiveFillMode>()?;
@@ -140,7 +144,9 @@ impl FromSexpr for PadGraphicsPrimitive {
             _ => unreachable!(),
         };
 
-        Ok(Self { kind, width })
+        let extra = parser.expect_end_with_extra()?;
+
+        Ok(Self { kind, width, extra })
     }
 }
 
@@ -163,74 +169,46 @@ impl MaybeFromSexpr for PadGraphicsPrimitive {
 }
 
 impl ToSexpr for PadGraphicsPrimitive {
-    fn to_sexpr(&self) -> Sexpr {
-        let (name, elements, fill) = match &self.kind {
-            PadGraphicsPrimitiveKind::Line(line) => (
-                "gr_line",
-                vec![
-                    line.start.to_sexpr_with_name("start"),
-                    line.end.to_sexpr_with_name("end"),
-                ],
-                None,
-            ),
-            PadGraphicsPrimitiveKind::AnnotationBoundingBox(bbox) => (
-                "gr_bbox",
-                vec![
-                    bbox.start.to_sexpr_with_name("start"),
-                    bbox.end.to_sexpr_with_name("end"),
-                ],
-                Some(bbox.fill),
-            ),
-            PadGraphicsPrimitiveKind::Rectangle(rect) => (
-                "gr_rect",
-                vec![
-                    rect.start.to_sexpr_with_name("start"),
-                    rect.end.to_sexpr_with_name("end"),
-                ],
-                Some(rect.fill),
-            ),
-            PadGraphicsPrimitiveKind::Arc(arc) => (
-                "gr_arc",
-                vec![
-                    arc.start.to_sexpr_with_name("start"),
-                    arc.midpoint.to_sexpr_with_name("mid"),
-                    arc.end.to_sexpr_with_name("end"),
-                ],
-                None,
-            ),
-            PadGraphicsPrimitiveKind::Circle(circle) => (
-                "gr_circle",
-                vec![
-                    circle.center.to_sexpr_with_name("center"),
-                    circle.end.to_sexpr_with_name("end"),
-                ],
-                Some(circle.fill),
-            ),
-            PadGraphicsPrimitiveKind::Curve(bezier) => (
-                "gr_curve",
-                bezier
-                    .points
-                    .iter()
-                    .map(|p| p.to_sexpr())
-                    .collect::<Vec<_>>(),
-                None,
-            ),
-            PadGraphicsPrimitiveKind::Polygon(ploy) => {
-                ("gr_poly", vec![ploy.points.to_sexpr()], Some(ploy.fill))
-            }
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        let (name, fill) = match &self.kind {
+            PadGraphicsPrimitiveKind::Line(_) => ("gr_line", None),
+            PadGraphicsPrimitiveKind::AnnotationBoundingBox(bbox) => ("gr_bbox", Some(bbox.fill)),
+            PadGraphicsPrimitiveKind::Rectangle(rect) => ("gr_rect", Some(rect.fill)),
+            PadGraphicsPrimitiveKind::Arc(_) => ("gr_arc", None),
+            PadGraphicsPrimitiveKind::Circle(circle) => ("gr_circle", Some(circle.fill)),
+            PadGraphicsPrimitiveKind::Curve(_) => ("gr_curve", None),
+            PadGraphicsPrimitiveKind::Polygon(poly) => ("gr_poly", Some(poly.fill)),
         };
 
-        Sexpr::list_with_name(
-            name,
-            [
-                &elements.into_iter().map(Option::Some).collect::<Vec<_>>(),
-                &[
-                    Some(Sexpr::number_with_name("width", self.width)),
-                    fill.map(|f| Sexpr::symbol_with_name("fill", f)),
-                ][..],
-            ]
-            .concat(),
-        )
+        s.append_list_with_extra(name, &self.extra, |s| {
+            match &self.kind {
+                PadGraphicsPrimitiveKind::Line(line) => {
+                    s.append_with_name("start", &line.start);
+                    s.append_with_name("end", &line.end);
+                }
+                PadGraphicsPrimitiveKind::AnnotationBoundingBox(bbox) => {
+                    s.append_with_name("start", &bbox.start);
+                    s.append_with_name("end", &bbox.end);
+                }
+                PadGraphicsPrimitiveKind::Rectangle(rect) => {
+                    s.append_with_name("start", &rect.start);
+                    s.append_with_name("end", &rect.end);
+                }
+                PadGraphicsPrimitiveKind::Arc(arc) => {
+                    s.append_with_name("start", &arc.start);
+                    s.append_with_name("mid", &arc.midpoint);
+                    s.append_with_name("end", &arc.end);
+                }
+                PadGraphicsPrimitiveKind::Circle(circle) => {
+                    s.append_with_name("center", &circle.center);
+                    s.append_with_name("end", &circle.end);
+                }
+                PadGraphicsPrimitiveKind::Curve(bezier) => s.append_many(&bezier.points),
+                PadGraphicsPrimitiveKind::Polygon(poly) => s.append(&poly.points),
+            }
+            s.append_number_with_name("width", self.width);
+            s.append_maybe_symbol_with_name("fill", fill);
+        });
     }
 }
 
@@ -5,11 +5,12 @@
 //! [`FootprintShape`](crate::common::footprint::shape::FootprintShape), and
 //! [`PcbShape`](crate::pcb::graphics::shape::PcbShape).
 
-use kicad_sexpr::{Sexpr, SexprList};
+use kicad_sexpr::SexprList;
 
 use crate::{
-    convert::{FromSexpr, MaybeFromSexpr, Parser, SexprListExt, ToSexpr, ToSexprWithName},
-    KiCadParseError,
+    convert::{Extra, FromSexpr, ListBuilder, MaybeFromSexpr, Parser, SexprListExt, ToSexpr},
+    length::Length,
+    simple_maybe_from_sexpr, KiCadParseError,
 };
 
 use super::{Color, CoordinatePointList, Stroke, Uuid, Vec2D};
@@ -24,6 +25,7 @@ pub struct Shape {
     pub stroke: Stroke,
     pub fill: ShapeFillMode,
     pub uuid: Option<Uuid>,
+    pub extra: Extra,
 }
 
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -50,6 +52,8 @@ impl FromSexpr for Shape {
 
         let private = parser.maybe_symbol_matching("private");
 
+        let mut parser = parser.keyed();
+
         let kind = match name.as_str() {
             "arc" => ShapeKind::Arc(Arc {
                 start: parser.expect_with_name::<Vec2D>("start")?,
@@ -58,17 +62,17 @@ impl FromSexpr for Shape {
             }),
             "circle" => ShapeKind::Circle(Circle {
                 center: parser.expect_with_name::<Vec2D>("center")?,
-                radius: parser.expect_number_with_name("radius")?,
+                radius: parser.expect_length_with_name("radius")?,
             }),
             "rectangle" => ShapeKind::Rectangle(Rectangle {
                 start: parser.expect_with_name::<Vec2D>("start")?,
                 end: parser.expect_with_name::<Vec2D>("end")?,
             }),
             "polyline" => ShapeKind::PolyLine(PolyLine {
-                points: parser.expect::<CoordinatePointList>()?,
+                points: parser.expect_points()?,
             }),
             "bezier" => {
-                let points = parser.expect::<CoordinatePointList>().and_then(|v| {
+                let points = parser.expect_points().and_then(|v| {
                     v.try_into()
                         .map_err(|v: Vec<_>| KiCadParseError::IncorrectNumberOfPoints {
                             expected: 4,
@@ -82,15 +86,18 @@ impl FromSexpr for Shape {
         };
 
         let stroke = parser.expect::<Stroke>()?;
-        let fill = parser.expect::<ShapeFillMode>()?;
+        let fill = parser.expect_list_with_name("fill", ShapeFillMode::parse)?;
         let uuid = parser.maybe::<Uuid>()?;
 
+        let extra = parser.expect_end_with_extra()?;
+
         Ok(Self {
             private,
             kind,
             stroke,
             fill,
             uuid,
+            extra,
         })
     }
 }
@@ -106,54 +113,38 @@ impl MaybeFromSexpr for Shape {
 }
 
 impl ToSexpr for Shape {
-    fn to_sexpr(&self) -> Sexpr {
-        let (name, elements) = match &self.kind {
-            ShapeKind::Arc(arc) => (
-                "arc",
-                vec![
-                    arc.start.to_sexpr_with_name("start"),
-                    arc.midpoint.to_sexpr_with_name("mid"),
-                    arc.end.to_sexpr_with_name("end"),
-                ],
-            ),
-            ShapeKind::Circle(circle) => (
-                "circle",
-                vec![
-                    circle.center.to_sexpr_with_name("center"),
-                    Sexpr::number_with_name("radius", circle.radius),
-                ],
-            ),
-            ShapeKind::Rectangle(rect) => (
-                "rectangle",
-                vec![
-                    rect.start.to_sexpr_with_name("start"),
-                    rect.end.to_sexpr_with_name("end"),
-                ],
-            ),
-            ShapeKind::Bezier(bezier) => (
-                "bezier",
-                bezier
-                    .points
-                    .iter()
-                    .map(|p| p.to_sexpr())
-                    .collect::<Vec<_>>(),
-            ),
-            ShapeKind::PolyLine(ploy) => ("polyline", vec![ploy.points.to_sexpr()]),
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        let name = match &self.kind {
+            ShapeKind::Arc(_) => "arc",
+            ShapeKind::Circle(_) => "circle",
+            ShapeKind::Rectangle(_) => "rectangle",
+            ShapeKind::Bezier(_) => "bezier",
+            ShapeKind::PolyLine(_) => "polyline",
         };
 
-        Sexpr::list_with_name(
-            name,
-            [
-                &[self.private.then(|| Sexpr::symbol("private"))][..],
-                &elements.into_iter().map(Option::Some).collect::<Vec<_>>(),
-                &[
-                    Some(self.stroke.to_sexpr()),
-                    Some(self.fill.to_sexpr()),
-                    self.uuid.as_ref().map(ToSexpr::to_sexpr),
-                ][..],
-            ]
-            .concat(),
-        )
+        s.append_list_with_extra(name, &self.extra, |s| {
+            s.append_symbol_if("private", self.private);
+            match &self.kind {
+                ShapeKind::Arc(arc) => {
+                    s.append_with_name("start", &arc.start);
+                    s.append_with_name("mid", &arc.midpoint);
+                    s.append_with_name("end", &arc.end);
+                }
+                ShapeKind::Circle(circle) => {
+                    s.append_with_name("center", &circle.center);
+                    s.append_number_with_name("radius", circle.radius);
+                }
+                ShapeKind::Rectangle(rect) => {
+                    s.append_with_name("start", &rect.start);
+                    s.append_with_name("end", &rect.end);
+                }
+                ShapeKind::Bezier(bezier) => s.append_many(&bezier.points),
+                ShapeKind::PolyLine(poly) => s.append(&poly.points),
+            }
+            s.append(&self.stroke);
+            s.append(&self.fill);
+            s.append_maybe(&self.uuid);
+        });
     }
 }
 
@@ -171,7 +162,7 @@ pub struct Arc {
 #[derive(Debug, PartialEq, Clone)]
 pub struct Circle {
     pub center: Vec2D,
-    pub radius: f32,
+    pub radius: Length,
 }
 
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -213,18 +204,28 @@ pub enum ShapeFillMode {
     Color(Color),
 }
 
-impl FromSexpr for ShapeFillMode {
-    fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
-        parser.expect_symbol_matching("fill")?;
-
+impl ShapeFillMode {
+    /// Reads the children of a `fill` list. Shapes read it with
+    /// [`Parser::expect_list_with_name`], so that unknown lists within it are
+    /// kept in the [`Extra`](crate::convert::Extra) of the shape.
+    pub(crate) fn parse(parser: &mut Parser) -> Result<Self, KiCadParseError> {
         let mode = parser.expect_symbol_with_name("type")?;
-        let result = match mode.as_str() {
+
+        Ok(match mode.as_str() {
             "none" => Self::None,
             "outline" => Self::Outline,
             "background" => Self::Background,
             "color" => Self::Color(parser.expect::<Color>()?),
             value => return Err(KiCadParseError::invalid_enum_value::<Self>(value)),
-        };
+        })
+    }
+}
+
+impl FromSexpr for ShapeFillMode {
+    fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
+        parser.expect_symbol_matching("fill")?;
+
+        let result = Self::parse(&mut parser)?;
 
         parser.expect_end()?;
 
@@ -232,8 +233,10 @@ impl FromSexpr for ShapeFillMode {
     }
 }
 
+simple_maybe_from_sexpr!(ShapeFillMode, fill);
+
 impl ToSexpr for ShapeFillMode {
-    fn to_sexpr(&self) -> Sexpr {
+    fn write_sexpr(&self, s: &mut ListBuilder) {
         let kind = match self {
             Self::None => "none",
             Self::Outline => "outline",
@@ -241,11 +244,11 @@ impl ToSexpr for ShapeFillMode {
             Self::Color(_) => "color",
         };
 
-        let color = match self {
-            Self::Color(color) => Some(color.to_sexpr()),
-            _ => None,
-        };
-
-        Sexpr::list_with_name("fill", [Some(Sexpr::symbol_with_name("type", kind)), color])
+        s.append_list("fill", |s| {
+            s.append_symbol_with_name("type", kind);
+            if let Self::Color(color) = self {
+                s.append(color);
+            }
+        });
     }
 }
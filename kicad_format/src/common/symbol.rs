@@ -3,18 +3,25 @@
 
 use std::{fmt::Display, str::FromStr};
 
-use kicad_sexpr::{Sexpr, SexprList};
+use kicad_sexpr::SexprList;
 use regex::Regex;
 
 use crate::{
     convert::{
-        FromSexpr, MaybeFromSexpr, Parser, SexprListExt, ToSexpr, ToSexprWithName,
-        VecToMaybeSexprVec,
+        writes, Extra, FromSexpr, ListBuilder, MaybeFromSexpr, Parser, SexprListExt, ToSexpr,
     },
-    simple_maybe_from_sexpr, simple_to_from_string, KiCadParseError, SexprKind,
+    length::Length,
+    simple_maybe_from_sexpr, simple_to_from_string,
+    version::Syntax,
+    KiCadParseError, SexprKind,
 };
 
 use super::{
+    embedded::{
+        parse_embedded_files, parse_embedded_fonts, write_embedded_files, write_embedded_fonts,
+        EmbeddedFile,
+    },
+    footprint::jumper_groups,
     shape::{Shape, ShapeFillMode},
     Position, Stroke, TextEffects, Vec2D,
 };
@@ -30,6 +37,11 @@ pub struct LibSymbol {
     pub exclude_from_sim: Option<bool>,
     pub in_bom: bool,
     pub on_board: bool,
+    /// Whether pins sharing a number are connected by a jumper, since KiCad
+    /// 9.
+    pub duplicate_pin_numbers_are_jumpers: Option<bool>,
+    /// The groups of pins connected by a jumper, since KiCad 9.
+    pub jumper_pin_groups: Option<Vec<Vec<String>>>,
     pub properties: Vec<SymbolProperty>,
     /// Graphics items directly in the symbol (alternative to units)
     pub graphic_items: Vec<LibSymbolGraphicsItem>,
@@ -37,44 +49,47 @@ pub struct LibSymbol {
     pub pins: Vec<Pin>,
     pub units: Vec<LibSymbolSubUnit>,
     pub embedded_fonts: Option<bool>,
+    /// The files embedded in the symbol, since KiCad 9.
+    pub embedded_files: Option<Vec<EmbeddedFile>>,
+    pub extra: Extra,
 }
 
 impl FromSexpr for LibSymbol {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("symbol")?;
 
+        let mut parser = parser.keyed();
+
         let id = parser.expect_string()?.parse::<LibraryId>()?;
         let power = parser.maybe_empty_list_with_name("power")?;
         let hide_pin_numbers = parser
-            .maybe_list_with_name("pin_numbers")
-            .map(|mut p| {
-                // Handle both old format (hide) and new format (hide yes)
-                let hide = if p.maybe_symbol_matching("hide") {
-                    true
-                } else if let Some(hide_value) = p.maybe_bool_with_name("hide")? {
-                    hide_value
-                } else {
-                    return Err(KiCadParseError::NonMatchingSymbol {
-                        expected: "hide".to_string(),
-                        found: "unknown".to_string(),
-                    });
-                };
-                p.expect_end()?;
-                Ok::<_, KiCadParseError>(hide)
-            })
-            .transpose()?
+            .maybe_list_with_name("pin_numbers", |p| {
+                let hide = p.maybe_flag("hide")?;
+
+                Ok(hide)
+            })?
             .unwrap_or(false);
         let pin_names = parser.maybe::<PinNames>()?;
         let exclude_from_sim = parser.maybe_bool_with_name("exclude_from_sim")?;
         let in_bom = parser.expect_bool_with_name("in_bom")?;
         let on_board = parser.expect_bool_with_name("on_board")?;
+        let duplicate_pin_numbers_are_jumpers = parser.maybe_since(
+            Syntax::Jumpers,
+            "duplicate_pin_numbers_are_jumpers",
+            |parser| parser.maybe_bool_with_name("duplicate_pin_numbers_are_jumpers"),
+        )?;
+        let jumper_pin_groups =
+            parser.maybe_since(Syntax::Jumpers, "jumper_pin_groups", |parser| {
+                jumper_groups::parse(parser, "jumper_pin_groups")
+            })?;
         let properties = parser.expect_many::<SymbolProperty>()?;
         let graphic_items = parser.expect_many::<LibSymbolGraphicsItem>()?;
         let pins = parser.expect_many::<Pin>()?;
         let units = parser.expect_many::<LibSymbolSubUnit>()?;
-        let embedded_fonts = parser.maybe_bool_with_name("embedded_fonts")?;
+        let embedded_fonts = parse_embedded_fonts(&mut parser)?;
+        let embedded_files = parse_embedded_files(&mut parser)?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             id,
@@ -84,11 +99,15 @@ impl FromSexpr for LibSymbol {
             exclude_from_sim,
             in_bom,
             on_board,
+            duplicate_pin_numbers_are_jumpers,
+            jumper_pin_groups,
             properties,
             graphic_items,
             pins,
             units,
             embedded_fonts,
+            embedded_files,
+            extra,
         })
     }
 }
@@ -96,31 +115,33 @@ impl FromSexpr for LibSymbol {
 simple_maybe_from_sexpr!(LibSymbol, symbol);
 
 impl ToSexpr for LibSymbol {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "symbol",
-            [
-                &[
-                    Some(self.id.to_sexpr()),
-                    self.power.then(|| Sexpr::list_with_name("power", [])),
-                    self.hide_pin_numbers
-                        .then(|| Sexpr::symbol_with_name("pin_numbers", "hide")),
-                    self.pin_names.as_ref().map(ToSexpr::to_sexpr),
-                    self.exclude_from_sim
-                        .map(|v| Sexpr::bool_with_name("exclude_from_sim", v)),
-                    Some(Sexpr::bool_with_name("in_bom", self.in_bom)),
-                    Some(Sexpr::bool_with_name("on_board", self.on_board)),
-                ][..],
-                &self.properties.into_sexpr_vec(),
-                &self.graphic_items.into_sexpr_vec(),
-                &self.pins.into_sexpr_vec(),
-                &self.units.into_sexpr_vec(),
-                &[self
-                    .embedded_fonts
-                    .map(|v| Sexpr::bool_with_name("embedded_fonts", v))][..],
-            ]
-            .concat(),
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("symbol", &self.extra, |s| {
+            s.append(&self.id);
+            if self.power {
+                s.append_list("power", |_| {});
+            }
+            if self.hide_pin_numbers {
+                s.append_list("pin_numbers", |s| s.append_flag("hide", true));
+            }
+            s.append_maybe(&self.pin_names);
+            s.append_maybe_bool_with_name("exclude_from_sim", self.exclude_from_sim);
+            s.append_bool_with_name("in_bom", self.in_bom);
+            s.append_bool_with_name("on_board", self.on_board);
+            if writes(Syntax::Jumpers) {
+                s.append_maybe_bool_with_name(
+                    "duplicate_pin_numbers_are_jumpers",
+                    self.duplicate_pin_numbers_are_jumpers,
+                );
+                jumper_groups::write(s, "jumper_pin_groups", &self.jumper_pin_groups);
+            }
+            s.append_many(&self.properties);
+            s.append_many(&self.graphic_items);
+            s.append_many(&self.pins);
+            s.append_many(&self.units);
+            write_embedded_fonts(s, self.embedded_fonts);
+            write_embedded_files(s, &self.embedded_files);
+        });
     }
 }
 
@@ -132,24 +153,28 @@ pub struct LibSymbolSubUnit {
     pub unit_name: Option<String>,
     pub graphic_items: Vec<LibSymbolGraphicsItem>,
     pub pins: Vec<Pin>,
+    pub extra: Extra,
 }
 
 impl FromSexpr for LibSymbolSubUnit {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("symbol")?;
 
+        let mut parser = parser.keyed();
+
         let id = parser.expect_string()?.parse::<UnitId>()?;
         let unit_name = parser.maybe_string_with_name("unit_name")?;
         let graphic_items = parser.expect_many::<LibSymbolGraphicsItem>()?;
         let pins = parser.expect_many::<Pin>()?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             id,
             unit_name,
             graphic_items,
             pins,
+            extra,
         })
     }
 }
@@ -157,21 +182,13 @@ impl FromSexpr for LibSymbolSubUnit {
 simple_maybe_from_sexpr!(LibSymbolSubUnit, symbol);
 
 impl ToSexpr for LibSymbolSubUnit {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "symbol",
-            [
-                &[
-                    Some(self.id.to_sexpr()),
-                    self.unit_name
-                        .as_ref()
-                        .map(|s| Sexpr::string_with_name("unit_name", s)),
-                ][..],
-                &self.graphic_items.into_sexpr_vec(),
-                &self.pins.into_sexpr_vec(),
-            ]
-            .concat(),
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("symbol", &self.extra, |s| {
+            s.append(&self.id);
+            s.append_maybe_string_with_name("unit_name", self.unit_name.as_ref());
+            s.append_many(&self.graphic_items);
+            s.append_many(&self.pins);
+        });
     }
 }
 
@@ -231,14 +248,14 @@ impl Display for LibraryId {
 }
 
 impl ToSexpr for LibraryId {
-    fn to_sexpr(&self) -> Sexpr {
+    fn write_sexpr(&self, s: &mut ListBuilder) {
         let result = if let Some(library_nickname) = &self.library_nickname {
             format!("{}:{}", library_nickname, self.entry_name)
         } else {
             self.entry_name.clone()
         };
 
-        Sexpr::string(result)
+        s.append_string(result);
     }
 }
 
@@ -311,8 +328,8 @@ impl FromStr for UnitId {
 }
 
 impl ToSexpr for UnitId {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::string(format!("{}_{}_{}", self.parent, self.unit, self.style))
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_string(format!("{}_{}_{}", self.parent, self.unit, self.style));
     }
 }
 
@@ -354,32 +371,26 @@ mod unit_id_tests {
 #[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 #[derive(Debug, PartialEq, Clone)]
 pub struct PinNames {
-    pub offset: Option<f32>,
+    pub offset: Option<Length>,
     pub hide: bool,
-    /// Whether the hide was in legacy format (just "hide") vs new format ("hide yes/no")
-    pub hide_legacy_format: bool,
+    pub extra: Extra,
 }
 
 impl FromSexpr for PinNames {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("pin_names")?;
 
-        let offset = parser.maybe_number_with_name("offset")?;
-        // Handle both old format (hide) and new format (hide yes)
-        let (hide, hide_legacy_format) = if parser.maybe_symbol_matching("hide") {
-            (true, true)
-        } else if let Some(hide_value) = parser.maybe_bool_with_name("hide")? {
-            (hide_value, false)
-        } else {
-            (false, false)
-        };
+        let mut parser = parser.keyed();
+
+        let offset = parser.maybe_length_with_name("offset")?;
+        let hide = parser.maybe_flag("hide")?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             offset,
             hide,
-            hide_legacy_format,
+            extra,
         })
     }
 }
@@ -387,21 +398,11 @@ impl FromSexpr for PinNames {
 simple_maybe_from_sexpr!(PinNames, pin_names);
 
 impl ToSexpr for PinNames {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "pin_names",
-            [
-                self.offset.map(|o| Sexpr::number_with_name("offset", o)),
-                // Preserve original format: legacy uses just "hide", newer uses "hide yes/no"
-                if self.hide && self.hide_legacy_format {
-                    Some(Sexpr::symbol("hide"))
-                } else if self.hide {
-                    Some(Sexpr::bool_with_name("hide", true))
-                } else {
-                    None
-                },
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("pin_names", &self.extra, |s| {
+            s.append_maybe_number_with_name("offset", self.offset);
+            s.append_flag("hide", self.hide);
+        });
     }
 }
 
@@ -432,24 +433,31 @@ pub struct SymbolProperty {
     pub effects: TextEffects,
     /// Legacy ID field for older KiCad formats (pre-version 6.0)
     pub legacy_id: Option<i32>,
+    pub extra: Extra,
 }
 
 impl FromSexpr for SymbolProperty {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("property")?;
 
+        let mut parser = parser.keyed();
+
         let key = parser.expect_string()?;
         let value = parser.expect_string()?;
 
-        // Handle legacy format with (id X) before position
-        let legacy_id = parser.maybe_number_with_name("id")?.map(|n| n as i32);
+        // Older versions number the properties with an (id X) before the
+        // position
+        let legacy_id = match parser.reads(Syntax::UnnumberedProperties) {
+            Some(true) => None,
+            _ => parser.maybe_integer_with_name::<i32>("id")?,
+        };
 
         let position = parser.expect::<Position>()?;
         let show_name = parser.maybe_empty_list_with_name("show_name")?;
         let do_not_autoplace = parser.maybe_empty_list_with_name("do_not_autoplace")?;
         let effects = parser.expect::<TextEffects>()?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             key,
@@ -459,6 +467,7 @@ impl FromSexpr for SymbolProperty {
             do_not_autoplace,
             effects,
             legacy_id,
+            extra,
         })
     }
 }
@@ -466,22 +475,20 @@ impl FromSexpr for SymbolProperty {
 simple_maybe_from_sexpr!(SymbolProperty, property);
 
 impl ToSexpr for SymbolProperty {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "property",
-            [
-                Some(Sexpr::string(&self.key)),
-                Some(Sexpr::string(&self.value)),
-                self.legacy_id
-                    .map(|id| Sexpr::number_with_name("id", id as f32)),
-                Some(self.position.to_sexpr()),
-                self.show_name
-                    .then(|| Sexpr::list_with_name("show_name", [])),
-                self.do_not_autoplace
-                    .then(|| Sexpr::list_with_name("do_not_autoplace", [])),
-                Some(self.effects.to_sexpr()),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("property", &self.extra, |s| {
+            s.append_string(&self.key);
+            s.append_string(&self.value);
+            s.append_maybe_number_with_name("id", self.legacy_id);
+            s.append(&self.position);
+            if self.show_name {
+                s.append_list("show_name", |_| {});
+            }
+            if self.do_not_autoplace {
+                s.append_list("do_not_autoplace", |_| {});
+            }
+            s.append(&self.effects);
+        });
     }
 }
 
@@ -541,11 +548,11 @@ impl MaybeFromSexpr for LibSymbolGraphicsItem {
 }
 
 impl ToSexpr for LibSymbolGraphicsItem {
-    fn to_sexpr(&self) -> Sexpr {
+    fn write_sexpr(&self, s: &mut ListBuilder) {
         match self {
-            LibSymbolGraphicsItem::Shape(shape) => shape.to_sexpr(),
-            LibSymbolGraphicsItem::Text(text) => text.to_sexpr(),
-            LibSymbolGraphicsItem::TextBox(text_box) => text_box.to_sexpr(),
+            LibSymbolGraphicsItem::Shape(shape) => s.append(shape),
+            LibSymbolGraphicsItem::Text(text) => s.append(text),
+            LibSymbolGraphicsItem::TextBox(text_box) => s.append(text_box),
         }
     }
 }
@@ -558,39 +565,40 @@ pub struct LibSymbolText {
     pub text: String,
     pub position: Position,
     pub effects: TextEffects,
+    pub extra: Extra,
 }
 
 impl FromSexpr for LibSymbolText {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("text")?;
 
+        let mut parser = parser.keyed();
+
         let private = parser.maybe_symbol_matching("private");
         let text = parser.expect_string()?;
         let position = parser.expect::<Position>()?;
         let effects = parser.expect::<TextEffects>()?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             private,
             text,
             position,
             effects,
+            extra,
         })
     }
 }
 
 impl ToSexpr for LibSymbolText {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "text",
-            [
-                self.private.then(|| Sexpr::symbol("private")),
-                Some(Sexpr::string(&self.text)),
-                Some(self.position.to_sexpr()),
-                Some(self.effects.to_sexpr()),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("text", &self.extra, |s| {
+            s.append_symbol_if("private", self.private);
+            s.append_string(&self.text);
+            s.append(&self.position);
+            s.append(&self.effects);
+        });
     }
 }
 
@@ -603,39 +611,39 @@ pub struct LibSymbolTextBox {
     pub text: String,
     pub position: Position,
     pub size: Vec2D,
-    pub margins: Option<(f32, f32, f32, f32)>,
+    pub margins: Option<(f64, f64, f64, f64)>,
     pub stroke: Stroke,
     pub fill: ShapeFillMode,
     pub effects: TextEffects,
+    pub extra: Extra,
 }
 
 impl FromSexpr for LibSymbolTextBox {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("text_box")?;
 
+        let mut parser = parser.keyed();
+
         let private = parser.maybe_symbol_matching("private");
         let text = parser.expect_string()?;
         let position = parser.expect::<Position>()?;
         let size = parser.expect_with_name::<Vec2D>("size")?;
 
         // Parse optional margins (left, top, right, bottom)
-        let margins = parser
-            .maybe_list_with_name("margins")
-            .map(|mut p| {
-                let left = p.expect_number()? as f32;
-                let top = p.expect_number()? as f32;
-                let right = p.expect_number()? as f32;
-                let bottom = p.expect_number()? as f32;
-                p.expect_end()?;
-                Ok::<_, KiCadParseError>((left, top, right, bottom))
-            })
-            .transpose()?;
+        let margins = parser.maybe_list_with_name("margins", |p| {
+            let left = p.expect_number()?;
+            let top = p.expect_number()?;
+            let right = p.expect_number()?;
+            let bottom = p.expect_number()?;
+
+            Ok((left, top, right, bottom))
+        })?;
 
         let stroke = parser.expect::<Stroke>()?;
-        let fill = parser.expect::<ShapeFillMode>()?;
+        let fill = parser.expect_list_with_name("fill", ShapeFillMode::parse)?;
         let effects = parser.expect::<TextEffects>()?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             private,
@@ -646,35 +654,30 @@ impl FromSexpr for LibSymbolTextBox {
             stroke,
             fill,
             effects,
+            extra,
         })
     }
 }
 
 impl ToSexpr for LibSymbolTextBox {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "text_box",
-            [
-                self.private.then(|| Sexpr::symbol("private")),
-                Some(Sexpr::string(&self.text)),
-                Some(self.position.to_sexpr()),
-                Some(self.size.to_sexpr_with_name("size")),
-                self.margins.map(|(l, t, r, b)| {
-                    Sexpr::list_with_name(
-                        "margins",
-                        [
-                            Some(Sexpr::number(l)),
-                            Some(Sexpr::number(t)),
-                            Some(Sexpr::number(r)),
-                            Some(Sexpr::number(b)),
-                        ],
-                    )
-                }),
-                Some(self.stroke.to_sexpr()),
-                Some(self.fill.to_sexpr()),
-                Some(self.effects.to_sexpr()),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("text_box", &self.extra, |s| {
+            s.append_symbol_if("private", self.private);
+            s.append_string(&self.text);
+            s.append(&self.position);
+            s.append_with_name("size", &self.size);
+            if let Some((left, top, right, bottom)) = self.margins {
+                s.append_list("margins", |s| {
+                    s.append_number(left);
+                    s.append_number(top);
+                    s.append_number(right);
+                    s.append_number(bottom);
+                });
+            }
+            s.append(&self.stroke);
+            s.append(&self.fill);
+            s.append(&self.effects);
+        });
     }
 }
 
@@ -701,11 +704,9 @@ pub struct Pin {
     /// TODO: Pin position angles can only be 0, 90, 180, or 270 degrees.
     pub position: Position,
     /// The length token attribute defines the LENGTH of the pin.
-    pub length: f32,
+    pub length: Length,
     /// UNDOCUMENTED
     pub hide: bool,
-    /// Whether the hide was in legacy format (just "hide") vs new format ("hide yes/no")
-    pub hide_legacy_format: bool,
     /// The name token defines a quoted string containing the NAME of the pin
     /// and the TEXT_EFFECTS defines how the text is displayed.
     pub name: String,
@@ -716,42 +717,35 @@ pub struct Pin {
     pub number_effects: TextEffects,
     /// UNDOCUMENTED
     pub alternates: Vec<PinAlternate>,
+    pub extra: Extra,
 }
 
 impl FromSexpr for Pin {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("pin")?;
 
+        let mut parser = parser.keyed();
+
         let electrical_kind = parser.expect_symbol()?.parse::<PinElectricalKind>()?;
         let graphical_style = parser.expect_symbol()?.parse::<PinGraphicalStyle>()?;
         let position = parser.expect::<Position>()?;
-        let length = parser.expect_number_with_name("length")?;
-        // Handle both old format (hide) and new format (hide yes/no)
-        let (hide, hide_legacy_format) = if parser.maybe_symbol_matching("hide") {
-            (true, true)
-        } else if let Some(hide_value) = parser.maybe_bool_with_name("hide")? {
-            (hide_value, false)
-        } else {
-            (false, false)
-        };
-        let (name, name_effects) = parser.expect_list_with_name("name").and_then(|mut p| {
+        let length = parser.expect_length_with_name("length")?;
+        let hide = parser.maybe_flag("hide")?;
+        let (name, name_effects) = parser.expect_list_with_name("name", |p| {
             let name = p.expect_string()?;
             let name_effects = p.expect::<TextEffects>()?;
-            p.expect_end()?;
 
             Ok((name, name_effects))
         })?;
-        let (number, number_effects) =
-            parser.expect_list_with_name("number").and_then(|mut p| {
-                let number = p.expect_string()?;
-                let number_effects = p.expect::<TextEffects>()?;
-                p.expect_end()?;
+        let (number, number_effects) = parser.expect_list_with_name("number", |p| {
+            let number = p.expect_string()?;
+            let number_effects = p.expect::<TextEffects>()?;
 
-                Ok((number, number_effects))
-            })?;
+            Ok((number, number_effects))
+        })?;
         let alternates = parser.expect_many::<PinAlternate>()?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             electrical_kind,
@@ -759,12 +753,12 @@ impl FromSexpr for Pin {
             position,
             length,
             hide,
-            hide_legacy_format,
             name,
             name_effects,
             number,
             number_effects,
             alternates,
+            extra,
         })
     }
 }
@@ -772,42 +766,23 @@ impl FromSexpr for Pin {
 simple_maybe_from_sexpr!(Pin, pin);
 
 impl ToSexpr for Pin {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "pin",
-            [
-                &[
-                    Some(Sexpr::symbol(self.electrical_kind)),
-                    Some(Sexpr::symbol(self.graphical_style)),
-                    Some(self.position.to_sexpr()),
-                    Some(Sexpr::number_with_name("length", self.length)),
-                    // Preserve original format: legacy uses just "hide", newer uses "hide yes/no"
-                    if self.hide && self.hide_legacy_format {
-                        Some(Sexpr::symbol("hide"))
-                    } else if self.hide {
-                        Some(Sexpr::bool_with_name("hide", true))
-                    } else {
-                        None
-                    },
-                    Some(Sexpr::list_with_name(
-                        "name",
-                        [
-                            Some(Sexpr::string(&self.name)),
-                            Some(self.name_effects.to_sexpr()),
-                        ],
-                    )),
-                    Some(Sexpr::list_with_name(
-                        "number",
-                        [
-                            Some(Sexpr::string(&self.number)),
-                            Some(self.number_effects.to_sexpr()),
-                        ],
-                    )),
-                ][..],
-                &self.alternates.into_sexpr_vec(),
-            ]
-            .concat(),
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("pin", &self.extra, |s| {
+            s.append_symbol(self.electrical_kind);
+            s.append_symbol(self.graphical_style);
+            s.append(&self.position);
+            s.append_number_with_name("length", self.length);
+            s.append_flag("hide", self.hide);
+            s.append_list("name", |s| {
+                s.append_string(&self.name);
+                s.append(&self.name_effects);
+            });
+            s.append_list("number", |s| {
+                s.append_string(&self.number);
+                s.append(&self.number_effects);
+            });
+            s.append_many(&self.alternates);
+        });
     }
 }
 
@@ -896,22 +871,26 @@ pub struct PinAlternate {
     pub name: String,
     pub electrical_kind: PinElectricalKind,
     pub graphical_style: PinGraphicalStyle,
+    pub extra: Extra,
 }
 
 impl FromSexpr for PinAlternate {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("alternate")?;
 
+        let mut parser = parser.keyed();
+
         let name = parser.expect_string()?;
         let electrical_kind = parser.expect_symbol()?.parse::<PinElectricalKind>()?;
         let graphical_style = parser.expect_symbol()?.parse::<PinGraphicalStyle>()?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             name,
             electrical_kind,
             graphical_style,
+            extra,
         })
     }
 }
@@ -919,14 +898,11 @@ impl FromSexpr for PinAlternate {
 simple_maybe_from_sexpr!(PinAlternate, alternate);
 
 impl ToSexpr for PinAlternate {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "alternate",
-            [
-                Some(Sexpr::string(&self.name)),
-                Some(Sexpr::symbol(self.electrical_kind)),
-                Some(Sexpr::symbol(self.graphical_style)),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("alternate", &self.extra, |s| {
+            s.append_string(&self.name);
+            s.append_symbol(self.electrical_kind);
+            s.append_symbol(self.graphical_style);
+        });
     }
 }
@@ -0,0 +1,170 @@
+//! Common structures related to tables.
+//!
+//! Used both in schematic files and, since KiCad 9, board files.
+
+use super::Stroke;
+use crate::{
+    convert::{Extra, FromSexpr, ListBuilder, Parser, SexprListExt, ToSexpr},
+    length::Length,
+    simple_maybe_from_sexpr, KiCadParseError,
+};
+
+/// The lines drawn around a table and below its header row.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct TableBorder {
+    pub external: bool,
+    pub header: bool,
+    pub stroke: Option<Stroke>,
+    pub extra: Extra,
+}
+
+impl FromSexpr for TableBorder {
+    fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
+        parser.expect_symbol_matching("border")?;
+
+        let mut parser = parser.keyed();
+
+        let external = parser.expect_bool_with_name("external")?;
+        let header = parser.expect_bool_with_name("header")?;
+        let stroke = parser.maybe::<Stroke>()?;
+
+        let extra = parser.expect_end_with_extra()?;
+
+        Ok(Self {
+            external,
+            header,
+            stroke,
+            extra,
+        })
+    }
+}
+
+simple_maybe_from_sexpr!(TableBorder, border);
+
+impl ToSexpr for TableBorder {
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("border", &self.extra, |s| {
+            s.append_bool_with_name("external", self.external);
+            s.append_bool_with_name("header", self.header);
+            s.append_maybe(&self.stroke);
+        });
+    }
+}
+
+/// The lines drawn between the rows and columns of a table.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct TableSeparators {
+    pub rows: bool,
+    pub cols: bool,
+    pub stroke: Option<Stroke>,
+    pub extra: Extra,
+}
+
+impl FromSexpr for TableSeparators {
+    fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
+        parser.expect_symbol_matching("separators")?;
+
+        let mut parser = parser.keyed();
+
+        let rows = parser.expect_bool_with_name("rows")?;
+        let cols = parser.expect_bool_with_name("cols")?;
+        let stroke = parser.maybe::<Stroke>()?;
+
+        let extra = parser.expect_end_with_extra()?;
+
+        Ok(Self {
+            rows,
+            cols,
+            stroke,
+            extra,
+        })
+    }
+}
+
+simple_maybe_from_sexpr!(TableSeparators, separators);
+
+impl ToSexpr for TableSeparators {
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("separators", &self.extra, |s| {
+            s.append_bool_with_name("rows", self.rows);
+            s.append_bool_with_name("cols", self.cols);
+            s.append_maybe(&self.stroke);
+        });
+    }
+}
+
+/// Reads a list of lengths such as `(column_widths 10 20)`.
+pub(crate) fn parse_lengths_with_name(
+    parser: &mut Parser,
+    name: &str,
+) -> Result<Vec<Length>, KiCadParseError> {
+    let lengths = parser.maybe_list_with_name(name, |p| {
+        let mut lengths = Vec::new();
+        while let Some(length) = p.maybe_length() {
+            lengths.push(length);
+        }
+
+        Ok(lengths)
+    })?;
+
+    Ok(lengths.unwrap_or_default())
+}
+
+pub(crate) fn write_lengths_with_name(s: &mut ListBuilder, name: &str, lengths: &[Length]) {
+    if !lengths.is_empty() {
+        s.append_list(name, |s| {
+            for length in lengths {
+                s.append_number(*length);
+            }
+        });
+    }
+}
+
+/// Reads the `(margins left top right bottom)` of a table cell.
+pub(crate) fn parse_cell_margins(
+    parser: &mut Parser,
+) -> Result<Option<[Length; 4]>, KiCadParseError> {
+    parser.maybe_list_with_name("margins", |p| {
+        let margins = [
+            p.expect_length()?,
+            p.expect_length()?,
+            p.expect_length()?,
+            p.expect_length()?,
+        ];
+
+        Ok(margins)
+    })
+}
+
+pub(crate) fn write_cell_margins(s: &mut ListBuilder, margins: &Option<[Length; 4]>) {
+    if let Some(margins) = margins {
+        s.append_list("margins", |s| {
+            for margin in margins {
+                s.append_number(*margin);
+            }
+        });
+    }
+}
+
+/// Reads the `(span columns rows)` of a table cell. Cells that do not say
+/// span one column and one row.
+pub(crate) fn parse_cell_span(parser: &mut Parser) -> Result<(u32, u32), KiCadParseError> {
+    parser
+        .maybe_list_with_name("span", |p| {
+            let span = (p.expect_integer::<u32>()?, p.expect_integer::<u32>()?);
+
+            Ok(span)
+        })
+        .map(|span| span.unwrap_or((1, 1)))
+}
+
+pub(crate) fn write_cell_span(s: &mut ListBuilder, (columns, rows): (u32, u32)) {
+    s.append_list("span", |s| {
+        s.append_number(columns);
+        s.append_number(rows);
+    });
+}
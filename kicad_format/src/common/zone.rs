@@ -1,11 +1,8 @@
 //! Common structures related to board Zones.
 
-use std::vec;
-
-use kicad_sexpr::Sexpr;
-
 use crate::{
-    convert::{FromSexpr, Parser, SexprListExt, ToSexpr, ToSexprWithName, VecToMaybeSexprVec},
+    convert::{Extra, FieldOrder, FromSexpr, ListBuilder, Parser, SexprListExt, ToSexpr},
+    length::Length,
     simple_maybe_from_sexpr, simple_to_from_string, KiCadParseError,
 };
 
@@ -13,192 +10,142 @@ use super::{CoordinatePointList, LayerId, Uuid};
 
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, FromSexpr, ToSexpr)]
+#[sexpr(name = "zone")]
 pub struct Zone {
+    #[sexpr(locked)]
     pub locked: bool,
+    #[sexpr(name = "net")]
     pub net_number: i32,
     pub net_name: String,
+    #[sexpr(with = "zone_layers")]
     pub layers: Vec<LayerId>,
+    #[sexpr(id)]
     pub tstamp: Uuid,
     pub name: Option<String>,
     pub hatch: Hatch,
     pub priority: Option<i32>,
+    #[sexpr(name = "attr", with = "tear_drop_kind")]
     pub tear_drop_kind: Option<TearDropKind>,
     pub connect_pads: PadConnection,
-    pub min_thickness: f32,
+    pub min_thickness: Length,
     pub filled_areas_thickness: bool,
     pub keep_out_settings: Option<KeepOutSettings>,
+    #[sexpr(name = "fill")]
     pub fill_settings: FillSettings,
+    #[sexpr(with = "polygon")]
     pub polygon: Option<CoordinatePointList>,
     pub fill_polygons: Vec<FilledPolygon>,
     pub fill_segments: Vec<FilledSegments>,
+    #[sexpr(field_order)]
+    pub field_order: FieldOrder,
+    #[sexpr(extra)]
+    pub extra: Extra,
+}
+
+/// A zone has a `layer` when it is on a single layer, and `layers` otherwise.
+mod zone_layers {
+    use crate::{
+        common::LayerId,
+        convert::{ListBuilder, Parser},
+        KiCadParseError,
+    };
+
+    pub fn parse(parser: &mut Parser, _name: &str) -> Result<Vec<LayerId>, KiCadParseError> {
+        let maybe_layer = parser.maybe_list_with_name("layer", |p| {
+            let layer = p.expect_string()?.parse::<LayerId>()?;
+
+            Ok(layer)
+        })?;
+        let maybe_layers = parser.maybe_list_with_name("layers", |p| {
+            let layers = p
+                .expect_many_strings()?
+                .into_iter()
+                .map(|s| s.parse())
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(layers)
+        })?;
+
+        match (maybe_layer, maybe_layers) {
+            (Some(layer), None) => Ok(vec![layer]),
+            (None, Some(layers)) => Ok(layers),
+            (Some(_), Some(_)) => Err(KiCadParseError::FoundMutuallyExclusiveFields {
+                field1: "layer".to_string(),
+                field2: "layers".to_string(),
+            }),
+            _ => Err(KiCadParseError::ExpectedMutuallyExclusiveField {
+                field1: "layer".to_string(),
+                field2: "layers".to_string(),
+            }),
+        }
+    }
+
+    pub fn write(s: &mut ListBuilder, _name: &str, layers: &[LayerId]) {
+        if layers.len() == 1 && !layers[0].is_wildcard() {
+            s.append_string_with_name("layer", layers[0]);
+        } else {
+            s.append_list("layers", |s| {
+                for layer in layers {
+                    s.append_string(*layer);
+                }
+            });
+        }
+    }
 }
 
-impl FromSexpr for Zone {
-    fn from_sexpr(mut parser: Parser) -> Result<Self, crate::KiCadParseError> {
-        parser.expect_symbol_matching("zone")?;
+/// The teardrop kind is written as `(attr (teardrop (type padvia)))`.
+mod tear_drop_kind {
+    use super::TearDropKind;
+    use crate::{
+        convert::{ListBuilder, Parser},
+        KiCadParseError,
+    };
 
-        let locked = parser.maybe_symbol_matching("locked");
-        let net_number = parser.expect_number_with_name("net")? as i32;
-        let net_name = parser.expect_string_with_name("net_name")?;
+    pub fn parse(parser: &mut Parser, name: &str) -> Result<Option<TearDropKind>, KiCadParseError> {
+        parser.maybe_list_with_name(name, |p| {
+            let kind = p.expect_list_with_name("teardrop", |p| {
+                let kind = p.expect_symbol_with_name("type")?.parse()?;
 
-        /* Zone may have a `layer` or a `layers` field */
-        let maybe_layer = parser
-            .maybe_list_with_name("layer")
-            .map(|mut p| {
-                let layer = p.expect_string()?.parse::<LayerId>()?;
-                p.expect_end()?;
-                Ok::<_, KiCadParseError>(layer)
-            })
-            .transpose()?;
-        let maybe_layers = parser
-            .maybe_list_with_name("layers")
-            .map(|mut p| {
-                let layers = p
-                    .expect_many_strings()?
-                    .into_iter()
-                    .map(|s| s.parse())
-                    .collect::<Result<Vec<_>, _>>()?;
-                p.expect_end()?;
-                Ok::<_, KiCadParseError>(layers)
-            })
-            .transpose()?;
-        let layers = match (maybe_layer, maybe_layers) {
-            (Some(layer), None) => vec![layer],
-            (None, Some(layers)) => layers,
-            (Some(_), Some(_)) => {
-                return Err(KiCadParseError::FoundMutuallyExclusiveFields {
-                    field1: "layer".to_string(),
-                    field2: "layers".to_string(),
-                });
-            }
-            _ => {
-                return Err(KiCadParseError::ExpectedMutuallyExclusiveField {
-                    field1: "layer".to_string(),
-                    field2: "layers".to_string(),
-                });
-            }
-        };
-        let tstamp = parser.expect_with_name::<Uuid>("tstamp")?;
-        let name = parser.maybe_string_with_name("name")?;
-        let hatch = parser.expect::<Hatch>()?;
-        let priority = parser.maybe_number_with_name("priority")?.map(|n| n as i32);
-        let tear_drop_kind = parser
-            .maybe_list_with_name("attr")
-            .map(|mut p| {
-                let kind = p.expect_list_with_name("teardrop").and_then(|mut p| {
-                    let kind = p.expect_symbol_with_name("type")?.parse()?;
-                    p.expect_end()?;
-
-                    Ok::<_, KiCadParseError>(kind)
-                })?;
-                p.expect_end()?;
-
-                Ok::<_, KiCadParseError>(kind)
-            })
-            .transpose()?;
-        let connect_pads = parser.expect::<PadConnection>()?;
-        let min_thickness = parser.expect_number_with_name("min_thickness")?;
-        let filled_areas_thickness = parser.expect_bool_with_name("filled_areas_thickness")?;
-        let keep_out_settings = parser.maybe::<KeepOutSettings>()?;
-        let fill_settings = parser.expect::<FillSettings>()?;
-        let polygon = parser
-            .maybe_list_with_name("polygon")
-            .map(|mut p| {
-                let polygon = p.expect::<CoordinatePointList>()?;
-                p.expect_end()?;
-                Ok::<_, KiCadParseError>(polygon)
-            })
-            .transpose()?;
-        let fill_polygons = parser.expect_many::<FilledPolygon>()?;
-        let fill_segments = parser.expect_many::<FilledSegments>()?;
+                Ok(kind)
+            })?;
 
-        Ok(Self {
-            locked,
-            net_number,
-            net_name,
-            layers,
-            tstamp,
-            name,
-            hatch,
-            priority,
-            tear_drop_kind,
-            connect_pads,
-            min_thickness,
-            filled_areas_thickness,
-            keep_out_settings,
-            fill_settings,
-            polygon,
-            fill_polygons,
-            fill_segments,
+            Ok(kind)
         })
     }
+
+    pub fn write(s: &mut ListBuilder, name: &str, kind: &Option<TearDropKind>) {
+        if let Some(kind) = *kind {
+            s.append_list(name, |s| {
+                s.append_list("teardrop", |s| s.append_symbol_with_name("type", kind));
+            });
+        }
+    }
 }
 
-simple_maybe_from_sexpr!(Zone, zone);
+/// The outline of a zone is written as `(polygon (pts ...))`.
+mod polygon {
+    use crate::{
+        common::CoordinatePointList,
+        convert::{ListBuilder, Parser},
+        KiCadParseError,
+    };
 
-impl ToSexpr for Zone {
-    fn to_sexpr(&self) -> Sexpr {
-        let layer_or_layers = if self.layers.len() == 1 && !self.layers[0].is_wildcard() {
-            Sexpr::string_with_name("layer", self.layers[0])
-        } else {
-            Sexpr::list_with_name(
-                "layers",
-                self.layers
-                    .iter()
-                    .map(Clone::clone)
-                    .map(Sexpr::string)
-                    .map(Option::Some)
-                    .collect::<Vec<_>>(),
-            )
-        };
-
-        Sexpr::list_with_name(
-            "zone",
-            [
-                vec![
-                    self.locked.then(|| Sexpr::symbol("locked")),
-                    Some(Sexpr::number_with_name("net", self.net_number as f32)),
-                    Some(Sexpr::string_with_name("net_name", &self.net_name)),
-                    Some(layer_or_layers),
-                    Some(self.tstamp.to_sexpr_with_name("tstamp")),
-                    self.name
-                        .as_ref()
-                        .map(|name| Sexpr::string_with_name("name", name)),
-                    Some(self.hatch.to_sexpr()),
-                    self.priority
-                        .map(|p| Sexpr::number_with_name("priority", p as f32)),
-                    self.tear_drop_kind.map(|k| {
-                        Sexpr::list_with_name(
-                            "attr",
-                            [Sexpr::list_with_name(
-                                "teardrop",
-                                [Sexpr::symbol_with_name("type", k)].map(Option::Some),
-                            )]
-                            .map(Option::Some),
-                        )
-                    }),
-                    Some(self.connect_pads.to_sexpr()),
-                    Some(Sexpr::number_with_name("min_thickness", self.min_thickness)),
-                    Some(Sexpr::symbol_with_name(
-                        "filled_areas_thickness",
-                        if self.filled_areas_thickness {
-                            "yes"
-                        } else {
-                            "no"
-                        },
-                    )),
-                    self.keep_out_settings.as_ref().map(ToSexpr::to_sexpr),
-                    Some(self.fill_settings.to_sexpr()),
-                    self.polygon
-                        .as_ref()
-                        .map(|p| Sexpr::list_with_name("polygon", [Some(p.to_sexpr())])),
-                ],
-                self.fill_polygons.into_sexpr_vec(),
-                self.fill_segments.into_sexpr_vec(),
-            ]
-            .concat(),
-        )
+    pub fn parse(
+        parser: &mut Parser,
+        name: &str,
+    ) -> Result<Option<CoordinatePointList>, KiCadParseError> {
+        parser.maybe_list_with_name(name, |p| {
+            let polygon = p.expect_points()?;
+
+            Ok(polygon)
+        })
+    }
+
+    pub fn write(s: &mut ListBuilder, name: &str, polygon: &Option<CoordinatePointList>) {
+        if let Some(polygon) = polygon {
+            s.append_list(name, |s| s.append(polygon));
+        }
     }
 }
 
@@ -207,7 +154,7 @@ impl ToSexpr for Zone {
 #[derive(Debug, PartialEq, Clone)]
 pub struct Hatch {
     pub style: HatchStyle,
-    pub pitch: f32,
+    pub pitch: Length,
 }
 
 impl FromSexpr for Hatch {
@@ -215,7 +162,7 @@ impl FromSexpr for Hatch {
         parser.expect_symbol_matching("hatch")?;
 
         let style = parser.expect_symbol()?.parse()?;
-        let pitch = parser.expect_number()?;
+        let pitch = parser.expect_length()?;
 
         parser.expect_end()?;
 
@@ -223,15 +170,14 @@ impl FromSexpr for Hatch {
     }
 }
 
+simple_maybe_from_sexpr!(Hatch, hatch);
+
 impl ToSexpr for Hatch {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "hatch",
-            [
-                Some(Sexpr::symbol(self.style)),
-                Some(Sexpr::number(self.pitch)),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list("hatch", |s| {
+            s.append_symbol(self.style);
+            s.append_number(self.pitch);
+        });
     }
 }
 
@@ -270,34 +216,40 @@ simple_to_from_string! {
 #[derive(Debug, PartialEq, Clone)]
 pub struct PadConnection {
     pub kind: Option<PadConnectionKind>,
-    pub clearance: f32,
+    pub clearance: Length,
+    pub extra: Extra,
 }
 
 impl FromSexpr for PadConnection {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("connect_pads")?;
 
+        let mut parser = parser.keyed();
+
         let kind = parser
             .maybe_symbol()
             .map(|s| s.parse::<PadConnectionKind>())
             .transpose()?;
-        let clearance = parser.expect_number_with_name("clearance")?;
+        let clearance = parser.expect_length_with_name("clearance")?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
-        Ok(Self { kind, clearance })
+        Ok(Self {
+            kind,
+            clearance,
+            extra,
+        })
     }
 }
 
+simple_maybe_from_sexpr!(PadConnection, connect_pads);
+
 impl ToSexpr for PadConnection {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "connect_pads",
-            [
-                self.kind.map(Sexpr::symbol),
-                Some(Sexpr::number_with_name("clearance", self.clearance)),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("connect_pads", &self.extra, |s| {
+            s.append_maybe_symbol(self.kind);
+            s.append_number_with_name("clearance", self.clearance);
+        });
     }
 }
 
@@ -326,19 +278,22 @@ pub struct KeepOutSettings {
     pub pads: KeepOut,
     pub copper_pour: KeepOut,
     pub footprints: KeepOut,
+    pub extra: Extra,
 }
 
 impl FromSexpr for KeepOutSettings {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("keepout")?;
 
+        let mut parser = parser.keyed();
+
         let tracks = parser.expect_symbol_with_name("tracks")?.parse()?;
         let vias = parser.expect_symbol_with_name("vias")?.parse()?;
         let pads = parser.expect_symbol_with_name("pads")?.parse()?;
         let copper_pour = parser.expect_symbol_with_name("copperpour")?.parse()?;
         let footprints = parser.expect_symbol_with_name("footprints")?.parse()?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             tracks,
@@ -346,6 +301,7 @@ impl FromSexpr for KeepOutSettings {
             pads,
             copper_pour,
             footprints,
+            extra,
         })
     }
 }
@@ -353,17 +309,14 @@ impl FromSexpr for KeepOutSettings {
 simple_maybe_from_sexpr!(KeepOutSettings, keepout);
 
 impl ToSexpr for KeepOutSettings {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "keepout",
-            [
-                Some(Sexpr::symbol_with_name("tracks", self.tracks)),
-                Some(Sexpr::symbol_with_name("vias", self.vias)),
-                Some(Sexpr::symbol_with_name("pads", self.pads)),
-                Some(Sexpr::symbol_with_name("copperpour", self.copper_pour)),
-                Some(Sexpr::symbol_with_name("footprints", self.footprints)),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("keepout", &self.extra, |s| {
+            s.append_symbol_with_name("tracks", self.tracks);
+            s.append_symbol_with_name("vias", self.vias);
+            s.append_symbol_with_name("pads", self.pads);
+            s.append_symbol_with_name("copperpour", self.copper_pour);
+            s.append_symbol_with_name("footprints", self.footprints);
+        });
     }
 }
 
@@ -387,49 +340,50 @@ simple_to_from_string! {
 pub struct FillSettings {
     pub filled: bool,
     pub mode: Option<ZoneFillMode>,
-    pub thermal_gap: f32,
-    pub thermal_bridge_width: f32,
+    pub thermal_gap: Length,
+    pub thermal_bridge_width: Length,
     pub smoothing: Option<FillSmoothingStyle>,
-    pub radius: Option<f32>,
+    pub radius: Option<Length>,
     pub island_removal_mode: Option<FillIslandRemovalMode>,
-    pub island_area_min: Option<f32>,
-    pub hatch_thickness: Option<f32>,
-    pub hatch_gap: Option<f32>,
-    pub hatch_orientation: Option<f32>,
+    pub island_area_min: Option<f64>,
+    pub hatch_thickness: Option<Length>,
+    pub hatch_gap: Option<Length>,
+    pub hatch_orientation: Option<f64>,
     pub hatch_smoothing_level: Option<HatchSmoothingLevel>,
-    pub hatch_smoothing_value: Option<f32>,
+    pub hatch_smoothing_value: Option<f64>,
     pub hatch_border_algorithm: Option<HatchBorderAlgorithm>,
-    pub hatch_min_hole_area: Option<f32>,
+    pub hatch_min_hole_area: Option<f64>,
+    pub extra: Extra,
 }
 
 impl FromSexpr for FillSettings {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("fill")?;
 
+        let mut parser = parser.keyed();
+
         let filled = parser.maybe_symbol_matching("yes");
         let mode = parser
             .maybe_symbol_with_name("mode")?
             .map(|s| s.parse::<ZoneFillMode>())
             .transpose()?;
-        let thermal_gap = parser.expect_number_with_name("thermal_gap")?;
-        let thermal_bridge_width = parser.expect_number_with_name("thermal_bridge_width")?;
+        let thermal_gap = parser.expect_length_with_name("thermal_gap")?;
+        let thermal_bridge_width = parser.expect_length_with_name("thermal_bridge_width")?;
         let smoothing = parser
             .maybe_symbol_with_name("smoothing")?
             .map(|s| s.parse())
             .transpose()?;
-        let radius = parser.maybe_number_with_name("radius")?;
+        let radius = parser.maybe_length_with_name("radius")?;
         let island_removal_mode = parser
-            .maybe_number_with_name("island_removal_mode")?
-            .map(|n| n as u8)
+            .maybe_integer_with_name::<u8>("island_removal_mode")?
             .map(FillIslandRemovalMode::try_from)
             .transpose()?;
         let island_area_min = parser.maybe_number_with_name("island_area_min")?;
-        let hatch_thickness = parser.maybe_number_with_name("hatch_thickness")?;
-        let hatch_gap = parser.maybe_number_with_name("hatch_gap")?;
+        let hatch_thickness = parser.maybe_length_with_name("hatch_thickness")?;
+        let hatch_gap = parser.maybe_length_with_name("hatch_gap")?;
         let hatch_orientation = parser.maybe_number_with_name("hatch_orientation")?;
         let hatch_smoothing_level = parser
-            .maybe_number_with_name("hatch_smoothing_level")?
-            .map(|n| n as u8)
+            .maybe_integer_with_name::<u8>("hatch_smoothing_level")?
             .map(HatchSmoothingLevel::try_from)
             .transpose()?;
         let hatch_smoothing_value = parser.maybe_number_with_name("hatch_smoothing_value")?;
@@ -439,7 +393,7 @@ impl FromSexpr for FillSettings {
             .transpose()?;
         let hatch_min_hole_area = parser.maybe_number_with_name("hatch_min_hole_area")?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             filled,
@@ -457,45 +411,38 @@ impl FromSexpr for FillSettings {
             hatch_smoothing_value,
             hatch_border_algorithm,
             hatch_min_hole_area,
+            extra,
         })
     }
 }
 
+simple_maybe_from_sexpr!(FillSettings, fill);
+
 impl ToSexpr for FillSettings {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "fill",
-            [
-                self.filled.then(|| Sexpr::symbol("yes")),
-                self.mode.map(|m| Sexpr::symbol_with_name("mode", m)),
-                Some(Sexpr::number_with_name("thermal_gap", self.thermal_gap)),
-                Some(Sexpr::number_with_name(
-                    "thermal_bridge_width",
-                    self.thermal_bridge_width,
-                )),
-                self.smoothing
-                    .map(|s| Sexpr::symbol_with_name("smoothing", s)),
-                self.radius.map(|r| Sexpr::number_with_name("radius", r)),
-                self.island_removal_mode
-                    .map(|m| Sexpr::number_with_name("island_removal_mode", m as u8 as f32)),
-                self.island_area_min
-                    .map(|a| Sexpr::number_with_name("island_area_min", a)),
-                self.hatch_thickness
-                    .map(|t| Sexpr::number_with_name("hatch_thickness", t)),
-                self.hatch_gap
-                    .map(|g| Sexpr::number_with_name("hatch_gap", g)),
-                self.hatch_orientation
-                    .map(|o| Sexpr::number_with_name("hatch_orientation", o)),
-                self.hatch_smoothing_level
-                    .map(|l| Sexpr::number_with_name("hatch_smoothing_level", l as u8 as f32)),
-                self.hatch_smoothing_value
-                    .map(|v| Sexpr::number_with_name("hatch_smoothing_value", v)),
-                self.hatch_border_algorithm
-                    .map(|a| Sexpr::symbol_with_name("hatch_border_algorithm", a)),
-                self.hatch_min_hole_area
-                    .map(|a| Sexpr::number_with_name("hatch_min_hole_area", a)),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("fill", &self.extra, |s| {
+            s.append_symbol_if("yes", self.filled);
+            s.append_maybe_symbol_with_name("mode", self.mode);
+            s.append_number_with_name("thermal_gap", self.thermal_gap);
+            s.append_number_with_name("thermal_bridge_width", self.thermal_bridge_width);
+            s.append_maybe_symbol_with_name("smoothing", self.smoothing);
+            s.append_maybe_number_with_name("radius", self.radius);
+            s.append_maybe_number_with_name(
+                "island_removal_mode",
+                self.island_removal_mode.map(|m| m as u8),
+            );
+            s.append_maybe_number_with_name("island_area_min", self.island_area_min);
+            s.append_maybe_number_with_name("hatch_thickness", self.hatch_thickness);
+            s.append_maybe_number_with_name("hatch_gap", self.hatch_gap);
+            s.append_maybe_number_with_name("hatch_orientation", self.hatch_orientation);
+            s.append_maybe_number_with_name(
+                "hatch_smoothing_level",
+                self.hatch_smoothing_level.map(|l| l as u8),
+            );
+            s.append_maybe_number_with_name("hatch_smoothing_value", self.hatch_smoothing_value);
+            s.append_maybe_symbol_with_name("hatch_border_algorithm", self.hatch_border_algorithm);
+            s.append_maybe_number_with_name("hatch_min_hole_area", self.hatch_min_hole_area);
+        });
     }
 }
 
@@ -600,24 +547,28 @@ pub struct FilledPolygon {
     pub layer: LayerId,
     pub island: bool,
     pub polygon: CoordinatePointList,
+    pub extra: Extra,
 }
 
 impl FromSexpr for FilledPolygon {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("filled_polygon")?;
 
+        let mut parser = parser.keyed();
+
         let layer = parser
             .expect_string_with_name("layer")?
             .parse::<LayerId>()?;
         let island = parser.maybe_empty_list_with_name("island")?;
-        let polygon = parser.expect::<CoordinatePointList>()?;
+        let polygon = parser.expect_points()?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             layer,
             island,
             polygon,
+            extra,
         })
     }
 }
@@ -625,15 +576,14 @@ impl FromSexpr for FilledPolygon {
 simple_maybe_from_sexpr!(FilledPolygon, filled_polygon);
 
 impl ToSexpr for FilledPolygon {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "filled_polygon",
-            [
-                Some(Sexpr::string_with_name("layer", self.layer)),
-                self.island.then(|| Sexpr::list_with_name("island", [])),
-                Some(self.polygon.to_sexpr()),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("filled_polygon", &self.extra, |s| {
+            s.append_string_with_name("layer", self.layer);
+            if self.island {
+                s.append_list("island", |_| {});
+            }
+            s.append(&self.polygon);
+        });
     }
 }
 
@@ -643,33 +593,37 @@ impl ToSexpr for FilledPolygon {
 pub struct FilledSegments {
     pub layer: LayerId,
     pub segments: CoordinatePointList,
+    pub extra: Extra,
 }
 
 impl FromSexpr for FilledSegments {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("fill_segments")?;
 
+        let mut parser = parser.keyed();
+
         let layer = parser
             .expect_string_with_name("layer")?
             .parse::<LayerId>()?;
-        let segments = parser.expect::<CoordinatePointList>()?;
+        let segments = parser.expect_points()?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
-        Ok(Self { layer, segments })
+        Ok(Self {
+            layer,
+            segments,
+            extra,
+        })
     }
 }
 
 simple_maybe_from_sexpr!(FilledSegments, fill_segments);
 
 impl ToSexpr for FilledSegments {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "fill_segments",
-            [
-                Some(Sexpr::string_with_name("layer", self.layer)),
-                Some(self.segments.to_sexpr()),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("fill_segments", &self.extra, |s| {
+            s.append_string_with_name("layer", self.layer);
+            s.append(&self.segments);
+        });
     }
 }
@@ -1,13 +1,28 @@
 //! Conversion traits and utilities for converting between KiCad's S-expressions
 //! and Rust types.
 //!
-//! This module provides traits like [`FromSexpr`] and [`ToSexpr`]
-
-use std::{iter::Peekable, vec::IntoIter};
-
-use kicad_sexpr::{Sexpr, SexprList};
-
-use crate::{KiCadParseError, SexprKind};
+//! This module provides traits like [`FromSexpr`] and [`ToSexpr`], along with
+//! derive macros of the same names that implement them for structs. See the
+//! `kicad_format_derive` crate for the attributes the derives accept.
+
+use std::{
+    cell::{Cell, RefCell},
+    cmp::Reverse,
+    collections::{HashMap, VecDeque},
+    io::{self, Write},
+    rc::Rc,
+};
+
+pub use kicad_format_derive::{FromSexpr, ToSexpr};
+use kicad_sexpr::{stream::Writer, Number, Sexpr, SexprList};
+
+use crate::{
+    angle::Angle,
+    common::{CoordinatePointList, Uuid, Vec2D},
+    length::Length,
+    version::{FormatVersion, Syntax},
+    Diagnostic, KiCadParseError, PathSegment, SexprKind,
+};
 
 /* ========= DESERIALIZATION ========= */
 
@@ -40,53 +55,390 @@ impl SexprListExt for SexprList {
 }
 
 /// A parser used internally for easily lifting fields out of S-expression trees.
+///
+/// By default the children of the list are consumed strictly in order. A
+/// parser switched to keyed mode with [`Parser::keyed`] instead looks up child
+/// lists by their name and flags by their symbol wherever they are in the
+/// list, while positional values are still consumed in order.
 #[derive(Debug, Clone)]
 pub struct Parser {
-    inner: Peekable<IntoIter<Sexpr>>,
+    /// The children of the list, with the ones already consumed taken out
+    items: Vec<Option<Sexpr>>,
+    /// The position of the first child that has not been consumed
+    cursor: usize,
+    keys: Option<Keys>,
+    /// Where problems are recorded in lenient mode. Shared with the parsers
+    /// of the children.
+    diagnostics: Option<Diagnostics>,
+    /// The version of the file being parsed, once it has been read. Shared
+    /// with the parsers of the children.
+    version: Option<FormatVersion>,
+    /// Unknown lists found in the children read inline with
+    /// [`Parser::expect_list_with_name`], kept until
+    /// [`Parser::expect_end_with_extra`] hands them over to the [`Extra`].
+    inline_extra: Vec<(usize, String, Extra)>,
+}
+
+pub(crate) type Diagnostics = Rc<RefCell<Vec<Diagnostic>>>;
+
+/// The lookup table of a keyed [`Parser`].
+#[derive(Debug, Clone)]
+struct Keys {
+    /// Positions of the child lists by their name. Positions that have been
+    /// consumed in the meantime are skipped when looking them up.
+    lists: HashMap<String, VecDeque<usize>>,
+    order: FieldOrder,
 }
 
 impl Parser {
     pub fn new(inner: SexprList) -> Self {
         Self {
-            inner: inner.into_iter().peekable(),
+            items: inner.into_iter().map(Some).collect(),
+            cursor: 0,
+            keys: None,
+            diagnostics: None,
+            version: None,
+            inline_extra: Vec::new(),
+        }
+    }
+
+    /// Returns the version of the file being parsed, if it is known.
+    ///
+    /// Types that are written differently by different versions of KiCad can
+    /// use it to tell which syntax to expect.
+    pub fn version(&self) -> Option<FormatVersion> {
+        self.version
+    }
+
+    /// Sets the version of the file being parsed. Called by the file types
+    /// right after reading their `version` token, so that the parsers of all
+    /// the children see it.
+    pub fn set_version(&mut self, version: FormatVersion) {
+        self.version = Some(version);
+    }
+
+    /// Whether the file being parsed uses the given syntax, or `None` if its
+    /// version is not known, in which case the syntax of every version is
+    /// accepted.
+    pub fn reads(&self, syntax: Syntax) -> Option<bool> {
+        self.version.map(|version| version.supports(syntax))
+    }
+
+    /// Checks that the file being parsed uses `syntax`, as it contains
+    /// `token`, which only the versions that do write. In lenient mode the
+    /// token is kept and the problem is recorded.
+    pub fn check_syntax(&self, syntax: Syntax, token: &str) -> Result<(), KiCadParseError> {
+        match self.version {
+            Some(version) if !version.supports(syntax) => {
+                self.recover(KiCadParseError::UnsupportedInVersion {
+                    token: token.to_string(),
+                    version: version.version,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Reads an optional field with `parse`, and checks that the file being
+    /// parsed uses `syntax` if the field is there. See
+    /// [`Parser::check_syntax`].
+    pub fn maybe_since<T>(
+        &mut self,
+        syntax: Syntax,
+        name: &str,
+        parse: impl FnOnce(&mut Parser) -> Result<Option<T>, KiCadParseError>,
+    ) -> Result<Option<T>, KiCadParseError> {
+        let value = parse(self)?;
+
+        if value.is_some() {
+            self.check_syntax(syntax, name)?;
+        }
+
+        Ok(value)
+    }
+
+    /// Switches the parser to lenient mode, where malformed optional and
+    /// repeated items are skipped and recorded in `diagnostics` instead of
+    /// failing the whole parse.
+    pub(crate) fn with_diagnostics(mut self, diagnostics: Diagnostics) -> Self {
+        self.diagnostics = Some(diagnostics);
+        self
+    }
+
+    /// Creates a parser for a child list, in the same mode as this one.
+    fn child(&self, list: SexprList) -> Parser {
+        Parser {
+            diagnostics: self.diagnostics.clone(),
+            version: self.version,
+            ..Parser::new(list)
+        }
+    }
+
+    /// In lenient mode, records the error so that the caller can skip over
+    /// what failed. Otherwise the error is returned.
+    fn recover(&self, error: KiCadParseError) -> Result<(), KiCadParseError> {
+        let Some(diagnostics) = &self.diagnostics else {
+            return Err(error);
+        };
+
+        diagnostics.borrow_mut().push(error.into());
+
+        Ok(())
+    }
+
+    /// Switches the parser to keyed mode, so that the remaining fields can be
+    /// read in any order. The order they appear in is recorded and available
+    /// from [`Parser::field_order`].
+    pub fn keyed(mut self) -> Self {
+        let mut lists = HashMap::<String, VecDeque<usize>>::new();
+        let mut order = Vec::new();
+
+        for (position, item) in self.items.iter().enumerate().skip(self.cursor) {
+            let Some(item) = item else {
+                continue;
+            };
+
+            let key = FieldOrder::key(item);
+
+            if let (Sexpr::List(_), Some(key)) = (item, &key) {
+                lists.entry(key.clone()).or_default().push_back(position);
+            }
+
+            order.push(key);
+        }
+
+        self.keys = Some(Keys {
+            lists,
+            order: FieldOrder(order),
+        });
+
+        self
+    }
+
+    /// Returns the order the fields of a keyed parser appeared in, or an
+    /// empty order if the parser is not keyed.
+    pub fn field_order(&self) -> FieldOrder {
+        self.keys
+            .as_ref()
+            .map(|keys| keys.order.clone())
+            .unwrap_or_default()
+    }
+
+    fn next_position(&mut self) -> Option<usize> {
+        while self.items.get(self.cursor)?.is_none() {
+            self.cursor += 1;
+        }
+
+        Some(self.cursor)
+    }
+
+    fn take(&mut self, position: usize) -> Sexpr {
+        self.items[position].take().unwrap()
+    }
+
+    /// Takes the first remaining child that matches the predicate, wherever
+    /// it is in the list.
+    fn take_first(&mut self, predicate: impl Fn(&Sexpr) -> bool) -> Option<(usize, Sexpr)> {
+        let position = self.cursor
+            + self.items[self.cursor..]
+                .iter()
+                .position(|item| item.as_ref().is_some_and(&predicate))?;
+
+        Some((position, self.take(position)))
+    }
+
+    /// Takes the first remaining child list with the given name from a keyed
+    /// parser.
+    fn take_keyed_list(&mut self, name: &str) -> Option<(usize, SexprList)> {
+        let positions = self.keys.as_mut()?.lists.get_mut(name)?;
+
+        while let Some(position) = positions.pop_front() {
+            if let Some(Sexpr::List(list)) = self.items[position].take() {
+                return Some((position, list));
+            }
+        }
+
+        None
+    }
+
+    /// Parses a child list with `parse`, adding the child to the path of any
+    /// error so that it can be traced back to where it happened.
+    fn parse_child<T>(
+        &self,
+        position: usize,
+        list: SexprList,
+        parse: impl FnOnce(Parser) -> Result<T, KiCadParseError>,
+    ) -> Result<T, KiCadParseError> {
+        let segment = PathSegment::new(position, &list);
+        let recorded = self.diagnostics.as_ref().map(|d| d.borrow().len());
+
+        let result = parse(self.child(list)).map_err(|e| e.within(segment.clone()));
+
+        // Problems skipped over inside the child happened within it too
+        if let (Some(diagnostics), Some(recorded)) = (&self.diagnostics, recorded) {
+            for diagnostic in &mut diagnostics.borrow_mut()[recorded..] {
+                diagnostic.context.path.insert(0, segment.clone());
+            }
         }
+
+        result
     }
 
     fn expect_next(&mut self) -> Result<Sexpr, KiCadParseError> {
-        self.inner
-            .next()
-            .ok_or(KiCadParseError::UnexpectedEndOfList)
+        let position = self
+            .next_position()
+            .ok_or(KiCadParseError::UnexpectedEndOfList)?;
+
+        Ok(self.take(position))
     }
 
     pub fn peek_next(&mut self) -> Option<&Sexpr> {
-        self.inner.peek()
+        let position = self.next_position()?;
+
+        self.items[position].as_ref()
     }
 
     /// Expects the next sexpr to be a list and returns a parser for it.
     ///
     /// If the next sexpr is not a list, an error is returned.
     pub fn expect_list(&mut self) -> Result<Parser, KiCadParseError> {
-        let next = self.expect_next()?;
+        let (_, list) = self.expect_next_list()?;
+
+        Ok(self.child(list))
+    }
 
-        let Sexpr::List(list) = next else {
+    fn expect_next_list(&mut self) -> Result<(usize, SexprList), KiCadParseError> {
+        let position = self
+            .next_position()
+            .ok_or(KiCadParseError::UnexpectedEndOfList)?;
+
+        let Sexpr::List(list) = self.take(position) else {
             return Err(KiCadParseError::UnexpectedSexprType {
                 expected: SexprKind::List,
             });
         };
 
-        Ok(Parser::new(list))
+        Ok((position, list))
     }
 
     /// Expects the next sexpr to be a list with the given string matching the
-    /// first symbol in the list, returning a parser for it.
+    /// first symbol in the list, and reads its children with `parse`.
+    ///
+    /// `parse` gets a keyed parser positioned after the name. Child lists it
+    /// does not read are kept in the [`Extra`] of this parser, and anything
+    /// else it leaves is an error. Errors within the list have it in their
+    /// path, like the ones of [`Parser::expect`].
     ///
     /// If the next sexpr is not a list or the first symbol does not match, an
     /// error is returned.
-    pub fn expect_list_with_name(&mut self, name: &str) -> Result<Parser, KiCadParseError> {
-        let mut list = self.expect_list()?;
-        list.expect_symbol_matching(name)?;
+    pub fn expect_list_with_name<T>(
+        &mut self,
+        name: &str,
+        parse: impl FnOnce(&mut Parser) -> Result<T, KiCadParseError>,
+    ) -> Result<T, KiCadParseError> {
+        let (position, list) = self.expect_named_list(name)?;
 
-        Ok(list)
+        self.parse_inline(position, list, parse)
+    }
+
+    fn expect_named_list(&mut self, name: &str) -> Result<(usize, SexprList), KiCadParseError> {
+        if self.keys.is_some() {
+            return self
+                .take_list_with_name(name)
+                .ok_or_else(|| KiCadParseError::MissingField(name.to_string()));
+        }
+
+        let (position, list) = self.expect_next_list()?;
+
+        match list.first() {
+            Some(Sexpr::Symbol(symbol)) if symbol == name => Ok((position, list)),
+            Some(Sexpr::Symbol(symbol)) => Err(KiCadParseError::NonMatchingSymbol {
+                found: symbol.clone(),
+                expected: name.into(),
+            }),
+            Some(_) => Err(KiCadParseError::UnexpectedSexprType {
+                expected: SexprKind::Symbol,
+            }),
+            None => Err(KiCadParseError::UnexpectedEndOfList),
+        }
+    }
+
+    /// Takes the child list with the given name, wherever it is in a keyed
+    /// parser and only if it is next otherwise.
+    fn take_list_with_name(&mut self, name: &str) -> Option<(usize, SexprList)> {
+        if self.keys.is_some() {
+            return self.take_keyed_list(name);
+        }
+
+        let position = self.next_position()?;
+
+        let Some(Sexpr::List(list)) = &self.items[position] else {
+            return None;
+        };
+
+        if list.first_symbol() != Some(name) {
+            return None;
+        }
+
+        self.take(position).take_list().map(|list| (position, list))
+    }
+
+    /// Reads the children of a list that was taken from this parser with
+    /// `parse`, after skipping its name, and keeps the lists it leaves.
+    fn parse_inline<T>(
+        &mut self,
+        position: usize,
+        list: SexprList,
+        parse: impl FnOnce(&mut Parser) -> Result<T, KiCadParseError>,
+    ) -> Result<T, KiCadParseError> {
+        let name = list.first_symbol().unwrap_or_default().to_string();
+
+        let (value, extra) = self.parse_child(position, list, |mut p| {
+            p.expect_symbol()?;
+
+            let mut p = p.keyed();
+            let value = parse(&mut p)?;
+
+            Ok((value, p.expect_end_with_extra()?))
+        })?;
+
+        if !extra.is_empty() {
+            self.inline_extra.push((position, name, extra));
+        }
+
+        Ok(value)
+    }
+
+    /// Reads the value of a list such as `(width 0.1)` with `read`. Anything
+    /// after the value is ignored.
+    fn expect_value_with_name<T>(
+        &mut self,
+        name: &str,
+        read: impl FnOnce(&mut Parser) -> Result<T, KiCadParseError>,
+    ) -> Result<T, KiCadParseError> {
+        let (position, list) = self.expect_named_list(name)?;
+
+        self.parse_child(position, list, |mut p| {
+            p.expect_symbol()?;
+            read(&mut p)
+        })
+    }
+
+    /// Reads the value of a list like [`Parser::expect_value_with_name`] if
+    /// it is present.
+    fn maybe_value_with_name<T>(
+        &mut self,
+        name: &str,
+        read: impl FnOnce(&mut Parser) -> Result<T, KiCadParseError>,
+    ) -> Result<Option<T>, KiCadParseError> {
+        self.take_list_with_name(name)
+            .map(|(position, list)| {
+                self.parse_child(position, list, |mut p| {
+                    p.expect_symbol()?;
+                    read(&mut p)
+                })
+            })
+            .transpose()
     }
 
     /// Expects the next sexpr to be a symbol and returns it.
@@ -105,7 +457,7 @@ impl Parser {
     }
 
     pub fn expect_symbol_with_name(&mut self, name: &str) -> Result<String, KiCadParseError> {
-        self.expect_list_with_name(name)?.expect_symbol()
+        self.expect_value_with_name(name, Parser::expect_symbol)
     }
 
     /// Expects the next sexpr to be a symbol and checks if it matches the
@@ -158,13 +510,33 @@ impl Parser {
     }
 
     pub fn expect_string_with_name(&mut self, name: &str) -> Result<String, KiCadParseError> {
-        self.expect_list_with_name(name)?.expect_string()
+        self.expect_value_with_name(name, Parser::expect_string)
     }
 
-    /// Expects the next sexpr to be a number and returns it.
+    /// Expects the next sexpr to be a string or a symbol and returns it. Used
+    /// for tokens that KiCad changed from a symbol to a string, like the
+    /// `generator` of a file.
+    pub fn expect_string_or_symbol(&mut self) -> Result<String, KiCadParseError> {
+        match self.expect_next()? {
+            Sexpr::String(string) | Sexpr::Symbol(string) => Ok(string),
+            _ => Err(KiCadParseError::UnexpectedSexprType {
+                expected: SexprKind::String,
+            }),
+        }
+    }
+
+    pub fn expect_string_or_symbol_with_name(
+        &mut self,
+        name: &str,
+    ) -> Result<String, KiCadParseError> {
+        self.expect_list_with_name(name, Parser::expect_string_or_symbol)
+    }
+
+    /// Expects the next sexpr to be a number and returns it without any
+    /// conversion.
     ///
     /// If the next sexpr is not a number, an error is returned.
-    pub fn expect_number(&mut self) -> Result<f32, KiCadParseError> {
+    pub fn expect_raw_number(&mut self) -> Result<Number, KiCadParseError> {
         let next = self.expect_next()?;
 
         let Sexpr::Number(number) = next else {
@@ -176,8 +548,63 @@ impl Parser {
         Ok(number)
     }
 
-    pub fn expect_number_with_name(&mut self, name: &str) -> Result<f32, KiCadParseError> {
-        self.expect_list_with_name(name)?.expect_number()
+    /// Expects the next sexpr to be a number and returns it.
+    ///
+    /// If the next sexpr is not a number, an error is returned.
+    pub fn expect_number(&mut self) -> Result<f64, KiCadParseError> {
+        self.expect_raw_number().map(Number::to_f64)
+    }
+
+    pub fn expect_number_with_name(&mut self, name: &str) -> Result<f64, KiCadParseError> {
+        self.expect_value_with_name(name, Parser::expect_number)
+    }
+
+    /// Expects the next sexpr to be a number of millimetres and returns it as
+    /// a [`Length`].
+    ///
+    /// If the next sexpr is not a number, an error is returned.
+    pub fn expect_length(&mut self) -> Result<Length, KiCadParseError> {
+        self.expect_raw_number().map(Length::from)
+    }
+
+    pub fn expect_length_with_name(&mut self, name: &str) -> Result<Length, KiCadParseError> {
+        self.expect_value_with_name(name, Parser::expect_length)
+    }
+
+    /// Expects the next sexpr to be a number of degrees and returns it as an
+    /// [`Angle`].
+    ///
+    /// If the next sexpr is not a number, an error is returned.
+    pub fn expect_angle(&mut self) -> Result<Angle, KiCadParseError> {
+        self.expect_raw_number().map(Angle::from)
+    }
+
+    pub fn expect_angle_with_name(&mut self, name: &str) -> Result<Angle, KiCadParseError> {
+        self.expect_value_with_name(name, Parser::expect_angle)
+    }
+
+    /// Expects the next sexpr to be a whole number that fits in `T` and
+    /// returns it.
+    ///
+    /// If the next sexpr is not a number, has a fractional part or is out of
+    /// range for `T`, an error is returned.
+    pub fn expect_integer<T>(&mut self) -> Result<T, KiCadParseError>
+    where
+        T: TryFrom<i64>,
+    {
+        let number = self.expect_raw_number()?;
+
+        number
+            .to_integer()
+            .and_then(|i| T::try_from(i).ok())
+            .ok_or_else(|| KiCadParseError::invalid_number::<T>(number))
+    }
+
+    pub fn expect_integer_with_name<T>(&mut self, name: &str) -> Result<T, KiCadParseError>
+    where
+        T: TryFrom<i64>,
+    {
+        self.expect_value_with_name(name, Parser::expect_integer)
     }
 
     pub fn expect_bool_with_name(&mut self, name: &str) -> Result<bool, KiCadParseError> {
@@ -193,12 +620,14 @@ impl Parser {
         }
     }
 
+    /// Expects `(name true)` or `(name false)`, which KiCad 8 replaced with
+    /// `yes` and `no`.
     pub fn expect_alt_bool_with_name(&mut self, name: &str) -> Result<bool, KiCadParseError> {
         let result = self.expect_symbol_with_name(name)?;
 
         match result.as_str() {
-            "true" => Ok(true),
-            "false" => Ok(false),
+            "true" | "yes" => Ok(true),
+            "false" | "no" => Ok(false),
             _ => Err(KiCadParseError::InvalidEnumValue {
                 value: result,
                 enum_name: "bool",
@@ -207,26 +636,100 @@ impl Parser {
     }
 
     /// Expects the end of the list.
+    ///
+    /// In lenient mode, anything left is skipped.
     pub fn expect_end(mut self) -> Result<(), KiCadParseError> {
-        if let Some(next) = self.inner.next() {
-            return Err(KiCadParseError::ExpectedEndOfList { found: next });
+        while let Some(next) = self.next_position().map(|p| self.take(p)) {
+            self.recover(KiCadParseError::ExpectedEndOfList { found: next })?;
+        }
+
+        // Lists kept from the children have nowhere to go either
+        for (_, _, extra) in std::mem::take(&mut self.inline_extra) {
+            if let Some(found) = extra.iter().next() {
+                self.recover(KiCadParseError::ExpectedEndOfList {
+                    found: found.clone(),
+                })?;
+            }
         }
 
         Ok(())
     }
 
+    /// Expects the end of the list, but keeps any child lists that are left
+    /// instead of failing on them.
+    ///
+    /// Used together with [`Parser::keyed`] so that fields the type does not
+    /// know about, such as ones added by a newer version of KiCad, survive a
+    /// round trip. Anything left that is not a list is still an error.
+    pub fn expect_end_with_extra(mut self) -> Result<Extra, KiCadParseError> {
+        let mut extra = Vec::new();
+
+        while let Some(position) = self.next_position() {
+            match self.take(position) {
+                Sexpr::List(list) => extra.push((position, Kept::List(Sexpr::List(list)))),
+                found => self.recover(KiCadParseError::ExpectedEndOfList { found })?,
+            }
+        }
+
+        extra.extend(
+            self.inline_extra
+                .into_iter()
+                .map(|(position, name, extra)| (position, Kept::Child(name, extra))),
+        );
+
+        Ok(Extra(extra))
+    }
+
     /// Expects the next sexpr to be of a specific type and returns it.
+    ///
+    /// A keyed parser takes the first remaining list of that type, wherever
+    /// it is.
     pub fn expect<T>(&mut self) -> Result<T, KiCadParseError>
     where
-        T: FromSexpr,
+        T: FromSexpr + MaybeFromSexpr,
     {
-        T::from_sexpr(self.expect_list()?)
+        if self.keys.is_some() {
+            if let Some((position, sexpr)) =
+                self.take_first(|s| s.as_list().is_some_and(T::is_present))
+            {
+                return self.parse_child(position, sexpr.take_list().unwrap(), T::from_sexpr);
+            }
+        }
+
+        let (position, list) = self.expect_next_list()?;
+
+        self.parse_child(position, list, T::from_sexpr)
     }
 
+    /// Parses the next sexpr as `T` if it is present.
+    ///
+    /// In lenient mode, items that fail to parse are skipped and the next one
+    /// is tried instead.
     pub fn maybe<T>(&mut self) -> Result<Option<T>, KiCadParseError>
     where
         T: FromSexpr + MaybeFromSexpr,
     {
+        loop {
+            match self.try_maybe::<T>() {
+                Err(error) => self.recover(error)?,
+                result => return result,
+            }
+        }
+    }
+
+    fn try_maybe<T>(&mut self) -> Result<Option<T>, KiCadParseError>
+    where
+        T: FromSexpr + MaybeFromSexpr,
+    {
+        if self.keys.is_some() {
+            return self
+                .take_first(|s| s.as_list().is_some_and(T::is_present))
+                .map(|(position, s)| {
+                    self.parse_child(position, s.take_list().unwrap(), T::from_sexpr)
+                })
+                .transpose();
+        }
+
         // If there are no more tokens, return None
         let Some(sexpr) = self.peek_next() else {
             return Ok(None);
@@ -244,13 +747,53 @@ impl Parser {
     where
         T: FromSexprWithName,
     {
-        T::from_sexpr_with_name(self.expect_list()?, name)
+        if self.keys.is_some() {
+            let (position, list) = self
+                .take_keyed_list(name)
+                .ok_or_else(|| KiCadParseError::MissingField(name.to_string()))?;
+
+            return self.parse_child(position, list, |p| T::from_sexpr_with_name(p, name));
+        }
+
+        let (position, list) = self.expect_next_list()?;
+
+        self.parse_child(position, list, |p| T::from_sexpr_with_name(p, name))
     }
 
+    /// Parses the next sexpr as `T` if it is present with the given name.
+    ///
+    /// In lenient mode, items that fail to parse are skipped and the next one
+    /// is tried instead.
     pub fn maybe_with_name<T>(&mut self, name: &str) -> Result<Option<T>, KiCadParseError>
     where
         T: FromSexprWithName + MaybeFromSexprWithName,
     {
+        loop {
+            match self.try_maybe_with_name::<T>(name) {
+                Err(error) => self.recover(error)?,
+                result => return result,
+            }
+        }
+    }
+
+    fn try_maybe_with_name<T>(&mut self, name: &str) -> Result<Option<T>, KiCadParseError>
+    where
+        T: FromSexprWithName + MaybeFromSexprWithName,
+    {
+        if self.keys.is_some() {
+            return self
+                .take_first(|s| {
+                    s.as_list()
+                        .is_some_and(|l| T::is_present_with_name(l, name))
+                })
+                .map(|(position, s)| {
+                    self.parse_child(position, s.take_list().unwrap(), |p| {
+                        T::from_sexpr_with_name(p, name)
+                    })
+                })
+                .transpose();
+        }
+
         // If there are no more tokens, return None
         let Some(sexpr) = self.peek_next() else {
             return Ok(None);
@@ -266,6 +809,46 @@ impl Parser {
             .transpose()
     }
 
+    /// Expects the identifier of a board item, which older versions of KiCad
+    /// write as a `tstamp` and newer ones as a `uuid`.
+    pub fn expect_id(&mut self) -> Result<Uuid, KiCadParseError> {
+        match self.reads(Syntax::Uuid) {
+            Some(true) => self.expect_with_name::<Uuid>("uuid"),
+            Some(false) => self.expect_with_name::<Uuid>("tstamp"),
+            None => match self.maybe_with_name::<Uuid>("tstamp")? {
+                Some(id) => Ok(id),
+                None => self.expect_with_name::<Uuid>("uuid"),
+            },
+        }
+    }
+
+    /// Reads the identifier of a board item if it has one. See
+    /// [`Parser::expect_id`].
+    pub fn maybe_id(&mut self) -> Result<Option<Uuid>, KiCadParseError> {
+        match self.reads(Syntax::Uuid) {
+            Some(true) => self.maybe_with_name::<Uuid>("uuid"),
+            Some(false) => self.maybe_with_name::<Uuid>("tstamp"),
+            None => match self.maybe_with_name::<Uuid>("tstamp")? {
+                Some(id) => Ok(Some(id)),
+                None => self.maybe_with_name::<Uuid>("uuid"),
+            },
+        }
+    }
+
+    /// Expects a `pts` list of coordinates. Unlike reading a
+    /// [`CoordinatePointList`] with [`Parser::expect`], the list is read
+    /// inline, so unknown lists within it are kept in the [`Extra`] of this
+    /// parser.
+    pub fn expect_points(&mut self) -> Result<CoordinatePointList, KiCadParseError> {
+        self.expect_list_with_name("pts", |p| p.expect_many::<Vec2D>())
+    }
+
+    /// Reads a `pts` list of coordinates if there is one. See
+    /// [`Parser::expect_points`].
+    pub fn maybe_points(&mut self) -> Result<Option<CoordinatePointList>, KiCadParseError> {
+        self.maybe_list_with_name("pts", |p| p.expect_many::<Vec2D>())
+    }
+
     pub fn expect_many<T>(&mut self) -> Result<Vec<T>, KiCadParseError>
     where
         T: FromSexpr + MaybeFromSexpr,
@@ -345,7 +928,7 @@ impl Parser {
     ///
     /// If the next sexpr is not a number or there are no more tokens in the
     /// parser, `None` is returned.
-    pub fn maybe_number(&mut self) -> Option<f32> {
+    pub fn maybe_number(&mut self) -> Option<f64> {
         let next = self.peek_next()?;
 
         let Sexpr::Number(_) = next else {
@@ -355,82 +938,192 @@ impl Parser {
         Some(self.expect_number().unwrap())
     }
 
-    pub fn maybe_list_with_name(&mut self, name: &str) -> Option<Parser> {
-        let next = self.peek_next()?;
-
-        let Sexpr::List(list) = next else {
+    /// Returns the next sexpr as a [`Length`] if it is a number.
+    ///
+    /// If the next sexpr is not a number or there are no more tokens in the
+    /// parser, `None` is returned.
+    pub fn maybe_length(&mut self) -> Option<Length> {
+        let Some(Sexpr::Number(_)) = self.peek_next() else {
             return None;
         };
 
-        if list.is_empty() {
+        Some(self.expect_length().unwrap())
+    }
+
+    /// Returns the next sexpr as an [`Angle`] if it is a number.
+    ///
+    /// If the next sexpr is not a number or there are no more tokens in the
+    /// parser, `None` is returned.
+    pub fn maybe_angle(&mut self) -> Option<Angle> {
+        let Some(Sexpr::Number(_)) = self.peek_next() else {
             return None;
-        }
+        };
 
-        let first = &list[0];
+        Some(self.expect_angle().unwrap())
+    }
 
-        let Sexpr::Symbol(symbol) = first else {
-            return None;
+    /// Returns the next sexpr as an integer if it is a number.
+    ///
+    /// If there are no more tokens or the next sexpr is not a number, `None`
+    /// is returned. If it is a number but not a valid `T`, an error is
+    /// returned.
+    pub fn maybe_integer<T>(&mut self) -> Result<Option<T>, KiCadParseError>
+    where
+        T: TryFrom<i64>,
+    {
+        let Some(Sexpr::Number(_)) = self.peek_next() else {
+            return Ok(None);
         };
 
-        if symbol != name {
-            return None;
-        }
+        self.expect_integer().map(Some)
+    }
 
-        Some(self.expect_list_with_name(name).unwrap())
+    /// Reads the children of the list with the given name with `parse` if it
+    /// is present. See [`Parser::expect_list_with_name`].
+    pub fn maybe_list_with_name<T>(
+        &mut self,
+        name: &str,
+        parse: impl FnOnce(&mut Parser) -> Result<T, KiCadParseError>,
+    ) -> Result<Option<T>, KiCadParseError> {
+        self.take_list_with_name(name)
+            .map(|(position, list)| self.parse_inline(position, list, parse))
+            .transpose()
     }
 
     pub fn maybe_empty_list_with_name(&mut self, name: &str) -> Result<bool, KiCadParseError> {
-        Ok(self
-            .maybe_list_with_name(name)
-            .map(|p| {
-                p.expect_end()?;
-                Ok::<_, KiCadParseError>(())
-            })
-            .transpose()?
-            .is_some())
+        Ok(self.maybe_list_with_name(name, |_| Ok(()))?.is_some())
     }
 
     pub fn maybe_symbol_with_name(
         &mut self,
         name: &str,
     ) -> Result<Option<String>, KiCadParseError> {
-        self.maybe_list_with_name(name)
-            .map(|mut d| d.expect_symbol())
-            .transpose()
+        self.maybe_value_with_name(name, Parser::expect_symbol)
     }
 
     pub fn maybe_string_with_name(
         &mut self,
         name: &str,
     ) -> Result<Option<String>, KiCadParseError> {
-        self.maybe_list_with_name(name)
-            .map(|mut d| d.expect_string())
-            .transpose()
+        self.maybe_value_with_name(name, Parser::expect_string)
     }
 
-    pub fn maybe_number_with_name(&mut self, name: &str) -> Result<Option<f32>, KiCadParseError> {
-        self.maybe_list_with_name(name)
-            .map(|mut d| d.expect_number())
-            .transpose()
+    pub fn maybe_number_with_name(&mut self, name: &str) -> Result<Option<f64>, KiCadParseError> {
+        self.maybe_value_with_name(name, Parser::expect_number)
+    }
+
+    pub fn maybe_length_with_name(
+        &mut self,
+        name: &str,
+    ) -> Result<Option<Length>, KiCadParseError> {
+        self.maybe_value_with_name(name, Parser::expect_length)
+    }
+
+    pub fn maybe_angle_with_name(&mut self, name: &str) -> Result<Option<Angle>, KiCadParseError> {
+        self.maybe_value_with_name(name, Parser::expect_angle)
+    }
+
+    pub fn maybe_integer_with_name<T>(&mut self, name: &str) -> Result<Option<T>, KiCadParseError>
+    where
+        T: TryFrom<i64>,
+    {
+        self.maybe_value_with_name(name, Parser::expect_integer)
     }
 
     pub fn maybe_bool_with_name(&mut self, name: &str) -> Result<Option<bool>, KiCadParseError> {
-        self.maybe_list_with_name(name)
-            .map(|mut d| {
-                let result = d.expect_symbol()?;
-                match result.as_str() {
-                    "yes" => Ok(true),
-                    "no" => Ok(false),
-                    _ => Err(KiCadParseError::InvalidEnumValue {
-                        value: result,
-                        enum_name: "bool",
-                    }),
+        self.maybe_value_with_name(name, |d| {
+            let result = d.expect_symbol()?;
+            match result.as_str() {
+                "yes" => Ok(true),
+                "no" => Ok(false),
+                _ => Err(KiCadParseError::InvalidEnumValue {
+                    value: result,
+                    enum_name: "bool",
+                }),
+            }
+        })
+    }
+
+    pub fn maybe_alt_bool_with_name(
+        &mut self,
+        name: &str,
+    ) -> Result<Option<bool>, KiCadParseError> {
+        self.maybe_value_with_name(name, |d| {
+            let result = d.expect_symbol()?;
+            match result.as_str() {
+                "true" | "yes" => Ok(true),
+                "false" | "no" => Ok(false),
+                _ => Err(KiCadParseError::InvalidEnumValue {
+                    value: result,
+                    enum_name: "bool",
+                }),
+            }
+        })
+    }
+
+    /// Reads a flag such as `hide`, which older versions of KiCad write as a
+    /// bare symbol and newer ones as `(hide yes)` or `(hide no)`. Only the
+    /// form of the file's version is accepted, and a missing flag is `false`.
+    pub fn maybe_flag(&mut self, name: &str) -> Result<bool, KiCadParseError> {
+        self.maybe_versioned_flag(name, Syntax::YesNoFlags)
+    }
+
+    /// Reads the `locked` flag of an item. See [`ListBuilder::append_locked`].
+    pub fn maybe_locked(&mut self) -> Result<bool, KiCadParseError> {
+        self.maybe_versioned_flag("locked", Syntax::LockedFlags)
+    }
+
+    /// Reads a flag that is written as `(name yes)` in the versions that use
+    /// `syntax`, and as a bare symbol before. Both forms are accepted if the
+    /// version is not known.
+    pub fn maybe_versioned_flag(
+        &mut self,
+        name: &str,
+        syntax: Syntax,
+    ) -> Result<bool, KiCadParseError> {
+        match self.reads(syntax) {
+            Some(true) => Ok(self.maybe_bool_with_name(name)?.unwrap_or(false)),
+            Some(false) => Ok(self.maybe_symbol_matching(name)),
+            None => {
+                if self.maybe_symbol_matching(name) {
+                    return Ok(true);
                 }
-            })
-            .transpose()
+
+                Ok(self.maybe_bool_with_name(name)?.unwrap_or(false))
+            }
+        }
+    }
+
+    /// Reads a flag such as `remove_unused_layers`, which older versions of
+    /// KiCad write as an empty list when it is set and newer ones as
+    /// `(remove_unused_layers yes)` or `(remove_unused_layers no)`. A missing
+    /// flag is `None`.
+    pub fn maybe_list_flag(&mut self, name: &str) -> Result<Option<bool>, KiCadParseError> {
+        self.maybe_list_with_name(name, |list| {
+            if list.peek_next().is_none() {
+                return Ok(true);
+            }
+
+            let value = list.expect_symbol()?;
+
+            match value.as_str() {
+                "yes" => Ok(true),
+                "no" => Ok(false),
+                _ => Err(KiCadParseError::InvalidEnumValue {
+                    value,
+                    enum_name: "bool",
+                }),
+            }
+        })
     }
 
     pub fn maybe_symbol_matching(&mut self, expected: &str) -> bool {
+        if self.keys.is_some() {
+            return self
+                .take_first(|s| s.as_symbol().is_some_and(|s| s == expected))
+                .is_some();
+        }
+
         let Some(symbol) = self.peek_symbol() else {
             return false;
         };
@@ -465,38 +1158,716 @@ impl Parser {
 
 /* ========= SERIALIZATION ========= */
 
-// TODO: investigate a syntax like this:
-//
-// Sexpr::list_with_name("symbol", |s| {
-//     s.append(&self.id.to_sexpr());
-//     s.append_bool_with_name("power", self.power);
-//     s.append_bool_with_name("hide_pin_numbers", self.hide_pin_numbers);
-//     s.append_maybe("pin_names", &self.pin_names);
-//     s.append_bool_with_name("in_bom", self.in_bom);
-//     s.append_bool_with_name("on_board", self.on_board);
-//     s.append_many("properties", &self.properties);
-//     s.append_many("units", &self.units);
-// })
+thread_local! {
+    static WRITE_VERSION: Cell<Option<FormatVersion>> = const { Cell::new(None) };
+}
+
+/// Runs `f` with `version` as the version of the file format that
+/// [`ToSexpr`] implementations write.
+///
+/// The file types set this to their own version while they are serialized,
+/// unless a version of the same family is already set, so wrapping a call to
+/// `to_sexpr` in this function writes the file in the syntax of another
+/// version.
+pub fn with_write_version<R>(version: FormatVersion, f: impl FnOnce() -> R) -> R {
+    struct Restore(Option<FormatVersion>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            WRITE_VERSION.with(|v| v.set(self.0));
+        }
+    }
+
+    let _restore = Restore(WRITE_VERSION.with(|v| v.replace(Some(version))));
+
+    f()
+}
+
+/// Returns the version of the file format being written, if one is set with
+/// [`with_write_version`].
+pub fn write_version() -> Option<FormatVersion> {
+    WRITE_VERSION.with(Cell::get)
+}
+
+/// Whether the version being written uses the given syntax. Without a
+/// version, the newest syntax is used.
+pub fn writes(syntax: Syntax) -> bool {
+    write_version().is_none_or(|version| version.supports(syntax))
+}
+
+/// Runs `f` with the version a file is written in, which is passed to it: the
+/// one set with [`with_write_version`] if it is of the same family, or the
+/// version the file was read with otherwise.
+pub(crate) fn with_file_version<R>(own: FormatVersion, f: impl FnOnce(u32) -> R) -> R {
+    let version = write_version()
+        .filter(|version| version.family == own.family)
+        .unwrap_or(own);
+
+    with_write_version(version, || f(version.version))
+}
+
+/// Child lists of a node that the typed model does not know about, collected
+/// by [`Parser::expect_end_with_extra`].
+///
+/// Each list is kept along with the position it was found at, so that
+/// serializing the node puts it back in the same place. This includes the
+/// unknown lists of children that the node reads inline, like the `layers`
+/// of a board, which are put back into the child with the same name.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Extra(Vec<(usize, Kept)>);
+
+/// Something kept in an [`Extra`], along with the position it was read from.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, PartialEq, Clone)]
+enum Kept {
+    /// An unknown list
+    List(Sexpr),
+    /// The extra lists of a child with the given name that was read inline
+    Child(String, Extra),
+}
+
+impl Extra {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The number of lists kept, including the ones of the children read
+    /// inline.
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    /// The lists kept, including the ones of the children read inline.
+    pub fn iter(&self) -> Box<dyn Iterator<Item = &Sexpr> + '_> {
+        Box::new(self.0.iter().flat_map(|(_, kept)| match kept {
+            Kept::List(sexpr) => Box::new(std::iter::once(sexpr)),
+            Kept::Child(_, extra) => extra.iter(),
+        }))
+    }
+
+    /// Adds a list that is written at the end of the node.
+    pub fn push(&mut self, sexpr: Sexpr) {
+        self.0.push((usize::MAX, Kept::List(sexpr)));
+    }
+
+    fn lists(&self) -> impl Iterator<Item = (usize, &Sexpr)> {
+        self.0.iter().filter_map(|(position, kept)| match kept {
+            Kept::List(sexpr) => Some((*position, sexpr)),
+            Kept::Child(..) => None,
+        })
+    }
+
+    fn children(&self) -> impl Iterator<Item = (usize, &str, &Extra)> {
+        self.0.iter().filter_map(|(position, kept)| match kept {
+            Kept::List(_) => None,
+            Kept::Child(name, extra) => Some((*position, name.as_str(), extra)),
+        })
+    }
+
+    /// Inserts the extra lists into a serialized node at the positions they
+    /// were read from.
+    pub fn apply(&self, sexpr: Sexpr) -> Sexpr {
+        let Sexpr::List(mut list) = sexpr else {
+            return sexpr;
+        };
+
+        let mut lists = self.lists().collect::<Vec<_>>();
+        lists.sort_by_key(|(position, _)| *position);
+
+        for (position, sexpr) in lists {
+            list.insert(position.min(list.len()), sexpr.clone());
+        }
+
+        for (position, name, extra) in self.children() {
+            let is_child = |sexpr: &Sexpr| {
+                sexpr
+                    .as_list()
+                    .is_some_and(|l| l.first_symbol() == Some(name))
+            };
+
+            // The child is where it was read from, unless the node changed
+            let index = Some(position)
+                .filter(|&i| list.get(i).is_some_and(is_child))
+                .or_else(|| list.iter().position(is_child));
+
+            if let Some(index) = index {
+                let child = std::mem::replace(&mut list[index], Sexpr::List(Vec::new()));
+                list[index] = extra.apply(child);
+            }
+        }
+
+        Sexpr::List(list)
+    }
+}
+
+/// The order the children of a list were read in by a keyed [`Parser`], so
+/// that serialization can put them back in the same order.
+///
+/// Lists are recorded by their name, symbols by themselves, and strings and
+/// numbers as positional values without a name.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct FieldOrder(Vec<Option<String>>);
+
+impl FieldOrder {
+    fn key(sexpr: &Sexpr) -> Option<String> {
+        match sexpr {
+            Sexpr::List(list) => list.first_symbol().map(ToString::to_string),
+            Sexpr::Symbol(symbol) => Some(symbol.clone()),
+            Sexpr::String(_) | Sexpr::Number(_) => None,
+        }
+    }
+
+    /// Reorders the children of a list, after its name, to follow the recorded
+    /// order.
+    ///
+    /// The n-th child with a given name takes the place of the n-th recorded
+    /// child with that name. Children beyond the recorded count follow the
+    /// last recorded one, and children with names that were not recorded stay
+    /// right after the child they follow. An empty order leaves the list
+    /// unchanged.
+    pub fn apply(&self, sexpr: Sexpr) -> Sexpr {
+        let Sexpr::List(mut list) = sexpr else {
+            return sexpr;
+        };
+
+        if self.0.is_empty() || list.is_empty() {
+            return Sexpr::List(list);
+        }
+
+        let mut positions = HashMap::<&Option<String>, Vec<usize>>::new();
+
+        for (position, key) in self.0.iter().enumerate() {
+            positions.entry(key).or_default().push(position);
+        }
+
+        let children = list.split_off(1);
+        let mut seen = HashMap::<Option<String>, usize>::new();
+        let mut rank = (0, 0);
+
+        let mut ranked = children
+            .into_iter()
+            .map(|child| {
+                let key = Self::key(&child);
+                let count = seen.entry(key.clone()).or_default();
+
+                rank = match positions.get(&key) {
+                    Some(recorded) if *count < recorded.len() => (recorded[*count], 0),
+                    Some(recorded) => (recorded[recorded.len() - 1], *count),
+                    None => (rank.0, rank.1 + 1),
+                };
+
+                *count += 1;
+
+                (rank, child)
+            })
+            .collect::<Vec<_>>();
+
+        ranked.sort_by_key(|(rank, _)| *rank);
+
+        list.extend(ranked.into_iter().map(|(_, child)| child));
+
+        Sexpr::List(list)
+    }
+}
+
+/// Types that can be written as an S-expression.
+///
+/// Implementations write themselves through a [`ListBuilder`], which either
+/// builds a tree for [`ToSexpr::to_sexpr`] or streams the output straight to
+/// a [`Writer`] with [`to_writer`]:
+///
+/// ```
+/// use kicad_format::convert::{ListBuilder, ToSexpr};
+///
+/// struct Stroke {
+///     width: f64,
+///     hide: bool,
+/// }
+///
+/// impl ToSexpr for Stroke {
+///     fn write_sexpr(&self, s: &mut ListBuilder) {
+///         s.append_list("stroke", |s| {
+///             s.append_number_with_name("width", self.width);
+///             s.append_symbol_if("hide", self.hide);
+///         });
+///     }
+/// }
+///
+/// let stroke = Stroke { width: 0.1, hide: true };
+///
+/// assert_eq!(
+///     stroke.to_sexpr(),
+///     kicad_sexpr::from_str("(stroke (width 0.1) hide)").unwrap(),
+/// );
+/// ```
 pub trait ToSexpr {
-    fn to_sexpr(&self) -> Sexpr;
+    /// Writes the value into `s` as a single S-expression.
+    fn write_sexpr(&self, s: &mut ListBuilder);
+
+    fn to_sexpr(&self) -> Sexpr {
+        let mut sink = TreeSink::default();
+        self.write_sexpr(&mut ListBuilder::new(&mut sink));
+
+        sink.finish()
+    }
 }
 
 pub trait ToSexprWithName {
-    fn to_sexpr_with_name(&self, name: &str) -> Sexpr;
-}
+    /// Writes the value into `s` as a single S-expression, named `name`.
+    fn write_sexpr_with_name(&self, s: &mut ListBuilder, name: &str);
 
-pub trait VecToMaybeSexprVec {
-    fn into_sexpr_vec(self) -> Vec<Option<Sexpr>>;
+    fn to_sexpr_with_name(&self, name: &str) -> Sexpr {
+        let mut sink = TreeSink::default();
+        self.write_sexpr_with_name(&mut ListBuilder::new(&mut sink), name);
+
+        sink.finish()
+    }
 }
 
-impl<T> VecToMaybeSexprVec for &[T]
+/// Writes a value to a streaming [`Writer`] in one pass, without building
+/// the S-expression tree first.
+pub fn to_writer<T, W>(value: &T, writer: &mut Writer<W>) -> io::Result<()>
 where
-    T: ToSexpr,
+    T: ToSexpr + ?Sized,
+    W: Write,
 {
-    fn into_sexpr_vec(self) -> Vec<Option<Sexpr>> {
-        self.iter()
-            .map(ToSexpr::to_sexpr)
-            .map(Option::Some)
-            .collect()
+    let mut sink = StreamSink {
+        writer,
+        result: Ok(()),
+    };
+
+    value.write_sexpr(&mut ListBuilder::new(&mut sink));
+
+    sink.result
+}
+
+/// Receives what a [`ListBuilder`] writes, one token at a time.
+pub trait SexprSink {
+    fn start_list(&mut self);
+    /// Receives a number, string or symbol. Never a [`Sexpr::List`].
+    fn atom(&mut self, atom: Sexpr);
+    fn end_list(&mut self);
+}
+
+/// Collects what is written to it into a tree.
+struct TreeSink {
+    /// The lists that are still open, with the finished values at the bottom
+    stack: Vec<SexprList>,
+}
+
+impl Default for TreeSink {
+    fn default() -> Self {
+        Self {
+            stack: vec![Vec::new()],
+        }
+    }
+}
+
+impl TreeSink {
+    fn finish(mut self) -> Sexpr {
+        self.stack
+            .pop()
+            .and_then(|mut values| values.pop())
+            .expect("nothing was written")
+    }
+}
+
+impl SexprSink for TreeSink {
+    fn start_list(&mut self) {
+        self.stack.push(Vec::new());
+    }
+
+    fn atom(&mut self, atom: Sexpr) {
+        self.stack.last_mut().unwrap().push(atom);
+    }
+
+    fn end_list(&mut self) {
+        let list = self.stack.pop().unwrap();
+        self.stack.last_mut().unwrap().push(Sexpr::List(list));
+    }
+}
+
+/// Passes what is written to it on to a [`Writer`], stopping at the first
+/// error.
+struct StreamSink<'w, W: Write> {
+    writer: &'w mut Writer<W>,
+    result: io::Result<()>,
+}
+
+impl<W: Write> StreamSink<'_, W> {
+    fn write(&mut self, f: impl FnOnce(&mut Writer<W>) -> io::Result<()>) {
+        if self.result.is_ok() {
+            self.result = f(self.writer);
+        }
+    }
+}
+
+impl<W: Write> SexprSink for StreamSink<'_, W> {
+    fn start_list(&mut self) {
+        self.write(Writer::start_list);
+    }
+
+    fn atom(&mut self, atom: Sexpr) {
+        self.write(|w| w.write_atom(&atom));
+    }
+
+    fn end_list(&mut self) {
+        self.write(Writer::end_list);
+    }
+}
+
+/// Writes the children of a list, one `append` call after the other.
+///
+/// The `maybe` variants write nothing when given `None`, and
+/// [`ListBuilder::append_list`] opens a nested list with its own builder.
+pub struct ListBuilder<'a> {
+    sink: &'a mut dyn SexprSink,
+    /// The number of children written so far, including the name
+    written: usize,
+    /// The [`Extra`] lists that have not been put back yet, last one first
+    extra: Vec<(usize, &'a Sexpr)>,
+    /// The [`Extra`] lists of the children read inline that have not been
+    /// put back yet
+    inline: Vec<(usize, &'a str, &'a Extra)>,
+}
+
+impl<'a> ListBuilder<'a> {
+    /// Creates a builder that writes S-expressions straight into `sink`.
+    pub fn new(sink: &'a mut dyn SexprSink) -> Self {
+        Self {
+            sink,
+            written: 0,
+            extra: Vec::new(),
+            inline: Vec::new(),
+        }
+    }
+
+    /// Writes the lists of an [`Extra`] that were read from before the next
+    /// child.
+    fn write_extra(&mut self) {
+        while let Some((_, sexpr)) = self.extra.pop_if(|(position, _)| *position <= self.written) {
+            write_tree(self.sink, sexpr);
+            self.written = self.written.saturating_add(1);
+        }
+    }
+
+    fn write_atom(&mut self, atom: Sexpr) {
+        self.write_extra();
+        self.sink.atom(atom);
+        self.written += 1;
+    }
+
+    /// Writes a child list named `name`, whose children are written by `f`.
+    pub fn append_list(&mut self, name: &str, f: impl FnOnce(&mut ListBuilder)) {
+        self.append_list_with_extra(name, &Extra::default(), f);
+    }
+
+    /// Writes a child list like [`ListBuilder::append_list`], putting the
+    /// lists of `extra` back at the positions they were read from.
+    ///
+    /// Without an `extra`, the lists kept from a child of the same name that
+    /// was read inline are put back instead.
+    pub fn append_list_with_extra(
+        &mut self,
+        name: &str,
+        extra: &Extra,
+        f: impl FnOnce(&mut ListBuilder),
+    ) {
+        let extra = match extra.is_empty() {
+            true => self.take_inline(name).unwrap_or(extra),
+            false => extra,
+        };
+
+        self.write_list(extra, |s| {
+            s.append_symbol(name);
+            f(s);
+        });
+    }
+
+    /// Takes the [`Extra`] of the child read inline that is written next
+    /// with the given name, preferring the one read at the same position.
+    fn take_inline(&mut self, name: &str) -> Option<&'a Extra> {
+        self.write_extra();
+
+        let index = self
+            .inline
+            .iter()
+            .position(|&(position, n, _)| n == name && position == self.written)
+            .or_else(|| self.inline.iter().position(|&(_, n, _)| n == name))?;
+
+        Some(self.inline.remove(index).2)
+    }
+
+    /// Writes a child list that does not start with a name, like the entries
+    /// of the `layers` of a board.
+    pub fn append_unnamed_list(&mut self, f: impl FnOnce(&mut ListBuilder)) {
+        self.write_list(&Extra::default(), f);
+    }
+
+    fn write_list(&mut self, extra: &Extra, f: impl FnOnce(&mut ListBuilder)) {
+        self.write_extra();
+        self.sink.start_list();
+
+        let mut list = ListBuilder {
+            sink: &mut *self.sink,
+            written: 0,
+            extra: extra.lists().collect(),
+            inline: extra.children().collect(),
+        };
+        list.extra.sort_by_key(|(position, _)| Reverse(*position));
+
+        f(&mut list);
+
+        list.written = usize::MAX;
+        list.write_extra();
+
+        self.sink.end_list();
+        self.written += 1;
+    }
+
+    /// Writes a child list like [`ListBuilder::append_list_with_extra`],
+    /// ordering its children to follow `order`.
+    ///
+    /// The children have to be reordered before they are written, so the list
+    /// is collected into a tree first unless `order` is empty.
+    pub fn append_list_in_order(
+        &mut self,
+        name: &str,
+        order: &FieldOrder,
+        extra: &Extra,
+        f: impl FnOnce(&mut ListBuilder),
+    ) {
+        if order.0.is_empty() {
+            return self.append_list_with_extra(name, extra, f);
+        }
+
+        let extra = match extra.is_empty() {
+            true => self.take_inline(name).unwrap_or(extra),
+            false => extra,
+        };
+
+        let mut sink = TreeSink::default();
+        ListBuilder::new(&mut sink).append_list(name, f);
+
+        self.append_sexpr(extra.apply(order.apply(sink.finish())));
+    }
+
+    pub fn append<T: ToSexpr + ?Sized>(&mut self, value: &T) {
+        value.write_sexpr(self);
+    }
+
+    pub fn append_maybe<T: ToSexpr>(&mut self, value: &Option<T>) {
+        if let Some(value) = value {
+            value.write_sexpr(self);
+        }
+    }
+
+    pub fn append_many<'v, T: ToSexpr + 'v>(&mut self, values: impl IntoIterator<Item = &'v T>) {
+        for value in values {
+            value.write_sexpr(self);
+        }
+    }
+
+    pub fn append_with_name<T: ToSexprWithName + ?Sized>(&mut self, name: &str, value: &T) {
+        value.write_sexpr_with_name(self, name);
+    }
+
+    pub fn append_maybe_with_name<T: ToSexprWithName>(&mut self, name: &str, value: &Option<T>) {
+        if let Some(value) = value {
+            value.write_sexpr_with_name(self, name);
+        }
+    }
+
+    /// Writes an S-expression that has already been built.
+    pub fn append_sexpr(&mut self, sexpr: Sexpr) {
+        match sexpr {
+            Sexpr::List(_) => {
+                self.write_extra();
+                write_tree(self.sink, &sexpr);
+                self.written += 1;
+            }
+            atom => self.write_atom(atom),
+        }
+    }
+
+    pub fn append_maybe_sexpr(&mut self, sexpr: Option<Sexpr>) {
+        if let Some(sexpr) = sexpr {
+            self.append_sexpr(sexpr);
+        }
+    }
+
+    pub fn append_symbol(&mut self, symbol: impl Into<String>) {
+        self.write_atom(Sexpr::symbol(symbol));
+    }
+
+    pub fn append_maybe_symbol(&mut self, symbol: Option<impl Into<String>>) {
+        if let Some(symbol) = symbol {
+            self.append_symbol(symbol);
+        }
+    }
+
+    /// Writes `symbol` as a bare flag if `condition` is set.
+    pub fn append_symbol_if(&mut self, symbol: &str, condition: bool) {
+        if condition {
+            self.append_symbol(symbol);
+        }
+    }
+
+    pub fn append_string(&mut self, string: impl Into<String>) {
+        self.write_atom(Sexpr::string(string));
+    }
+
+    pub fn append_maybe_string(&mut self, string: Option<impl Into<String>>) {
+        if let Some(string) = string {
+            self.append_string(string);
+        }
+    }
+
+    pub fn append_number(&mut self, number: impl Into<Number>) {
+        self.write_atom(Sexpr::number(number));
+    }
+
+    pub fn append_maybe_number(&mut self, number: Option<impl Into<Number>>) {
+        if let Some(number) = number {
+            self.append_number(number);
+        }
+    }
+
+    pub fn append_symbol_with_name(&mut self, name: &str, symbol: impl Into<String>) {
+        self.append_list(name, |s| s.append_symbol(symbol));
+    }
+
+    pub fn append_maybe_symbol_with_name(&mut self, name: &str, symbol: Option<impl Into<String>>) {
+        if let Some(symbol) = symbol {
+            self.append_symbol_with_name(name, symbol);
+        }
+    }
+
+    pub fn append_string_with_name(&mut self, name: &str, string: impl Into<String>) {
+        self.append_list(name, |s| s.append_string(string));
+    }
+
+    pub fn append_maybe_string_with_name(&mut self, name: &str, string: Option<impl Into<String>>) {
+        if let Some(string) = string {
+            self.append_string_with_name(name, string);
+        }
+    }
+
+    pub fn append_number_with_name(&mut self, name: &str, number: impl Into<Number>) {
+        self.append_list(name, |s| s.append_number(number));
+    }
+
+    pub fn append_maybe_number_with_name(&mut self, name: &str, number: Option<impl Into<Number>>) {
+        if let Some(number) = number {
+            self.append_number_with_name(name, number);
+        }
+    }
+
+    /// Writes `(name yes)` or `(name no)`.
+    pub fn append_bool_with_name(&mut self, name: &str, value: bool) {
+        self.append_symbol_with_name(name, if value { "yes" } else { "no" });
+    }
+
+    pub fn append_maybe_bool_with_name(&mut self, name: &str, value: Option<bool>) {
+        if let Some(value) = value {
+            self.append_bool_with_name(name, value);
+        }
+    }
+
+    /// Writes `(name true)` or `(name false)`, or `yes` and `no` in the
+    /// versions that write [`Syntax::YesNoFlags`].
+    pub fn append_alt_bool_with_name(&mut self, name: &str, value: bool) {
+        let value = match (value, writes(Syntax::YesNoFlags)) {
+            (true, true) => "yes",
+            (false, true) => "no",
+            (true, false) => "true",
+            (false, false) => "false",
+        };
+
+        self.append_symbol_with_name(name, value);
+    }
+
+    pub fn append_maybe_alt_bool_with_name(&mut self, name: &str, value: Option<bool>) {
+        if let Some(value) = value {
+            self.append_alt_bool_with_name(name, value);
+        }
+    }
+
+    /// Writes a flag such as `hide` in the syntax of the version being
+    /// written, or nothing if the flag is not set. See [`Parser::maybe_flag`].
+    pub fn append_flag(&mut self, name: &str, value: bool) {
+        if !value {
+            return;
+        }
+
+        if writes(Syntax::YesNoFlags) {
+            self.append_bool_with_name(name, true);
+        } else {
+            self.append_symbol(name);
+        }
+    }
+
+    /// Writes the `locked` flag of a board item. KiCad kept writing it as a
+    /// bare symbol for a while after other flags became `(name yes)`.
+    pub fn append_locked(&mut self, locked: bool) {
+        if !locked {
+            return;
+        }
+
+        if writes(Syntax::LockedFlags) {
+            self.append_bool_with_name("locked", true);
+        } else {
+            self.append_symbol("locked");
+        }
+    }
+
+    /// Writes a flag such as `remove_unused_layers` in the syntax of the
+    /// version being written. See [`Parser::maybe_list_flag`].
+    ///
+    /// Older versions have no way to write a flag that is not set, so it is
+    /// left out for them.
+    pub fn append_maybe_list_flag(&mut self, name: &str, value: Option<bool>) {
+        if writes(Syntax::YesNoFlags) {
+            self.append_maybe_bool_with_name(name, value);
+        } else if value == Some(true) {
+            self.append_list(name, |_| {});
+        }
+    }
+
+    /// Writes the identifier of a board item as a `uuid`, or as a `tstamp`
+    /// in the versions before it was renamed. See [`Parser::expect_id`].
+    pub fn append_id(&mut self, id: &Uuid) {
+        if writes(Syntax::Uuid) {
+            self.append_with_name("uuid", id);
+        } else {
+            self.append_with_name("tstamp", id);
+        }
+    }
+
+    /// Writes the `generator` of a file, which is a symbol in older versions
+    /// of the format and a string in newer ones.
+    pub fn append_generator(&mut self, generator: &str) {
+        if writes(Syntax::GeneratorString) {
+            self.append_string_with_name("generator", generator);
+        } else {
+            self.append_symbol_with_name("generator", generator);
+        }
+    }
+}
+
+fn write_tree(sink: &mut dyn SexprSink, sexpr: &Sexpr) {
+    match sexpr {
+        Sexpr::List(list) => {
+            sink.start_list();
+
+            for child in list {
+                write_tree(sink, child);
+            }
+
+            sink.end_list();
+        }
+        atom => sink.atom(atom.clone()),
     }
 }
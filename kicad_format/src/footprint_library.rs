@@ -1,16 +1,22 @@
 //! Footprint library file format (`.kicad_mod` files)
 
-use kicad_sexpr::Sexpr;
-
 use crate::{
     common::{
-        footprint::{FootprintAttributes, FootprintGraphicsItem, Model, ZoneConnectKind},
+        embedded::{
+            parse_embedded_files, parse_embedded_fonts, write_embedded_files, write_embedded_fonts,
+            EmbeddedFile,
+        },
+        footprint::{
+            jumper_groups, net_tie_pad_groups, text::FootprintProperty, FootprintAttributes,
+            FootprintGraphicsItem, Model, ZoneConnectKind,
+        },
         pad::Pad,
         zone::Zone,
-        Group, LayerId, Property,
+        Group, LayerId,
     },
-    convert::{FromSexpr, Parser, ToSexpr, VecToMaybeSexprVec},
-    KiCadParseError,
+    convert::{with_file_version, writes, Extra, FromSexpr, ListBuilder, Parser, ToSexpr},
+    length::Length,
+    version::{FormatVersion, Syntax},
 };
 
 /// Stores a footprint which can be instanced within a PCB board file
@@ -27,78 +33,92 @@ pub struct FootprintLibraryFile {
     pub name: String,
     pub version: u32,
     pub generator: String,
+    /// The version of the program that wrote the file, since KiCad 8.
+    pub generator_version: Option<String>,
     pub layer: LayerId,
     pub description: Option<String>,
     pub tags: Option<String>,
-    pub properties: Vec<Property>,
-    pub solder_mask_margin: Option<f32>,
-    pub solder_paste_margin: Option<f32>,
-    pub solder_paste_ratio: Option<f32>,
-    pub clearance: Option<f32>,
+    pub properties: Vec<FootprintProperty>,
+    pub solder_mask_margin: Option<Length>,
+    pub solder_paste_margin: Option<Length>,
+    pub solder_paste_ratio: Option<f64>,
+    pub clearance: Option<Length>,
     pub zone_connect: Option<ZoneConnectKind>,
     pub attributes: Option<FootprintAttributes>,
     pub private_layers: Option<Vec<LayerId>>,
     pub net_tie_pad_groups: Option<Vec<Vec<String>>>,
+    /// Whether pads sharing a number are connected by a jumper, since KiCad
+    /// 9.
+    pub duplicate_pad_numbers_are_jumpers: Option<bool>,
+    /// The groups of pads connected by a jumper, since KiCad 9.
+    pub jumper_pad_groups: Option<Vec<Vec<String>>>,
     pub graphics_items: Vec<FootprintGraphicsItem>,
     pub pads: Vec<Pad>,
     pub keep_out_zones: Vec<Zone>,
     pub groups: Vec<Group>,
+    /// Whether the fonts used by the footprint are embedded in it, since
+    /// KiCad 9.
+    pub embedded_fonts: Option<bool>,
+    pub embedded_files: Option<Vec<EmbeddedFile>>,
     pub models: Vec<Model>,
+    pub extra: Extra,
 }
 
 impl FromSexpr for FootprintLibraryFile {
     fn from_sexpr(mut parser: Parser) -> Result<Self, crate::KiCadParseError> {
         parser.expect_symbol_matching("footprint")?;
 
+        let mut parser = parser.keyed();
+
         let name = parser.expect_string()?;
-        let version = parser.expect_number_with_name("version")? as u32;
-        let generator = parser.expect_symbol_with_name("generator")?;
+        let version = parser.expect_integer_with_name::<u32>("version")?;
+        parser.set_version(FormatVersion::board(version));
+        let generator = parser.expect_string_or_symbol_with_name("generator")?;
+        let generator_version = parser.maybe_string_with_name("generator_version")?;
         let layer = parser.expect_string_with_name("layer")?.parse()?;
         let description = parser.maybe_string_with_name("descr")?;
         let tags = parser.maybe_string_with_name("tags")?;
-        let properties = parser.expect_many::<Property>()?;
-        let solder_mask_margin = parser.maybe_number_with_name("solder_mask_margin")?;
-        let solder_paste_margin = parser.maybe_number_with_name("solder_paste_margin")?;
+        let properties = parser.expect_many::<FootprintProperty>()?;
+        let solder_mask_margin = parser.maybe_length_with_name("solder_mask_margin")?;
+        let solder_paste_margin = parser.maybe_length_with_name("solder_paste_margin")?;
         let solder_paste_ratio = parser.maybe_number_with_name("solder_paste_ratio")?;
-        let clearance = parser.maybe_number_with_name("clearance")?;
+        let clearance = parser.maybe_length_with_name("clearance")?;
         let zone_connect = parser
-            .maybe_number_with_name("zone_connect")?
-            .map(|n| n as u8)
+            .maybe_integer_with_name::<u8>("zone_connect")?
             .map(ZoneConnectKind::try_from)
             .transpose()?;
         let attributes = parser.maybe::<FootprintAttributes>()?;
-        let private_layers = parser
-            .maybe_list_with_name("private_layers")
-            .map(|mut a| {
-                a.expect_many_strings()?
-                    .into_iter()
-                    .map(|s| s.parse())
-                    .collect()
-            })
-            .transpose()?;
-        let net_tie_pad_groups = parser
-            .maybe_list_with_name("net_tie_pad_groups")
-            .map(|mut a| {
-                Ok::<_, KiCadParseError>(
-                    a.expect_many_strings()?
-                        .into_iter()
-                        .map(|s| s.split(',').map(|s| s.to_owned()).collect::<Vec<String>>())
-                        .collect(),
-                )
-            })
-            .transpose()?;
+        let private_layers = parser.maybe_list_with_name("private_layers", |a| {
+            a.expect_many_strings()?
+                .into_iter()
+                .map(|s| s.parse())
+                .collect()
+        })?;
+        let net_tie_pad_groups = net_tie_pad_groups::parse(&mut parser, "net_tie_pad_groups")?;
+        let duplicate_pad_numbers_are_jumpers = parser.maybe_since(
+            Syntax::Jumpers,
+            "duplicate_pad_numbers_are_jumpers",
+            |parser| parser.maybe_bool_with_name("duplicate_pad_numbers_are_jumpers"),
+        )?;
+        let jumper_pad_groups =
+            parser.maybe_since(Syntax::Jumpers, "jumper_pad_groups", |parser| {
+                jumper_groups::parse(parser, "jumper_pad_groups")
+            })?;
         let graphics_items = parser.expect_many::<FootprintGraphicsItem>()?;
         let pads = parser.expect_many::<Pad>()?;
         let keep_out_zones = parser.expect_many::<Zone>()?;
         let groups = parser.expect_many::<Group>()?;
+        let embedded_fonts = parse_embedded_fonts(&mut parser)?;
+        let embedded_files = parse_embedded_files(&mut parser)?;
         let models = parser.expect_many::<Model>()?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(FootprintLibraryFile {
             name,
             version,
             generator,
+            generator_version,
             layer,
             description,
             tags,
@@ -111,73 +131,66 @@ impl FromSexpr for FootprintLibraryFile {
             attributes,
             private_layers,
             net_tie_pad_groups,
+            duplicate_pad_numbers_are_jumpers,
+            jumper_pad_groups,
             graphics_items,
             pads,
             keep_out_zones,
             groups,
+            embedded_fonts,
+            embedded_files,
             models,
+            extra,
         })
     }
 }
 
 impl ToSexpr for FootprintLibraryFile {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "footprint",
-            [
-                vec![
-                    Some(Sexpr::string(&self.name)),
-                    Some(Sexpr::number_with_name("version", self.version as f32)),
-                    Some(Sexpr::symbol_with_name("generator", &self.generator)),
-                    Some(Sexpr::string_with_name("layer", self.layer)),
-                    self.description
-                        .as_ref()
-                        .map(|s| Sexpr::string_with_name("descr", s)),
-                    self.tags
-                        .as_ref()
-                        .map(|s| Sexpr::string_with_name("tags", s)),
-                ],
-                self.properties.into_sexpr_vec(),
-                vec![
-                    self.solder_mask_margin
-                        .map(|n| Sexpr::number_with_name("solder_mask_margin", n)),
-                    self.solder_paste_margin
-                        .map(|n| Sexpr::number_with_name("solder_paste_margin", n)),
-                    self.solder_paste_ratio
-                        .map(|n| Sexpr::number_with_name("solder_paste_ratio", n)),
-                    self.clearance
-                        .map(|n| Sexpr::number_with_name("clearance", n)),
-                    self.zone_connect
-                        .map(|n| Sexpr::number_with_name("zone_connect", n as u8 as f32)),
-                    self.attributes.as_ref().map(ToSexpr::to_sexpr),
-                    self.private_layers.as_ref().map(|l| {
-                        Sexpr::list_with_name(
-                            "private_layers",
-                            l.iter()
-                                .map(Clone::clone)
-                                .map(Sexpr::string)
-                                .map(Option::Some)
-                                .collect::<Vec<_>>(),
-                        )
-                    }),
-                    self.net_tie_pad_groups.as_ref().map(|a| {
-                        Sexpr::list_with_name(
-                            "net_tie_pad_groups",
-                            a.iter()
-                                .map(|group| group.join(","))
-                                .map(Sexpr::string)
-                                .map(Option::Some)
-                                .collect::<Vec<_>>(),
-                        )
-                    }),
-                ],
-                self.graphics_items.into_sexpr_vec(),
-                self.pads.into_sexpr_vec(),
-                self.keep_out_zones.into_sexpr_vec(),
-                self.groups.into_sexpr_vec(),
-                self.models.into_sexpr_vec(),
-            ]
-            .concat(),
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        with_file_version(FormatVersion::board(self.version), |version| {
+            s.append_list_with_extra("footprint", &self.extra, |s| {
+                s.append_string(&self.name);
+                s.append_number_with_name("version", version);
+                s.append_generator(&self.generator);
+                if writes(Syntax::GeneratorVersion) {
+                    s.append_maybe_string_with_name(
+                        "generator_version",
+                        self.generator_version.as_ref(),
+                    );
+                }
+                s.append_string_with_name("layer", self.layer);
+                s.append_maybe_string_with_name("descr", self.description.as_ref());
+                s.append_maybe_string_with_name("tags", self.tags.as_ref());
+                s.append_many(&self.properties);
+                s.append_maybe_number_with_name("solder_mask_margin", self.solder_mask_margin);
+                s.append_maybe_number_with_name("solder_paste_margin", self.solder_paste_margin);
+                s.append_maybe_number_with_name("solder_paste_ratio", self.solder_paste_ratio);
+                s.append_maybe_number_with_name("clearance", self.clearance);
+                s.append_maybe_number_with_name("zone_connect", self.zone_connect.map(|n| n as u8));
+                s.append_maybe(&self.attributes);
+                if let Some(layers) = &self.private_layers {
+                    s.append_list("private_layers", |s| {
+                        for layer in layers {
+                            s.append_string(*layer);
+                        }
+                    });
+                }
+                net_tie_pad_groups::write(s, "net_tie_pad_groups", &self.net_tie_pad_groups);
+                if writes(Syntax::Jumpers) {
+                    s.append_maybe_bool_with_name(
+                        "duplicate_pad_numbers_are_jumpers",
+                        self.duplicate_pad_numbers_are_jumpers,
+                    );
+                    jumper_groups::write(s, "jumper_pad_groups", &self.jumper_pad_groups);
+                }
+                s.append_many(&self.graphics_items);
+                s.append_many(&self.pads);
+                s.append_many(&self.keep_out_zones);
+                s.append_many(&self.groups);
+                write_embedded_fonts(s, self.embedded_fonts);
+                write_embedded_files(s, &self.embedded_files);
+                s.append_many(&self.models);
+            });
+        });
     }
 }
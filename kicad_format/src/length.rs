@@ -0,0 +1,268 @@
+//! Lengths in KiCad's internal unit, the nanometre.
+//!
+//! KiCad stores every coordinate and dimension of a board as a whole number
+//! of nanometres and writes them to files as millimetres with up to six
+//! decimals. Reading them into a [`Length`] instead of an `f64` keeps that
+//! precision, so moving an item back and forth does not leave it slightly off
+//! where it started.
+//!
+//! ```
+//! use kicad_format::length::{Length, LengthUnit};
+//!
+//! let pitch = Length::from_mils(50.0);
+//!
+//! assert_eq!(pitch, Length::from_mm(1.27));
+//! assert_eq!(pitch * 3, Length::from_nm(3_810_000));
+//! assert_eq!(pitch.to_string(), "1.27 mm");
+//! assert_eq!(pitch.display_in(LengthUnit::Mils).to_string(), "50 mil");
+//! ```
+
+use std::{
+    fmt::{self, Display},
+    iter::Sum,
+    ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign},
+};
+
+use kicad_sexpr::Number;
+
+const NM_PER_MM: i64 = 1_000_000;
+const NM_PER_MIL: i64 = 25_400;
+const NM_PER_INCH: i64 = 25_400_000;
+
+/// A length, stored as a whole number of nanometres.
+///
+/// Values given in other units are rounded to the nearest nanometre.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Clone, Copy)]
+pub struct Length(i64);
+
+impl Length {
+    pub const ZERO: Self = Self(0);
+
+    pub const fn from_nm(nm: i64) -> Self {
+        Self(nm)
+    }
+
+    pub fn from_mm(mm: f64) -> Self {
+        Self::from_unit(mm, NM_PER_MM)
+    }
+
+    pub fn from_mils(mils: f64) -> Self {
+        Self::from_unit(mils, NM_PER_MIL)
+    }
+
+    pub fn from_inches(inches: f64) -> Self {
+        Self::from_unit(inches, NM_PER_INCH)
+    }
+
+    /// Creates a length from a value in the given unit.
+    pub fn from_value(value: f64, unit: LengthUnit) -> Self {
+        Self::from_unit(value, unit.nm())
+    }
+
+    fn from_unit(value: f64, nm_per_unit: i64) -> Self {
+        Self((value * nm_per_unit as f64).round() as i64)
+    }
+
+    pub const fn nm(self) -> i64 {
+        self.0
+    }
+
+    pub fn mm(self) -> f64 {
+        self.to_value(LengthUnit::Millimetres)
+    }
+
+    pub fn mils(self) -> f64 {
+        self.to_value(LengthUnit::Mils)
+    }
+
+    pub fn inches(self) -> f64 {
+        self.to_value(LengthUnit::Inches)
+    }
+
+    /// Returns the length as a value in the given unit.
+    pub fn to_value(self, unit: LengthUnit) -> f64 {
+        self.0 as f64 / unit.nm() as f64
+    }
+
+    pub const fn abs(self) -> Self {
+        Self(self.0.abs())
+    }
+
+    /// Formats the length in the given unit, like `50 mil`.
+    ///
+    /// Without a precision in the format string, the value is rounded to the
+    /// nearest nanometre in millimetres and to the nearest 0.001 mil or
+    /// 0.000001 inch otherwise.
+    pub fn display_in(self, unit: LengthUnit) -> DisplayLength {
+        DisplayLength { length: self, unit }
+    }
+}
+
+impl Display for Length {
+    /// Formats the length in millimetres, like `1.27 mm`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.display_in(LengthUnit::Millimetres).fmt(f)
+    }
+}
+
+/// Lengths are written to files in millimetres.
+impl From<Length> for Number {
+    fn from(length: Length) -> Self {
+        if length.0 % NM_PER_MM == 0 {
+            Number::Integer(length.0 / NM_PER_MM)
+        } else {
+            Number::Float(length.mm(), None)
+        }
+    }
+}
+
+/// Numbers in files are millimetres.
+impl From<Number> for Length {
+    fn from(number: Number) -> Self {
+        match number {
+            Number::Integer(mm) => Self(mm * NM_PER_MM),
+            Number::Float(mm, _) => Self::from_mm(mm),
+        }
+    }
+}
+
+impl Add for Length {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self(self.0 + rhs.0)
+    }
+}
+
+impl AddAssign for Length {
+    fn add_assign(&mut self, rhs: Self) {
+        self.0 += rhs.0;
+    }
+}
+
+impl Sub for Length {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self(self.0 - rhs.0)
+    }
+}
+
+impl SubAssign for Length {
+    fn sub_assign(&mut self, rhs: Self) {
+        self.0 -= rhs.0;
+    }
+}
+
+impl Neg for Length {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self(-self.0)
+    }
+}
+
+impl Mul<i64> for Length {
+    type Output = Self;
+
+    fn mul(self, rhs: i64) -> Self {
+        Self(self.0 * rhs)
+    }
+}
+
+/// Scales the length, rounding to the nearest nanometre.
+impl Mul<f64> for Length {
+    type Output = Self;
+
+    fn mul(self, rhs: f64) -> Self {
+        Self((self.0 as f64 * rhs).round() as i64)
+    }
+}
+
+impl Div<i64> for Length {
+    type Output = Self;
+
+    fn div(self, rhs: i64) -> Self {
+        Self(self.0 / rhs)
+    }
+}
+
+/// The ratio between two lengths.
+impl Div for Length {
+    type Output = f64;
+
+    fn div(self, rhs: Self) -> f64 {
+        self.0 as f64 / rhs.0 as f64
+    }
+}
+
+impl Sum for Length {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        Self(iter.map(|l| l.0).sum())
+    }
+}
+
+/// The units a [`Length`] can be created from and formatted in.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum LengthUnit {
+    Millimetres,
+    /// Thousandths of an inch
+    Mils,
+    Inches,
+}
+
+impl LengthUnit {
+    const fn nm(self) -> i64 {
+        match self {
+            Self::Millimetres => NM_PER_MM,
+            Self::Mils => NM_PER_MIL,
+            Self::Inches => NM_PER_INCH,
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Self::Millimetres => "mm",
+            Self::Mils => "mil",
+            Self::Inches => "in",
+        }
+    }
+
+    /// The number of decimals that are printed when no precision is given.
+    fn default_precision(self) -> usize {
+        match self {
+            Self::Millimetres | Self::Inches => 6,
+            Self::Mils => 3,
+        }
+    }
+}
+
+/// Formats a [`Length`] in a given unit. Returned by [`Length::display_in`].
+#[derive(Debug, Clone, Copy)]
+pub struct DisplayLength {
+    length: Length,
+    unit: LengthUnit,
+}
+
+impl Display for DisplayLength {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = self.length.to_value(self.unit);
+
+        match f.precision() {
+            Some(precision) => write!(f, "{value:.precision$}")?,
+            None => {
+                let value = format!("{value:.*}", self.unit.default_precision());
+                let value = value.trim_end_matches('0').trim_end_matches('.');
+
+                // Small negative values round to "-0"
+                f.write_str(if value == "-0" { "0" } else { value })?;
+            }
+        }
+
+        write!(f, " {}", self.unit.symbol())
+    }
+}
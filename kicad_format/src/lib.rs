@@ -1,18 +1,29 @@
 use common::LayerId;
-use convert::{FromSexpr, Parser, ToSexpr};
+use convert::{Diagnostics, FromSexpr, Parser, SexprListExt, ToSexpr};
 use footprint_library::FootprintLibraryFile;
-use kicad_sexpr::Sexpr;
+use kicad_sexpr::{
+    stream::Writer, FormatStyle, Location, Number, Sexpr, SexprList, Span, SpannedSexpr,
+};
 use pcb::PcbFile;
 use schematic::SchematicFile;
+use std::io::{self, Write};
 use symbol_library::SymbolLibraryFile;
 use thiserror::Error;
 
+// Lets the derive macros refer to this crate by name from inside it
+extern crate self as kicad_format;
+
+pub mod angle;
 pub mod common;
 pub mod convert;
 pub mod footprint_library;
+pub mod length;
+pub mod migrate;
 pub mod pcb;
 pub mod schematic;
 pub mod symbol_library;
+pub mod validate;
+pub mod version;
 
 /// The type of an S-expression token without the inner data.
 ///
@@ -45,6 +56,11 @@ pub enum KiCadParseError {
         value: String,
         enum_name: &'static str,
     },
+    #[error("Invalid number `{value}` for type `{type_name}`.")]
+    InvalidNumber {
+        value: Number,
+        type_name: &'static str,
+    },
     #[error("Invalid UUID: {0}")]
     InvalidUuid(#[from] uuid::Error),
     #[error("Incorrect number of points!: Expected: {expected}; Found: {found}")]
@@ -66,6 +82,18 @@ pub enum KiCadParseError {
     },
     #[error("Expected field")]
     ExpectedField,
+    #[error("Missing field `{0}`")]
+    MissingField(String),
+    /// A token that only newer versions of the file format have.
+    #[error("`{token}` is not supported by version {version} of the file format")]
+    UnsupportedInVersion { token: String, version: u32 },
+    /// An error that happened inside a nested list, together with the path to
+    /// that list.
+    #[error("{context}: {source}")]
+    Context {
+        context: ErrorContext,
+        source: Box<KiCadParseError>,
+    },
 }
 
 impl KiCadParseError {
@@ -75,6 +103,204 @@ impl KiCadParseError {
             enum_name: std::any::type_name::<T>(),
         }
     }
+
+    pub fn invalid_number<T>(value: Number) -> Self {
+        Self::InvalidNumber {
+            value,
+            type_name: std::any::type_name::<T>(),
+        }
+    }
+
+    /// Returns where the error happened, if it happened inside a list that
+    /// was parsed as a nested type.
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Self::Context { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// Returns the error without the context around it.
+    pub fn inner(&self) -> &KiCadParseError {
+        match self {
+            Self::Context { source, .. } => source,
+            _ => self,
+        }
+    }
+
+    /// Adds a list to the front of the path of the error.
+    pub(crate) fn within(self, segment: PathSegment) -> Self {
+        match self {
+            Self::Context {
+                mut context,
+                source,
+            } => {
+                context.path.insert(0, segment);
+
+                Self::Context { context, source }
+            }
+            error => Self::Context {
+                context: ErrorContext {
+                    path: vec![segment],
+                    span: None,
+                    location: None,
+                },
+                source: Box::new(error),
+            },
+        }
+    }
+
+    /// Splits the error into where it happened and the error itself. Errors
+    /// without a context get an empty path.
+    pub(crate) fn into_parts(self) -> (ErrorContext, KiCadParseError) {
+        match self {
+            Self::Context { context, source } => (context, *source),
+            error => (ErrorContext::default(), error),
+        }
+    }
+
+    /// Fills in the span of the list the error happened in from the input
+    /// the file was parsed from.
+    fn with_span(mut self, input: &str) -> Self {
+        if let Self::Context { context, .. } = &mut self {
+            // The spans are only needed when something went wrong, so the
+            // input is parsed a second time rather than tracking them always
+            if let Ok(root) = kicad_sexpr::from_str_spanned(input) {
+                context.locate(&root, input);
+            }
+        }
+
+        self
+    }
+}
+
+/// Where in a file a [`KiCadParseError`] happened.
+///
+/// Displayed as the path of lists leading to the error, such as
+/// `kicad_pcb > footprint "Resistor_SMD:R_0603" (tstamp ...) > pad "2" > drill`,
+/// followed by the line and column when they are known.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ErrorContext {
+    /// The lists leading to the error, starting from the outermost one.
+    pub path: Vec<PathSegment>,
+    /// The span of the innermost list. Only known for errors returned by the
+    /// `parse_*_file` functions, which have the input at hand.
+    pub span: Option<Span>,
+    /// The line and column of the start of [`ErrorContext::span`].
+    pub location: Option<Location>,
+}
+
+impl ErrorContext {
+    /// Looks up the span of the innermost list in the spanned tree of the
+    /// input.
+    fn locate(&mut self, root: &SpannedSexpr, input: &str) {
+        let node = self
+            .path
+            .iter()
+            .skip(1)
+            .try_fold(root, |node, segment| node.as_list()?.get(segment.index));
+
+        if let Some(node) = node {
+            self.span = Some(node.span);
+            self.location = Some(node.span.location(input));
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, segment) in self.path.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" > ")?;
+            }
+
+            write!(f, "{segment}")?;
+        }
+
+        if let Some(location) = self.location {
+            write!(f, " (line {}, column {})", location.line, location.column)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A single list in the path of an [`ErrorContext`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct PathSegment {
+    /// The position of the list within its parent, counting the name of the
+    /// parent as position 0.
+    pub index: usize,
+    /// The symbol at the head of the list
+    pub name: String,
+    /// The first string in the list, such as the number of a pad or the
+    /// library link of a footprint, to tell lists with the same name apart.
+    pub label: Option<String>,
+    /// The `tstamp` or `uuid` of the list, if it has one.
+    pub id: Option<Sexpr>,
+}
+
+impl PathSegment {
+    pub(crate) fn new(index: usize, list: &SexprList) -> Self {
+        let label = list.iter().skip(1).find_map(|s| s.as_string().cloned());
+
+        let id = list
+            .iter()
+            .find(|s| {
+                s.as_list()
+                    .and_then(|l| l.first_symbol())
+                    .is_some_and(|name| name == "tstamp" || name == "uuid")
+            })
+            .cloned();
+
+        Self {
+            index,
+            name: list.first_symbol().unwrap_or_default().to_string(),
+            label,
+            id,
+        }
+    }
+}
+
+impl std::fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)?;
+
+        if let Some(label) = &self.label {
+            write!(f, " {}", Sexpr::string(label))?;
+        }
+
+        if let Some(id) = &self.id {
+            write!(f, " {id}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A problem that was skipped over while parsing a file in lenient mode, such
+/// as with [`parse_schematic_file_lenient`].
+///
+/// Lenient mode drops optional and repeated items that fail to parse, like a
+/// malformed label or a wire with a broken stroke, and ignores tokens left
+/// over at the end of a list. Each of them is recorded as a diagnostic.
+/// Anything else, like a missing required field of the file itself, is still
+/// an error.
+#[derive(Debug, Error, PartialEq, Clone)]
+#[error("{context}: {error}")]
+pub struct Diagnostic {
+    /// Where the problem is
+    pub context: ErrorContext,
+    /// What was wrong with the item that was skipped
+    pub error: KiCadParseError,
+}
+
+impl From<KiCadParseError> for Diagnostic {
+    fn from(error: KiCadParseError) -> Self {
+        let (context, error) = error.into_parts();
+
+        Self { context, error }
+    }
 }
 
 macro_rules! simple_to_from_string {
@@ -102,13 +328,13 @@ macro_rules! simple_to_from_string {
             }
         }
 
-        impl std::string::ToString for $name {
-            fn to_string(&self) -> String {
-                match self {
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str(match self {
                     $(
-                        Self::$variant => stringify!($string).to_string(),
+                        Self::$variant => stringify!($string),
                     )*
-                }
+                })
             }
         }
     };
@@ -141,13 +367,68 @@ fn parse_file<T: FromSexpr>(input: &str) -> Result<T, KiCadParseError> {
         });
     };
 
-    T::from_sexpr(Parser::new(list))
+    let root = PathSegment::new(0, &list);
+
+    T::from_sexpr(Parser::new(list)).map_err(|e| e.within(root).with_span(input))
+}
+
+fn parse_file_lenient<T: FromSexpr>(input: &str) -> Result<(T, Vec<Diagnostic>), KiCadParseError> {
+    let sexpr = kicad_sexpr::from_str(input)?;
+
+    let Some(list) = sexpr.take_list() else {
+        return Err(KiCadParseError::UnexpectedSexprType {
+            expected: SexprKind::List,
+        });
+    };
+
+    let root = PathSegment::new(0, &list);
+    let diagnostics = Diagnostics::default();
+
+    let file = T::from_sexpr(Parser::new(list).with_diagnostics(diagnostics.clone()))
+        .map_err(|e| e.within(root.clone()).with_span(input))?;
+
+    let mut diagnostics = diagnostics.take();
+
+    if !diagnostics.is_empty() {
+        let spanned = kicad_sexpr::from_str_spanned(input)?;
+
+        for diagnostic in &mut diagnostics {
+            diagnostic.context.path.insert(0, root.clone());
+            diagnostic.context.locate(&spanned, input);
+        }
+    }
+
+    Ok((file, diagnostics))
 }
 
-fn serialize_file<T: ToSexpr>(file: T) -> String {
+/// The first board and footprint file version written by KiCad 8.
+pub(crate) const KICAD_8_PCB_VERSION: u32 = 20240108;
+
+/// The first schematic and symbol library file version written by KiCad 8.
+const KICAD_8_SCHEMATIC_VERSION: u32 = 20231120;
+
+/// Picks the layout KiCad uses for files of the given version, so that
+/// re-serializing a file does not reformat it.
+fn format_style(version: u32, kicad_8_version: u32) -> FormatStyle {
+    if version >= kicad_8_version {
+        FormatStyle::KiCad8
+    } else {
+        FormatStyle::KiCad7
+    }
+}
+
+fn serialize_file<T: ToSexpr>(file: T, style: FormatStyle) -> String {
     let sexpr = file.to_sexpr();
 
-    kicad_sexpr::to_string(&sexpr)
+    // KiCad always ends its files with a newline
+    kicad_sexpr::format(&sexpr, style) + "\n"
+}
+
+fn write_file<T: ToSexpr>(file: &T, style: FormatStyle, output: impl Write) -> io::Result<()> {
+    let mut writer = Writer::with_style(output, style);
+    convert::to_writer(file, &mut writer)?;
+
+    writer.finish()?.write_all(b"\n")
 }
 
 /* Exposed APIs */
@@ -157,9 +438,30 @@ pub fn parse_footprint_library_file(input: &str) -> Result<FootprintLibraryFile,
     parse_file(input)
 }
 
+/// Parses a Footprint Library file from a string, skipping over malformed items instead
+/// of failing. See [`Diagnostic`] for what is skipped.
+pub fn parse_footprint_library_file_lenient(
+    input: &str,
+) -> Result<(FootprintLibraryFile, Vec<Diagnostic>), KiCadParseError> {
+    parse_file_lenient(input)
+}
+
 /// Serializes a Footprint Library file to a string.
 pub fn serialize_footprint_library_file(footprint_library: FootprintLibraryFile) -> String {
-    serialize_file(footprint_library)
+    let style = format_style(footprint_library.version, KICAD_8_PCB_VERSION);
+
+    serialize_file(footprint_library, style)
+}
+
+/// Writes a Footprint Library file to `output` as it is serialized, without building the
+/// whole file in memory first.
+pub fn write_footprint_library_file(
+    footprint_library: &FootprintLibraryFile,
+    output: impl Write,
+) -> io::Result<()> {
+    let style = format_style(footprint_library.version, KICAD_8_PCB_VERSION);
+
+    write_file(footprint_library, style, output)
 }
 
 /// Parses a Symbol Library file from a string.
@@ -167,9 +469,30 @@ pub fn parse_symbol_library_file(input: &str) -> Result<SymbolLibraryFile, KiCad
     parse_file(input)
 }
 
+/// Parses a Symbol Library file from a string, skipping over malformed items instead
+/// of failing. See [`Diagnostic`] for what is skipped.
+pub fn parse_symbol_library_file_lenient(
+    input: &str,
+) -> Result<(SymbolLibraryFile, Vec<Diagnostic>), KiCadParseError> {
+    parse_file_lenient(input)
+}
+
 /// Serializes a Symbol Library file to a string.
 pub fn serialize_symbol_library_file(symbol_library: SymbolLibraryFile) -> String {
-    serialize_file(symbol_library)
+    let style = format_style(symbol_library.version, KICAD_8_SCHEMATIC_VERSION);
+
+    serialize_file(symbol_library, style)
+}
+
+/// Writes a Symbol Library file to `output` as it is serialized, without building the
+/// whole file in memory first.
+pub fn write_symbol_library_file(
+    symbol_library: &SymbolLibraryFile,
+    output: impl Write,
+) -> io::Result<()> {
+    let style = format_style(symbol_library.version, KICAD_8_SCHEMATIC_VERSION);
+
+    write_file(symbol_library, style, output)
 }
 
 /// Parses a Schematic file from a string.
@@ -177,9 +500,27 @@ pub fn parse_schematic_file(input: &str) -> Result<SchematicFile, KiCadParseErro
     parse_file(input)
 }
 
+/// Parses a Schematic file from a string, skipping over malformed items instead
+/// of failing. See [`Diagnostic`] for what is skipped.
+pub fn parse_schematic_file_lenient(
+    input: &str,
+) -> Result<(SchematicFile, Vec<Diagnostic>), KiCadParseError> {
+    parse_file_lenient(input)
+}
+
 /// Serializes a Schematic file to a string.
 pub fn serialize_schematic_file(schematic: SchematicFile) -> String {
-    serialize_file(schematic)
+    let style = format_style(schematic.version, KICAD_8_SCHEMATIC_VERSION);
+
+    serialize_file(schematic, style)
+}
+
+/// Writes a Schematic file to `output` as it is serialized, without building the
+/// whole file in memory first.
+pub fn write_schematic_file(schematic: &SchematicFile, output: impl Write) -> io::Result<()> {
+    let style = format_style(schematic.version, KICAD_8_SCHEMATIC_VERSION);
+
+    write_file(schematic, style, output)
 }
 
 /// Parses a PCB file from a string.
@@ -187,7 +528,23 @@ pub fn parse_pcb_file(input: &str) -> Result<PcbFile, KiCadParseError> {
     parse_file(input)
 }
 
+/// Parses a PCB file from a string, skipping over malformed items instead
+/// of failing. See [`Diagnostic`] for what is skipped.
+pub fn parse_pcb_file_lenient(input: &str) -> Result<(PcbFile, Vec<Diagnostic>), KiCadParseError> {
+    parse_file_lenient(input)
+}
+
 /// Serializes a PCB file to a string.
 pub fn serialize_pcb_file(pcb: PcbFile) -> String {
-    serialize_file(pcb)
+    let style = format_style(pcb.version, KICAD_8_PCB_VERSION);
+
+    serialize_file(pcb, style)
+}
+
+/// Writes a PCB file to `output` as it is serialized, without building the
+/// whole file in memory first.
+pub fn write_pcb_file(pcb: &PcbFile, output: impl Write) -> io::Result<()> {
+    let style = format_style(pcb.version, KICAD_8_PCB_VERSION);
+
+    write_file(pcb, style, output)
 }
@@ -0,0 +1,943 @@
+//! Moves files from one version of the file formats to another.
+//!
+//! Serializing a file already writes the syntax of its `version`, such as
+//! `uuid` rather than `tstamp` or `(hide yes)` rather than a bare `hide`, so
+//! for most changes setting the version is enough. Other changes move data
+//! around, like the reference of a footprint becoming one of its properties
+//! in KiCad 8. `upgrade_to` and `downgrade_to` make those changes as well as
+//! setting the version:
+//!
+//! - [`PcbFile::upgrade_to`] and [`PcbFile::downgrade_to`]
+//! - [`SchematicFile::upgrade_to`] and [`SchematicFile::downgrade_to`]
+//! - [`FootprintLibraryFile::upgrade_to`] and
+//!   [`FootprintLibraryFile::downgrade_to`]
+//! - [`SymbolLibraryFile::upgrade_to`] and
+//!   [`SymbolLibraryFile::downgrade_to`]
+//!
+//! Settings that an older version does not have are left out when
+//! downgrading. Items it cannot hold, like a table before KiCad 9, are
+//! refused with a [`MigrationError`] instead, and the file is left as it was.
+
+use thiserror::Error;
+
+use crate::{
+    common::{
+        embedded::EmbeddedFile,
+        footprint::{
+            text::{FootprintProperty, FootprintText, FootprintTextKind, FootprintTextPosition},
+            FootprintGraphicsItem,
+        },
+        pad::Pad,
+        symbol::{LibSymbol, SymbolProperty},
+        LayerId, TextEffects, Uuid,
+    },
+    convert::{Extra, FieldOrder},
+    footprint_library::FootprintLibraryFile,
+    length::Length,
+    pcb::{graphics::PcbGraphicsItem, PcbFile, Track},
+    schematic::{
+        symbol::{Symbol, SymbolInstance, SymbolInstanceProject},
+        SchematicFile, SchematicSymbolInstance,
+    },
+    symbol_library::{SymbolDefinition, SymbolLibraryFile},
+    validate::ItemRef,
+    version::{FileFamily, Syntax},
+    KICAD_8_PCB_VERSION,
+};
+
+/// The first board and footprint file version written by KiCad 9.
+const KICAD_9_PCB_VERSION: u32 = 20241229;
+
+/// The first schematic file version written by KiCad 9.
+const KICAD_9_SCHEMATIC_VERSION: u32 = 20250114;
+
+/// The first symbol library file version written by KiCad 9.
+const KICAD_9_SYMBOL_LIBRARY_VERSION: u32 = 20241209;
+
+/// "Don't save property ID"
+const SYMBOL_PROPERTY_IDS_REMOVED: u32 = 20220929;
+
+/// "Move instance data back into symbol definition"
+const SYMBOL_INSTANCES_IN_SYMBOLS: u32 = 20221002;
+
+/// Why a file could not be moved to another version.
+#[derive(Debug, Error, PartialEq, Clone)]
+pub enum MigrationError {
+    #[error("Cannot upgrade from version {from} to the older version {to}")]
+    NotAnUpgrade { from: u32, to: u32 },
+    #[error("Cannot downgrade from version {from} to the newer version {to}")]
+    NotADowngrade { from: u32, to: u32 },
+    /// An item that files of the older version have no way to hold.
+    #[error("{item} cannot be written in version {version}: {reason}")]
+    Unrepresentable {
+        version: u32,
+        item: ItemRef,
+        reason: String,
+    },
+}
+
+fn unrepresentable(version: u32, item: ItemRef, reason: impl Into<String>) -> MigrationError {
+    MigrationError::Unrepresentable {
+        version,
+        item,
+        reason: reason.into(),
+    }
+}
+
+fn check_upgrade(from: u32, to: u32) -> Result<(), MigrationError> {
+    if to < from {
+        return Err(MigrationError::NotAnUpgrade { from, to });
+    }
+
+    Ok(())
+}
+
+fn check_downgrade(from: u32, to: u32) -> Result<(), MigrationError> {
+    if to > from {
+        return Err(MigrationError::NotADowngrade { from, to });
+    }
+
+    Ok(())
+}
+
+/// Whether moving between the two versions crosses the change made in
+/// version `change`, in either direction.
+fn crosses(from: u32, to: u32, change: u32) -> bool {
+    from.min(to) < change && change <= from.max(to)
+}
+
+/// Drops a list that is present but empty, which older versions write by
+/// leaving the list out.
+fn clear_empty<T>(list: &mut Option<Vec<T>>) {
+    if list.as_ref().is_some_and(Vec::is_empty) {
+        *list = None;
+    }
+}
+
+/// Drops a flag that is set to its default, which older versions write by
+/// leaving the flag out.
+fn clear_default(flag: &mut Option<bool>, default: bool) {
+    if *flag == Some(default) {
+        *flag = None;
+    }
+}
+
+fn check_embedded_files(
+    version: u32,
+    files: &Option<Vec<EmbeddedFile>>,
+) -> Result<(), MigrationError> {
+    match files.iter().flatten().next() {
+        Some(file) => Err(unrepresentable(
+            version,
+            ItemRef::new("file", Some(file.name.clone()), None),
+            "files can only be embedded since KiCad 9",
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Checks that a list of jumpers, which KiCad 9 added to footprints and
+/// symbols, is empty.
+fn check_jumpers(
+    version: u32,
+    item: &ItemRef,
+    duplicate_numbers_are_jumpers: Option<bool>,
+    groups: &Option<Vec<Vec<String>>>,
+) -> Result<(), MigrationError> {
+    if duplicate_numbers_are_jumpers == Some(true) || groups.iter().flatten().next().is_some() {
+        return Err(unrepresentable(
+            version,
+            item.clone(),
+            "jumpers can only be defined since KiCad 9",
+        ));
+    }
+
+    Ok(())
+}
+
+// ############################################################################
+
+/// The parts of a footprint that KiCad 9 added, shared by the footprints of
+/// boards and of footprint libraries.
+struct Kicad9Footprint<'a> {
+    item: ItemRef,
+    component_classes: Option<&'a Vec<String>>,
+    duplicate_pad_numbers_are_jumpers: Option<bool>,
+    jumper_pad_groups: &'a Option<Vec<Vec<String>>>,
+    embedded_files: &'a Option<Vec<EmbeddedFile>>,
+    pads: &'a [Pad],
+}
+
+impl Kicad9Footprint<'_> {
+    fn check(&self, version: u32) -> Result<(), MigrationError> {
+        if self.component_classes.is_some_and(|c| !c.is_empty()) {
+            return Err(unrepresentable(
+                version,
+                self.item.clone(),
+                "component classes can only be assigned since KiCad 9",
+            ));
+        }
+
+        check_jumpers(
+            version,
+            &self.item,
+            self.duplicate_pad_numbers_are_jumpers,
+            self.jumper_pad_groups,
+        )?;
+        check_embedded_files(version, self.embedded_files)?;
+
+        if let Some(pad) = self.pads.iter().find(|p| p.padstack.is_some()) {
+            return Err(unrepresentable(
+                version,
+                ItemRef::new("pad", Some(pad.index.clone()), Some(&pad.tstamp)),
+                "pads can only have a padstack since KiCad 9",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// The properties a footprint has before KiCad 8 instead of its
+/// `sheetname` and `sheetfile`.
+const SHEET_NAME_PROPERTY: &str = "Sheetname";
+const SHEET_FILE_PROPERTY: &str = "Sheetfile";
+
+fn take_property(properties: &mut Vec<FootprintProperty>, key: &str) -> Option<String> {
+    let index = properties.iter().position(|p| p.key == key)?;
+
+    Some(properties.remove(index).value)
+}
+
+/// Turns the reference and value texts of a footprint into properties, and
+/// gives plain key-value properties the hidden text that every property has
+/// since KiCad 8.
+fn texts_to_properties(
+    layer: LayerId,
+    properties: &mut Vec<FootprintProperty>,
+    graphics_items: &mut Vec<FootprintGraphicsItem>,
+) {
+    let mut texts = Vec::new();
+
+    graphics_items.retain(|item| match item {
+        FootprintGraphicsItem::Text(text) if text.kind != FootprintTextKind::User => {
+            texts.push(text.clone());
+            false
+        }
+        _ => true,
+    });
+
+    // KiCad lists the reference before the value
+    texts.sort_by_key(|text| text.kind != FootprintTextKind::Reference);
+
+    // KiCad puts the text of plain properties on the fabrication layer of
+    // the side the footprint is on
+    let fab_layer = match layer {
+        LayerId::BCu => LayerId::BFab,
+        _ => LayerId::FFab,
+    };
+
+    for property in properties.iter_mut().filter(|p| p.position.is_none()) {
+        let mut effects = TextEffects::from_size(Length::from_mm(1.0), Length::from_mm(1.0));
+        effects.font.thickness = Some(Length::from_mm(0.15));
+
+        property.position = Some(FootprintTextPosition {
+            x: Length::ZERO,
+            y: Length::ZERO,
+            angle: None,
+            unlocked: false,
+        });
+        property.layer = Some(fab_layer);
+        property.hide = true;
+        property.tstamp = Some(Uuid::new());
+        property.effects = Some(effects);
+    }
+
+    let texts = texts.into_iter().map(|text| FootprintProperty {
+        key: match text.kind {
+            FootprintTextKind::Reference => "Reference",
+            _ => "Value",
+        }
+        .to_string(),
+        value: text.text,
+        position: Some(text.position),
+        layer: Some(text.layer),
+        knockout: text.knockout,
+        hide: text.hide,
+        tstamp: Some(text.tstamp),
+        effects: Some(text.effects),
+        render_cache: text.render_cache,
+        // The order of a text does not fit a property
+        field_order: FieldOrder::default(),
+        extra: text.extra,
+    });
+
+    properties.splice(0..0, texts);
+}
+
+/// Splits the properties of a footprint into the reference and value texts
+/// and the plain key-value properties it has before KiCad 8.
+fn properties_to_texts(
+    version: u32,
+    item: &ItemRef,
+    properties: &[FootprintProperty],
+) -> Result<(Vec<FootprintText>, Vec<FootprintProperty>), MigrationError> {
+    let mut texts = Vec::new();
+    let mut plain = Vec::new();
+
+    for property in properties {
+        let kind = match property.key.as_str() {
+            "Reference" => FootprintTextKind::Reference,
+            "Value" => FootprintTextKind::Value,
+            _ => {
+                let hidden = property.hide || property.effects.as_ref().is_some_and(|e| e.hide);
+
+                if property.position.is_some() && !hidden {
+                    return Err(unrepresentable(
+                        version,
+                        item.clone(),
+                        format!(
+                            "property `{}` is shown, but properties can only be shown since \
+                             KiCad 8",
+                            property.key
+                        ),
+                    ));
+                }
+
+                plain.push(FootprintProperty {
+                    extra: property.extra.clone(),
+                    ..FootprintProperty::new(&property.key, &property.value)
+                });
+
+                continue;
+            }
+        };
+
+        let missing = |field: &str| {
+            unrepresentable(
+                version,
+                item.clone(),
+                format!(
+                    "property `{}` has no {field} to give its text",
+                    property.key
+                ),
+            )
+        };
+
+        texts.push(FootprintText {
+            kind,
+            locked: false,
+            text: property.value.clone(),
+            position: property
+                .position
+                .clone()
+                .ok_or_else(|| missing("position"))?,
+            layer: property.layer.ok_or_else(|| missing("layer"))?,
+            knockout: property.knockout,
+            hide: property.hide,
+            effects: property.effects.clone().ok_or_else(|| missing("effects"))?,
+            tstamp: property.tstamp.clone().unwrap_or_else(Uuid::new),
+            render_cache: property.render_cache.clone(),
+            field_order: FieldOrder::default(),
+            extra: property.extra.clone(),
+        });
+    }
+
+    Ok((texts, plain))
+}
+
+fn insert_texts(graphics_items: &mut Vec<FootprintGraphicsItem>, texts: Vec<FootprintText>) {
+    graphics_items.splice(0..0, texts.into_iter().map(FootprintGraphicsItem::Text));
+}
+
+// ############################################################################
+
+impl PcbFile {
+    /// Moves the board to a newer version.
+    ///
+    /// Going to KiCad 8 turns the reference and value texts of footprints
+    /// into properties, gives the other properties a hidden text, and moves
+    /// the `Sheetname` and `Sheetfile` properties into
+    /// [`FootprintInlined::sheet_name`](crate::common::footprint::FootprintInlined::sheet_name)
+    /// and `sheet_file`.
+    pub fn upgrade_to(&mut self, version: u32) -> Result<(), MigrationError> {
+        check_upgrade(self.version, version)?;
+
+        if crosses(self.version, version, KICAD_8_PCB_VERSION) {
+            for footprint in &mut self.footprints {
+                let sheet_name = take_property(&mut footprint.properties, SHEET_NAME_PROPERTY);
+                let sheet_file = take_property(&mut footprint.properties, SHEET_FILE_PROPERTY);
+
+                footprint.sheet_name = footprint.sheet_name.take().or(sheet_name);
+                footprint.sheet_file = footprint.sheet_file.take().or(sheet_file);
+
+                texts_to_properties(
+                    footprint.layer,
+                    &mut footprint.properties,
+                    &mut footprint.graphics_items,
+                );
+
+                // The order the footprint was read in is the one of the
+                // older version
+                footprint.field_order = FieldOrder::default();
+            }
+        }
+
+        self.version = version;
+
+        Ok(())
+    }
+
+    /// Moves the board to an older version.
+    ///
+    /// This reverses [`PcbFile::upgrade_to`]. It fails if the board has
+    /// items that the version cannot hold, like tables, padstacks or
+    /// embedded files before KiCad 9, or footprint properties that are shown
+    /// on the board before KiCad 8.
+    pub fn downgrade_to(&mut self, version: u32) -> Result<(), MigrationError> {
+        check_downgrade(self.version, version)?;
+
+        let before_kicad_9 = version < KICAD_9_PCB_VERSION;
+
+        if before_kicad_9 {
+            self.check_kicad_9_items(version)?;
+        }
+
+        let legacy_texts = if crosses(self.version, version, KICAD_8_PCB_VERSION) {
+            self.footprints
+                .iter()
+                .map(|footprint| {
+                    let item = ItemRef::new(
+                        "footprint",
+                        Some(footprint.library_link.to_string()),
+                        Some(&footprint.tstamp),
+                    );
+
+                    properties_to_texts(version, &item, &footprint.properties)
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        } else {
+            Vec::new()
+        };
+
+        if before_kicad_9 {
+            self.clear_kicad_9_settings();
+        }
+
+        if !legacy_texts.is_empty() {
+            for (footprint, (texts, properties)) in self.footprints.iter_mut().zip(legacy_texts) {
+                let sheet_properties = [
+                    (SHEET_FILE_PROPERTY, footprint.sheet_file.take()),
+                    (SHEET_NAME_PROPERTY, footprint.sheet_name.take()),
+                ]
+                .into_iter()
+                .filter_map(|(key, value)| Some(FootprintProperty::new(key, value?)));
+
+                footprint.properties = sheet_properties.chain(properties).collect();
+                insert_texts(&mut footprint.graphics_items, texts);
+                footprint.field_order = FieldOrder::default();
+            }
+
+            self.general_settings.legacy_teardrops = None;
+            self.setup.plot_options.pdf_front_fp_property_popups = None;
+            self.setup.plot_options.pdf_back_fp_property_popups = None;
+            self.setup.plot_options.plot_footprint_text = None;
+        }
+
+        self.version = version;
+
+        Ok(())
+    }
+
+    fn check_kicad_9_items(&self, version: u32) -> Result<(), MigrationError> {
+        check_embedded_files(version, &self.embedded_files)?;
+
+        for footprint in &self.footprints {
+            Kicad9Footprint {
+                item: ItemRef::new(
+                    "footprint",
+                    Some(footprint.library_link.to_string()),
+                    Some(&footprint.tstamp),
+                ),
+                component_classes: footprint.component_classes.as_ref(),
+                duplicate_pad_numbers_are_jumpers: footprint.duplicate_pad_numbers_are_jumpers,
+                jumper_pad_groups: &footprint.jumper_pad_groups,
+                embedded_files: &footprint.embedded_files,
+                pads: &footprint.pads,
+            }
+            .check(version)?;
+        }
+
+        for item in &self.graphics_items {
+            if let PcbGraphicsItem::Table(table) = item {
+                let id = table.cells.first().map(|cell| &cell.tstamp);
+
+                return Err(unrepresentable(
+                    version,
+                    ItemRef::new("table", None, id),
+                    "boards can only have tables since KiCad 9",
+                ));
+            }
+        }
+
+        for track in &self.tracks {
+            let Track::Via(via) = track else {
+                continue;
+            };
+
+            let reason = if via.padstack.is_some() {
+                "vias can only have a padstack since KiCad 9"
+            } else if via.tenting.is_some() {
+                "vias can only be tented on their own since KiCad 9"
+            } else {
+                continue;
+            };
+
+            return Err(unrepresentable(
+                version,
+                ItemRef::new("via", None, Some(&via.tstamp)),
+                reason,
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn clear_kicad_9_settings(&mut self) {
+        self.embedded_fonts = None;
+        clear_empty(&mut self.embedded_files);
+        self.setup.tenting = None;
+
+        for footprint in &mut self.footprints {
+            clear_empty(&mut footprint.component_classes);
+            clear_default(&mut footprint.duplicate_pad_numbers_are_jumpers, false);
+            clear_empty(&mut footprint.jumper_pad_groups);
+            footprint.embedded_fonts = None;
+            clear_empty(&mut footprint.embedded_files);
+        }
+
+        for item in &mut self.graphics_items {
+            if let PcbGraphicsItem::Dimension(dimension) = item {
+                dimension.style.arrow_direction = None;
+            }
+        }
+    }
+}
+
+impl FootprintLibraryFile {
+    /// Moves the footprint to a newer version.
+    ///
+    /// Going to KiCad 8 turns the reference and value texts into properties
+    /// and gives the other properties a hidden text.
+    pub fn upgrade_to(&mut self, version: u32) -> Result<(), MigrationError> {
+        check_upgrade(self.version, version)?;
+
+        if crosses(self.version, version, KICAD_8_PCB_VERSION) {
+            texts_to_properties(self.layer, &mut self.properties, &mut self.graphics_items);
+        }
+
+        self.version = version;
+
+        Ok(())
+    }
+
+    /// Moves the footprint to an older version.
+    ///
+    /// This reverses [`FootprintLibraryFile::upgrade_to`]. It fails if the
+    /// footprint has items that the version cannot hold, like padstacks or
+    /// embedded files before KiCad 9, or properties that are shown before
+    /// KiCad 8.
+    pub fn downgrade_to(&mut self, version: u32) -> Result<(), MigrationError> {
+        check_downgrade(self.version, version)?;
+
+        let item = ItemRef::new("footprint", Some(self.name.clone()), None);
+        let before_kicad_9 = version < KICAD_9_PCB_VERSION;
+
+        if before_kicad_9 {
+            Kicad9Footprint {
+                item: item.clone(),
+                component_classes: None,
+                duplicate_pad_numbers_are_jumpers: self.duplicate_pad_numbers_are_jumpers,
+                jumper_pad_groups: &self.jumper_pad_groups,
+                embedded_files: &self.embedded_files,
+                pads: &self.pads,
+            }
+            .check(version)?;
+        }
+
+        let legacy_texts = crosses(self.version, version, KICAD_8_PCB_VERSION)
+            .then(|| properties_to_texts(version, &item, &self.properties))
+            .transpose()?;
+
+        if before_kicad_9 {
+            clear_default(&mut self.duplicate_pad_numbers_are_jumpers, false);
+            clear_empty(&mut self.jumper_pad_groups);
+            self.embedded_fonts = None;
+            clear_empty(&mut self.embedded_files);
+        }
+
+        if let Some((texts, properties)) = legacy_texts {
+            self.properties = properties;
+            insert_texts(&mut self.graphics_items, texts);
+        }
+
+        self.version = version;
+
+        Ok(())
+    }
+}
+
+// ############################################################################
+
+fn clear_property_ids(properties: &mut [SymbolProperty]) {
+    for property in properties {
+        property.legacy_id = None;
+    }
+}
+
+fn lib_symbol_item(symbol: &LibSymbol) -> ItemRef {
+    ItemRef::new("symbol", Some(symbol.id.to_string()), None)
+}
+
+fn symbol_item(symbol: &Symbol) -> ItemRef {
+    ItemRef::new(
+        "symbol",
+        Some(symbol.lib_id.to_string()),
+        Some(&symbol.uuid),
+    )
+}
+
+fn check_kicad_9_lib_symbol(version: u32, symbol: &LibSymbol) -> Result<(), MigrationError> {
+    check_jumpers(
+        version,
+        &lib_symbol_item(symbol),
+        symbol.duplicate_pin_numbers_are_jumpers,
+        &symbol.jumper_pin_groups,
+    )?;
+
+    check_embedded_files(version, &symbol.embedded_files)
+}
+
+fn clear_kicad_9_lib_symbol_settings(symbol: &mut LibSymbol) {
+    clear_default(&mut symbol.duplicate_pin_numbers_are_jumpers, false);
+    clear_empty(&mut symbol.jumper_pin_groups);
+    symbol.embedded_fonts = None;
+    clear_empty(&mut symbol.embedded_files);
+}
+
+fn property_value(properties: &[SymbolProperty], key: &str) -> Option<String> {
+    properties
+        .iter()
+        .find(|p| p.key == key)
+        .map(|p| p.value.clone())
+}
+
+fn set_property_value(properties: &mut [SymbolProperty], key: &str, value: Option<String>) {
+    let Some(value) = value else {
+        return;
+    };
+
+    if let Some(property) = properties.iter_mut().find(|p| p.key == key) {
+        property.value = value;
+    }
+}
+
+impl SchematicFile {
+    /// Moves the schematic to a newer version.
+    ///
+    /// Going to KiCad 7 drops the `id` of symbol properties and moves the
+    /// [`SchematicFile::symbol_instances`] into the
+    /// [`Symbol::instances`] of each symbol, under the name of `project`.
+    /// Files before KiCad 7 do not name their project, which KiCad takes
+    /// from the name of the `.kicad_pro` file instead.
+    ///
+    /// Before KiCad 7 only the root schematic lists instances, including
+    /// those of the symbols in other sheets' files. The entries of symbols
+    /// that are not in this file stay in `symbol_instances`.
+    pub fn upgrade_to(&mut self, version: u32, project: &str) -> Result<(), MigrationError> {
+        check_upgrade(self.version, version)?;
+
+        if crosses(self.version, version, SYMBOL_PROPERTY_IDS_REMOVED) {
+            for symbol in &mut self.lib_symbols {
+                clear_property_ids(&mut symbol.properties);
+            }
+
+            for symbol in &mut self.symbols {
+                clear_property_ids(&mut symbol.properties);
+            }
+
+            for sheet in &mut self.sheets {
+                clear_property_ids(&mut sheet.properties);
+            }
+        }
+
+        if crosses(self.version, version, SYMBOL_INSTANCES_IN_SYMBOLS) {
+            self.move_symbol_instances_to_symbols(project);
+        }
+
+        self.version = version;
+
+        Ok(())
+    }
+
+    /// Moves the schematic to an older version.
+    ///
+    /// Going back before KiCad 7 collects the [`Symbol::instances`] into
+    /// [`SchematicFile::symbol_instances`], which only the root schematic of
+    /// a single project can do. It also fails if the schematic has items
+    /// that the version cannot hold, like tables or embedded files before
+    /// KiCad 9.
+    pub fn downgrade_to(&mut self, version: u32) -> Result<(), MigrationError> {
+        check_downgrade(self.version, version)?;
+
+        let before_kicad_9 = version < KICAD_9_SCHEMATIC_VERSION;
+
+        if before_kicad_9 {
+            self.check_kicad_9_items(version)?;
+        }
+
+        if crosses(
+            self.version,
+            version,
+            Syntax::Dnp.since(FileFamily::Schematic),
+        ) {
+            if let Some(symbol) = self.symbols.iter().find(|s| s.dnp) {
+                return Err(unrepresentable(
+                    version,
+                    symbol_item(symbol),
+                    "symbols can only be marked as not populated since KiCad 7",
+                ));
+            }
+        }
+
+        let symbol_instances = crosses(self.version, version, SYMBOL_INSTANCES_IN_SYMBOLS)
+            .then(|| self.collect_symbol_instances(version))
+            .transpose()?;
+
+        if before_kicad_9 {
+            self.clear_kicad_9_settings();
+        }
+
+        if let Some(instances) = symbol_instances {
+            for symbol in &mut self.symbols {
+                symbol.instances = None;
+            }
+
+            if !instances.is_empty() {
+                self.symbol_instances
+                    .get_or_insert_with(Vec::new)
+                    .extend(instances);
+            }
+        }
+
+        self.version = version;
+
+        Ok(())
+    }
+
+    fn move_symbol_instances_to_symbols(&mut self, project: &str) {
+        let Some(entries) = self.symbol_instances.take() else {
+            return;
+        };
+
+        let root = format!("/{}", self.uuid);
+        let mut remaining = Vec::new();
+
+        for entry in entries {
+            // The path ends with the symbol, after the sheets leading to it
+            let (sheet_path, id) = entry.path.rsplit_once('/').unwrap_or(("", &entry.path));
+
+            let Some(symbol) = self.symbols.iter_mut().find(|s| s.uuid.to_string() == id) else {
+                remaining.push(entry);
+                continue;
+            };
+
+            // KiCad 7 no longer keeps a value and footprint for each
+            // instance, only the ones of the symbol
+            set_property_value(&mut symbol.properties, "Value", entry.value);
+            set_property_value(&mut symbol.properties, "Footprint", entry.footprint);
+
+            let projects = symbol.instances.get_or_insert_with(Vec::new);
+            let index = match projects.iter().position(|p| p.project == project) {
+                Some(index) => index,
+                None => {
+                    projects.push(SymbolInstanceProject {
+                        project: project.to_string(),
+                        instances: Vec::new(),
+                        extra: Extra::default(),
+                    });
+
+                    projects.len() - 1
+                }
+            };
+
+            // Since KiCad 7 paths start at the root schematic
+            projects[index].instances.push(SymbolInstance {
+                path: format!("{root}{sheet_path}"),
+                reference: entry.reference,
+                unit: entry.unit,
+                extra: entry.extra,
+            });
+        }
+
+        self.symbol_instances = (!remaining.is_empty()).then_some(remaining);
+    }
+
+    fn collect_symbol_instances(
+        &self,
+        version: u32,
+    ) -> Result<Vec<SchematicSymbolInstance>, MigrationError> {
+        let root = format!("/{}", self.uuid);
+        let mut project = None;
+        let mut entries = Vec::new();
+
+        for symbol in &self.symbols {
+            for instances in symbol.instances.iter().flatten() {
+                if *project.get_or_insert(&instances.project) != &instances.project {
+                    return Err(unrepresentable(
+                        version,
+                        symbol_item(symbol),
+                        "the symbol has instances in more than one project, but files before \
+                         KiCad 7 only list the instances of one",
+                    ));
+                }
+
+                for instance in &instances.instances {
+                    let sheet_path = instance
+                        .path
+                        .strip_prefix(&root)
+                        .filter(|rest| rest.is_empty() || rest.starts_with('/'))
+                        .ok_or_else(|| {
+                            unrepresentable(
+                                version,
+                                symbol_item(symbol),
+                                format!(
+                                    "instance `{}` is not in the hierarchy of this schematic, \
+                                     but before KiCad 7 only the root schematic lists instances",
+                                    instance.path
+                                ),
+                            )
+                        })?;
+
+                    entries.push(SchematicSymbolInstance {
+                        path: format!("{sheet_path}/{}", symbol.uuid),
+                        reference: instance.reference.clone(),
+                        unit: instance.unit,
+                        value: property_value(&symbol.properties, "Value"),
+                        footprint: property_value(&symbol.properties, "Footprint"),
+                        extra: instance.extra.clone(),
+                    });
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+
+    fn check_kicad_9_items(&self, version: u32) -> Result<(), MigrationError> {
+        check_embedded_files(version, &self.embedded_files)?;
+
+        if !self.tables.is_empty() {
+            return Err(unrepresentable(
+                version,
+                ItemRef::new("table", None, None),
+                "schematics can only have tables since KiCad 9",
+            ));
+        }
+
+        for symbol in &self.lib_symbols {
+            check_kicad_9_lib_symbol(version, symbol)?;
+        }
+
+        for sheet in &self.sheets {
+            let overridden = sheet.exclude_from_sim == Some(true)
+                || sheet.in_bom == Some(false)
+                || sheet.on_board == Some(false)
+                || sheet.dnp == Some(true);
+
+            if overridden {
+                let name = sheet
+                    .properties
+                    .iter()
+                    .find(|p| p.key == "Sheetname")
+                    .map(|p| p.value.clone());
+
+                return Err(unrepresentable(
+                    version,
+                    ItemRef::new("sheet", name, Some(&sheet.uuid)),
+                    "sheets can only change the attributes of their symbols since KiCad 9",
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn clear_kicad_9_settings(&mut self) {
+        self.embedded_fonts = None;
+        clear_empty(&mut self.embedded_files);
+
+        for symbol in &mut self.lib_symbols {
+            clear_kicad_9_lib_symbol_settings(symbol);
+        }
+
+        for sheet in &mut self.sheets {
+            sheet.exclude_from_sim = None;
+            sheet.in_bom = None;
+            sheet.on_board = None;
+            sheet.dnp = None;
+        }
+    }
+}
+
+impl SymbolLibraryFile {
+    /// Moves the library to a newer version.
+    ///
+    /// Going to KiCad 7 drops the `id` of symbol properties.
+    pub fn upgrade_to(&mut self, version: u32) -> Result<(), MigrationError> {
+        check_upgrade(self.version, version)?;
+
+        if crosses(self.version, version, SYMBOL_PROPERTY_IDS_REMOVED) {
+            for symbol in &mut self.symbols {
+                match symbol {
+                    SymbolDefinition::RootSymbol(symbol) => {
+                        clear_property_ids(&mut symbol.properties)
+                    }
+                    SymbolDefinition::DerivedSymbol(symbol) => {
+                        clear_property_ids(&mut symbol.properties)
+                    }
+                }
+            }
+        }
+
+        self.version = version;
+
+        Ok(())
+    }
+
+    /// Moves the library to an older version.
+    ///
+    /// It fails if a symbol has items that the version cannot hold, like
+    /// jumpers or embedded files before KiCad 9.
+    pub fn downgrade_to(&mut self, version: u32) -> Result<(), MigrationError> {
+        check_downgrade(self.version, version)?;
+
+        if version < KICAD_9_SYMBOL_LIBRARY_VERSION {
+            for symbol in &self.symbols {
+                if let SymbolDefinition::RootSymbol(symbol) = symbol {
+                    check_kicad_9_lib_symbol(version, symbol)?;
+                }
+            }
+
+            for symbol in &mut self.symbols {
+                if let SymbolDefinition::RootSymbol(symbol) = symbol {
+                    clear_kicad_9_lib_symbol_settings(symbol);
+                }
+            }
+        }
+
+        self.version = version;
+
+        Ok(())
+    }
+}
@@ -1,8 +1,9 @@
-use kicad_sexpr::Sexpr;
-
 use crate::{
     common::{LayerId, Uuid, Vec2D},
-    convert::{FromSexpr, Parser, ToSexpr},
+    convert::{writes, Extra, FieldOrder, FromSexpr, ListBuilder, Parser, SexprListExt, ToSexpr},
+    length::{Length, LengthUnit},
+    simple_maybe_from_sexpr, simple_to_from_string,
+    version::Syntax,
     KiCadParseError,
 };
 
@@ -30,13 +31,13 @@ pub struct PcbDimension {
     pub points: [Vec2D; 2],
     /// The optional `height` token attribute defines the height of aligned
     /// dimensions.
-    pub height: Option<f32>,
-    /// The optional `orientation` token attribute defines the rotation angle
-    /// for orthogonal dimensions.
-    pub orientation: Option<f32>,
+    pub height: Option<Length>,
+    /// The optional `orientation` token attribute defines whether orthogonal
+    /// dimensions measure horizontally or vertically.
+    pub orientation: Option<DimensionOrientation>,
     /// The optional `leader_length` token attribute defines the distance from
     /// the marked radius to the knee for radial dimensions.
-    pub leader_length: Option<f32>,
+    pub leader_length: Option<Length>,
     /// The optional `gr_text` token attributes define the dimension text
     /// formatting for all dimension types except center dimensions.
     pub text: Option<PcbText>,
@@ -45,21 +46,148 @@ pub struct PcbDimension {
     pub format: Option<DimensionFormat>,
     /// The `style` token attributes define the dimension style information.
     pub style: DimensionStyle,
+    pub field_order: FieldOrder,
+    pub extra: Extra,
+}
+
+impl PcbDimension {
+    /// The distance the dimension measures, or `None` for leader and center
+    /// dimensions, which do not measure anything.
+    pub fn measured_length(&self) -> Option<Length> {
+        let [start, end] = self.points;
+        let delta = end - start;
+
+        match self.kind {
+            DimensionKind::Aligned | DimensionKind::Radial => {
+                let length = (delta.x.nm() as f64).hypot(delta.y.nm() as f64);
+                Some(Length::from_nm(length.round() as i64))
+            }
+            DimensionKind::Orthogonal => match self.orientation {
+                Some(DimensionOrientation::Vertical) => Some(delta.y.abs()),
+                _ => Some(delta.x.abs()),
+            },
+            DimensionKind::Leader | DimensionKind::Center => None,
+        }
+    }
+
+    /// The measured value as KiCad displays it, following the units, units
+    /// format, precision and zero suppression of the dimension's
+    /// [`DimensionFormat`].
+    ///
+    /// The prefix, suffix and override value are not applied, and automatic
+    /// units are shown in millimetres since the board's display units are
+    /// not stored in the file. Returns `None` if the dimension measures
+    /// nothing or has no format.
+    pub fn measured_value(&self) -> Option<String> {
+        let format = self.format.as_ref()?;
+        let length = self.measured_length()?;
+
+        let unit = format.units.length_unit();
+        let mut value = format!("{:.*}", format.decimal_places(), length.to_value(unit));
+
+        if format.suppress_zeros && value.contains('.') {
+            value.truncate(value.trim_end_matches('0').trim_end_matches('.').len());
+        }
+
+        let label = format.units.label();
+        match format.units_format {
+            DimensionUnitsFormat::NoSuffix => {}
+            DimensionUnitsFormat::BareSuffix => value = format!("{value} {label}"),
+            DimensionUnitsFormat::WrapSuffixInParenthesis => value = format!("{value} ({label})"),
+        }
+
+        Some(value)
+    }
 }
 
 impl FromSexpr for PcbDimension {
-    fn from_sexpr(_parser: Parser) -> Result<Self, KiCadParseError> {
-        todo!("impl FromSexpr for PcbDimension")
+    fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
+        parser.expect_symbol_matching("dimension")?;
+
+        let mut parser = parser.keyed();
+        let field_order = parser.field_order();
+
+        let locked = parser.maybe_locked()?;
+        let kind = parser
+            .expect_symbol_with_name("type")?
+            .parse::<DimensionKind>()?;
+        let layer = parser
+            .expect_string_with_name("layer")?
+            .parse::<LayerId>()?;
+        let tstamp = parser.expect_id()?;
+        let points = parser
+            .maybe_points()?
+            .ok_or_else(|| KiCadParseError::MissingField("pts".to_string()))?
+            .try_into()
+            .map_err(|v: Vec<_>| KiCadParseError::IncorrectNumberOfPoints {
+                expected: 2,
+                found: v.len(),
+            })?;
+        let height = parser.maybe_length_with_name("height")?;
+        let orientation = parser
+            .maybe_integer_with_name::<u8>("orientation")?
+            .map(DimensionOrientation::try_from)
+            .transpose()?;
+        let leader_length = parser.maybe_length_with_name("leader_length")?;
+        let text = parser.maybe::<PcbText>()?;
+        let format = parser.maybe::<DimensionFormat>()?;
+        let style = parser
+            .maybe::<DimensionStyle>()?
+            .ok_or_else(|| KiCadParseError::MissingField("style".to_string()))?;
+
+        let extra = parser.expect_end_with_extra()?;
+
+        Ok(Self {
+            locked,
+            kind,
+            layer,
+            tstamp,
+            points,
+            height,
+            orientation,
+            leader_length,
+            text,
+            format,
+            style,
+            field_order,
+            extra,
+        })
     }
 }
 
 impl ToSexpr for PcbDimension {
-    fn to_sexpr(&self) -> Sexpr {
-        todo!("impl ToSexpr for PcbDimension")
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_in_order("dimension", &self.field_order, &self.extra, |s| {
+            s.append_locked(self.locked);
+            s.append_symbol_with_name("type", self.kind);
+            s.append_string_with_name("layer", self.layer);
+            s.append_id(&self.tstamp);
+            s.append_list("pts", |s| s.append_many(&self.points));
+            s.append_maybe_number_with_name("height", self.height);
+            s.append_maybe_number_with_name("orientation", self.orientation.map(|o| o as u8));
+            s.append_maybe_number_with_name("leader_length", self.leader_length);
+            s.append_maybe(&self.text);
+            s.append_maybe(&self.format);
+            s.append(&self.style);
+        });
     }
 }
 
-/// See `kind` field in [`Dimension`].
+/// Writes a flag of a dimension's format or style, which KiCad kept writing as
+/// a bare symbol until KiCad 9.
+fn append_dimension_flag(s: &mut ListBuilder, name: &str, value: bool) {
+    if !value {
+        return;
+    }
+
+    if writes(Syntax::DimensionFlags) {
+        s.append_bool_with_name(name, true);
+    } else {
+        s.append_symbol(name);
+    }
+}
+
+/// See `kind` field in [`PcbDimension`].
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -71,6 +199,41 @@ pub enum DimensionKind {
     Radial,
 }
 
+simple_to_from_string! {
+    DimensionKind,
+    aligned <-> Aligned,
+    leader <-> Leader,
+    center <-> Center,
+    orthogonal <-> Orthogonal,
+    radial <-> Radial,
+}
+
+/// See `orientation` field in [`PcbDimension`].
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[repr(u8)]
+pub enum DimensionOrientation {
+    Horizontal = 0,
+    Vertical = 1,
+}
+
+impl TryFrom<u8> for DimensionOrientation {
+    type Error = KiCadParseError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => Self::Horizontal,
+            1 => Self::Vertical,
+            _ => {
+                return Err(KiCadParseError::invalid_enum_value::<Self>(
+                    value.to_string(),
+                ))
+            }
+        })
+    }
+}
+
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 #[derive(Debug, PartialEq, Clone)]
@@ -105,9 +268,76 @@ pub struct DimensionFormat {
     /// The optional `override_value` token attribute defines the text to
     /// substitute for the actual physical dimension.
     pub override_value: Option<String>,
-    /// The optional `suppress_zeros` token removes all trailing zeros from the
-    /// dimension text.
+    /// The optional `suppress_zeroes` token removes all trailing zeros from
+    /// the dimension text.
     pub suppress_zeros: bool,
+    pub extra: Extra,
+}
+
+impl DimensionFormat {
+    /// The number of digits after the decimal point, resolving units-scaled
+    /// precisions for the format's units.
+    pub fn decimal_places(&self) -> usize {
+        let precision = self.precision as usize;
+        if precision < 6 {
+            return precision;
+        }
+
+        match self.units.length_unit() {
+            LengthUnit::Inches => precision - 4,
+            LengthUnit::Mils => precision.saturating_sub(7),
+            LengthUnit::Millimetres => precision - 5,
+        }
+    }
+}
+
+impl FromSexpr for DimensionFormat {
+    fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
+        parser.expect_symbol_matching("format")?;
+
+        let mut parser = parser.keyed();
+
+        let prefix = parser.maybe_string_with_name("prefix")?;
+        let suffix = parser.maybe_string_with_name("suffix")?;
+        let units = parser.expect_integer_with_name::<u8>("units")?.try_into()?;
+        let units_format = parser
+            .expect_integer_with_name::<u8>("units_format")?
+            .try_into()?;
+        let precision = parser.expect_integer_with_name::<u8>("precision")?;
+        let override_value = parser.maybe_string_with_name("override_value")?;
+        let suppress_zeros = parser
+            .maybe_versioned_flag("suppress_zeroes", Syntax::DimensionFlags)?
+            || parser.maybe_versioned_flag("suppress_zeros", Syntax::DimensionFlags)?;
+
+        let extra = parser.expect_end_with_extra()?;
+
+        Ok(Self {
+            prefix,
+            suffix,
+            units,
+            units_format,
+            precision,
+            override_value,
+            suppress_zeros,
+            extra,
+        })
+    }
+}
+
+simple_maybe_from_sexpr!(DimensionFormat, format);
+
+impl ToSexpr for DimensionFormat {
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("format", &self.extra, |s| {
+            s.append_maybe_string_with_name("prefix", self.prefix.as_deref());
+            s.append_maybe_string_with_name("suffix", self.suffix.as_deref());
+            s.append_number_with_name("units", self.units as u8);
+            s.append_number_with_name("units_format", self.units_format as u8);
+            s.append_number_with_name("precision", self.precision);
+            s.append_maybe_string_with_name("override_value", self.override_value.as_deref());
+            append_dimension_flag(s, "suppress_zeroes", self.suppress_zeros);
+        });
+    }
 }
 
 /// See `units` field in [`DimensionUnits`].
@@ -123,6 +353,45 @@ pub enum DimensionUnits {
     Automatic = 3,
 }
 
+impl DimensionUnits {
+    /// The unit values are shown in. Automatic units fall back to
+    /// millimetres.
+    pub fn length_unit(self) -> LengthUnit {
+        match self {
+            Self::Inches => LengthUnit::Inches,
+            Self::Mils => LengthUnit::Mils,
+            Self::Millimeters | Self::Automatic => LengthUnit::Millimetres,
+        }
+    }
+
+    /// The unit label KiCad adds to dimension text.
+    fn label(self) -> &'static str {
+        match self.length_unit() {
+            LengthUnit::Inches => "in",
+            LengthUnit::Mils => "mils",
+            LengthUnit::Millimetres => "mm",
+        }
+    }
+}
+
+impl TryFrom<u8> for DimensionUnits {
+    type Error = KiCadParseError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => Self::Inches,
+            1 => Self::Mils,
+            2 => Self::Millimeters,
+            3 => Self::Automatic,
+            _ => {
+                return Err(KiCadParseError::invalid_enum_value::<Self>(
+                    value.to_string(),
+                ))
+            }
+        })
+    }
+}
+
 /// See `units_format` field in [`DimensionFormat`].
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
@@ -134,6 +403,23 @@ pub enum DimensionUnitsFormat {
     WrapSuffixInParenthesis = 2,
 }
 
+impl TryFrom<u8> for DimensionUnitsFormat {
+    type Error = KiCadParseError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => Self::NoSuffix,
+            1 => Self::BareSuffix,
+            2 => Self::WrapSuffixInParenthesis,
+            _ => {
+                return Err(KiCadParseError::invalid_enum_value::<Self>(
+                    value.to_string(),
+                ))
+            }
+        })
+    }
+}
+
 /// https://dev-docs.kicad.org/en/file-formats/sexpr-intro/#_dimension_style
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
@@ -141,19 +427,22 @@ pub enum DimensionUnitsFormat {
 pub struct DimensionStyle {
     /// The `thickness` token attribute defines the line thickness of the
     /// dimension.
-    pub thickness: f32,
+    pub thickness: Length,
     /// The `arrow_length` token attribute defines the length of the dimension
     /// arrows.
-    pub arrow_length: f32,
+    pub arrow_length: Length,
     /// The text_position_mode token attribute defines the position mode of the
     /// dimension text. Valid position modes are as follows:
     ///   0 - Text is outside the dimension line.
     ///   1 - Text is in line with the dimension line.
     ///   2 - Text has been manually placed by the user.
     pub text_position_mode: DimensionTextPositionMode,
+    /// The optional `arrow_direction` token attribute defines whether the
+    /// arrows point outward or inward. From version 9.
+    pub arrow_direction: Option<DimensionArrowDirection>,
     /// The optional `extension_height` token attribute defines the length of
     /// the extension lines past the dimension crossbar.
-    pub extension_height: Option<f32>,
+    pub extension_height: Option<Length>,
     /// The optional text_frame token attribute defines the style of the frame
     /// around the dimension text. This only applies to leader dimensions.
     /// Valid text frames are as follows:
@@ -164,12 +453,85 @@ pub struct DimensionStyle {
     pub text_frame: Option<TextFrameKind>,
     /// The optional `extension_offset` token attribute defines the distance
     /// from feature points to extension line start.
-    pub extension_offset: Option<f32>,
+    pub extension_offset: Option<Length>,
     /// The optional keep_text_aligned token indicates that the dimension text
     /// should be kept in line with the dimension crossbar. When not defined,
     /// the dimension text is shown horizontally regardless of the orientation
     /// of the dimension.
     pub keep_text_aligned: bool,
+    pub extra: Extra,
+}
+
+impl FromSexpr for DimensionStyle {
+    fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
+        parser.expect_symbol_matching("style")?;
+
+        let mut parser = parser.keyed();
+
+        let thickness = parser.expect_length_with_name("thickness")?;
+        let arrow_length = parser.expect_length_with_name("arrow_length")?;
+        let text_position_mode = parser
+            .expect_integer_with_name::<u8>("text_position_mode")?
+            .try_into()?;
+        let arrow_direction = parser
+            .maybe_symbol_with_name("arrow_direction")?
+            .map(|s| s.parse::<DimensionArrowDirection>())
+            .transpose()?;
+        let extension_height = parser.maybe_length_with_name("extension_height")?;
+        let text_frame = parser
+            .maybe_integer_with_name::<u8>("text_frame")?
+            .map(TextFrameKind::try_from)
+            .transpose()?;
+        let extension_offset = parser.maybe_length_with_name("extension_offset")?;
+        let keep_text_aligned =
+            parser.maybe_versioned_flag("keep_text_aligned", Syntax::DimensionFlags)?;
+
+        let extra = parser.expect_end_with_extra()?;
+
+        Ok(Self {
+            thickness,
+            arrow_length,
+            text_position_mode,
+            arrow_direction,
+            extension_height,
+            text_frame,
+            extension_offset,
+            keep_text_aligned,
+            extra,
+        })
+    }
+}
+
+simple_maybe_from_sexpr!(DimensionStyle, style);
+
+impl ToSexpr for DimensionStyle {
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("style", &self.extra, |s| {
+            s.append_number_with_name("thickness", self.thickness);
+            s.append_number_with_name("arrow_length", self.arrow_length);
+            s.append_number_with_name("text_position_mode", self.text_position_mode as u8);
+            s.append_maybe_symbol_with_name("arrow_direction", self.arrow_direction);
+            s.append_maybe_number_with_name("extension_height", self.extension_height);
+            s.append_maybe_number_with_name("text_frame", self.text_frame.map(|t| t as u8));
+            s.append_maybe_number_with_name("extension_offset", self.extension_offset);
+            append_dimension_flag(s, "keep_text_aligned", self.keep_text_aligned);
+        });
+    }
+}
+
+/// See `arrow_direction` field in [`DimensionStyle`].
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DimensionArrowDirection {
+    Outward,
+    Inward,
+}
+
+simple_to_from_string! {
+    DimensionArrowDirection,
+    outward <-> Outward,
+    inward <-> Inward,
 }
 
 /// See `text_position_mode` field in [`DimensionStyle`].
@@ -183,6 +545,23 @@ pub enum DimensionTextPositionMode {
     Manual = 2,
 }
 
+impl TryFrom<u8> for DimensionTextPositionMode {
+    type Error = KiCadParseError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => Self::Outside,
+            1 => Self::Inline,
+            2 => Self::Manual,
+            _ => {
+                return Err(KiCadParseError::invalid_enum_value::<Self>(
+                    value.to_string(),
+                ))
+            }
+        })
+    }
+}
+
 /// See `text_frame` field in [`DimensionStyle`].
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
@@ -194,3 +573,21 @@ pub enum TextFrameKind {
     Circle = 2,
     RoundedRectangle = 3,
 }
+
+impl TryFrom<u8> for TextFrameKind {
+    type Error = KiCadParseError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => Self::NoFrame,
+            1 => Self::Rectangle,
+            2 => Self::Circle,
+            3 => Self::RoundedRectangle,
+            _ => {
+                return Err(KiCadParseError::invalid_enum_value::<Self>(
+                    value.to_string(),
+                ))
+            }
+        })
+    }
+}
@@ -1,24 +1,25 @@
 //! This module contains the common graphics items that are used in KiCad
 
-use kicad_sexpr::{Sexpr, SexprList};
+use kicad_sexpr::SexprList;
 
 use crate::{
-    convert::{FromSexpr, MaybeFromSexpr, Parser, SexprListExt, ToSexpr},
+    convert::{writes, FromSexpr, ListBuilder, MaybeFromSexpr, Parser, SexprListExt, ToSexpr},
+    version::Syntax,
     KiCadParseError, SexprKind,
 };
 
 use self::{
     dimension::PcbDimension,
     shape::PcbShape,
+    table::PcbTable,
     text::{PcbText, PcbTextBox},
 };
 
 pub mod dimension;
 pub mod shape;
+pub mod table;
 pub mod text;
 
-// TODO: Dimension
-
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 #[cfg_attr(feature = "serde", serde(tag = "type"))]
@@ -28,6 +29,7 @@ pub enum PcbGraphicsItem {
     TextBox(PcbTextBox),
     Shape(PcbShape),
     Dimension(PcbDimension),
+    Table(PcbTable),
 }
 
 impl FromSexpr for PcbGraphicsItem {
@@ -48,6 +50,10 @@ impl FromSexpr for PcbGraphicsItem {
             "gr_poly" => PcbShape::from_sexpr(parser).map(PcbGraphicsItem::Shape),
             "bezier" => PcbShape::from_sexpr(parser).map(PcbGraphicsItem::Shape),
             "dimension" => PcbDimension::from_sexpr(parser).map(PcbGraphicsItem::Dimension),
+            "table" => {
+                parser.check_syntax(Syntax::Tables, "table")?;
+                PcbTable::from_sexpr(parser).map(PcbGraphicsItem::Table)
+            }
             _ => Err(KiCadParseError::invalid_enum_value::<Self>(symbol)),
         }
     }
@@ -65,6 +71,7 @@ impl MaybeFromSexpr for PcbGraphicsItem {
             "gr_poly",
             "bezier",
             "dimension",
+            "table",
         ];
 
         sexpr
@@ -74,12 +81,17 @@ impl MaybeFromSexpr for PcbGraphicsItem {
 }
 
 impl ToSexpr for PcbGraphicsItem {
-    fn to_sexpr(&self) -> Sexpr {
+    fn write_sexpr(&self, s: &mut ListBuilder) {
         match self {
-            PcbGraphicsItem::Text(text) => text.to_sexpr(),
-            PcbGraphicsItem::TextBox(text_box) => text_box.to_sexpr(),
-            PcbGraphicsItem::Shape(shape) => shape.to_sexpr(),
-            PcbGraphicsItem::Dimension(dimension) => dimension.to_sexpr(),
+            PcbGraphicsItem::Text(text) => s.append(text),
+            PcbGraphicsItem::TextBox(text_box) => s.append(text_box),
+            PcbGraphicsItem::Shape(shape) => s.append(shape),
+            PcbGraphicsItem::Dimension(dimension) => s.append(dimension),
+            PcbGraphicsItem::Table(table) => {
+                if writes(Syntax::Tables) {
+                    s.append(table);
+                }
+            }
         }
     }
 }
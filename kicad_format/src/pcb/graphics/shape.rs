@@ -1,8 +1,10 @@
-use kicad_sexpr::{Sexpr, SexprList};
+use kicad_sexpr::SexprList;
 
 use crate::{
     common::{CoordinatePointList, LayerId, SimpleFillMode, Stroke, Uuid, Vec2D},
-    convert::{FromSexpr, MaybeFromSexpr, Parser, SexprListExt, ToSexpr, ToSexprWithName},
+    convert::{
+        Extra, FieldOrder, FromSexpr, ListBuilder, MaybeFromSexpr, Parser, SexprListExt, ToSexpr,
+    },
     KiCadParseError,
 };
 
@@ -15,6 +17,8 @@ pub struct PcbShape {
     pub stroke: Stroke,
     pub layer: LayerId,
     pub tstamp: Uuid,
+    pub field_order: FieldOrder,
+    pub extra: Extra,
 }
 
 impl PcbShape {
@@ -25,6 +29,8 @@ impl PcbShape {
             stroke,
             layer,
             tstamp: Uuid::new(),
+            field_order: FieldOrder::default(),
+            extra: Extra::default(),
         }
     }
 
@@ -41,6 +47,8 @@ impl PcbShape {
             stroke,
             layer,
             tstamp: Uuid::new(),
+            field_order: FieldOrder::default(),
+            extra: Extra::default(),
         }
     }
 
@@ -57,6 +65,8 @@ impl PcbShape {
             stroke,
             layer,
             tstamp: Uuid::new(),
+            field_order: FieldOrder::default(),
+            extra: Extra::default(),
         }
     }
 
@@ -71,6 +81,8 @@ impl PcbShape {
             stroke,
             layer,
             tstamp: Uuid::new(),
+            field_order: FieldOrder::default(),
+            extra: Extra::default(),
         }
     }
 
@@ -86,6 +98,8 @@ impl PcbShape {
             stroke,
             layer,
             tstamp: Uuid::new(),
+            field_order: FieldOrder::default(),
+            extra: Extra::default(),
         }
     }
 
@@ -96,6 +110,8 @@ impl PcbShape {
             stroke,
             layer,
             tstamp: Uuid::new(),
+            field_order: FieldOrder::default(),
+            extra: Extra::default(),
         }
     }
 }
@@ -124,7 +140,10 @@ impl FromSexpr for PcbShape {
             "bezier",
         ])?;
 
-        let locked = parser.maybe_symbol_matching("locked");
+        let mut parser = parser.keyed();
+        let field_order = parser.field_order();
+
+        let locked = parser.maybe_locked()?;
 
         let (kind, stroke) = match name.as_str() {
             "gr_line" => {
@@ -176,7 +195,7 @@ impl FromSexpr for PcbShape {
                 )
             }
             "gr_poly" => {
-                let points = parser.expect::<CoordinatePointList>()?;
+                let points = parser.expect_points()?;
                 let stroke = parser.expect::<Stroke>()?;
                 let fill = parser
                     .expect_symbol_with_name("fill")?
@@ -185,7 +204,7 @@ impl FromSexpr for PcbShape {
                 (PcbShapeKind::Polygon(PcbPolygon { points, fill }), stroke)
             }
             "bezier" => {
-                let points = parser.expect::<CoordinatePointList>().and_then(|v| {
+                let points = parser.expect_points().and_then(|v| {
                     v.try_into()
                         .map_err(|v: Vec<_>| KiCadParseError::IncorrectNumberOfPoints {
                             expected: 4,
@@ -200,7 +219,9 @@ impl FromSexpr for PcbShape {
         };
 
         let layer = parser.expect_string_with_name("layer")?.parse()?;
-        let tstamp = parser.expect_with_name::<Uuid>("tstamp")?;
+        let tstamp = parser.expect_id()?;
+
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             locked,
@@ -208,6 +229,8 @@ impl FromSexpr for PcbShape {
             stroke,
             layer,
             tstamp,
+            field_order,
+            extra,
         })
     }
 }
@@ -230,69 +253,44 @@ impl MaybeFromSexpr for PcbShape {
 }
 
 impl ToSexpr for PcbShape {
-    fn to_sexpr(&self) -> Sexpr {
-        let (name, elements, fill) = match &self.kind {
-            PcbShapeKind::Line(line) => (
-                "gr_line",
-                vec![
-                    line.start.to_sexpr_with_name("start"),
-                    line.end.to_sexpr_with_name("end"),
-                ],
-                None,
-            ),
-            PcbShapeKind::Rectangle(rect) => (
-                "gr_rect",
-                vec![
-                    rect.start.to_sexpr_with_name("start"),
-                    rect.end.to_sexpr_with_name("end"),
-                ],
-                Some(rect.fill),
-            ),
-            PcbShapeKind::Circle(circle) => (
-                "gr_circle",
-                vec![
-                    circle.center.to_sexpr_with_name("center"),
-                    circle.end.to_sexpr_with_name("end"),
-                ],
-                Some(circle.fill),
-            ),
-            PcbShapeKind::Arc(arc) => (
-                "gr_arc",
-                vec![
-                    arc.start.to_sexpr_with_name("start"),
-                    arc.midpoint.to_sexpr_with_name("mid"),
-                    arc.end.to_sexpr_with_name("end"),
-                ],
-                None,
-            ),
-            PcbShapeKind::Polygon(ploy) => {
-                ("gr_poly", vec![ploy.points.to_sexpr()], Some(ploy.fill))
-            }
-            PcbShapeKind::Curve(bezier) => (
-                "bezier",
-                bezier
-                    .points
-                    .iter()
-                    .map(|p| p.to_sexpr())
-                    .collect::<Vec<_>>(),
-                None,
-            ),
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        let (name, fill) = match &self.kind {
+            PcbShapeKind::Line(_) => ("gr_line", None),
+            PcbShapeKind::Rectangle(rect) => ("gr_rect", Some(rect.fill)),
+            PcbShapeKind::Circle(circle) => ("gr_circle", Some(circle.fill)),
+            PcbShapeKind::Arc(_) => ("gr_arc", None),
+            PcbShapeKind::Polygon(poly) => ("gr_poly", Some(poly.fill)),
+            PcbShapeKind::Curve(_) => ("bezier", None),
         };
 
-        Sexpr::list_with_name(
-            name,
-            [
-                &[self.locked.then(|| Sexpr::symbol("locked"))][..],
-                &elements.into_iter().map(Option::Some).collect::<Vec<_>>(),
-                &[
-                    Some(self.stroke.to_sexpr()),
-                    fill.map(|f| Sexpr::symbol_with_name("fill", f)),
-                    Some(Sexpr::string_with_name("layer", self.layer)),
-                    Some(self.tstamp.to_sexpr_with_name("tstamp")),
-                ][..],
-            ]
-            .concat(),
-        )
+        s.append_list_in_order(name, &self.field_order, &self.extra, |s| {
+            s.append_locked(self.locked);
+            match &self.kind {
+                PcbShapeKind::Line(line) => {
+                    s.append_with_name("start", &line.start);
+                    s.append_with_name("end", &line.end);
+                }
+                PcbShapeKind::Rectangle(rect) => {
+                    s.append_with_name("start", &rect.start);
+                    s.append_with_name("end", &rect.end);
+                }
+                PcbShapeKind::Circle(circle) => {
+                    s.append_with_name("center", &circle.center);
+                    s.append_with_name("end", &circle.end);
+                }
+                PcbShapeKind::Arc(arc) => {
+                    s.append_with_name("start", &arc.start);
+                    s.append_with_name("mid", &arc.midpoint);
+                    s.append_with_name("end", &arc.end);
+                }
+                PcbShapeKind::Polygon(poly) => s.append(&poly.points),
+                PcbShapeKind::Curve(bezier) => s.append_many(&bezier.points),
+            }
+            s.append(&self.stroke);
+            s.append_maybe_symbol_with_name("fill", fill);
+            s.append_string_with_name("layer", self.layer);
+            s.append_id(&self.tstamp);
+        });
     }
 }
 
@@ -0,0 +1,163 @@
+use crate::{
+    angle::Angle,
+    common::{
+        table::{
+            parse_cell_margins, parse_cell_span, parse_lengths_with_name, write_cell_margins,
+            write_cell_span, write_lengths_with_name, TableBorder, TableSeparators,
+        },
+        LayerId, TextEffects, Uuid, Vec2D,
+    },
+    convert::{Extra, FieldOrder, FromSexpr, ListBuilder, Parser, SexprListExt, ToSexpr},
+    length::Length,
+    simple_maybe_from_sexpr, KiCadParseError,
+};
+
+/// A table of text cells on a board, since KiCad 9.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct PcbTable {
+    pub column_count: u32,
+    pub locked: bool,
+    pub layer: LayerId,
+    pub border: Option<TableBorder>,
+    pub separators: Option<TableSeparators>,
+    pub column_widths: Vec<Length>,
+    pub row_heights: Vec<Length>,
+    /// The cells of the table, row by row.
+    pub cells: Vec<PcbTableCell>,
+    pub field_order: FieldOrder,
+    pub extra: Extra,
+}
+
+impl FromSexpr for PcbTable {
+    fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
+        parser.expect_symbol_matching("table")?;
+
+        let mut parser = parser.keyed();
+        let field_order = parser.field_order();
+
+        let column_count = parser.expect_integer_with_name::<u32>("column_count")?;
+        let locked = parser.maybe_locked()?;
+        let layer = parser
+            .expect_string_with_name("layer")?
+            .parse::<LayerId>()?;
+        let border = parser.maybe::<TableBorder>()?;
+        let separators = parser.maybe::<TableSeparators>()?;
+        let column_widths = parse_lengths_with_name(&mut parser, "column_widths")?;
+        let row_heights = parse_lengths_with_name(&mut parser, "row_heights")?;
+        let cells = parser
+            .maybe_list_with_name("cells", |p| {
+                let cells = p.expect_many::<PcbTableCell>()?;
+
+                Ok(cells)
+            })?
+            .unwrap_or_default();
+
+        let extra = parser.expect_end_with_extra()?;
+
+        Ok(Self {
+            column_count,
+            locked,
+            layer,
+            border,
+            separators,
+            column_widths,
+            row_heights,
+            cells,
+            field_order,
+            extra,
+        })
+    }
+}
+
+impl ToSexpr for PcbTable {
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_in_order("table", &self.field_order, &self.extra, |s| {
+            s.append_number_with_name("column_count", self.column_count);
+            s.append_locked(self.locked);
+            s.append_string_with_name("layer", self.layer);
+            s.append_maybe(&self.border);
+            s.append_maybe(&self.separators);
+            write_lengths_with_name(s, "column_widths", &self.column_widths);
+            write_lengths_with_name(s, "row_heights", &self.row_heights);
+            s.append_list("cells", |s| s.append_many(&self.cells));
+        });
+    }
+}
+
+/// A cell of a [`PcbTable`], which is a text box spanning one or more rows
+/// and columns.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct PcbTableCell {
+    pub text: String,
+    pub start: Vec2D,
+    pub end: Vec2D,
+    pub angle: Option<Angle>,
+    /// The space between the text and the left, top, right and bottom edges
+    /// of the cell.
+    pub margins: Option<[Length; 4]>,
+    /// The number of columns and rows the cell spans.
+    pub span: (u32, u32),
+    pub layer: LayerId,
+    pub tstamp: Uuid,
+    pub effects: TextEffects,
+    pub extra: Extra,
+}
+
+impl FromSexpr for PcbTableCell {
+    fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
+        parser.expect_symbol_matching("table_cell")?;
+
+        let mut parser = parser.keyed();
+
+        let text = parser.expect_string()?;
+        let start = parser.expect_with_name::<Vec2D>("start")?;
+        let end = parser.expect_with_name::<Vec2D>("end")?;
+        let angle = parser.maybe_angle_with_name("angle")?;
+        let margins = parse_cell_margins(&mut parser)?;
+        let span = parse_cell_span(&mut parser)?;
+        let layer = parser
+            .expect_string_with_name("layer")?
+            .parse::<LayerId>()?;
+        let tstamp = parser.expect_id()?;
+        let effects = parser
+            .maybe::<TextEffects>()?
+            .ok_or_else(|| KiCadParseError::MissingField("effects".to_string()))?;
+
+        let extra = parser.expect_end_with_extra()?;
+
+        Ok(Self {
+            text,
+            start,
+            end,
+            angle,
+            margins,
+            span,
+            layer,
+            tstamp,
+            effects,
+            extra,
+        })
+    }
+}
+
+simple_maybe_from_sexpr!(PcbTableCell, table_cell);
+
+impl ToSexpr for PcbTableCell {
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("table_cell", &self.extra, |s| {
+            s.append_string(&self.text);
+            s.append_with_name("start", &self.start);
+            s.append_with_name("end", &self.end);
+            s.append_maybe_number_with_name("angle", self.angle);
+            write_cell_margins(s, &self.margins);
+            write_cell_span(s, self.span);
+            s.append_string_with_name("layer", self.layer);
+            s.append_id(&self.tstamp);
+            s.append(&self.effects);
+        });
+    }
+}
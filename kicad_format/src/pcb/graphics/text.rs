@@ -1,9 +1,8 @@
-use kicad_sexpr::Sexpr;
-
 use crate::{
-    common::{CoordinatePointList, LayerId, Position, Stroke, TextEffects, Uuid, Vec2D},
-    convert::{FromSexpr, Parser, SexprListExt, ToSexpr, ToSexprWithName},
-    KiCadParseError,
+    angle::Angle,
+    common::{LayerId, Position, Stroke, TextEffects, TextRenderCache, Uuid, Vec2D},
+    convert::{Extra, FieldOrder, FromSexpr, ListBuilder, Parser, SexprListExt, ToSexpr},
+    simple_maybe_from_sexpr, KiCadParseError,
 };
 
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -17,34 +16,31 @@ pub struct PcbText {
     pub knockout: bool,
     pub tstamp: Uuid,
     pub effects: TextEffects,
+    pub render_cache: Option<TextRenderCache>,
+    pub field_order: FieldOrder,
+    pub extra: Extra,
 }
 
 impl FromSexpr for PcbText {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("gr_text")?;
 
-        let locked = parser.maybe_symbol_matching("locked");
+        let mut parser = parser.keyed();
+        let field_order = parser.field_order();
+
+        let locked = parser.maybe_locked()?;
         let text = parser.expect_string()?;
         let position = parser.expect::<Position>()?;
-        let (layer, knockout) = parser.expect_list_with_name("layer").and_then(|mut list| {
+        let (layer, knockout) = parser.expect_list_with_name("layer", |list| {
             let layer = list.expect_string()?.parse()?;
             let knockout = list.maybe_symbol_matching("knockout");
             Ok((layer, knockout))
         })?;
-        let tstamp = parser.expect_with_name::<Uuid>("tstamp")?;
+        let tstamp = parser.expect_id()?;
         let effects = parser.expect::<TextEffects>()?;
+        let render_cache = parser.maybe::<TextRenderCache>()?;
 
-        if parser
-            .peek_list()
-            .is_some_and(|l| l.first_symbol().is_some_and(|s| s == "render_cache"))
-        {
-            unimplemented!(
-                "Found render_cache list in PcbText: {:?}",
-                parser.peek_next().unwrap()
-            );
-        };
-
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             locked,
@@ -54,29 +50,29 @@ impl FromSexpr for PcbText {
             knockout,
             tstamp,
             effects,
+            render_cache,
+            field_order,
+            extra,
         })
     }
 }
 
+simple_maybe_from_sexpr!(PcbText, gr_text);
+
 impl ToSexpr for PcbText {
-    fn to_sexpr(&self) -> kicad_sexpr::Sexpr {
-        Sexpr::list_with_name(
-            "gr_text",
-            [
-                self.locked.then(|| Sexpr::symbol("locked")),
-                Some(Sexpr::string(&self.text)),
-                Some(self.position.to_sexpr()),
-                Some(Sexpr::list_with_name(
-                    "layer",
-                    [
-                        Some(Sexpr::string(self.layer)),
-                        self.knockout.then(|| Sexpr::symbol("knockout")),
-                    ],
-                )),
-                Some(self.tstamp.to_sexpr_with_name("tstamp")),
-                Some(self.effects.to_sexpr()),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_in_order("gr_text", &self.field_order, &self.extra, |s| {
+            s.append_locked(self.locked);
+            s.append_string(&self.text);
+            s.append(&self.position);
+            s.append_list("layer", |s| {
+                s.append_string(self.layer);
+                s.append_symbol_if("knockout", self.knockout);
+            });
+            s.append_id(&self.tstamp);
+            s.append(&self.effects);
+            s.append_maybe(&self.render_cache);
+        });
     }
 }
 
@@ -87,18 +83,24 @@ pub struct PcbTextBox {
     pub locked: bool,
     pub text: String,
     pub position: TextBoxPosition,
-    pub angle: Option<f32>,
+    pub angle: Option<Angle>,
     pub layer: LayerId,
     pub tstamp: Uuid,
     pub effects: TextEffects,
     pub stroke: Option<Stroke>,
+    pub render_cache: Option<TextRenderCache>,
+    pub field_order: FieldOrder,
+    pub extra: Extra,
 }
 
 impl FromSexpr for PcbTextBox {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("gr_text_box")?;
 
-        let locked = parser.maybe_symbol_matching("locked");
+        let mut parser = parser.keyed();
+        let field_order = parser.field_order();
+
+        let locked = parser.maybe_locked()?;
         let text = parser.expect_string()?;
 
         let Some(next_token) = parser
@@ -116,7 +118,7 @@ impl FromSexpr for PcbTextBox {
                 TextBoxPosition::StartEnd(start, end)
             }
             "pts" => {
-                let points = parser.expect::<CoordinatePointList>().and_then(|v| {
+                let points = parser.expect_points().and_then(|v| {
                     v.try_into()
                         .map_err(|v: Vec<_>| KiCadParseError::IncorrectNumberOfPoints {
                             expected: 4,
@@ -133,23 +135,16 @@ impl FromSexpr for PcbTextBox {
             }
         };
 
-        let angle = parser.maybe_number_with_name("angle")?;
-        let layer = parser.expect_string()?.parse::<LayerId>()?;
-        let tstamp = parser.expect_with_name::<Uuid>("tstamp")?;
+        let angle = parser.maybe_angle_with_name("angle")?;
+        let layer = parser
+            .expect_string_with_name("layer")?
+            .parse::<LayerId>()?;
+        let tstamp = parser.expect_id()?;
         let effects = parser.expect::<TextEffects>()?;
         let stroke = parser.maybe::<Stroke>()?;
+        let render_cache = parser.maybe::<TextRenderCache>()?;
 
-        if parser
-            .peek_list()
-            .is_some_and(|l| l.first_symbol().is_some_and(|s| s == "render_cache"))
-        {
-            unimplemented!(
-                "Found render_cache list in PcbTextBox: {:?}",
-                parser.peek_next().unwrap()
-            );
-        };
-
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             locked,
@@ -160,36 +155,32 @@ impl FromSexpr for PcbTextBox {
             tstamp,
             effects,
             stroke,
+            render_cache,
+            field_order,
+            extra,
         })
     }
 }
 
 impl ToSexpr for PcbTextBox {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "gr_text_box",
-            [
-                &[
-                    self.locked.then(|| Sexpr::symbol("locked")),
-                    Some(Sexpr::string(&self.text)),
-                ][..],
-                &match &self.position {
-                    TextBoxPosition::StartEnd(start, end) => vec![
-                        Some(start.to_sexpr_with_name("start")),
-                        Some(end.to_sexpr_with_name("end")),
-                    ],
-                    TextBoxPosition::Points(points) => vec![Some(points.to_vec().to_sexpr())],
-                },
-                &[
-                    self.angle.map(|a| Sexpr::number_with_name("angle", a)),
-                    Some(Sexpr::string(self.layer)),
-                    Some(self.tstamp.to_sexpr_with_name("tstamp")),
-                    Some(self.effects.to_sexpr()),
-                    self.stroke.as_ref().map(ToSexpr::to_sexpr),
-                ][..],
-            ]
-            .concat(),
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_in_order("gr_text_box", &self.field_order, &self.extra, |s| {
+            s.append_locked(self.locked);
+            s.append_string(&self.text);
+            match &self.position {
+                TextBoxPosition::StartEnd(start, end) => {
+                    s.append_with_name("start", start);
+                    s.append_with_name("end", end);
+                }
+                TextBoxPosition::Points(points) => s.append(&points.to_vec()),
+            }
+            s.append_maybe_number_with_name("angle", self.angle);
+            s.append_string_with_name("layer", self.layer);
+            s.append_id(&self.tstamp);
+            s.append(&self.effects);
+            s.append_maybe(&self.stroke);
+            s.append_maybe(&self.render_cache);
+        });
     }
 }
 
@@ -1,17 +1,30 @@
 //! PCB board file format (`.kicad_pcb` files)
 
-use kicad_sexpr::{Sexpr, SexprList};
+use kicad_sexpr::SexprList;
 
 use crate::{
     common::{
-        footprint::FootprintInlined, pad::Net, zone::Zone, Group, Image, LayerId, PageSettings,
-        PageSize, Property, StandardPageSize, TitleBlock, Uuid, Vec2D,
+        embedded::{
+            parse_embedded_files, parse_embedded_fonts, write_embedded_files, write_embedded_fonts,
+            EmbeddedFile,
+        },
+        footprint::FootprintInlined,
+        pad::{
+            padstack::{PadstackLayerId, PadstackMode},
+            Net,
+        },
+        zone::Zone,
+        Group, Image, LayerId, PageSettings, PageSize, Property, StandardPageSize, TitleBlock,
+        Uuid, Vec2D,
     },
     convert::{
-        FromSexpr, MaybeFromSexpr, Parser, SexprListExt, ToSexpr, ToSexprWithName,
-        VecToMaybeSexprVec,
+        with_file_version, writes, Extra, FieldOrder, FromSexpr, ListBuilder, MaybeFromSexpr,
+        Parser, SexprListExt, ToSexpr,
     },
-    simple_to_from_string, KiCadParseError, SexprKind,
+    length::Length,
+    simple_maybe_from_sexpr, simple_to_from_string,
+    version::{FormatVersion, Syntax},
+    KiCadParseError, SexprKind,
 };
 
 use self::{
@@ -32,6 +45,8 @@ pub mod setup;
 pub struct PcbFile {
     pub version: u32,
     pub generator: String,
+    /// The version of the program that wrote the file, since KiCad 8.
+    pub generator_version: Option<String>,
     pub general_settings: GeneralSettings,
     pub page_settings: PageSettings,
     pub title_block: Option<TitleBlock>,
@@ -45,18 +60,29 @@ pub struct PcbFile {
     pub tracks: Vec<Track>,
     pub zones: Vec<Zone>,
     pub groups: Vec<Group>,
+    /// Whether the fonts used by the board are embedded in it.
+    pub embedded_fonts: Option<bool>,
+    /// The files embedded in the board, since KiCad 9.
+    pub embedded_files: Option<Vec<EmbeddedFile>>,
+    pub extra: Extra,
 }
 
 impl Default for PcbFile {
     #[allow(deprecated)]
     fn default() -> Self {
         Self {
-            version: 20221018,
+            version: 20240108,
             generator: "kicad_lib".to_string(),
-            general_settings: GeneralSettings { thickness: 1.6 },
+            generator_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            general_settings: GeneralSettings {
+                thickness: Length::from_mm(1.6),
+                legacy_teardrops: Some(false),
+                extra: Extra::default(),
+            },
             page_settings: PageSettings {
                 size: PageSize::Standard(StandardPageSize::A4),
                 portrait: false,
+                extra: Extra::default(),
             },
             title_block: None,
             layers: vec![
@@ -141,6 +167,7 @@ impl Default for PcbFile {
                             material: None,
                             epsilon_r: None,
                             loss_tangent: None,
+                            extra: Extra::default(),
                         },
                         StackupLayer {
                             id: StackupLayerId::BoardLayer(LayerId::FPaste),
@@ -150,51 +177,57 @@ impl Default for PcbFile {
                             material: None,
                             epsilon_r: None,
                             loss_tangent: None,
+                            extra: Extra::default(),
                         },
                         StackupLayer {
                             id: StackupLayerId::BoardLayer(LayerId::FMask),
                             kind: "Top Solder Mask".to_string(),
                             color: None,
-                            thickness: Some(0.01),
+                            thickness: Some(Length::from_mm(0.01)),
                             material: None,
                             epsilon_r: None,
                             loss_tangent: None,
+                            extra: Extra::default(),
                         },
                         StackupLayer {
                             id: StackupLayerId::BoardLayer(LayerId::FCu),
                             kind: "copper".to_string(),
                             color: None,
-                            thickness: Some(0.035),
+                            thickness: Some(Length::from_mm(0.035)),
                             material: None,
                             epsilon_r: None,
                             loss_tangent: None,
+                            extra: Extra::default(),
                         },
                         StackupLayer {
                             id: StackupLayerId::Dielectric(1),
                             kind: "core".to_string(),
                             color: None,
-                            thickness: Some(1.51),
+                            thickness: Some(Length::from_mm(1.51)),
                             material: Some("FR4".to_string()),
                             epsilon_r: Some(4.5),
                             loss_tangent: Some(0.02),
+                            extra: Extra::default(),
                         },
                         StackupLayer {
                             id: StackupLayerId::BoardLayer(LayerId::BCu),
                             kind: "copper".to_string(),
                             color: None,
-                            thickness: Some(0.035),
+                            thickness: Some(Length::from_mm(0.035)),
                             material: None,
                             epsilon_r: None,
                             loss_tangent: None,
+                            extra: Extra::default(),
                         },
                         StackupLayer {
                             id: StackupLayerId::BoardLayer(LayerId::BMask),
                             kind: "Bottom Solder Mask".to_string(),
                             color: None,
-                            thickness: Some(0.01),
+                            thickness: Some(Length::from_mm(0.01)),
                             material: None,
                             epsilon_r: None,
                             loss_tangent: None,
+                            extra: Extra::default(),
                         },
                         StackupLayer {
                             id: StackupLayerId::BoardLayer(LayerId::BPaste),
@@ -204,6 +237,7 @@ impl Default for PcbFile {
                             material: None,
                             epsilon_r: None,
                             loss_tangent: None,
+                            extra: Extra::default(),
                         },
                         StackupLayer {
                             id: StackupLayerId::BoardLayer(LayerId::BSilkS),
@@ -213,6 +247,7 @@ impl Default for PcbFile {
                             material: None,
                             epsilon_r: None,
                             loss_tangent: None,
+                            extra: Extra::default(),
                         },
                     ],
                     copper_finish: Some("None".to_string()),
@@ -220,12 +255,14 @@ impl Default for PcbFile {
                     edge_connector: None,
                     castellated_pads: false,
                     edge_plating: false,
+                    extra: Extra::default(),
                 }),
-                pad_to_mask_clearance: 0.0,
+                pad_to_mask_clearance: Length::ZERO,
                 solder_mask_min_width: None,
                 pad_to_paste_clearance: None,
                 pad_to_paste_clearance_ratio: None,
                 allow_soldermask_bridges_in_footprints: false,
+                tenting: None,
                 aux_axis_origin: None,
                 grid_origin: None,
                 plot_options: PcbPlotOptions {
@@ -247,6 +284,8 @@ impl Default for PcbFile {
                     hpgl_pen_number: 1,
                     hpgl_pen_speed: 20,
                     hpgl_pen_diameter: 15.0,
+                    pdf_front_fp_property_popups: Some(true),
+                    pdf_back_fp_property_popups: Some(true),
                     dxf_use_polygon_mode: true,
                     dxf_use_imperial_units: true,
                     dxf_use_pcbnew_font: true,
@@ -254,6 +293,7 @@ impl Default for PcbFile {
                     postscript_a4_output: false,
                     plot_references: true,
                     plot_values: true,
+                    plot_footprint_text: Some(true),
                     plot_invisible_text: false,
                     sketch_pads_on_fab: false,
                     subtract_mask_from_silk: false,
@@ -262,7 +302,9 @@ impl Default for PcbFile {
                     drill_shape: DrillMarks::Small,
                     scale_selection: 1,
                     output_directory: "".to_string(),
+                    extra: Extra::default(),
                 },
+                extra: Extra::default(),
             },
             properties: Vec::new(),
             nets: Vec::new(),
@@ -272,6 +314,9 @@ impl Default for PcbFile {
             tracks: Vec::new(),
             zones: Vec::new(),
             groups: Vec::new(),
+            embedded_fonts: None,
+            embedded_files: None,
+            extra: Extra::default(),
         }
     }
 }
@@ -280,18 +325,27 @@ impl FromSexpr for PcbFile {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("kicad_pcb")?;
 
-        let version = parser.expect_number_with_name("version")? as u32;
-        let generator = parser.expect_symbol_with_name("generator")?;
-        let general_settings = parser.expect::<GeneralSettings>()?;
-        let page_settings = parser.expect::<PageSettings>()?;
+        let mut parser = parser.keyed();
+
+        let version = parser.expect_integer_with_name::<u32>("version")?;
+        parser.set_version(FormatVersion::board(version));
+        let generator = parser.expect_string_or_symbol_with_name("generator")?;
+        let generator_version = parser.maybe_string_with_name("generator_version")?;
+        let general_settings = parser
+            .maybe::<GeneralSettings>()?
+            .ok_or_else(|| KiCadParseError::MissingField("general".to_string()))?;
+        let page_settings = parser
+            .maybe::<PageSettings>()?
+            .ok_or_else(|| KiCadParseError::MissingField("paper".to_string()))?;
         let title_block = parser.maybe::<TitleBlock>()?;
-        let layers = parser.expect_list_with_name("layers").and_then(|mut p| {
+        let layers = parser.expect_list_with_name("layers", |p| {
             let layers = p.expect_many::<BoardLayer>()?;
-            p.expect_end()?;
 
             Ok(layers)
         })?;
-        let setup = parser.expect::<BoardSetup>()?;
+        let setup = parser
+            .maybe::<BoardSetup>()?
+            .ok_or_else(|| KiCadParseError::MissingField("setup".to_string()))?;
         let properties = parser.expect_many::<Property>()?;
         let nets = parser.expect_many::<Net>()?;
         let footprints = parser.expect_many::<FootprintInlined>()?;
@@ -300,12 +354,15 @@ impl FromSexpr for PcbFile {
         let tracks = parser.expect_many::<Track>()?;
         let zones = parser.expect_many::<Zone>()?;
         let groups = parser.expect_many::<Group>()?;
+        let embedded_fonts = parse_embedded_fonts(&mut parser)?;
+        let embedded_files = parse_embedded_files(&mut parser)?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             version,
             generator,
+            generator_version,
             general_settings,
             page_settings,
             title_block,
@@ -319,69 +376,85 @@ impl FromSexpr for PcbFile {
             tracks,
             zones,
             groups,
+            embedded_fonts,
+            embedded_files,
+            extra,
         })
     }
 }
 
 impl ToSexpr for PcbFile {
-    fn to_sexpr(&self) -> kicad_sexpr::Sexpr {
-        Sexpr::list_with_name(
-            "kicad_pcb",
-            [
-                &[
-                    Some(Sexpr::number_with_name("version", self.version as f32)),
-                    Some(Sexpr::symbol_with_name("generator", &self.generator)),
-                    Some(self.general_settings.to_sexpr()),
-                    Some(self.page_settings.to_sexpr()),
-                    self.title_block.as_ref().map(ToSexpr::to_sexpr),
-                    Some(Sexpr::list_with_name(
-                        "layers",
-                        self.layers.into_sexpr_vec(),
-                    )),
-                    Some(self.setup.to_sexpr()),
-                ][..],
-                &self.properties.into_sexpr_vec(),
-                &self.nets.into_sexpr_vec(),
-                &self.footprints.into_sexpr_vec(),
-                &self.graphics_items.into_sexpr_vec(),
-                &self.images.into_sexpr_vec(),
-                &self.tracks.into_sexpr_vec(),
-                &self.zones.into_sexpr_vec(),
-                &self.groups.into_sexpr_vec(),
-            ]
-            .concat(),
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        with_file_version(FormatVersion::board(self.version), |version| {
+            s.append_list_with_extra("kicad_pcb", &self.extra, |s| {
+                s.append_number_with_name("version", version);
+                s.append_generator(&self.generator);
+                if writes(Syntax::GeneratorVersion) {
+                    s.append_maybe_string_with_name(
+                        "generator_version",
+                        self.generator_version.as_ref(),
+                    );
+                }
+                s.append(&self.general_settings);
+                s.append(&self.page_settings);
+                s.append_maybe(&self.title_block);
+                s.append_list("layers", |s| s.append_many(&self.layers));
+                s.append(&self.setup);
+                s.append_many(&self.properties);
+                s.append_many(&self.nets);
+                s.append_many(&self.footprints);
+                s.append_many(&self.graphics_items);
+                s.append_many(&self.images);
+                s.append_many(&self.tracks);
+                s.append_many(&self.zones);
+                s.append_many(&self.groups);
+                write_embedded_fonts(s, self.embedded_fonts);
+                write_embedded_files(s, &self.embedded_files);
+            });
+        });
     }
 }
 
 // ############################################################################
 
-/// General board settings. Currently only holds the board thickness.
+/// General board settings: the board thickness and, since KiCad 8, whether
+/// the board still uses teardrops made of zones.
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 #[derive(Debug, PartialEq, Clone)]
 pub struct GeneralSettings {
-    pub thickness: f32,
+    pub thickness: Length,
+    pub legacy_teardrops: Option<bool>,
+    pub extra: Extra,
 }
 
 impl FromSexpr for GeneralSettings {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("general")?;
 
-        let thickness = parser.expect_number_with_name("thickness")?;
+        let mut parser = parser.keyed();
 
-        parser.expect_end()?;
+        let thickness = parser.expect_length_with_name("thickness")?;
+        let legacy_teardrops = parser.maybe_bool_with_name("legacy_teardrops")?;
+
+        let extra = parser.expect_end_with_extra()?;
 
-        Ok(Self { thickness })
+        Ok(Self {
+            thickness,
+            legacy_teardrops,
+            extra,
+        })
     }
 }
 
+simple_maybe_from_sexpr!(GeneralSettings, general);
+
 impl ToSexpr for GeneralSettings {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "general",
-            [Some(Sexpr::number_with_name("thickness", self.thickness))],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("general", &self.extra, |s| {
+            s.append_number_with_name("thickness", self.thickness);
+            s.append_maybe_bool_with_name("legacy_teardrops", self.legacy_teardrops);
+        });
     }
 }
 
@@ -410,7 +483,7 @@ impl BoardLayer {
 
 impl FromSexpr for BoardLayer {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
-        let id = parser.expect_number()? as u8;
+        let id = parser.expect_integer::<u8>()?;
         let layer = parser.expect_string()?.parse::<LayerId>()?;
 
         if layer as u8 != id {
@@ -433,13 +506,13 @@ impl MaybeFromSexpr for BoardLayer {
 }
 
 impl ToSexpr for BoardLayer {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list([
-            Some(Sexpr::number(self.layer as u8 as f32)),
-            Some(Sexpr::string(self.layer)),
-            Some(Sexpr::symbol(self.kind)),
-            self.name.as_ref().map(Sexpr::string),
-        ])
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_unnamed_list(|s| {
+            s.append_number(self.layer as u8);
+            s.append_string(self.layer);
+            s.append_symbol(self.kind);
+            s.append_maybe_string(self.name.as_ref());
+        });
     }
 }
 
@@ -509,11 +582,11 @@ impl MaybeFromSexpr for Track {
 }
 
 impl ToSexpr for Track {
-    fn to_sexpr(&self) -> Sexpr {
+    fn write_sexpr(&self, s: &mut ListBuilder) {
         match self {
-            Track::Segment(segment) => segment.to_sexpr(),
-            Track::Via(via) => via.to_sexpr(),
-            Track::Arc(arc) => arc.to_sexpr(),
+            Track::Segment(segment) => s.append(segment),
+            Track::Via(via) => s.append(via),
+            Track::Arc(arc) => s.append(arc),
         }
     }
 }
@@ -526,27 +599,32 @@ pub struct TrackSegment {
     pub locked: bool,
     pub start: Vec2D,
     pub end: Vec2D,
-    pub width: f32,
+    pub width: Length,
     pub layer: LayerId,
     pub net: i32,
     pub tstamp: Uuid,
+    pub field_order: FieldOrder,
+    pub extra: Extra,
 }
 
 impl FromSexpr for TrackSegment {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("segment")?;
 
-        let locked = parser.maybe_symbol_matching("locked");
+        let mut parser = parser.keyed();
+        let field_order = parser.field_order();
+
+        let locked = parser.maybe_locked()?;
         let start = parser.expect_with_name::<Vec2D>("start")?;
         let end = parser.expect_with_name::<Vec2D>("end")?;
-        let width = parser.expect_number_with_name("width")?;
+        let width = parser.expect_length_with_name("width")?;
         let layer = parser
             .expect_string_with_name("layer")?
             .parse::<LayerId>()?;
-        let net = parser.expect_number_with_name("net")? as i32;
-        let tstamp = parser.expect_with_name::<Uuid>("tstamp")?;
+        let net = parser.expect_integer_with_name::<i32>("net")?;
+        let tstamp = parser.expect_id()?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             locked,
@@ -556,24 +634,23 @@ impl FromSexpr for TrackSegment {
             layer,
             net,
             tstamp,
+            field_order,
+            extra,
         })
     }
 }
 
 impl ToSexpr for TrackSegment {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "segment",
-            [
-                self.locked.then(|| Sexpr::symbol("locked")),
-                Some(self.start.to_sexpr_with_name("start")),
-                Some(self.end.to_sexpr_with_name("end")),
-                Some(Sexpr::number_with_name("width", self.width)),
-                Some(Sexpr::string_with_name("layer", self.layer)),
-                Some(Sexpr::number_with_name("net", self.net as f32)),
-                Some(self.tstamp.to_sexpr_with_name("tstamp")),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_in_order("segment", &self.field_order, &self.extra, |s| {
+            s.append_locked(self.locked);
+            s.append_with_name("start", &self.start);
+            s.append_with_name("end", &self.end);
+            s.append_number_with_name("width", self.width);
+            s.append_string_with_name("layer", self.layer);
+            s.append_number_with_name("net", self.net);
+            s.append_id(&self.tstamp);
+        });
     }
 }
 
@@ -585,59 +662,70 @@ pub struct TrackVia {
     pub kind: ViaKind,
     pub locked: bool,
     pub position: Vec2D,
-    pub size: f32,
-    pub drill: f32,
+    pub size: Length,
+    pub drill: Length,
     pub layers: (LayerId, LayerId),
     pub remove_unused_layers: bool,
     pub keep_end_layers: bool,
     pub free: bool,
     pub zone_layer_connections: Option<Vec<LayerId>>,
+    /// The sizes of the via on other copper layers, since KiCad 9. `None` if
+    /// the via has the same size on all layers.
+    pub padstack: Option<ViaPadstack>,
+    /// Whether each side of the via is covered by solder mask, since KiCad
+    /// 9. `None` if the board's setting is used.
+    pub tenting: Option<ViaTenting>,
     pub net: i32,
     pub tstamp: Uuid,
+    pub field_order: FieldOrder,
+    pub extra: Extra,
 }
 
 impl FromSexpr for TrackVia {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("via")?;
 
+        let mut parser = parser.keyed();
+        let field_order = parser.field_order();
+
+        let locked = parser.maybe_locked()?;
         let kind = parser
             .maybe_symbol()
             .map(|k| k.parse::<ViaKind>())
             .transpose()?
             .unwrap_or_default();
-        let locked = parser.maybe_symbol_matching("locked");
         let position = parser.expect_with_name::<Vec2D>("at")?;
-        let size = parser.expect_number_with_name("size")?;
-        let drill = parser.expect_number_with_name("drill")?;
-        let layers = parser.expect_list_with_name("layers").and_then(|mut p| {
+        let size = parser.expect_length_with_name("size")?;
+        let drill = parser.expect_length_with_name("drill")?;
+        let layers = parser.expect_list_with_name("layers", |p| {
             let layers = (
                 p.expect_string()?.parse::<LayerId>()?,
                 p.expect_string()?.parse::<LayerId>()?,
             );
-            p.expect_end()?;
 
             Ok(layers)
         })?;
-        let remove_unused_layers = parser.maybe_empty_list_with_name("remove_unused_layers")?;
-        let keep_end_layers = parser.maybe_empty_list_with_name("keep_end_layers")?;
-        let free = parser.maybe_empty_list_with_name("free")?;
-        let zone_layer_connections = parser
-            .maybe_list_with_name("zone_layer_connections")
-            .map(|mut p| {
+        let remove_unused_layers = parser
+            .maybe_list_flag("remove_unused_layers")?
+            .unwrap_or(false);
+        let keep_end_layers = parser.maybe_list_flag("keep_end_layers")?.unwrap_or(false);
+        let free = parser.maybe_list_flag("free")?.unwrap_or(false);
+        let zone_layer_connections =
+            parser.maybe_list_with_name("zone_layer_connections", |p| {
                 let layers = p
                     .expect_many_strings()?
                     .iter()
                     .map(|s| s.parse::<LayerId>())
                     .collect::<Result<Vec<_>, _>>()?;
-                p.expect_end()?;
 
-                Ok::<_, KiCadParseError>(layers)
-            })
-            .transpose()?;
-        let net = parser.expect_number_with_name("net")? as i32;
-        let tstamp = parser.expect_with_name::<Uuid>("tstamp")?;
+                Ok(layers)
+            })?;
+        let padstack = parser.maybe_since(Syntax::Padstacks, "padstack", Parser::maybe)?;
+        let tenting = parser.maybe_since(Syntax::Tenting, "tenting", Parser::maybe)?;
+        let net = parser.expect_integer_with_name::<i32>("net")?;
+        let tstamp = parser.expect_id()?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             kind,
@@ -650,53 +738,168 @@ impl FromSexpr for TrackVia {
             keep_end_layers,
             free,
             zone_layer_connections,
+            padstack,
+            tenting,
             net,
             tstamp,
+            field_order,
+            extra,
         })
     }
 }
 
 impl ToSexpr for TrackVia {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "via",
-            [
-                if self.kind != ViaKind::Through {
-                    Some(Sexpr::symbol_with_name("kind", self.kind))
-                } else {
-                    None
-                },
-                self.locked.then(|| Sexpr::symbol("locked")),
-                Some(self.position.to_sexpr_with_name("at")),
-                Some(Sexpr::number_with_name("size", self.size)),
-                Some(Sexpr::number_with_name("drill", self.drill)),
-                Some(Sexpr::list_with_name(
-                    "layers",
-                    [
-                        Some(Sexpr::string(self.layers.0)),
-                        Some(Sexpr::string(self.layers.1)),
-                    ],
-                )),
-                self.remove_unused_layers
-                    .then(|| Sexpr::list_with_name("remove_unused_layers", [])),
-                self.keep_end_layers
-                    .then(|| Sexpr::list_with_name("keep_end_layers", [])),
-                self.free.then(|| Sexpr::list_with_name("free", [])),
-                self.zone_layer_connections.as_ref().map(|layers| {
-                    Sexpr::list_with_name(
-                        "zone_layer_connections",
-                        layers
-                            .iter()
-                            .map(Clone::clone)
-                            .map(Sexpr::string)
-                            .map(Option::Some)
-                            .collect::<Vec<_>>(),
-                    )
-                }),
-                Some(Sexpr::number_with_name("net", self.net as f32)),
-                Some(self.tstamp.to_sexpr_with_name("tstamp")),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_in_order("via", &self.field_order, &self.extra, |s| {
+            if self.kind != ViaKind::Through {
+                s.append_symbol(self.kind);
+            }
+            s.append_locked(self.locked);
+            s.append_with_name("at", &self.position);
+            s.append_number_with_name("size", self.size);
+            s.append_number_with_name("drill", self.drill);
+            s.append_list("layers", |s| {
+                s.append_string(self.layers.0);
+                s.append_string(self.layers.1);
+            });
+            s.append_maybe_list_flag(
+                "remove_unused_layers",
+                self.remove_unused_layers.then_some(true),
+            );
+            s.append_maybe_list_flag("keep_end_layers", self.keep_end_layers.then_some(true));
+            s.append_maybe_list_flag("free", self.free.then_some(true));
+            if let Some(layers) = &self.zone_layer_connections {
+                s.append_list("zone_layer_connections", |s| {
+                    for layer in layers {
+                        s.append_string(*layer);
+                    }
+                });
+            }
+            if writes(Syntax::Padstacks) {
+                s.append_maybe(&self.padstack);
+            }
+            if writes(Syntax::Tenting) {
+                s.append_maybe(&self.tenting);
+            }
+            s.append_number_with_name("net", self.net);
+            s.append_id(&self.tstamp);
+        });
+    }
+}
+
+/// The sizes of a via on the layers where it differs from its
+/// [`TrackVia::size`], which is the size on the front copper layer.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct ViaPadstack {
+    pub mode: PadstackMode,
+    pub layers: Vec<ViaPadstackLayer>,
+    pub extra: Extra,
+}
+
+impl FromSexpr for ViaPadstack {
+    fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
+        parser.expect_symbol_matching("padstack")?;
+
+        let mut parser = parser.keyed();
+
+        let mode = parser
+            .expect_symbol_with_name("mode")?
+            .parse::<PadstackMode>()?;
+        let layers = parser.expect_many::<ViaPadstackLayer>()?;
+
+        let extra = parser.expect_end_with_extra()?;
+
+        Ok(Self {
+            mode,
+            layers,
+            extra,
+        })
+    }
+}
+
+simple_maybe_from_sexpr!(ViaPadstack, padstack);
+
+impl ToSexpr for ViaPadstack {
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("padstack", &self.extra, |s| {
+            s.append_symbol_with_name("mode", self.mode);
+            s.append_many(&self.layers);
+        });
+    }
+}
+
+/// The size of a via on one layer of a [`ViaPadstack`].
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct ViaPadstackLayer {
+    pub layer: PadstackLayerId,
+    pub size: Length,
+    pub extra: Extra,
+}
+
+impl FromSexpr for ViaPadstackLayer {
+    fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
+        parser.expect_symbol_matching("layer")?;
+
+        let mut parser = parser.keyed();
+
+        let layer = parser.expect_string()?.parse::<PadstackLayerId>()?;
+        let size = parser.expect_length_with_name("size")?;
+
+        let extra = parser.expect_end_with_extra()?;
+
+        Ok(Self { layer, size, extra })
+    }
+}
+
+simple_maybe_from_sexpr!(ViaPadstackLayer, layer);
+
+impl ToSexpr for ViaPadstackLayer {
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("layer", &self.extra, |s| {
+            s.append_string(self.layer);
+            s.append_number_with_name("size", self.size);
+        });
+    }
+}
+
+/// Whether the sides of a via are covered by solder mask. A side that is
+/// `None` follows the board's setting.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct ViaTenting {
+    pub front: Option<bool>,
+    pub back: Option<bool>,
+    pub extra: Extra,
+}
+
+impl FromSexpr for ViaTenting {
+    fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
+        parser.expect_symbol_matching("tenting")?;
+
+        let mut parser = parser.keyed();
+
+        let front = parser.maybe_bool_with_name("front")?;
+        let back = parser.maybe_bool_with_name("back")?;
+
+        let extra = parser.expect_end_with_extra()?;
+
+        Ok(Self { front, back, extra })
+    }
+}
+
+simple_maybe_from_sexpr!(ViaTenting, tenting);
+
+impl ToSexpr for ViaTenting {
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("tenting", &self.extra, |s| {
+            s.append_maybe_bool_with_name("front", self.front);
+            s.append_maybe_bool_with_name("back", self.back);
+        });
     }
 }
 
@@ -731,28 +934,33 @@ pub struct TrackArc {
     pub start: Vec2D,
     pub midpoint: Vec2D,
     pub end: Vec2D,
-    pub width: f32,
+    pub width: Length,
     pub layer: LayerId,
     pub net: i32,
     pub tstamp: Uuid,
+    pub field_order: FieldOrder,
+    pub extra: Extra,
 }
 
 impl FromSexpr for TrackArc {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("arc")?;
 
-        let locked = parser.maybe_symbol_matching("locked");
+        let mut parser = parser.keyed();
+        let field_order = parser.field_order();
+
+        let locked = parser.maybe_locked()?;
         let start = parser.expect_with_name::<Vec2D>("start")?;
         let midpoint = parser.expect_with_name::<Vec2D>("mid")?;
         let end = parser.expect_with_name::<Vec2D>("end")?;
-        let width = parser.expect_number_with_name("width")?;
+        let width = parser.expect_length_with_name("width")?;
         let layer = parser
             .expect_string_with_name("layer")?
             .parse::<LayerId>()?;
-        let net = parser.expect_number_with_name("net")? as i32;
-        let tstamp = parser.expect_with_name::<Uuid>("tstamp")?;
+        let net = parser.expect_integer_with_name::<i32>("net")?;
+        let tstamp = parser.expect_id()?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             locked,
@@ -763,24 +971,23 @@ impl FromSexpr for TrackArc {
             layer,
             net,
             tstamp,
+            field_order,
+            extra,
         })
     }
 }
 
 impl ToSexpr for TrackArc {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "arc",
-            [
-                self.locked.then(|| Sexpr::symbol("locked")),
-                Some(self.start.to_sexpr_with_name("start")),
-                Some(self.midpoint.to_sexpr_with_name("mid")),
-                Some(self.end.to_sexpr_with_name("end")),
-                Some(Sexpr::number_with_name("width", self.width)),
-                Some(Sexpr::string_with_name("layer", self.layer)),
-                Some(Sexpr::number_with_name("net", self.net as f32)),
-                Some(self.tstamp.to_sexpr_with_name("tstamp")),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_in_order("arc", &self.field_order, &self.extra, |s| {
+            s.append_locked(self.locked);
+            s.append_with_name("start", &self.start);
+            s.append_with_name("mid", &self.midpoint);
+            s.append_with_name("end", &self.end);
+            s.append_number_with_name("width", self.width);
+            s.append_string_with_name("layer", self.layer);
+            s.append_number_with_name("net", self.net);
+            s.append_id(&self.tstamp);
+        });
     }
 }
@@ -1,25 +1,33 @@
 //! Schematic file format (`.kicad_sch` files)
 
-use kicad_sexpr::{Sexpr, SexprList};
+use kicad_sexpr::SexprList;
 
 use crate::{
     common::{
+        embedded::{
+            parse_embedded_files, parse_embedded_fonts, write_embedded_files, write_embedded_fonts,
+            EmbeddedFile,
+        },
         shape::{Shape, ShapeFillMode},
         symbol::{LibSymbol, SymbolProperty},
         Color, CoordinatePointList, Image, PageSettings, Position, Stroke, TextEffects, TitleBlock,
         Uuid, Vec2D,
     },
     convert::{
-        FromSexpr, MaybeFromSexpr, Parser, SexprListExt, ToSexpr, ToSexprWithName,
-        VecToMaybeSexprVec,
+        with_file_version, writes, Extra, FromSexpr, ListBuilder, MaybeFromSexpr, Parser,
+        SexprListExt, ToSexpr,
     },
-    simple_maybe_from_sexpr, simple_to_from_string, KiCadParseError,
+    length::Length,
+    simple_maybe_from_sexpr, simple_to_from_string,
+    version::{FormatVersion, Syntax},
+    KiCadParseError,
 };
 
-use self::{sheet::Sheet, symbol::Symbol};
+use self::{sheet::Sheet, symbol::Symbol, table::SchematicTable};
 
 pub mod sheet;
 pub mod symbol;
+pub mod table;
 
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
@@ -55,6 +63,8 @@ simple_to_from_string! {
 pub struct SchematicFile {
     pub version: u32,
     pub generator: String,
+    /// The version of the program that wrote the file, since KiCad 8.
+    pub generator_version: Option<String>,
     pub uuid: Uuid,
     pub page_settings: PageSettings,
     pub title_block: Option<TitleBlock>,
@@ -67,6 +77,7 @@ pub struct SchematicFile {
     pub shapes: Vec<Shape>,
     pub images: Vec<Image>,
     pub text_boxes: Vec<SchematicTextBox>,
+    pub tables: Vec<SchematicTable>,
     pub texts: Vec<SchematicText>,
     pub local_labels: Vec<LocalLabel>,
     pub global_labels: Vec<GlobalLabel>,
@@ -74,25 +85,35 @@ pub struct SchematicFile {
     pub symbols: Vec<Symbol>,
     pub sheets: Vec<Sheet>,
     pub sheet_instances: Option<Vec<SchematicSheetInstance>>,
+    /// The references of the symbols in every sheet of the hierarchy, before
+    /// KiCad 7 moved them into the [`Symbol::instances`] of each symbol.
+    pub symbol_instances: Option<Vec<SchematicSymbolInstance>>,
+    /// Whether the fonts used by the schematic are embedded in it, since
+    /// KiCad 9.
+    pub embedded_fonts: Option<bool>,
+    /// The files embedded in the schematic, since KiCad 9.
+    pub embedded_files: Option<Vec<EmbeddedFile>>,
+    pub extra: Extra,
 }
 
 impl FromSexpr for SchematicFile {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("kicad_sch")?;
 
-        let version = parser.expect_number_with_name("version")? as u32;
-        let generator = parser.expect_symbol_with_name("generator")?;
+        let mut parser = parser.keyed();
+
+        let version = parser.expect_integer_with_name::<u32>("version")?;
+        parser.set_version(FormatVersion::schematic(version));
+        let generator = parser.expect_string_or_symbol_with_name("generator")?;
+        let generator_version = parser.maybe_string_with_name("generator_version")?;
         let uuid = parser.expect::<Uuid>()?;
         let page_settings = parser.expect::<PageSettings>()?;
         let title_block = parser.maybe::<TitleBlock>()?;
-        let lib_symbols = parser
-            .expect_list_with_name("lib_symbols")
-            .and_then(|mut p| {
-                let symbols = p.expect_many::<LibSymbol>()?;
-                p.expect_end()?;
-
-                Ok(symbols)
-            })?;
+        let lib_symbols = parser.expect_list_with_name("lib_symbols", |p| {
+            let symbols = p.expect_many::<LibSymbol>()?;
+
+            Ok(symbols)
+        })?;
         let bus_aliases = parser.expect_many::<BusAlias>()?;
         let junctions = parser.expect_many::<Junction>()?;
         let no_connects = parser.expect_many::<NoConnect>()?;
@@ -101,27 +122,35 @@ impl FromSexpr for SchematicFile {
         let shapes = parser.expect_many::<Shape>()?;
         let images = parser.expect_many::<Image>()?;
         let text_boxes = parser.expect_many::<SchematicTextBox>()?;
+        let tables = parser.expect_many::<SchematicTable>()?;
+        if !tables.is_empty() {
+            parser.check_syntax(Syntax::Tables, "table")?;
+        }
         let texts = parser.expect_many::<SchematicText>()?;
         let local_labels = parser.expect_many::<LocalLabel>()?;
         let global_labels = parser.expect_many::<GlobalLabel>()?;
         let hierarchical_labels = parser.expect_many::<HierarchicalLabel>()?;
         let symbols = parser.expect_many::<Symbol>()?;
         let sheets = parser.expect_many::<Sheet>()?;
-        let sheet_instances = parser
-            .maybe_list_with_name("sheet_instances")
-            .map(|mut p| {
-                let instances = p.expect_many::<SchematicSheetInstance>()?;
-                p.expect_end()?;
+        let sheet_instances = parser.maybe_list_with_name("sheet_instances", |p| {
+            let instances = p.expect_many::<SchematicSheetInstance>()?;
 
-                Ok::<_, KiCadParseError>(instances)
-            })
-            .transpose()?;
+            Ok(instances)
+        })?;
+        let symbol_instances = parser.maybe_list_with_name("symbol_instances", |p| {
+            let instances = p.expect_many::<SchematicSymbolInstance>()?;
+
+            Ok(instances)
+        })?;
+        let embedded_fonts = parse_embedded_fonts(&mut parser)?;
+        let embedded_files = parse_embedded_files(&mut parser)?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             version,
             generator,
+            generator_version,
             uuid,
             page_settings,
             title_block,
@@ -134,6 +163,7 @@ impl FromSexpr for SchematicFile {
             shapes,
             images,
             text_boxes,
+            tables,
             texts,
             local_labels,
             global_labels,
@@ -141,47 +171,57 @@ impl FromSexpr for SchematicFile {
             symbols,
             sheets,
             sheet_instances,
+            symbol_instances,
+            embedded_fonts,
+            embedded_files,
+            extra,
         })
     }
 }
 
 impl ToSexpr for SchematicFile {
-    fn to_sexpr(&self) -> kicad_sexpr::Sexpr {
-        Sexpr::list_with_name(
-            "kicad_sch",
-            [
-                &[
-                    Some(Sexpr::number_with_name("version", self.version as f32)),
-                    Some(Sexpr::symbol_with_name("generator", &self.generator)),
-                    Some(self.uuid.to_sexpr()),
-                    Some(self.page_settings.to_sexpr()),
-                    self.title_block.as_ref().map(ToSexpr::to_sexpr),
-                    Some(Sexpr::list_with_name(
-                        "lib_symbols",
-                        self.lib_symbols.into_sexpr_vec(),
-                    )),
-                ][..],
-                &self.bus_aliases.into_sexpr_vec(),
-                &self.junctions.into_sexpr_vec(),
-                &self.no_connects.into_sexpr_vec(),
-                &self.bus_entries.into_sexpr_vec(),
-                &self.lines.into_sexpr_vec(),
-                &self.shapes.into_sexpr_vec(),
-                &self.images.into_sexpr_vec(),
-                &self.text_boxes.into_sexpr_vec(),
-                &self.texts.into_sexpr_vec(),
-                &self.local_labels.into_sexpr_vec(),
-                &self.global_labels.into_sexpr_vec(),
-                &self.hierarchical_labels.into_sexpr_vec(),
-                &self.symbols.into_sexpr_vec(),
-                &self.sheets.into_sexpr_vec(),
-                &[self
-                    .sheet_instances
-                    .as_ref()
-                    .map(|s| Sexpr::list_with_name("sheet_instances", s.into_sexpr_vec()))][..],
-            ]
-            .concat(),
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        with_file_version(FormatVersion::schematic(self.version), |version| {
+            s.append_list_with_extra("kicad_sch", &self.extra, |s| {
+                s.append_number_with_name("version", version);
+                s.append_generator(&self.generator);
+                if writes(Syntax::GeneratorVersion) {
+                    s.append_maybe_string_with_name(
+                        "generator_version",
+                        self.generator_version.as_ref(),
+                    );
+                }
+                s.append(&self.uuid);
+                s.append(&self.page_settings);
+                s.append_maybe(&self.title_block);
+                s.append_list("lib_symbols", |s| s.append_many(&self.lib_symbols));
+                s.append_many(&self.bus_aliases);
+                s.append_many(&self.junctions);
+                s.append_many(&self.no_connects);
+                s.append_many(&self.bus_entries);
+                s.append_many(&self.lines);
+                s.append_many(&self.shapes);
+                s.append_many(&self.images);
+                s.append_many(&self.text_boxes);
+                if writes(Syntax::Tables) {
+                    s.append_many(&self.tables);
+                }
+                s.append_many(&self.texts);
+                s.append_many(&self.local_labels);
+                s.append_many(&self.global_labels);
+                s.append_many(&self.hierarchical_labels);
+                s.append_many(&self.symbols);
+                s.append_many(&self.sheets);
+                if let Some(sheet_instances) = &self.sheet_instances {
+                    s.append_list("sheet_instances", |s| s.append_many(sheet_instances));
+                }
+                if let Some(symbol_instances) = &self.symbol_instances {
+                    s.append_list("symbol_instances", |s| s.append_many(symbol_instances));
+                }
+                write_embedded_fonts(s, self.embedded_fonts);
+                write_embedded_files(s, &self.embedded_files);
+            });
+        });
     }
 }
 
@@ -191,45 +231,44 @@ impl ToSexpr for SchematicFile {
 pub struct BusAlias {
     pub name: String,
     pub members: Vec<String>,
+    pub extra: Extra,
 }
 
 impl FromSexpr for BusAlias {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("bus_alias")?;
 
+        let mut parser = parser.keyed();
+
         let name = parser.expect_string()?;
-        let members = parser.expect_list_with_name("members").and_then(|mut p| {
+        let members = parser.expect_list_with_name("members", |p| {
             let members = p.expect_many_strings()?;
 
-            p.expect_end()?;
-
             Ok(members)
         })?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
-        Ok(Self { name, members })
+        Ok(Self {
+            name,
+            members,
+            extra,
+        })
     }
 }
 
 simple_maybe_from_sexpr!(BusAlias, bus_alias);
 
 impl ToSexpr for BusAlias {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "bus_alias",
-            [
-                Some(Sexpr::string(&self.name)),
-                Some(Sexpr::list_with_name(
-                    "members",
-                    self.members
-                        .iter()
-                        .map(Sexpr::string)
-                        .map(Option::Some)
-                        .collect::<Vec<_>>(),
-                )),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("bus_alias", &self.extra, |s| {
+            s.append_string(&self.name);
+            s.append_list("members", |s| {
+                for member in &self.members {
+                    s.append_string(member);
+                }
+            });
+        });
     }
 }
 
@@ -238,27 +277,31 @@ impl ToSexpr for BusAlias {
 #[derive(Debug, PartialEq, Clone)]
 pub struct Junction {
     pub position: Position,
-    pub diameter: f32,
+    pub diameter: Length,
     pub color: Color,
     pub uuid: Uuid,
+    pub extra: Extra,
 }
 
 impl FromSexpr for Junction {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("junction")?;
 
+        let mut parser = parser.keyed();
+
         let position = parser.expect::<Position>()?;
-        let diameter = parser.expect_number_with_name("diameter")?;
+        let diameter = parser.expect_length_with_name("diameter")?;
         let color = parser.expect::<Color>()?;
         let uuid = parser.expect::<Uuid>()?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             position,
             diameter,
             color,
             uuid,
+            extra,
         })
     }
 }
@@ -266,16 +309,13 @@ impl FromSexpr for Junction {
 simple_maybe_from_sexpr!(Junction, junction);
 
 impl ToSexpr for Junction {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "junction",
-            [
-                Some(self.position.to_sexpr()),
-                Some(Sexpr::number_with_name("diameter", self.diameter)),
-                Some(self.color.to_sexpr()),
-                Some(self.uuid.to_sexpr()),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("junction", &self.extra, |s| {
+            s.append(&self.position);
+            s.append_number_with_name("diameter", self.diameter);
+            s.append(&self.color);
+            s.append(&self.uuid);
+        });
     }
 }
 
@@ -285,29 +325,36 @@ impl ToSexpr for Junction {
 pub struct NoConnect {
     pub position: Position,
     pub uuid: Uuid,
+    pub extra: Extra,
 }
 
 impl FromSexpr for NoConnect {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("no_connect")?;
 
+        let mut parser = parser.keyed();
+
         let position = parser.expect::<Position>()?;
         let uuid = parser.expect::<Uuid>()?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
-        Ok(Self { position, uuid })
+        Ok(Self {
+            position,
+            uuid,
+            extra,
+        })
     }
 }
 
 simple_maybe_from_sexpr!(NoConnect, no_connect);
 
 impl ToSexpr for NoConnect {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "no_connect",
-            [Some(self.position.to_sexpr()), Some(self.uuid.to_sexpr())],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("no_connect", &self.extra, |s| {
+            s.append(&self.position);
+            s.append(&self.uuid);
+        });
     }
 }
 
@@ -319,24 +366,28 @@ pub struct BusEntry {
     pub size: Vec2D,
     pub stroke: Stroke,
     pub uuid: Uuid,
+    pub extra: Extra,
 }
 
 impl FromSexpr for BusEntry {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("bus_entry")?;
 
+        let mut parser = parser.keyed();
+
         let position = parser.expect::<Position>()?;
         let size = parser.expect_with_name::<Vec2D>("size")?;
         let stroke = parser.expect::<Stroke>()?;
         let uuid = parser.expect::<Uuid>()?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             position,
             size,
             stroke,
             uuid,
+            extra,
         })
     }
 }
@@ -344,16 +395,13 @@ impl FromSexpr for BusEntry {
 simple_maybe_from_sexpr!(BusEntry, bus_entry);
 
 impl ToSexpr for BusEntry {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "bus_entry",
-            [
-                Some(self.position.to_sexpr()),
-                Some(self.size.to_sexpr_with_name("size")),
-                Some(self.stroke.to_sexpr()),
-                Some(self.uuid.to_sexpr()),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("bus_entry", &self.extra, |s| {
+            s.append(&self.position);
+            s.append_with_name("size", &self.size);
+            s.append(&self.stroke);
+            s.append(&self.uuid);
+        });
     }
 }
 
@@ -366,23 +414,27 @@ pub struct SchematicLine {
     pub points: CoordinatePointList,
     pub stroke: Stroke,
     pub uuid: Uuid,
+    pub extra: Extra,
 }
 
 impl FromSexpr for SchematicLine {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         let kind = parser.expect_symbol()?.parse::<SchematicLineKind>()?;
 
-        let points = parser.expect::<CoordinatePointList>()?;
+        let mut parser = parser.keyed();
+
+        let points = parser.expect_points()?;
         let stroke = parser.expect::<Stroke>()?;
         let uuid = parser.expect::<Uuid>()?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             kind,
             points,
             stroke,
             uuid,
+            extra,
         })
     }
 }
@@ -396,15 +448,12 @@ impl MaybeFromSexpr for SchematicLine {
 }
 
 impl ToSexpr for SchematicLine {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            self.kind.to_string(),
-            [
-                Some(self.points.to_sexpr()),
-                Some(self.stroke.to_sexpr()),
-                Some(self.uuid.to_sexpr()),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra(&self.kind.to_string(), &self.extra, |s| {
+            s.append(&self.points);
+            s.append(&self.stroke);
+            s.append(&self.uuid);
+        });
     }
 }
 
@@ -435,21 +484,24 @@ pub struct SchematicTextBox {
     pub fill: ShapeFillMode,
     pub effects: TextEffects,
     pub uuid: Option<Uuid>,
+    pub extra: Extra,
 }
 
 impl FromSexpr for SchematicTextBox {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("text_box")?;
 
+        let mut parser = parser.keyed();
+
         let text = parser.expect_string()?;
         let position = parser.expect::<Position>()?;
         let size = parser.expect_with_name::<Vec2D>("size")?;
         let stroke = parser.expect::<Stroke>()?;
-        let fill = parser.expect::<ShapeFillMode>()?;
+        let fill = parser.expect_list_with_name("fill", ShapeFillMode::parse)?;
         let effects = parser.expect::<TextEffects>()?;
         let uuid = parser.maybe::<Uuid>()?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             text,
@@ -459,6 +511,7 @@ impl FromSexpr for SchematicTextBox {
             fill,
             effects,
             uuid,
+            extra,
         })
     }
 }
@@ -466,19 +519,16 @@ impl FromSexpr for SchematicTextBox {
 simple_maybe_from_sexpr!(SchematicTextBox, text_box);
 
 impl ToSexpr for SchematicTextBox {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "text_box",
-            [
-                Some(Sexpr::string(&self.text)),
-                Some(self.position.to_sexpr()),
-                Some(self.size.to_sexpr_with_name("size")),
-                Some(self.stroke.to_sexpr()),
-                Some(self.fill.to_sexpr()),
-                Some(self.effects.to_sexpr()),
-                self.uuid.as_ref().map(ToSexpr::to_sexpr),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("text_box", &self.extra, |s| {
+            s.append_string(&self.text);
+            s.append(&self.position);
+            s.append_with_name("size", &self.size);
+            s.append(&self.stroke);
+            s.append(&self.fill);
+            s.append(&self.effects);
+            s.append_maybe(&self.uuid);
+        });
     }
 }
 
@@ -490,24 +540,28 @@ pub struct SchematicText {
     pub position: Position,
     pub effects: TextEffects,
     pub uuid: Uuid,
+    pub extra: Extra,
 }
 
 impl FromSexpr for SchematicText {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("text")?;
 
+        let mut parser = parser.keyed();
+
         let text = parser.expect_string()?;
         let position = parser.expect::<Position>()?;
         let effects = parser.expect::<TextEffects>()?;
         let uuid = parser.expect::<Uuid>()?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             text,
             position,
             effects,
             uuid,
+            extra,
         })
     }
 }
@@ -515,16 +569,13 @@ impl FromSexpr for SchematicText {
 simple_maybe_from_sexpr!(SchematicText, text);
 
 impl ToSexpr for SchematicText {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "text",
-            [
-                Some(Sexpr::string(&self.text)),
-                Some(self.position.to_sexpr()),
-                Some(self.effects.to_sexpr()),
-                Some(self.uuid.to_sexpr()),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("text", &self.extra, |s| {
+            s.append_string(&self.text);
+            s.append(&self.position);
+            s.append(&self.effects);
+            s.append(&self.uuid);
+        });
     }
 }
 
@@ -538,12 +589,15 @@ pub struct LocalLabel {
     pub effects: TextEffects,
     pub uuid: Uuid,
     pub properties: Vec<SymbolProperty>,
+    pub extra: Extra,
 }
 
 impl FromSexpr for LocalLabel {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("label")?;
 
+        let mut parser = parser.keyed();
+
         let text = parser.expect_string()?;
         let position = parser.expect::<Position>()?;
         let fields_autoplaced = parser.maybe_empty_list_with_name("fields_autoplaced")?;
@@ -551,7 +605,7 @@ impl FromSexpr for LocalLabel {
         let uuid = parser.expect::<Uuid>()?;
         let properties = parser.expect_many::<SymbolProperty>()?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             text,
@@ -560,6 +614,7 @@ impl FromSexpr for LocalLabel {
             effects,
             uuid,
             properties,
+            extra,
         })
     }
 }
@@ -567,22 +622,17 @@ impl FromSexpr for LocalLabel {
 simple_maybe_from_sexpr!(LocalLabel, label);
 
 impl ToSexpr for LocalLabel {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "label",
-            [
-                &[
-                    Some(Sexpr::string(&self.text)),
-                    Some(self.position.to_sexpr()),
-                    self.fields_autoplaced
-                        .then(|| Sexpr::list_with_name("fields_autoplaced", [])),
-                    Some(self.effects.to_sexpr()),
-                    Some(self.uuid.to_sexpr()),
-                ][..],
-                &self.properties.into_sexpr_vec(),
-            ]
-            .concat(),
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("label", &self.extra, |s| {
+            s.append_string(&self.text);
+            s.append(&self.position);
+            if self.fields_autoplaced {
+                s.append_list("fields_autoplaced", |_| {});
+            }
+            s.append(&self.effects);
+            s.append(&self.uuid);
+            s.append_many(&self.properties);
+        });
     }
 }
 
@@ -597,12 +647,15 @@ pub struct GlobalLabel {
     pub effects: TextEffects,
     pub uuid: Uuid,
     pub properties: Vec<SymbolProperty>,
+    pub extra: Extra,
 }
 
 impl FromSexpr for GlobalLabel {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("global_label")?;
 
+        let mut parser = parser.keyed();
+
         let text = parser.expect_string()?;
         let shape = parser
             .expect_symbol_with_name("shape")?
@@ -613,7 +666,7 @@ impl FromSexpr for GlobalLabel {
         let uuid = parser.expect::<Uuid>()?;
         let properties = parser.expect_many::<SymbolProperty>()?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             text,
@@ -623,6 +676,7 @@ impl FromSexpr for GlobalLabel {
             effects,
             uuid,
             properties,
+            extra,
         })
     }
 }
@@ -630,23 +684,18 @@ impl FromSexpr for GlobalLabel {
 simple_maybe_from_sexpr!(GlobalLabel, global_label);
 
 impl ToSexpr for GlobalLabel {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "global_label",
-            [
-                &[
-                    Some(Sexpr::string(&self.text)),
-                    Some(Sexpr::symbol_with_name("shape", self.shape)),
-                    Some(self.position.to_sexpr()),
-                    self.fields_autoplaced
-                        .then(|| Sexpr::list_with_name("fields_autoplaced", [])),
-                    Some(self.effects.to_sexpr()),
-                    Some(self.uuid.to_sexpr()),
-                ][..],
-                &self.properties.into_sexpr_vec(),
-            ]
-            .concat(),
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("global_label", &self.extra, |s| {
+            s.append_string(&self.text);
+            s.append_symbol_with_name("shape", self.shape);
+            s.append(&self.position);
+            if self.fields_autoplaced {
+                s.append_list("fields_autoplaced", |_| {});
+            }
+            s.append(&self.effects);
+            s.append(&self.uuid);
+            s.append_many(&self.properties);
+        });
     }
 }
 
@@ -662,12 +711,15 @@ pub struct HierarchicalLabel {
     pub effects: TextEffects,
     pub uuid: Uuid,
     pub properties: Vec<SymbolProperty>,
+    pub extra: Extra,
 }
 
 impl FromSexpr for HierarchicalLabel {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("hierarchical_label")?;
 
+        let mut parser = parser.keyed();
+
         let text = parser.expect_string()?;
         let shape = parser
             .expect_symbol_with_name("shape")?
@@ -678,7 +730,7 @@ impl FromSexpr for HierarchicalLabel {
         let uuid = parser.expect::<Uuid>()?;
         let properties = parser.expect_many::<SymbolProperty>()?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             text,
@@ -688,6 +740,7 @@ impl FromSexpr for HierarchicalLabel {
             effects,
             uuid,
             properties,
+            extra,
         })
     }
 }
@@ -695,23 +748,18 @@ impl FromSexpr for HierarchicalLabel {
 simple_maybe_from_sexpr!(HierarchicalLabel, hierarchical_label);
 
 impl ToSexpr for HierarchicalLabel {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "hierarchical_label",
-            [
-                &[
-                    Some(Sexpr::string(&self.text)),
-                    Some(Sexpr::symbol_with_name("shape", self.shape)),
-                    Some(self.position.to_sexpr()),
-                    self.fields_autoplaced
-                        .then(|| Sexpr::list_with_name("fields_autoplaced", [])),
-                    Some(self.effects.to_sexpr()),
-                    Some(self.uuid.to_sexpr()),
-                ][..],
-                &self.properties.into_sexpr_vec(),
-            ]
-            .concat(),
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("hierarchical_label", &self.extra, |s| {
+            s.append_string(&self.text);
+            s.append_symbol_with_name("shape", self.shape);
+            s.append(&self.position);
+            if self.fields_autoplaced {
+                s.append_list("fields_autoplaced", |_| {});
+            }
+            s.append(&self.effects);
+            s.append(&self.uuid);
+            s.append_many(&self.properties);
+        });
     }
 }
 
@@ -721,31 +769,86 @@ impl ToSexpr for HierarchicalLabel {
 pub struct SchematicSheetInstance {
     pub path: String,
     pub page: String,
+    pub extra: Extra,
 }
 
 impl FromSexpr for SchematicSheetInstance {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("path")?;
 
+        let mut parser = parser.keyed();
+
         let path = parser.expect_string()?;
         let page = parser.expect_string_with_name("page")?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
-        Ok(Self { path, page })
+        Ok(Self { path, page, extra })
     }
 }
 
 simple_maybe_from_sexpr!(SchematicSheetInstance, path);
 
 impl ToSexpr for SchematicSheetInstance {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "path",
-            [
-                Some(Sexpr::string(&self.path)),
-                Some(Sexpr::string_with_name("page", &self.page)),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("path", &self.extra, |s| {
+            s.append_string(&self.path);
+            s.append_string_with_name("page", &self.page);
+        });
+    }
+}
+
+/// The reference of a symbol in one sheet of the hierarchy, as listed in the
+/// `symbol_instances` of the root schematic before KiCad 7.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct SchematicSymbolInstance {
+    /// The path of sheet uuids leading to the symbol, ending with the uuid
+    /// of the symbol itself.
+    pub path: String,
+    pub reference: String,
+    pub unit: u16,
+    pub value: Option<String>,
+    pub footprint: Option<String>,
+    pub extra: Extra,
+}
+
+impl FromSexpr for SchematicSymbolInstance {
+    fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
+        parser.expect_symbol_matching("path")?;
+
+        let mut parser = parser.keyed();
+
+        let path = parser.expect_string()?;
+        let reference = parser.expect_string_with_name("reference")?;
+        let unit = parser.expect_integer_with_name::<u16>("unit")?;
+        let value = parser.maybe_string_with_name("value")?;
+        let footprint = parser.maybe_string_with_name("footprint")?;
+
+        let extra = parser.expect_end_with_extra()?;
+
+        Ok(Self {
+            path,
+            reference,
+            unit,
+            value,
+            footprint,
+            extra,
+        })
+    }
+}
+
+simple_maybe_from_sexpr!(SchematicSymbolInstance, path);
+
+impl ToSexpr for SchematicSymbolInstance {
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("path", &self.extra, |s| {
+            s.append_string(&self.path);
+            s.append_string_with_name("reference", &self.reference);
+            s.append_number_with_name("unit", self.unit);
+            s.append_maybe_string_with_name("value", self.value.as_ref());
+            s.append_maybe_string_with_name("footprint", self.footprint.as_ref());
+        });
     }
 }
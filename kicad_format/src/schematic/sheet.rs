@@ -1,10 +1,8 @@
 //! Common structures related to sub-sheets within a schematic.
 
-use kicad_sexpr::Sexpr;
-
 use crate::{
     common::{symbol::SymbolProperty, Color, Position, Stroke, TextEffects, Uuid, Vec2D},
-    convert::{FromSexpr, Parser, SexprListExt, ToSexpr, ToSexprWithName, VecToMaybeSexprVec},
+    convert::{Extra, FromSexpr, ListBuilder, Parser, SexprListExt, ToSexpr},
     simple_maybe_from_sexpr, KiCadParseError,
 };
 
@@ -16,6 +14,17 @@ use super::SheetPinShape;
 pub struct Sheet {
     pub position: Position,
     pub size: Vec2D,
+    /// Whether the symbols on the sheet are left out of simulations, since
+    /// KiCad 9.
+    pub exclude_from_sim: Option<bool>,
+    /// Whether the symbols on the sheet are in the bill of materials, since
+    /// KiCad 9.
+    pub in_bom: Option<bool>,
+    /// Whether the symbols on the sheet are placed on the board, since
+    /// KiCad 9.
+    pub on_board: Option<bool>,
+    /// Whether the symbols on the sheet are not populated, since KiCad 9.
+    pub dnp: Option<bool>,
     pub fields_autoplaced: bool,
     pub stroke: Stroke,
     pub fill: Color,
@@ -23,40 +32,46 @@ pub struct Sheet {
     pub properties: Vec<SymbolProperty>,
     pub pins: Vec<SheetHierarchicalPin>,
     pub instances: Option<Vec<SheetInstance>>,
+    pub extra: Extra,
 }
 
 impl FromSexpr for Sheet {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("sheet")?;
 
+        let mut parser = parser.keyed();
+
         let position = parser.expect::<Position>()?;
         let size = parser.expect_with_name::<Vec2D>("size")?;
+        let exclude_from_sim = parser.maybe_bool_with_name("exclude_from_sim")?;
+        let in_bom = parser.maybe_bool_with_name("in_bom")?;
+        let on_board = parser.maybe_bool_with_name("on_board")?;
+        let dnp = parser.maybe_bool_with_name("dnp")?;
         let fields_autoplaced = parser.maybe_empty_list_with_name("fields_autoplaced")?;
         let stroke = parser.expect::<Stroke>()?;
-        let fill = parser.expect_list_with_name("fill").and_then(|mut p| {
+        let fill = parser.expect_list_with_name("fill", |p| {
             let color = p.expect::<Color>()?;
-            p.expect_end()?;
 
             Ok(color)
         })?;
         let uuid = parser.expect::<Uuid>()?;
         let properties = parser.expect_many::<SymbolProperty>()?;
         let pins = parser.expect_many::<SheetHierarchicalPin>()?;
-        let instances = parser
-            .maybe_list_with_name("instances")
-            .map(|mut p| {
-                let instances = p.expect_many::<SheetInstance>()?;
-                p.expect_end()?;
+        let instances = parser.maybe_list_with_name("instances", |p| {
+            let instances = p.expect_many::<SheetInstance>()?;
 
-                Ok::<_, KiCadParseError>(instances)
-            })
-            .transpose()?;
+            Ok(instances)
+        })?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             position,
             size,
+            exclude_from_sim,
+            in_bom,
+            on_board,
+            dnp,
             fields_autoplaced,
             stroke,
             fill,
@@ -64,6 +79,7 @@ impl FromSexpr for Sheet {
             properties,
             pins,
             instances,
+            extra,
         })
     }
 }
@@ -71,28 +87,26 @@ impl FromSexpr for Sheet {
 simple_maybe_from_sexpr!(Sheet, sheet);
 
 impl ToSexpr for Sheet {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "sheet",
-            [
-                &[
-                    Some(self.position.to_sexpr()),
-                    Some(self.size.to_sexpr_with_name("size")),
-                    self.fields_autoplaced
-                        .then(|| Sexpr::list_with_name("fields_autoplaced", [])),
-                    Some(self.stroke.to_sexpr()),
-                    Some(Sexpr::list_with_name("fill", [Some(self.fill.to_sexpr())])),
-                    Some(self.uuid.to_sexpr()),
-                ][..],
-                &self.properties.into_sexpr_vec(),
-                &self.pins.into_sexpr_vec(),
-                &[self
-                    .instances
-                    .as_ref()
-                    .map(|i| Sexpr::list_with_name("instances", i.into_sexpr_vec()))][..],
-            ]
-            .concat(),
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("sheet", &self.extra, |s| {
+            s.append(&self.position);
+            s.append_with_name("size", &self.size);
+            s.append_maybe_bool_with_name("exclude_from_sim", self.exclude_from_sim);
+            s.append_maybe_bool_with_name("in_bom", self.in_bom);
+            s.append_maybe_bool_with_name("on_board", self.on_board);
+            s.append_maybe_bool_with_name("dnp", self.dnp);
+            if self.fields_autoplaced {
+                s.append_list("fields_autoplaced", |_| {});
+            }
+            s.append(&self.stroke);
+            s.append_list("fill", |s| s.append(&self.fill));
+            s.append(&self.uuid);
+            s.append_many(&self.properties);
+            s.append_many(&self.pins);
+            if let Some(instances) = &self.instances {
+                s.append_list("instances", |s| s.append_many(instances));
+            }
+        });
     }
 }
 
@@ -106,19 +120,22 @@ pub struct SheetHierarchicalPin {
     pub position: Position,
     pub effects: TextEffects,
     pub uuid: Uuid,
+    pub extra: Extra,
 }
 
 impl FromSexpr for SheetHierarchicalPin {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("pin")?;
 
+        let mut parser = parser.keyed();
+
         let name = parser.expect_string()?;
         let shape = parser.expect_symbol()?.parse::<SheetPinShape>()?;
         let position = parser.expect::<Position>()?;
         let effects = parser.expect::<TextEffects>()?;
         let uuid = parser.expect::<Uuid>()?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             name,
@@ -126,6 +143,7 @@ impl FromSexpr for SheetHierarchicalPin {
             position,
             effects,
             uuid,
+            extra,
         })
     }
 }
@@ -133,17 +151,14 @@ impl FromSexpr for SheetHierarchicalPin {
 simple_maybe_from_sexpr!(SheetHierarchicalPin, pin);
 
 impl ToSexpr for SheetHierarchicalPin {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "pin",
-            [
-                Some(Sexpr::string(&self.name)),
-                Some(Sexpr::symbol(self.shape)),
-                Some(self.position.to_sexpr()),
-                Some(self.effects.to_sexpr()),
-                Some(self.uuid.to_sexpr()),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("pin", &self.extra, |s| {
+            s.append_string(&self.name);
+            s.append_symbol(self.shape);
+            s.append(&self.position);
+            s.append(&self.effects);
+            s.append(&self.uuid);
+        });
     }
 }
 
@@ -154,28 +169,31 @@ pub struct SheetInstance {
     pub project: String,
     pub path: String,
     pub page: String,
+    pub extra: Extra,
 }
 
 impl FromSexpr for SheetInstance {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("project")?;
 
+        let mut parser = parser.keyed();
+
         let project = parser.expect_string()?;
 
-        let (path, page) = parser.expect_list_with_name("path").and_then(|mut p| {
+        let (path, page) = parser.expect_list_with_name("path", |p| {
             let path = p.expect_string()?;
             let page = p.expect_string_with_name("page")?;
-            p.expect_end()?;
 
             Ok((path, page))
         })?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             project,
             path,
             page,
+            extra,
         })
     }
 }
@@ -183,19 +201,13 @@ impl FromSexpr for SheetInstance {
 simple_maybe_from_sexpr!(SheetInstance, project);
 
 impl ToSexpr for SheetInstance {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "project",
-            [
-                Some(Sexpr::string(&self.project)),
-                Some(Sexpr::list_with_name(
-                    "path",
-                    [
-                        Some(Sexpr::string(&self.path)),
-                        Some(Sexpr::string_with_name("page", &self.page)),
-                    ],
-                )),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("project", &self.extra, |s| {
+            s.append_string(&self.project);
+            s.append_list("path", |s| {
+                s.append_string(&self.path);
+                s.append_string_with_name("page", &self.page);
+            });
+        });
     }
 }
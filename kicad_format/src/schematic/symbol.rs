@@ -4,15 +4,15 @@
 //! [`DerivedLibSymbol`](crate::symbol_library::DerivedLibSymbol), or
 //! [`LibSymbolSubUnit`](crate::common::symbol::LibSymbolSubUnit).
 
-use kicad_sexpr::Sexpr;
-
 use crate::{
     common::{
         symbol::{LibraryId, SymbolProperty},
         Position, Uuid,
     },
-    convert::{FromSexpr, Parser, SexprListExt, ToSexpr, VecToMaybeSexprVec},
-    simple_maybe_from_sexpr, KiCadParseError,
+    convert::{writes, Extra, FromSexpr, ListBuilder, Parser, SexprListExt, ToSexpr},
+    simple_maybe_from_sexpr,
+    version::Syntax,
+    KiCadParseError,
 };
 
 /// A symbol instance within a schematic
@@ -38,42 +38,41 @@ pub struct Symbol {
     pub properties: Vec<SymbolProperty>,
     pub pins: Vec<Pin>,
     pub instances: Option<Vec<SymbolInstanceProject>>,
+    pub extra: Extra,
 }
 
 impl FromSexpr for Symbol {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("symbol")?;
 
+        let mut parser = parser.keyed();
+
         let lib_name = parser.maybe_string_with_name("lib_name")?;
         let lib_id = parser
             .expect_string_with_name("lib_id")?
             .parse::<LibraryId>()?;
         let position = parser.expect::<Position>()?;
         let mirror = parser.maybe::<Mirror>()?;
-        let unit = parser.expect_number_with_name("unit")? as u16;
+        let unit = parser.expect_integer_with_name::<u16>("unit")?;
         let convert = parser
-            .maybe_number_with_name("convert")?
-            .map(|c| c as u8)
+            .maybe_integer_with_name::<u8>("convert")?
             .map(TryFrom::try_from)
             .transpose()?;
         let in_bom = parser.expect_bool_with_name("in_bom")?;
         let on_board = parser.expect_bool_with_name("on_board")?;
-        let dnp = parser.expect_bool_with_name("dnp")?;
+        // Symbols before KiCad 7 have no `dnp` flag
+        let dnp = parser.maybe_bool_with_name("dnp")?.unwrap_or_default();
         let fields_autoplaced = parser.maybe_empty_list_with_name("fields_autoplaced")?;
         let uuid = parser.expect::<Uuid>()?;
         let properties = parser.expect_many::<SymbolProperty>()?;
         let pins = parser.expect_many::<Pin>()?;
-        let instances = parser
-            .maybe_list_with_name("instances")
-            .map(|mut p| {
-                let instances = p.expect_many::<SymbolInstanceProject>()?;
-                p.expect_end()?;
-
-                Ok::<_, KiCadParseError>(instances)
-            })
-            .transpose()?;
+        let instances = parser.maybe_list_with_name("instances", |p| {
+            let instances = p.expect_many::<SymbolInstanceProject>()?;
 
-        parser.expect_end()?;
+            Ok(instances)
+        })?;
+
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             lib_name,
@@ -90,6 +89,7 @@ impl FromSexpr for Symbol {
             properties,
             pins,
             instances,
+            extra,
         })
     }
 }
@@ -97,42 +97,31 @@ impl FromSexpr for Symbol {
 simple_maybe_from_sexpr!(Symbol, symbol);
 
 impl ToSexpr for Symbol {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "symbol",
-            [
-                &[
-                    self.lib_name
-                        .as_ref()
-                        .map(|n| Sexpr::string_with_name("lib_name", n)),
-                    Some(Sexpr::list_with_name(
-                        "lib_id",
-                        [Some(self.lib_id.to_sexpr())],
-                    )),
-                    Some(self.position.to_sexpr()),
-                    self.mirror
-                        .as_ref()
-                        .filter(|m| m.x || m.y)
-                        .map(ToSexpr::to_sexpr),
-                    Some(Sexpr::number_with_name("unit", self.unit as f32)),
-                    self.convert
-                        .map(|c| Sexpr::number_with_name("convert", c as u8 as f32)),
-                    Some(Sexpr::bool_with_name("in_bom", self.in_bom)),
-                    Some(Sexpr::bool_with_name("on_board", self.on_board)),
-                    Some(Sexpr::bool_with_name("dnp", self.dnp)),
-                    self.fields_autoplaced
-                        .then(|| Sexpr::list_with_name("fields_autoplaced", [])),
-                    Some(self.uuid.to_sexpr()),
-                ][..],
-                &self.properties.into_sexpr_vec(),
-                &self.pins.into_sexpr_vec(),
-                &[self
-                    .instances
-                    .as_ref()
-                    .map(|i| Sexpr::list_with_name("instances", i.into_sexpr_vec()))][..],
-            ]
-            .concat(),
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("symbol", &self.extra, |s| {
+            s.append_maybe_string_with_name("lib_name", self.lib_name.as_ref());
+            s.append_list("lib_id", |s| s.append(&self.lib_id));
+            s.append(&self.position);
+            if let Some(mirror) = self.mirror.as_ref().filter(|m| m.x || m.y) {
+                s.append(mirror);
+            }
+            s.append_number_with_name("unit", self.unit);
+            s.append_maybe_number_with_name("convert", self.convert.map(|c| c as u8));
+            s.append_bool_with_name("in_bom", self.in_bom);
+            s.append_bool_with_name("on_board", self.on_board);
+            if writes(Syntax::Dnp) {
+                s.append_bool_with_name("dnp", self.dnp);
+            }
+            if self.fields_autoplaced {
+                s.append_list("fields_autoplaced", |_| {});
+            }
+            s.append(&self.uuid);
+            s.append_many(&self.properties);
+            s.append_many(&self.pins);
+            if let Some(instances) = &self.instances {
+                s.append_list("instances", |s| s.append_many(instances));
+            }
+        });
     }
 }
 
@@ -160,14 +149,11 @@ impl FromSexpr for Mirror {
 simple_maybe_from_sexpr!(Mirror, mirror);
 
 impl ToSexpr for Mirror {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "mirror",
-            [
-                self.x.then(|| Sexpr::symbol("x")),
-                self.y.then(|| Sexpr::symbol("y")),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list("mirror", |s| {
+            s.append_symbol_if("x", self.x);
+            s.append_symbol_if("y", self.y);
+        });
     }
 }
 
@@ -201,22 +187,26 @@ pub struct Pin {
     pub number: String,
     pub uuid: Uuid,
     pub alternate: Option<String>,
+    pub extra: Extra,
 }
 
 impl FromSexpr for Pin {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("pin")?;
 
+        let mut parser = parser.keyed();
+
         let number = parser.expect_string()?;
         let uuid = parser.expect::<Uuid>()?;
         let alternate = parser.maybe_string_with_name("alternate")?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             number,
             uuid,
             alternate,
+            extra,
         })
     }
 }
@@ -224,17 +214,12 @@ impl FromSexpr for Pin {
 simple_maybe_from_sexpr!(Pin, pin);
 
 impl ToSexpr for Pin {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "pin",
-            [
-                Some(Sexpr::string(&self.number)),
-                Some(self.uuid.to_sexpr()),
-                self.alternate
-                    .as_ref()
-                    .map(|a| Sexpr::string_with_name("alternate", a)),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("pin", &self.extra, |s| {
+            s.append_string(&self.number);
+            s.append(&self.uuid);
+            s.append_maybe_string_with_name("alternate", self.alternate.as_ref());
+        });
     }
 }
 
@@ -244,34 +229,37 @@ impl ToSexpr for Pin {
 pub struct SymbolInstanceProject {
     pub project: String,
     pub instances: Vec<SymbolInstance>,
+    pub extra: Extra,
 }
 
 impl FromSexpr for SymbolInstanceProject {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("project")?;
 
+        let mut parser = parser.keyed();
+
         let project = parser.expect_string()?;
 
         let instances = parser.expect_many::<SymbolInstance>()?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
-        Ok(Self { project, instances })
+        Ok(Self {
+            project,
+            instances,
+            extra,
+        })
     }
 }
 
 simple_maybe_from_sexpr!(SymbolInstanceProject, project);
 
 impl ToSexpr for SymbolInstanceProject {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "project",
-            [
-                &[Some(Sexpr::string(&self.project))][..],
-                &self.instances.into_sexpr_vec(),
-            ]
-            .concat(),
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("project", &self.extra, |s| {
+            s.append_string(&self.project);
+            s.append_many(&self.instances);
+        });
     }
 }
 
@@ -282,22 +270,26 @@ pub struct SymbolInstance {
     pub path: String,
     pub reference: String,
     pub unit: u16,
+    pub extra: Extra,
 }
 
 impl FromSexpr for SymbolInstance {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("path")?;
 
+        let mut parser = parser.keyed();
+
         let path = parser.expect_string()?;
         let reference = parser.expect_string_with_name("reference")?;
-        let unit = parser.expect_number_with_name("unit")? as u16;
+        let unit = parser.expect_integer_with_name::<u16>("unit")?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             path,
             reference,
             unit,
+            extra,
         })
     }
 }
@@ -305,14 +297,11 @@ impl FromSexpr for SymbolInstance {
 simple_maybe_from_sexpr!(SymbolInstance, path);
 
 impl ToSexpr for SymbolInstance {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "path",
-            [
-                Some(Sexpr::string(&self.path)),
-                Some(Sexpr::string_with_name("reference", &self.reference)),
-                Some(Sexpr::number_with_name("unit", self.unit as f32)),
-            ],
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("path", &self.extra, |s| {
+            s.append_string(&self.path);
+            s.append_string_with_name("reference", &self.reference);
+            s.append_number_with_name("unit", self.unit);
+        });
     }
 }
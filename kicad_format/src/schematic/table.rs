@@ -0,0 +1,155 @@
+//! Tables of text cells within a schematic.
+
+use crate::{
+    common::{
+        shape::ShapeFillMode,
+        table::{
+            parse_cell_margins, parse_cell_span, parse_lengths_with_name, write_cell_margins,
+            write_cell_span, write_lengths_with_name, TableBorder, TableSeparators,
+        },
+        Position, TextEffects, Uuid, Vec2D,
+    },
+    convert::{Extra, FromSexpr, ListBuilder, Parser, SexprListExt, ToSexpr},
+    length::Length,
+    simple_maybe_from_sexpr, KiCadParseError,
+};
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct SchematicTable {
+    pub column_count: u32,
+    pub border: Option<TableBorder>,
+    pub separators: Option<TableSeparators>,
+    pub column_widths: Vec<Length>,
+    pub row_heights: Vec<Length>,
+    /// The cells of the table, row by row.
+    pub cells: Vec<SchematicTableCell>,
+    pub extra: Extra,
+}
+
+impl FromSexpr for SchematicTable {
+    fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
+        parser.expect_symbol_matching("table")?;
+
+        let mut parser = parser.keyed();
+
+        let column_count = parser.expect_integer_with_name::<u32>("column_count")?;
+        let border = parser.maybe::<TableBorder>()?;
+        let separators = parser.maybe::<TableSeparators>()?;
+        let column_widths = parse_lengths_with_name(&mut parser, "column_widths")?;
+        let row_heights = parse_lengths_with_name(&mut parser, "row_heights")?;
+        let cells = parser
+            .maybe_list_with_name("cells", |p| {
+                let cells = p.expect_many::<SchematicTableCell>()?;
+
+                Ok(cells)
+            })?
+            .unwrap_or_default();
+
+        let extra = parser.expect_end_with_extra()?;
+
+        Ok(Self {
+            column_count,
+            border,
+            separators,
+            column_widths,
+            row_heights,
+            cells,
+            extra,
+        })
+    }
+}
+
+simple_maybe_from_sexpr!(SchematicTable, table);
+
+impl ToSexpr for SchematicTable {
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("table", &self.extra, |s| {
+            s.append_number_with_name("column_count", self.column_count);
+            s.append_maybe(&self.border);
+            s.append_maybe(&self.separators);
+            write_lengths_with_name(s, "column_widths", &self.column_widths);
+            write_lengths_with_name(s, "row_heights", &self.row_heights);
+            s.append_list("cells", |s| s.append_many(&self.cells));
+        });
+    }
+}
+
+/// A cell of a [`SchematicTable`], which is a text box spanning one or more
+/// rows and columns.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct SchematicTableCell {
+    pub text: String,
+    pub exclude_from_sim: Option<bool>,
+    pub position: Position,
+    pub size: Vec2D,
+    /// The space between the text and the left, top, right and bottom edges
+    /// of the cell.
+    pub margins: Option<[Length; 4]>,
+    /// The number of columns and rows the cell spans.
+    pub span: (u32, u32),
+    pub fill: ShapeFillMode,
+    pub effects: TextEffects,
+    pub uuid: Option<Uuid>,
+    pub extra: Extra,
+}
+
+impl FromSexpr for SchematicTableCell {
+    fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
+        parser.expect_symbol_matching("table_cell")?;
+
+        let mut parser = parser.keyed();
+
+        let text = parser.expect_string()?;
+        let exclude_from_sim = parser.maybe_bool_with_name("exclude_from_sim")?;
+        let position = parser
+            .maybe::<Position>()?
+            .ok_or_else(|| KiCadParseError::MissingField("at".to_string()))?;
+        let size = parser.expect_with_name::<Vec2D>("size")?;
+        let margins = parse_cell_margins(&mut parser)?;
+        let span = parse_cell_span(&mut parser)?;
+        let fill = parser
+            .maybe_list_with_name("fill", ShapeFillMode::parse)?
+            .unwrap_or_default();
+        let effects = parser
+            .maybe::<TextEffects>()?
+            .ok_or_else(|| KiCadParseError::MissingField("effects".to_string()))?;
+        let uuid = parser.maybe::<Uuid>()?;
+
+        let extra = parser.expect_end_with_extra()?;
+
+        Ok(Self {
+            text,
+            exclude_from_sim,
+            position,
+            size,
+            margins,
+            span,
+            fill,
+            effects,
+            uuid,
+            extra,
+        })
+    }
+}
+
+simple_maybe_from_sexpr!(SchematicTableCell, table_cell);
+
+impl ToSexpr for SchematicTableCell {
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("table_cell", &self.extra, |s| {
+            s.append_string(&self.text);
+            s.append_maybe_bool_with_name("exclude_from_sim", self.exclude_from_sim);
+            s.append(&self.position);
+            s.append_with_name("size", &self.size);
+            write_cell_margins(s, &self.margins);
+            write_cell_span(s, self.span);
+            s.append(&self.fill);
+            s.append(&self.effects);
+            s.append_maybe(&self.uuid);
+        });
+    }
+}
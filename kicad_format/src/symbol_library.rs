@@ -1,11 +1,13 @@
 //! Symbol library file format (`.kicad_sym` files)
 
-use kicad_sexpr::Sexpr;
-
 use crate::{
     common::symbol::{LibSymbol, LibraryId, SymbolProperty},
-    convert::{FromSexpr, Parser, SexprListExt, ToSexpr, VecToMaybeSexprVec},
-    simple_maybe_from_sexpr, KiCadParseError,
+    convert::{
+        with_file_version, writes, Extra, FromSexpr, ListBuilder, Parser, SexprListExt, ToSexpr,
+    },
+    simple_maybe_from_sexpr,
+    version::{FormatVersion, Syntax},
+    KiCadParseError,
 };
 
 /// Stores a collection of symbols which may or may not be derived from other
@@ -19,82 +21,52 @@ pub struct SymbolLibraryFile {
     pub version: u32,
     /// The `generator` token attribute defines the program used to write the file.
     pub generator: String,
-    /// Whether the generator was originally a string (newer format) or symbol (legacy format)
-    pub generator_is_string: bool,
     /// Optional generator version for newer KiCad formats
     pub generator_version: Option<String>,
     /// The symbol definitions go here. Symbol library files can have zero or more symbols.
     pub symbols: Vec<SymbolDefinition>,
+    pub extra: Extra,
 }
 
 impl FromSexpr for SymbolLibraryFile {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("kicad_symbol_lib")?;
 
-        let version = parser.expect_number_with_name("version")? as u32;
-        // Handle both old format (symbol) and new format (string) for generator
-        let (generator, generator_is_string) = {
-            let mut gen_parser = parser.expect_list_with_name("generator")?;
-            // Peek to see if it's a string or symbol
-            let next = gen_parser.peek_next();
-            if let Some(sexpr) = next {
-                match sexpr {
-                    kicad_sexpr::Sexpr::String(_) => {
-                        let s = gen_parser.expect_string()?;
-                        gen_parser.expect_end()?;
-                        (s, true)
-                    }
-                    kicad_sexpr::Sexpr::Symbol(_) => {
-                        let s = gen_parser.expect_symbol()?;
-                        gen_parser.expect_end()?;
-                        (s, false)
-                    }
-                    _ => {
-                        return Err(KiCadParseError::UnexpectedSexprType {
-                            expected: crate::SexprKind::String,
-                        })
-                    }
-                }
-            } else {
-                return Err(KiCadParseError::UnexpectedEndOfList);
-            }
-        };
+        let mut parser = parser.keyed();
+
+        let version = parser.expect_integer_with_name::<u32>("version")?;
+        parser.set_version(FormatVersion::schematic(version));
+        let generator = parser.expect_string_or_symbol_with_name("generator")?;
         let generator_version = parser.maybe_string_with_name("generator_version")?;
         let symbols = parser.expect_many::<SymbolDefinition>()?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             version,
             generator,
-            generator_is_string,
             generator_version,
             symbols,
+            extra,
         })
     }
 }
 
 impl ToSexpr for SymbolLibraryFile {
-    fn to_sexpr(&self) -> kicad_sexpr::Sexpr {
-        Sexpr::list_with_name(
-            "kicad_symbol_lib",
-            [
-                &[
-                    Some(Sexpr::number_with_name("version", self.version as f32)),
-                    // Preserve original format: string vs symbol
-                    Some(if self.generator_is_string {
-                        Sexpr::string_with_name("generator", &self.generator)
-                    } else {
-                        Sexpr::symbol_with_name("generator", &self.generator)
-                    }),
-                    self.generator_version
-                        .as_ref()
-                        .map(|v| Sexpr::string_with_name("generator_version", v)),
-                ][..],
-                &self.symbols.into_sexpr_vec(),
-            ]
-            .concat(),
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        with_file_version(FormatVersion::schematic(self.version), |version| {
+            s.append_list_with_extra("kicad_symbol_lib", &self.extra, |s| {
+                s.append_number_with_name("version", version);
+                s.append_generator(&self.generator);
+                if writes(Syntax::GeneratorVersion) {
+                    s.append_maybe_string_with_name(
+                        "generator_version",
+                        self.generator_version.as_ref(),
+                    );
+                }
+                s.append_many(&self.symbols);
+            });
+        });
     }
 }
 
@@ -134,10 +106,10 @@ impl FromSexpr for SymbolDefinition {
 simple_maybe_from_sexpr!(SymbolDefinition, symbol);
 
 impl ToSexpr for SymbolDefinition {
-    fn to_sexpr(&self) -> Sexpr {
+    fn write_sexpr(&self, s: &mut ListBuilder) {
         match self {
-            Self::RootSymbol(symbol) => symbol.to_sexpr(),
-            Self::DerivedSymbol(symbol) => symbol.to_sexpr(),
+            Self::RootSymbol(symbol) => s.append(symbol),
+            Self::DerivedSymbol(symbol) => s.append(symbol),
         }
     }
 }
@@ -151,38 +123,36 @@ pub struct DerivedLibSymbol {
     pub id: LibraryId,
     pub extends: String,
     pub properties: Vec<SymbolProperty>,
+    pub extra: Extra,
 }
 
 impl FromSexpr for DerivedLibSymbol {
     fn from_sexpr(mut parser: Parser) -> Result<Self, KiCadParseError> {
         parser.expect_symbol_matching("symbol")?;
 
+        let mut parser = parser.keyed();
+
         let id = parser.expect_string()?.parse::<LibraryId>()?;
         let extends = parser.expect_string_with_name("extends")?;
         let properties = parser.expect_many::<SymbolProperty>()?;
 
-        parser.expect_end()?;
+        let extra = parser.expect_end_with_extra()?;
 
         Ok(Self {
             id,
             extends,
             properties,
+            extra,
         })
     }
 }
 
 impl ToSexpr for DerivedLibSymbol {
-    fn to_sexpr(&self) -> Sexpr {
-        Sexpr::list_with_name(
-            "symbol",
-            [
-                &[
-                    Some(self.id.to_sexpr()),
-                    Some(Sexpr::string_with_name("extends", &self.extends)),
-                ][..],
-                &self.properties.into_sexpr_vec(),
-            ]
-            .concat(),
-        )
+    fn write_sexpr(&self, s: &mut ListBuilder) {
+        s.append_list_with_extra("symbol", &self.extra, |s| {
+            s.append(&self.id);
+            s.append_string_with_name("extends", &self.extends);
+            s.append_many(&self.properties);
+        });
     }
 }
@@ -0,0 +1,524 @@
+//! Checks that the references between the items of a file point at something.
+//!
+//! Parsing only checks that each item is well formed on its own. A track can
+//! still be on a net the board does not declare, or a schematic symbol can
+//! name a library symbol the schematic does not embed. KiCad drops or resets
+//! such references when it loads the file, so `validate` reports them
+//! instead:
+//!
+//! - [`PcbFile::validate`]
+//! - [`SchematicFile::validate`]
+//! - [`FootprintLibraryFile::validate`]
+//! - [`SymbolLibraryFile::validate`]
+
+use std::collections::{HashMap, HashSet};
+
+use thiserror::Error;
+
+use crate::{
+    common::{
+        footprint::{shape::FootprintShapeKind, FootprintGraphicsItem},
+        pad::Pad,
+        zone::Zone,
+        Group, LayerId, Uuid,
+    },
+    footprint_library::FootprintLibraryFile,
+    pcb::{
+        graphics::{shape::PcbShapeKind, PcbGraphicsItem},
+        PcbFile, Track,
+    },
+    schematic::SchematicFile,
+    symbol_library::{SymbolDefinition, SymbolLibraryFile},
+};
+
+/// A reference that does not point at anything, found by `validate`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ValidationIssue {
+    /// The item holding the reference, after the items it is nested in
+    pub path: Vec<ItemRef>,
+    /// What the reference is and why it is broken
+    pub kind: ValidationIssueKind,
+}
+
+impl std::error::Error for ValidationIssue {}
+
+impl std::fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, item) in self.path.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" > ")?;
+            }
+
+            write!(f, "{item}")?;
+        }
+
+        if !self.path.is_empty() {
+            f.write_str(": ")?;
+        }
+
+        write!(f, "{}", self.kind)
+    }
+}
+
+/// An item in the path of a [`ValidationIssue`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct ItemRef {
+    /// The symbol at the head of the item's list, like `segment` or `pad`
+    pub name: &'static str,
+    /// The name or number of the item, to tell items of the same kind apart
+    pub label: Option<String>,
+    /// The `tstamp` or `uuid` of the item, if it has one
+    pub id: Option<Uuid>,
+}
+
+impl ItemRef {
+    pub(crate) fn new(name: &'static str, label: Option<String>, id: Option<&Uuid>) -> Self {
+        Self {
+            name,
+            label,
+            id: id.cloned(),
+        }
+    }
+}
+
+impl std::fmt::Display for ItemRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name)?;
+
+        if let Some(label) = &self.label {
+            write!(f, " {label:?}")?;
+        }
+
+        if let Some(id) = &self.id {
+            write!(f, " {id}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The kinds of broken references found by `validate`.
+#[derive(Debug, Error, PartialEq, Clone)]
+pub enum ValidationIssueKind {
+    #[error("Net {code} is not declared in the board")]
+    UndeclaredNet { code: i32 },
+    #[error("Net {code} is named `{found}`, but the board declares it as `{declared}`")]
+    MismatchedNetName {
+        code: i32,
+        found: String,
+        declared: String,
+    },
+    #[error("Layer `{0}` is not declared in the board")]
+    UndeclaredLayer(LayerId),
+    #[error("Group member {0} does not refer to any item")]
+    UnknownGroupMember(Uuid),
+    #[error("Pad `{0}` of a net tie group does not exist in the footprint")]
+    UnknownNetTiePad(String),
+    #[error("Library symbol `{0}` is not embedded in `lib_symbols`")]
+    MissingLibSymbol(String),
+    #[error("Parent symbol `{0}` does not exist in the library")]
+    MissingParentSymbol(String),
+}
+
+/// Collects the issues of a file while walking through its items.
+///
+/// The layers and nets are only known for boards. Footprint library files do
+/// not declare any, so their layers and nets are not checked.
+#[derive(Default)]
+struct Validator<'a> {
+    path: Vec<ItemRef>,
+    issues: Vec<ValidationIssue>,
+    layers: Option<HashSet<LayerId>>,
+    nets: Option<HashMap<i32, &'a str>>,
+}
+
+impl Validator<'_> {
+    fn report(&mut self, kind: ValidationIssueKind) {
+        self.issues.push(ValidationIssue {
+            path: self.path.clone(),
+            kind,
+        });
+    }
+
+    /// Runs `f` with `item` added to the path of the issues it reports.
+    fn within(&mut self, item: ItemRef, f: impl FnOnce(&mut Self)) {
+        self.path.push(item);
+        f(self);
+        self.path.pop();
+    }
+
+    fn check_layer(&mut self, layer: LayerId) {
+        let Some(layers) = &self.layers else {
+            return;
+        };
+
+        // Wildcards like `*.Cu` stand for whichever layers the board has
+        if !layer.is_wildcard() && !layers.contains(&layer) {
+            self.report(ValidationIssueKind::UndeclaredLayer(layer));
+        }
+    }
+
+    fn check_layers<'l>(&mut self, layers: impl IntoIterator<Item = &'l LayerId>) {
+        for layer in layers {
+            self.check_layer(*layer);
+        }
+    }
+
+    fn check_net(&mut self, code: i32, name: Option<&str>) {
+        let Some(nets) = &self.nets else {
+            return;
+        };
+
+        match (nets.get(&code), name) {
+            (None, _) => self.report(ValidationIssueKind::UndeclaredNet { code }),
+            (Some(declared), Some(name)) if *declared != name => {
+                let declared = declared.to_string();
+
+                self.report(ValidationIssueKind::MismatchedNetName {
+                    code,
+                    found: name.to_string(),
+                    declared,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    fn check_groups(&mut self, groups: &[Group], ids: &HashSet<&Uuid>) {
+        for group in groups {
+            self.within(
+                ItemRef::new("group", Some(group.name.clone()), Some(&group.id)),
+                |v| {
+                    for member in &group.members {
+                        if !ids.contains(member) {
+                            v.report(ValidationIssueKind::UnknownGroupMember(member.clone()));
+                        }
+                    }
+                },
+            );
+        }
+    }
+
+    fn check_pad(&mut self, pad: &Pad) {
+        self.within(
+            ItemRef::new("pad", Some(pad.index.clone()), Some(&pad.tstamp)),
+            |v| {
+                v.check_layers(&pad.layers);
+                v.check_layers(pad.zone_layer_connections.iter().flatten());
+
+                if let Some(net) = &pad.net {
+                    v.check_net(net.code, Some(&net.name));
+                }
+            },
+        );
+    }
+
+    fn check_zone(&mut self, zone: &Zone) {
+        self.within(
+            ItemRef::new("zone", zone.name.clone(), Some(&zone.tstamp)),
+            |v| {
+                v.check_layers(&zone.layers);
+                v.check_layers(zone.fill_polygons.iter().map(|p| &p.layer));
+                v.check_layers(zone.fill_segments.iter().map(|s| &s.layer));
+                v.check_net(zone.net_number, Some(&zone.net_name));
+            },
+        );
+    }
+
+    /// Checks the items of a footprint, either in a board or in a library.
+    fn check_footprint(&mut self, footprint: Footprint) {
+        let mut ids = HashSet::new();
+
+        for item in footprint.graphics_items {
+            let (name, label, id, layer) = match item {
+                FootprintGraphicsItem::Image(image) => {
+                    ("image", None, image.unique_id.as_ref(), image.layer)
+                }
+                FootprintGraphicsItem::Text(text) => (
+                    "fp_text",
+                    Some(text.text.clone()),
+                    Some(&text.tstamp),
+                    Some(text.layer),
+                ),
+                FootprintGraphicsItem::TextBox(text_box) => (
+                    "fp_text_box",
+                    Some(text_box.text.clone()),
+                    Some(&text_box.tstamp),
+                    Some(text_box.layer),
+                ),
+                FootprintGraphicsItem::Shape(shape) => {
+                    let name = match shape.kind {
+                        FootprintShapeKind::Line(_) => "fp_line",
+                        FootprintShapeKind::Rectangle(_) => "fp_rect",
+                        FootprintShapeKind::Circle(_) => "fp_circle",
+                        FootprintShapeKind::Arc(_) => "fp_arc",
+                        FootprintShapeKind::Polygon(_) => "fp_poly",
+                        FootprintShapeKind::Curve(_) => "fp_curve",
+                    };
+
+                    (name, None, Some(&shape.tstamp), Some(shape.layer))
+                }
+            };
+
+            ids.extend(id);
+            self.within(ItemRef::new(name, label, id), |v| v.check_layers(&layer));
+        }
+
+        for pad in footprint.pads {
+            ids.insert(&pad.tstamp);
+            self.check_pad(pad);
+        }
+
+        for zone in footprint.zones {
+            ids.insert(&zone.tstamp);
+            self.check_zone(zone);
+        }
+
+        ids.extend(footprint.groups.iter().map(|g| &g.id));
+        self.check_groups(footprint.groups, &ids);
+
+        let pads = footprint
+            .pads
+            .iter()
+            .map(|p| p.index.as_str())
+            .collect::<HashSet<_>>();
+
+        // The groups are written as `"1, 2"`, so the numbers keep the spaces
+        // after the commas
+        for pad in footprint.net_tie_pad_groups.iter().flatten().flatten() {
+            let pad = pad.trim();
+
+            if !pads.contains(pad) {
+                self.report(ValidationIssueKind::UnknownNetTiePad(pad.to_string()));
+            }
+        }
+    }
+}
+
+/// The parts of [`FootprintInlined`](crate::common::footprint::FootprintInlined)
+/// and [`FootprintLibraryFile`] that hold references.
+struct Footprint<'a> {
+    graphics_items: &'a [FootprintGraphicsItem],
+    pads: &'a [Pad],
+    zones: &'a [Zone],
+    groups: &'a [Group],
+    net_tie_pad_groups: &'a Option<Vec<Vec<String>>>,
+}
+
+impl PcbFile {
+    /// Checks that the nets and layers used by the items of the board are
+    /// declared, and that the members of groups refer to items of the board.
+    ///
+    /// Returns every broken reference that was found, or an empty list if the
+    /// board is consistent.
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut v = Validator {
+            layers: Some(self.layers.iter().map(|l| l.layer).collect()),
+            nets: Some(
+                self.nets
+                    .iter()
+                    .map(|n| (n.code, n.name.as_str()))
+                    .collect(),
+            ),
+            ..Default::default()
+        };
+        let mut ids = HashSet::new();
+
+        for footprint in &self.footprints {
+            ids.insert(&footprint.tstamp);
+
+            let item = ItemRef::new(
+                "footprint",
+                Some(footprint.library_link.to_string()),
+                Some(&footprint.tstamp),
+            );
+
+            v.within(item, |v| {
+                v.check_layer(footprint.layer);
+                v.check_layers(footprint.private_layers.iter().flatten());
+                v.check_footprint(Footprint {
+                    graphics_items: &footprint.graphics_items,
+                    pads: &footprint.pads,
+                    zones: &footprint.keep_out_zones,
+                    groups: &footprint.groups,
+                    net_tie_pad_groups: &footprint.net_tie_pad_groups,
+                });
+            });
+        }
+
+        for item in &self.graphics_items {
+            let (name, label, tstamp, layer) = match item {
+                PcbGraphicsItem::Text(text) => {
+                    ("gr_text", Some(text.text.clone()), &text.tstamp, text.layer)
+                }
+                PcbGraphicsItem::TextBox(text_box) => (
+                    "gr_text_box",
+                    Some(text_box.text.clone()),
+                    &text_box.tstamp,
+                    text_box.layer,
+                ),
+                PcbGraphicsItem::Shape(shape) => {
+                    let name = match shape.kind {
+                        PcbShapeKind::Line(_) => "gr_line",
+                        PcbShapeKind::Rectangle(_) => "gr_rect",
+                        PcbShapeKind::Circle(_) => "gr_circle",
+                        PcbShapeKind::Arc(_) => "gr_arc",
+                        PcbShapeKind::Polygon(_) => "gr_poly",
+                        PcbShapeKind::Curve(_) => "bezier",
+                    };
+
+                    (name, None, &shape.tstamp, shape.layer)
+                }
+                PcbGraphicsItem::Dimension(dimension) => {
+                    ("dimension", None, &dimension.tstamp, dimension.layer)
+                }
+                // Tables have no identifier of their own, only their cells
+                PcbGraphicsItem::Table(table) => {
+                    for cell in &table.cells {
+                        ids.insert(&cell.tstamp);
+                        v.within(
+                            ItemRef::new("table_cell", Some(cell.text.clone()), Some(&cell.tstamp)),
+                            |v| v.check_layer(cell.layer),
+                        );
+                    }
+
+                    continue;
+                }
+            };
+
+            ids.insert(tstamp);
+            v.within(ItemRef::new(name, label, Some(tstamp)), |v| {
+                v.check_layer(layer)
+            });
+        }
+
+        for image in &self.images {
+            ids.extend(image.unique_id.as_ref());
+
+            v.within(ItemRef::new("image", None, image.unique_id.as_ref()), |v| {
+                v.check_layers(&image.layer)
+            });
+        }
+
+        for track in &self.tracks {
+            let (name, tstamp, net) = match track {
+                Track::Segment(segment) => ("segment", &segment.tstamp, segment.net),
+                Track::Via(via) => ("via", &via.tstamp, via.net),
+                Track::Arc(arc) => ("arc", &arc.tstamp, arc.net),
+            };
+
+            ids.insert(tstamp);
+            v.within(ItemRef::new(name, None, Some(tstamp)), |v| {
+                match track {
+                    Track::Segment(segment) => v.check_layer(segment.layer),
+                    Track::Via(via) => {
+                        v.check_layers([&via.layers.0, &via.layers.1]);
+                        v.check_layers(via.zone_layer_connections.iter().flatten());
+                    }
+                    Track::Arc(arc) => v.check_layer(arc.layer),
+                }
+
+                v.check_net(net, None);
+            });
+        }
+
+        for zone in &self.zones {
+            ids.insert(&zone.tstamp);
+            v.check_zone(zone);
+        }
+
+        ids.extend(self.groups.iter().map(|g| &g.id));
+        v.check_groups(&self.groups, &ids);
+
+        v.issues
+    }
+}
+
+impl FootprintLibraryFile {
+    /// Checks that the members of groups refer to items of the footprint, and
+    /// that the pads of net tie groups exist.
+    ///
+    /// A footprint does not declare its own layers or nets, so those are only
+    /// checked once it is placed on a board, by [`PcbFile::validate`].
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut v = Validator::default();
+
+        v.check_footprint(Footprint {
+            graphics_items: &self.graphics_items,
+            pads: &self.pads,
+            zones: &self.keep_out_zones,
+            groups: &self.groups,
+            net_tie_pad_groups: &self.net_tie_pad_groups,
+        });
+
+        v.issues
+    }
+}
+
+impl SchematicFile {
+    /// Checks that every symbol of the schematic has its library symbol
+    /// embedded in `lib_symbols`.
+    ///
+    /// The library symbol is looked up by the `lib_name` of the symbol if it
+    /// has one, like KiCad does, and by its `lib_id` otherwise.
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut v = Validator::default();
+
+        let lib_symbols = self
+            .lib_symbols
+            .iter()
+            .map(|s| s.id.to_string())
+            .collect::<HashSet<_>>();
+
+        for symbol in &self.symbols {
+            let lib_id = symbol.lib_id.to_string();
+            let name = symbol.lib_name.as_ref().unwrap_or(&lib_id);
+
+            if !lib_symbols.contains(name) {
+                let name = name.clone();
+
+                v.within(
+                    ItemRef::new("symbol", Some(lib_id), Some(&symbol.uuid)),
+                    |v| v.report(ValidationIssueKind::MissingLibSymbol(name)),
+                );
+            }
+        }
+
+        v.issues
+    }
+}
+
+impl SymbolLibraryFile {
+    /// Checks that every derived symbol extends a root symbol of the library.
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut v = Validator::default();
+
+        let roots = self
+            .symbols
+            .iter()
+            .filter_map(|s| match s {
+                SymbolDefinition::RootSymbol(symbol) => Some(symbol.id.entry_name.as_str()),
+                SymbolDefinition::DerivedSymbol(_) => None,
+            })
+            .collect::<HashSet<_>>();
+
+        for symbol in &self.symbols {
+            let SymbolDefinition::DerivedSymbol(symbol) = symbol else {
+                continue;
+            };
+
+            if !roots.contains(symbol.extends.as_str()) {
+                v.within(
+                    ItemRef::new("symbol", Some(symbol.id.to_string()), None),
+                    |v| {
+                        v.report(ValidationIssueKind::MissingParentSymbol(
+                            symbol.extends.clone(),
+                        ))
+                    },
+                );
+            }
+        }
+
+        v.issues
+    }
+}
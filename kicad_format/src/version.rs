@@ -0,0 +1,165 @@
+//! Versions of the file formats, and the syntax that changed between them.
+//!
+//! Every KiCad file starts with a `(version YYYYMMDD)` token. Board and
+//! footprint files share one sequence of versions and schematic and symbol
+//! library files another, so a version only means something together with
+//! its [`FileFamily`].
+//!
+//! While parsing, the version of the file is available from
+//! [`Parser::version`](crate::convert::Parser::version), and each type asks
+//! [`Parser::reads`](crate::convert::Parser::reads) which syntax to expect.
+//! While serializing, the version to write is set with
+//! [`with_write_version`](crate::convert::with_write_version), and each type
+//! asks [`writes`](crate::convert::writes) which syntax to use.
+//!
+//! The tokens that newer versions added, such as the padstacks and embedded
+//! files of KiCad 9, have a [`Syntax`] too. Reading one from an older file is
+//! an error, or a diagnostic in lenient mode, and they are left out when
+//! writing an older version. The `downgrade_to` functions of
+//! [`migrate`](crate::migrate) refuse to drop them silently.
+
+/// The group of file types that share a sequence of versions.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FileFamily {
+    /// Board (`.kicad_pcb`) and footprint library (`.kicad_mod`) files
+    Board,
+    /// Schematic (`.kicad_sch`) and symbol library (`.kicad_sym`) files
+    Schematic,
+}
+
+/// The version of a file, as written in its `version` token.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct FormatVersion {
+    pub family: FileFamily,
+    pub version: u32,
+}
+
+impl FormatVersion {
+    pub fn board(version: u32) -> Self {
+        Self {
+            family: FileFamily::Board,
+            version,
+        }
+    }
+
+    pub fn schematic(version: u32) -> Self {
+        Self {
+            family: FileFamily::Schematic,
+            version,
+        }
+    }
+
+    /// Whether files of this version use the given syntax.
+    pub fn supports(self, syntax: Syntax) -> bool {
+        self.version >= syntax.since(self.family)
+    }
+}
+
+/// Syntax that KiCad changed between versions of the file formats.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Syntax {
+    /// The `generator` is a quoted string rather than a bare symbol.
+    GeneratorString,
+    /// A `generator_version` token follows the `generator`.
+    GeneratorVersion,
+    /// Flags such as `hide`, `bold` and `italic` are written as `(hide yes)`
+    /// rather than as a bare `hide` symbol.
+    YesNoFlags,
+    /// Identifiers are written as quoted strings rather than bare symbols.
+    QuotedUuids,
+    /// Board items are identified by a `uuid` token rather than a `tstamp`.
+    Uuid,
+    /// Locked items are marked with `(locked yes)` rather than a bare
+    /// `locked` symbol.
+    LockedFlags,
+    /// Schematic symbols have a `dnp` (do not populate) flag.
+    Dnp,
+    /// The `suppress_zeroes` and `keep_text_aligned` flags of dimensions are
+    /// written as `(name yes)` rather than as bare symbols.
+    DimensionFlags,
+    /// Symbol properties are no longer numbered with an `(id N)`.
+    UnnumberedProperties,
+    /// Tables of text cells.
+    Tables,
+    /// Vias and the board setup say which sides are tented in a `tenting`
+    /// list.
+    Tenting,
+    /// Files and fonts can be embedded with `embedded_files` and
+    /// `embedded_fonts`.
+    EmbeddedFiles,
+    /// Footprints list the component classes of their symbol.
+    ComponentClasses,
+    /// Pads and vias can have a `padstack` with different shapes on
+    /// different layers.
+    Padstacks,
+    /// Pads and pins can be connected by jumpers.
+    Jumpers,
+}
+
+impl Syntax {
+    /// The first version of each family of files that uses the syntax.
+    ///
+    /// The dates are the ones KiCad gives the changes in its own list of file
+    /// format versions.
+    pub fn since(self, family: FileFamily) -> u32 {
+        match (self, family) {
+            // "V8 file format normalization"
+            (
+                Self::GeneratorString
+                | Self::GeneratorVersion
+                | Self::YesNoFlags
+                | Self::QuotedUuids,
+                FileFamily::Board,
+            ) => 20231014,
+            // "generator_version; V8 cleanups"
+            (
+                Self::GeneratorString
+                | Self::GeneratorVersion
+                | Self::YesNoFlags
+                | Self::QuotedUuids,
+                FileFamily::Schematic,
+            ) => 20231120,
+            // "Use 'uuid' rather than 'tstamp'"
+            (Self::Uuid, FileFamily::Board) => 20231231,
+            // Schematic items have always used `uuid`
+            (Self::Uuid, FileFamily::Schematic) => 0,
+            // "Rationalize locked flag parsing"
+            (Self::LockedFlags, FileFamily::Board) => 20240225,
+            // Schematic items are locked like other flags
+            (Self::LockedFlags, FileFamily::Schematic) => 20231120,
+            // "Add support for DNP"
+            (Self::Dnp, FileFamily::Schematic) => 20220914,
+            // Boards have no schematic symbols
+            (Self::Dnp, FileFamily::Board) => 0,
+            // Dimensions kept their bare flags until KiCad 9
+            (Self::DimensionFlags, FileFamily::Board) => 20241229,
+            // Schematics have no dimensions
+            (Self::DimensionFlags, FileFamily::Schematic) => 0,
+            // "Don't save property ID"
+            (Self::UnnumberedProperties, FileFamily::Schematic) => 20220929,
+            // Board properties were never numbered
+            (Self::UnnumberedProperties, FileFamily::Board) => 0,
+            // "Tables"
+            (Self::Tables, FileFamily::Board) => 20240202,
+            (Self::Tables, FileFamily::Schematic) => 20240101,
+            // "Add 'tenting' keyword"
+            (Self::Tenting, FileFamily::Board) => 20240609,
+            // "Embedded Files"
+            (Self::EmbeddedFiles, FileFamily::Board) => 20240706,
+            (Self::EmbeddedFiles, FileFamily::Schematic) => 20240620,
+            // "Component classes"
+            (Self::ComponentClasses, FileFamily::Board) => 20240928,
+            // "Complex padstacks"
+            (Self::Padstacks, FileFamily::Board) => 20240929,
+            // Jumpers came with KiCad 9
+            (Self::Jumpers, FileFamily::Board) => 20241229,
+            (Self::Jumpers, FileFamily::Schematic) => 20241209,
+            // Schematics have no vias, padstacks or footprints
+            (Self::Tenting | Self::ComponentClasses | Self::Padstacks, FileFamily::Schematic) => 0,
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use kicad_format::{
+    angle::Angle,
+    common::{LayerId, Uuid, Vec2D},
+    convert::{with_write_version, Extra, FieldOrder, FromSexpr, Parser, ToSexpr},
+    pcb::setup::{BoardStackup, OutlineMode},
+    version::FormatVersion,
+    KiCadParseError,
+};
+use kicad_sexpr::Sexpr;
+
+#[derive(Debug, PartialEq, FromSexpr, ToSexpr)]
+#[sexpr(name = "property")]
+struct Property {
+    #[sexpr(positional)]
+    key: String,
+    #[sexpr(positional)]
+    value: String,
+}
+
+#[derive(Debug, PartialEq, FromSexpr, ToSexpr)]
+#[sexpr(name = "pad")]
+struct Pad {
+    #[sexpr(positional)]
+    number: String,
+    #[sexpr(positional, symbol)]
+    layer: LayerId,
+    #[sexpr(positional)]
+    rotation: Option<f64>,
+    #[sexpr(flag)]
+    locked: bool,
+    #[sexpr(with_name)]
+    at: Vec2D,
+    #[sexpr(with_name)]
+    offset: Option<Vec2D>,
+    #[sexpr(positional, symbol)]
+    tags: Vec<String>,
+    net: Option<u32>,
+    #[sexpr(symbol)]
+    shape: String,
+    #[sexpr(name = "mode", try_from = "u8")]
+    outline: OutlineMode,
+    plated: bool,
+    #[sexpr(true_false)]
+    mirror: bool,
+    #[sexpr(true_false)]
+    visible: Option<bool>,
+    property: Vec<Property>,
+    stackup: Option<BoardStackup>,
+}
+
+#[derive(Debug, PartialEq, FromSexpr, ToSexpr)]
+#[sexpr(name = "net_class")]
+struct NetClass {
+    #[sexpr(positional)]
+    name: String,
+    clearance: f64,
+    #[sexpr(extra)]
+    extra: Extra,
+}
+
+#[derive(Debug, PartialEq, FromSexpr, ToSexpr)]
+#[sexpr(name = "via")]
+struct Via {
+    #[sexpr(locked)]
+    locked: bool,
+    #[sexpr(versioned_flag)]
+    free: bool,
+    #[sexpr(with_name)]
+    at: Vec2D,
+    angle: Option<Angle>,
+    #[sexpr(string)]
+    layers: Vec<LayerId>,
+    #[sexpr(list_flag)]
+    remove_unused_layers: Option<bool>,
+    #[sexpr(name = "properties")]
+    properties: Option<Vec<Property>>,
+    net_class: NetClass,
+    #[sexpr(id)]
+    uuid: Uuid,
+    #[sexpr(field_order)]
+    field_order: FieldOrder,
+}
+
+#[derive(Debug, PartialEq, FromSexpr, ToSexpr)]
+#[sexpr(name = "symbol")]
+struct Symbol {
+    #[sexpr(positional)]
+    name: String,
+    #[sexpr(since = "EmbeddedFiles")]
+    embedded_fonts: Option<bool>,
+}
+
+fn parse<T: FromSexpr>(input: &str) -> Result<T, KiCadParseError> {
+    let sexpr = kicad_sexpr::from_str(input).unwrap();
+
+    T::from_sexpr(Parser::new(sexpr.take_list().unwrap()))
+}
+
+#[test]
+fn test_derive_round_trip() {
+    let input = r#"(pad "1" F.Cu 90 locked (at 1 2) a b (net 3) (shape rect) (mode 2) (plated yes) (mirror false) (visible true) (property "x" "1") (property "y" "2"))"#;
+
+    let pad = parse::<Pad>(input).unwrap();
+
+    assert_eq!(
+        pad,
+        Pad {
+            number: "1".into(),
+            layer: LayerId::FCu,
+            rotation: Some(90.0),
+            locked: true,
+            at: Vec2D::from_mm(1.0, 2.0),
+            offset: None,
+            tags: vec!["a".into(), "b".into()],
+            net: Some(3),
+            shape: "rect".into(),
+            outline: OutlineMode::Sketch,
+            plated: true,
+            mirror: false,
+            visible: Some(true),
+            property: vec![
+                Property {
+                    key: "x".into(),
+                    value: "1".into()
+                },
+                Property {
+                    key: "y".into(),
+                    value: "2".into()
+                },
+            ],
+            stackup: None,
+        }
+    );
+
+    // `true_false` values are written as `yes` and `no` since KiCad 8
+    let kicad_7 = FormatVersion::board(20221018);
+    assert_eq!(
+        with_write_version(kicad_7, || pad.to_sexpr()),
+        kicad_sexpr::from_str(input).unwrap()
+    );
+
+    let output = pad.to_sexpr().to_string();
+    assert!(output.contains("(mirror no)"));
+    assert!(output.contains("(visible yes)"));
+}
+
+#[test]
+fn test_derive_missing_values() {
+    let input =
+        r#"(pad "2" B.Cu (at 0 0) (offset 1 1) (shape circle) (mode 1) (plated no) (mirror true))"#;
+
+    let pad = parse::<Pad>(input).unwrap();
+
+    assert_eq!(pad.rotation, None);
+    assert!(!pad.locked);
+    assert_eq!(pad.offset, Some(Vec2D::from_mm(1.0, 1.0)));
+    assert!(pad.tags.is_empty());
+    assert_eq!(pad.net, None);
+    assert_eq!(pad.visible, None);
+    assert!(pad.property.is_empty());
+
+    let output: Sexpr = with_write_version(FormatVersion::board(20221018), || pad.to_sexpr());
+    assert_eq!(output, kicad_sexpr::from_str(input).unwrap());
+}
+
+#[test]
+fn test_derive_errors() {
+    assert_eq!(
+        parse::<Property>(r#"(properties "x" "1")"#),
+        Err(KiCadParseError::NonMatchingSymbol {
+            found: "properties".into(),
+            expected: "property".into(),
+        })
+    );
+
+    assert!(matches!(
+        parse::<Property>(r#"(property "x" "1" "2")"#),
+        Err(KiCadParseError::ExpectedEndOfList { .. })
+    ));
+
+    assert!(matches!(
+        parse::<Pad>(
+            r#"(pad "1" F.Cu (at 1 2) (shape rect) (mode 7) (plated yes) (mirror false))"#
+        ),
+        Err(KiCadParseError::InvalidEnumValue { .. })
+    ));
+}
+
+#[test]
+fn test_derive_extra() {
+    let input = r#"(net_class "Power" (via_dia 0.8) (clearance 0.2) (diff_pair_gap 0.25))"#;
+
+    let net_class = parse::<NetClass>(input).unwrap();
+
+    assert_eq!(net_class.name, "Power");
+    assert_eq!(net_class.clearance, 0.2);
+    assert_eq!(net_class.extra.len(), 2);
+    assert_eq!(net_class.to_sexpr(), kicad_sexpr::from_str(input).unwrap());
+}
+
+#[test]
+fn test_derive_board_item() {
+    let input = r#"(via (locked yes) (free yes) (at 1 2) (angle 45) (layers "F.Cu" "B.Cu") (remove_unused_layers no) (properties (property "x" "1")) (net_class "Power" (clearance 0.2)) (uuid "a5e0b7c4-3f27-4b5b-9a36-0b6f1e0e3d1a"))"#;
+
+    let via = parse::<Via>(input).unwrap();
+
+    assert!(via.locked);
+    assert!(via.free);
+    assert_eq!(via.angle, Some(Angle::from_degrees(45.0)));
+    assert_eq!(via.layers, [LayerId::FCu, LayerId::BCu]);
+    assert_eq!(via.remove_unused_layers, Some(false));
+    assert_eq!(via.properties.as_ref().map(Vec::len), Some(1));
+    assert_eq!(via.net_class.name, "Power");
+    assert_eq!(via.to_sexpr(), kicad_sexpr::from_str(input).unwrap());
+
+    // KiCad 7 writes flags as bare symbols and the id as a `tstamp`
+    let input = r#"(via locked free (at 1 2) (layers "F.Cu" "B.Cu") (remove_unused_layers) (net_class "Power" (clearance 0.2)) (tstamp a5e0b7c4-3f27-4b5b-9a36-0b6f1e0e3d1a))"#;
+
+    let via = parse::<Via>(input).unwrap();
+
+    assert!(via.locked);
+    assert!(via.free);
+    assert_eq!(via.remove_unused_layers, Some(true));
+    assert_eq!(
+        with_write_version(FormatVersion::board(20221018), || via.to_sexpr()),
+        kicad_sexpr::from_str(input).unwrap()
+    );
+}
+
+#[test]
+fn test_derive_field_order() {
+    let input = r#"(via (uuid "a5e0b7c4-3f27-4b5b-9a36-0b6f1e0e3d1a") (net_class "Power" (clearance 0.2)) (layers "F.Cu") (at 1 2))"#;
+
+    let mut via = parse::<Via>(input).unwrap();
+
+    assert_eq!(via.at, Vec2D::from_mm(1.0, 2.0));
+    assert_eq!(via.to_sexpr(), kicad_sexpr::from_str(input).unwrap());
+
+    // Without a recorded order, the fields are written in declaration order
+    via.field_order = FieldOrder::default();
+    assert_eq!(
+        via.to_sexpr(),
+        kicad_sexpr::from_str(r#"(via (at 1 2) (layers "F.Cu") (net_class "Power" (clearance 0.2)) (uuid "a5e0b7c4-3f27-4b5b-9a36-0b6f1e0e3d1a"))"#).unwrap()
+    );
+
+    // Keyed structs report the lists they are missing by name
+    assert_eq!(
+        parse::<Via>(
+            r#"(via (at 1 2) (layers "F.Cu") (uuid "a5e0b7c4-3f27-4b5b-9a36-0b6f1e0e3d1a"))"#
+        ),
+        Err(KiCadParseError::MissingField("net_class".into()))
+    );
+}
+
+#[test]
+fn test_derive_since() {
+    let input = kicad_sexpr::from_str(r#"(symbol "R" (embedded_fonts no))"#).unwrap();
+    let parse_in = |version| {
+        let mut parser = Parser::new(input.as_list().unwrap().clone());
+        parser.set_version(version);
+        Symbol::from_sexpr(parser)
+    };
+
+    let symbol = parse_in(FormatVersion::schematic(20241209)).unwrap();
+    assert_eq!(symbol.embedded_fonts, Some(false));
+    assert_eq!(symbol.to_sexpr(), input);
+
+    // KiCad 8 has no embedded fonts to read or write
+    assert_eq!(
+        parse_in(FormatVersion::schematic(20231120)),
+        Err(KiCadParseError::UnsupportedInVersion {
+            token: "embedded_fonts".into(),
+            version: 20231120
+        })
+    );
+    assert_eq!(
+        with_write_version(FormatVersion::schematic(20231120), || symbol.to_sexpr()),
+        kicad_sexpr::from_str(r#"(symbol "R")"#).unwrap()
+    );
+}
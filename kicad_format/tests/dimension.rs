@@ -0,0 +1,91 @@
+use kicad_format::{
+    parse_pcb_file,
+    pcb::{
+        graphics::{
+            dimension::{DimensionKind, DimensionOrientation, PcbDimension},
+            PcbGraphicsItem,
+        },
+        PcbFile,
+    },
+    serialize_pcb_file,
+};
+
+fn dimensions(pcb: &PcbFile) -> Vec<&PcbDimension> {
+    pcb.graphics_items
+        .iter()
+        .filter_map(|item| match item {
+            PcbGraphicsItem::Dimension(dimension) => Some(dimension),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_dimensions() {
+    let input = std::fs::read_to_string("tests/pcb/Dimensions v8.kicad_pcb").unwrap();
+    let pcb = parse_pcb_file(&input).unwrap();
+
+    let dimensions = dimensions(&pcb);
+    assert_eq!(
+        dimensions.iter().map(|d| d.kind).collect::<Vec<_>>(),
+        [
+            DimensionKind::Aligned,
+            DimensionKind::Orthogonal,
+            DimensionKind::Leader,
+            DimensionKind::Center,
+            DimensionKind::Radial,
+        ]
+    );
+    assert!(dimensions.iter().all(|d| d.extra.is_empty()));
+
+    let orthogonal = dimensions[1];
+    assert!(orthogonal.locked);
+    assert_eq!(orthogonal.orientation, Some(DimensionOrientation::Vertical));
+    let format = orthogonal.format.as_ref().unwrap();
+    assert_eq!(format.override_value.as_deref(), Some("20 mm"));
+    assert!(format.suppress_zeros);
+
+    assert!(dimensions[3].text.is_none());
+    assert!(dimensions[3].format.is_none());
+
+    // KiCad 8 writes the dimension flags as bare symbols, KiCad 9 does not
+    let output = serialize_pcb_file(pcb.clone());
+    assert!(output.contains(" keep_text_aligned"));
+    assert!(output.contains(" suppress_zeroes"));
+
+    let mut kicad_9 = pcb;
+    kicad_9.version = 20241229;
+    let output = serialize_pcb_file(kicad_9);
+    assert!(output.contains("(keep_text_aligned yes)"));
+    assert!(output.contains("(suppress_zeroes yes)"));
+}
+
+#[test]
+fn test_dimension_measured_value() {
+    let input = std::fs::read_to_string("tests/pcb/Dimensions v8.kicad_pcb").unwrap();
+    let pcb = parse_pcb_file(&input).unwrap();
+    let mut dimensions = dimensions(&pcb).into_iter().cloned().collect::<Vec<_>>();
+
+    // Automatic units are shown in millimetres
+    assert_eq!(
+        dimensions[0].measured_value().as_deref(),
+        Some("30.0000 mm")
+    );
+    // A units-scaled precision of 8 gives one decimal place for mils
+    assert_eq!(
+        dimensions[1].measured_value().as_deref(),
+        Some("787.4 mils")
+    );
+    assert_eq!(dimensions[2].measured_value(), None);
+    assert_eq!(dimensions[3].measured_value(), None);
+    assert_eq!(dimensions[4].measured_value().as_deref(), Some("5.00 (mm)"));
+
+    let aligned = &mut dimensions[0];
+    aligned.format.as_mut().unwrap().suppress_zeros = true;
+    assert_eq!(aligned.measured_value().as_deref(), Some("30 mm"));
+
+    let orthogonal = &mut dimensions[1];
+    orthogonal.orientation = Some(DimensionOrientation::Horizontal);
+    orthogonal.format.as_mut().unwrap().precision = 2;
+    assert_eq!(orthogonal.measured_value().as_deref(), Some("78.74 mils"));
+}
@@ -0,0 +1,192 @@
+use kicad_format::{
+    parse_pcb_file, parse_pcb_file_lenient, parse_schematic_file, parse_schematic_file_lenient,
+    serialize_pcb_file, KiCadParseError,
+};
+use kicad_sexpr::Sexpr;
+
+#[test]
+fn test_error_path() {
+    let input = std::fs::read_to_string("tests/pcb/TMC2209 Dev Board v1.kicad_pcb").unwrap();
+    let input = input.replacen("(drill 0.68)", "(drill 0.68 0.5 0.3)", 1);
+
+    let error = parse_pcb_file(&input).unwrap_err();
+    let context = error.context().expect("error should have a context");
+
+    let path = context
+        .path
+        .iter()
+        .map(|segment| segment.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(path, ["kicad_pcb", "footprint", "pad", "drill"]);
+    assert_eq!(context.path[2].label.as_deref(), Some("1"));
+
+    let span = context.span.expect("error should have a span");
+    assert_eq!(&input[span.start..span.end], "(drill 0.68 0.5 0.3)");
+    assert_eq!(context.location.unwrap().line, 175);
+
+    assert!(matches!(
+        error.inner(),
+        KiCadParseError::ExpectedEndOfList {
+            found: Sexpr::Number(_)
+        }
+    ));
+
+    assert_eq!(
+        error.to_string(),
+        r#"kicad_pcb > footprint "Ray Library 1:ADOM_MEDIUM_PIN" (tstamp 0a1603b7-3812-4c8d-9dd8-c9c1bc050a0e) > pad "1" (tstamp 420a8f48-3bed-4daa-91f5-2bec3bc358fa) > drill (line 175, column 57): Expected end of list. Found: 0.5"#
+    );
+}
+
+#[test]
+fn test_lenient_parsing() {
+    let input = std::fs::read_to_string("tests/schematic/ampli_ht.kicad_sch").unwrap();
+    let expected = parse_schematic_file(&input).unwrap();
+
+    let input = input.replacen("(type solid)", "(type wavy)", 1).replacen(
+        r#"(label "S_OUT+" (at 180.34 162.56 0)"#,
+        r#"(label "S_OUT+" (at 180.34)"#,
+        1,
+    );
+
+    assert!(parse_schematic_file(&input).is_err());
+
+    let (schematic, diagnostics) = parse_schematic_file_lenient(&input).unwrap();
+
+    // The broken wire and label are dropped, and everything else is kept
+    assert_eq!(schematic.lines.len(), expected.lines.len() - 1);
+    assert_eq!(
+        schematic.local_labels.len(),
+        expected.local_labels.len() - 1
+    );
+    assert_eq!(schematic.symbols, expected.symbols);
+
+    let paths = diagnostics
+        .iter()
+        .map(|d| {
+            d.context
+                .path
+                .iter()
+                .map(|segment| segment.name.as_str())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        paths,
+        [
+            vec!["kicad_sch", "wire", "stroke"],
+            vec!["kicad_sch", "label", "at"]
+        ]
+    );
+
+    assert!(matches!(
+        diagnostics[0].error,
+        KiCadParseError::InvalidEnumValue { .. }
+    ));
+    assert_eq!(diagnostics[1].context.location.unwrap().line, 983);
+
+    // Files without problems have no diagnostics
+    let input = std::fs::read_to_string("tests/schematic/ampli_ht.kicad_sch").unwrap();
+    let (schematic, diagnostics) = parse_schematic_file_lenient(&input).unwrap();
+
+    assert_eq!(schematic, expected);
+    assert!(diagnostics.is_empty());
+}
+
+#[test]
+fn test_error_path_within_lib_symbols() {
+    let input = std::fs::read_to_string("tests/schematic/sallen_key.kicad_sch").unwrap();
+    let input = input.replacen(r#"(name "1" (effects"#, r#"(name 1 (effects"#, 1);
+
+    let error = parse_schematic_file(&input).unwrap_err();
+    let context = error.context().expect("error should have a context");
+
+    let path = context
+        .path
+        .iter()
+        .map(|segment| segment.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        path,
+        [
+            "kicad_sch",
+            "lib_symbols",
+            "symbol",
+            "symbol",
+            "pin",
+            "name"
+        ]
+    );
+    assert_eq!(context.path[2].label.as_deref(), Some("C_1"));
+    assert_eq!(context.location.unwrap().line, 44);
+
+    // Skipped pins are reported at the same place
+    let (_, diagnostics) = parse_schematic_file_lenient(&input).unwrap();
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].context.path, context.path);
+    assert_eq!(diagnostics[0].context.location.unwrap().line, 44);
+}
+
+#[test]
+fn test_error_path_within_board_layers() {
+    let input = std::fs::read_to_string("tests/pcb/Divider v8.kicad_pcb").unwrap();
+    let input = input.replacen(r#"(0 "F.Cu" signal)"#, r#"(0 "F.Cu" wavy)"#, 1);
+
+    let error = parse_pcb_file(&input).unwrap_err();
+    let context = error.context().expect("error should have a context");
+
+    let path = context
+        .path
+        .iter()
+        .map(|segment| segment.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(path, ["kicad_pcb", "layers", ""]);
+
+    let span = context.span.expect("error should have a span");
+    assert_eq!(&input[span.start..span.end], r#"(0 "F.Cu" wavy)"#);
+    assert_eq!(context.location.unwrap().line, 11);
+}
+
+#[test]
+fn test_newer_tokens_in_older_files() {
+    let input = std::fs::read_to_string("tests/pcb/Divider v9.kicad_pcb").unwrap();
+    let input = input.replacen("(version 20241229)", "(version 20240108)", 1);
+
+    let error = parse_pcb_file(&input).unwrap_err();
+    assert!(matches!(
+        error.inner(),
+        KiCadParseError::UnsupportedInVersion {
+            version: 20240108,
+            ..
+        }
+    ));
+
+    // Lenient parsing keeps the tokens, but they are not written back in the
+    // version of the file
+    let (pcb, diagnostics) = parse_pcb_file_lenient(&input).unwrap();
+    let mut tokens = diagnostics
+        .iter()
+        .map(|d| match &d.error {
+            KiCadParseError::UnsupportedInVersion { token, .. } => token.as_str(),
+            error => panic!("unexpected diagnostic: {error}"),
+        })
+        .collect::<Vec<_>>();
+    tokens.sort();
+    tokens.dedup();
+    assert_eq!(
+        tokens,
+        [
+            "component_classes",
+            "embedded_files",
+            "embedded_fonts",
+            "padstack",
+            "table",
+            "tenting"
+        ]
+    );
+    assert!(pcb.embedded_files.is_some());
+
+    let output = serialize_pcb_file(pcb);
+    assert!(!output.contains("(padstack"));
+    assert!(!output.contains("(embedded_files"));
+}
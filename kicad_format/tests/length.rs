@@ -0,0 +1,68 @@
+use kicad_format::{
+    common::Vec2D,
+    length::{Length, LengthUnit},
+    parse_pcb_file,
+    pcb::Track,
+    write_pcb_file,
+};
+
+#[test]
+fn test_length_units() {
+    assert_eq!(Length::from_mils(1.0), Length::from_nm(25_400));
+    assert_eq!(Length::from_inches(1.0), Length::from_mm(25.4));
+    assert_eq!(
+        Length::from_value(100.0, LengthUnit::Mils),
+        Length::from_mm(2.54)
+    );
+    assert_eq!(
+        Length::from_mm(0.1) + Length::from_mm(0.2),
+        Length::from_mm(0.3)
+    );
+
+    assert_eq!(Length::from_mm(-0.035).to_string(), "-0.035 mm");
+    assert_eq!(
+        Length::from_nm(-1).display_in(LengthUnit::Mils).to_string(),
+        "0 mil"
+    );
+    assert_eq!(
+        format!(
+            "{:.2}",
+            Length::from_mils(12.0).display_in(LengthUnit::Inches)
+        ),
+        "0.01 in"
+    );
+}
+
+#[test]
+fn test_moved_items_return_to_their_position() {
+    let input = std::fs::read_to_string("tests/pcb/TMC2209 Dev Board v1.kicad_pcb").unwrap();
+    let original = parse_pcb_file(&input).unwrap();
+    let mut pcb = original.clone();
+
+    let step = Vec2D::from_mm(0.1, -0.035);
+    let move_by = |pcb: &mut kicad_format::pcb::PcbFile, offset: Vec2D| {
+        for track in &mut pcb.tracks {
+            if let Track::Segment(segment) = track {
+                segment.start = segment.start + offset;
+                segment.end = segment.end + offset;
+            }
+        }
+    };
+
+    for _ in 0..10 {
+        move_by(&mut pcb, step);
+    }
+    for _ in 0..10 {
+        move_by(&mut pcb, Vec2D::default() - step);
+    }
+
+    assert_eq!(pcb, original);
+
+    // Including in the written file
+    let write = |pcb| {
+        let mut output = Vec::new();
+        write_pcb_file(pcb, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    };
+    assert_eq!(write(&pcb), write(&original));
+}
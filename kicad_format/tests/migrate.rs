@@ -0,0 +1,180 @@
+use kicad_format::{
+    common::footprint::{text::FootprintTextKind, FootprintGraphicsItem},
+    migrate::MigrationError,
+    parse_footprint_library_file, parse_pcb_file, parse_schematic_file, serialize_pcb_file,
+    serialize_schematic_file,
+};
+
+fn read(path: &str) -> String {
+    std::fs::read_to_string(path).unwrap()
+}
+
+fn has_reference_or_value_text(graphics_items: &[FootprintGraphicsItem]) -> bool {
+    graphics_items.iter().any(|item| {
+        matches!(item, FootprintGraphicsItem::Text(text) if text.kind != FootprintTextKind::User)
+    })
+}
+
+#[test]
+fn test_upgrade_board_to_kicad_8() {
+    let mut pcb = parse_pcb_file(&read("tests/pcb/TMC2209 Dev Board v1.kicad_pcb")).unwrap();
+
+    pcb.upgrade_to(20240108).unwrap();
+
+    assert_eq!(pcb.version, 20240108);
+    for footprint in &pcb.footprints {
+        assert!(!has_reference_or_value_text(&footprint.graphics_items));
+        assert_eq!(footprint.properties[0].key, "Reference");
+        assert_eq!(footprint.properties[1].key, "Value");
+        assert!(footprint.properties.iter().all(|p| p.position.is_some()));
+        assert!(footprint.properties.iter().all(|p| p.key != "Sheetfile"));
+        assert!(footprint.sheet_file.is_some());
+    }
+
+    let output = serialize_pcb_file(pcb);
+    assert!(!output.contains("(tstamp "));
+    assert!(!output.contains("fp_text reference"));
+    assert!(output.contains("(hide yes)"));
+
+    // The upgraded board reads back the same
+    let reparsed = parse_pcb_file(&output).unwrap();
+    assert_eq!(serialize_pcb_file(reparsed), output);
+}
+
+#[test]
+fn test_downgrade_board_to_kicad_7() {
+    let mut pcb = parse_pcb_file(&read("tests/pcb/Divider v8.kicad_pcb")).unwrap();
+
+    pcb.downgrade_to(20221018).unwrap();
+
+    for footprint in &pcb.footprints {
+        assert!(has_reference_or_value_text(&footprint.graphics_items));
+        assert!(footprint.properties.iter().all(|p| p.position.is_none()));
+        assert_eq!(footprint.sheet_file, None);
+    }
+
+    let output = serialize_pcb_file(pcb);
+    assert!(output.contains("(fp_text reference"));
+    assert!(output.contains("(property \"Sheetfile\""));
+    assert!(!output.contains("(uuid "));
+
+    // Upgrading again gives back the reference and value properties
+    let mut pcb = parse_pcb_file(&output).unwrap();
+    pcb.upgrade_to(20240108).unwrap();
+    assert!(pcb
+        .footprints
+        .iter()
+        .all(|f| f.properties[0].key == "Reference"));
+}
+
+#[test]
+fn test_downgrade_refuses_newer_items() {
+    let mut pcb = parse_pcb_file(&read("tests/pcb/Divider v9.kicad_pcb")).unwrap();
+    let original = pcb.clone();
+
+    let error = pcb.downgrade_to(20240108).unwrap_err();
+    assert!(matches!(
+        error,
+        MigrationError::Unrepresentable {
+            version: 20240108,
+            ..
+        }
+    ));
+
+    // A refused downgrade leaves the board as it was
+    assert_eq!(pcb, original);
+
+    let mut footprint = parse_footprint_library_file(&read(
+        "tests/footprint_library/Jumper_2_THT_Padstack.kicad_mod",
+    ))
+    .unwrap();
+    let error = footprint.downgrade_to(20240108).unwrap_err();
+    assert!(error.to_string().contains("jumpers"), "{error}");
+}
+
+#[test]
+fn test_migration_direction() {
+    let mut pcb = parse_pcb_file(&read("tests/pcb/Divider v8.kicad_pcb")).unwrap();
+
+    assert_eq!(
+        pcb.upgrade_to(20221018),
+        Err(MigrationError::NotAnUpgrade {
+            from: 20240108,
+            to: 20221018
+        })
+    );
+    assert_eq!(
+        pcb.downgrade_to(20241229),
+        Err(MigrationError::NotADowngrade {
+            from: 20240108,
+            to: 20241229
+        })
+    );
+
+    // Staying at the same version is allowed either way
+    pcb.upgrade_to(20240108).unwrap();
+    pcb.downgrade_to(20240108).unwrap();
+}
+
+#[test]
+fn test_upgrade_schematic_symbol_instances() {
+    let mut schematic =
+        parse_schematic_file(&read("tests/schematic/symbol_instances_v6.kicad_sch")).unwrap();
+
+    schematic.upgrade_to(20230121, "demo").unwrap();
+
+    let instances = schematic.symbols[0].instances.as_ref().unwrap();
+    assert_eq!(instances.len(), 1);
+    assert_eq!(instances[0].project, "demo");
+    assert_eq!(
+        instances[0].instances[0].path,
+        "/6d1c4e2b-8a3f-4b7e-9d21-3c5e8f0a1b2c"
+    );
+    assert_eq!(instances[0].instances[0].reference, "R1");
+
+    // The capacitor is in the sheet's own file, so its entry stays behind
+    let remaining = schematic.symbol_instances.as_ref().unwrap();
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(remaining[0].reference, "C1");
+
+    let output = serialize_schematic_file(schematic);
+    assert!(output.contains("(dnp no)"));
+    assert!(!output.contains("(id 0)"));
+}
+
+#[test]
+fn test_downgrade_schematic_symbol_instances() {
+    let mut schematic =
+        parse_schematic_file(&read("tests/schematic/complex_hierarchy.kicad_sch")).unwrap();
+    let instance_count = schematic
+        .symbols
+        .iter()
+        .flat_map(|s| s.instances.iter().flatten())
+        .map(|p| p.instances.len())
+        .sum::<usize>();
+
+    schematic.downgrade_to(20211123).unwrap();
+
+    assert!(schematic.symbols.iter().all(|s| s.instances.is_none()));
+
+    let entries = schematic.symbol_instances.as_ref().unwrap();
+    assert_eq!(entries.len(), instance_count);
+
+    // Paths leave out the root schematic and end with the symbol
+    let symbol = &schematic.symbols[0];
+    let entry = entries
+        .iter()
+        .find(|e| e.path.ends_with(&symbol.uuid.to_string()))
+        .unwrap();
+    assert_eq!(entry.path, format!("/{}", symbol.uuid));
+
+    let output = serialize_schematic_file(schematic);
+    assert!(output.contains("(symbol_instances"));
+    assert!(!output.contains("(dnp "));
+
+    // Upgrading again moves the entries back onto the symbols
+    let mut schematic = parse_schematic_file(&output).unwrap();
+    schematic.upgrade_to(20230121, "complex_hierarchy").unwrap();
+    assert_eq!(schematic.symbol_instances, None);
+    assert!(schematic.symbols.iter().all(|s| s.instances.is_some()));
+}
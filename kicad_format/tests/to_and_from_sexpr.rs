@@ -1,12 +1,22 @@
 use std::path::Path;
 
 use kicad_format::{
-    common::TextEffects,
-    convert::{FromSexpr, Parser, ToSexpr},
+    angle::Angle,
+    common::{
+        footprint::{FootprintGraphicsItem, FootprintInlined},
+        pad::{
+            padstack::{PadstackLayerId, PadstackMode},
+            Pad,
+        },
+        Color, LayerId, TextEffects,
+    },
+    convert::{with_write_version, FromSexpr, Parser, ToSexpr},
     footprint_library::FootprintLibraryFile,
-    pcb::PcbFile,
+    pcb::{graphics::PcbGraphicsItem, PcbFile, Track},
     schematic::SchematicFile,
     symbol_library::SymbolLibraryFile,
+    version::FormatVersion,
+    KiCadParseError,
 };
 use kicad_sexpr::Sexpr;
 
@@ -15,23 +25,27 @@ fn assert_sexprs_eq(input_sexpr: Sexpr, output_sexpr: Sexpr) {
         return;
     }
 
+    let patch = kicad_sexpr::diff(&input_sexpr, &output_sexpr);
     let mut output = String::new();
 
-    for diff in diff::lines(&format!("{input_sexpr}"), &format!("{output_sexpr}")) {
-        match diff {
-            diff::Result::Left(l) => output.push_str(&format!(
-                "{}",
-                ansi_term::Color::Red.paint(format!("-{}\n", l))
-            )),
-            diff::Result::Both(l, _) => output.push_str(&format!(" {}\n", l)),
-            diff::Result::Right(r) => output.push_str(&format!(
-                "{}",
-                ansi_term::Color::Green.paint(format!("+{}\n", r))
-            )),
-        }
+    for line in patch.to_string().lines() {
+        let line = match line.chars().next() {
+            Some('-') => ansi_term::Color::Red.paint(line).to_string(),
+            Some('+') => ansi_term::Color::Green.paint(line).to_string(),
+            _ => line.to_string(),
+        };
+
+        output.push_str(&line);
+        output.push('\n');
     }
 
-    panic!("input sexpr (red) did not match output sexpr (green): \n{output}");
+    panic!("output sexpr did not match input sexpr (red: removed, green: added):\n{output}");
+}
+
+/// Writes a board item that was parsed on its own, without the version of a
+/// file, in the KiCad 7 syntax it was written in.
+fn to_kicad_7_sexpr(value: &impl ToSexpr) -> Sexpr {
+    with_write_version(FormatVersion::board(20221018), || value.to_sexpr())
 }
 
 fn assert_in_out_eq<T: FromSexpr + ToSexpr>(input: &str, path: &Path) {
@@ -265,6 +279,226 @@ fn test_texteffects_hide_yes() {
     assert_eq!(sexpr, output_sexpr, "Round-trip mismatch for TextEffects");
 }
 
+#[test]
+fn test_number_precision_round_trip() {
+    let test_segment = r#"(segment
+        (start 17.832345 -9.558046)
+        (end 148.5875 20.000001)
+        (width 0.1524)
+        (layer "F.Cu")
+        (net 14)
+        (tstamp e550f0d6-187a-408f-9c6e-bcf32b7d5b70)
+    )"#;
+
+    let sexpr = kicad_sexpr::from_str(test_segment).unwrap();
+    let list = sexpr.as_list().unwrap();
+    let parser = Parser::new(list.clone());
+
+    use kicad_format::pcb::TrackSegment;
+    let segment = TrackSegment::from_sexpr(parser).unwrap();
+
+    // Compare the serialized text as well, since `Number` equality is numeric
+    let output_sexpr = to_kicad_7_sexpr(&segment);
+    assert_eq!(
+        kicad_sexpr::to_string(&sexpr),
+        kicad_sexpr::to_string(&output_sexpr),
+        "Round-trip mismatch for TrackSegment coordinates"
+    );
+}
+
+#[test]
+fn test_write_version_changes_syntax() {
+    let input = std::fs::read_to_string("./tests/legacy_symbols/MP34DT06JTR.kicad_sym").unwrap();
+    let input_sexpr = kicad_sexpr::from_str(&input).unwrap();
+    let parser = Parser::new(input_sexpr.as_list().unwrap().clone());
+    let symbol_lib = SymbolLibraryFile::from_sexpr(parser).unwrap();
+
+    let modern_sexpr =
+        with_write_version(FormatVersion::schematic(20231120), || symbol_lib.to_sexpr());
+    let modern = kicad_sexpr::to_string(&modern_sexpr);
+
+    assert!(modern.contains("(version 20231120)"));
+    assert!(modern.contains(r#"(generator "kicad_symbol_editor")"#));
+    assert!(modern.contains("(hide yes)"));
+    assert!(modern.contains("(italic yes)"));
+
+    // Reading the modern file back and writing it in the version of the
+    // original gives the original file
+    let parser = Parser::new(modern_sexpr.as_list().unwrap().clone());
+    let modern_lib = SymbolLibraryFile::from_sexpr(parser).unwrap();
+    assert_eq!(modern_lib.version, 20231120);
+
+    let legacy_sexpr =
+        with_write_version(FormatVersion::schematic(20211014), || modern_lib.to_sexpr());
+    assert_sexprs_eq(input_sexpr, legacy_sexpr);
+}
+
+#[test]
+fn test_kicad_8_board_written_as_kicad_7() {
+    let input = std::fs::read_to_string("./tests/pcb/Divider v8.kicad_pcb").unwrap();
+    let input_sexpr = kicad_sexpr::from_str(&input).unwrap();
+    let parser = Parser::new(input_sexpr.as_list().unwrap().clone());
+    let pcb = PcbFile::from_sexpr(parser).unwrap();
+
+    assert_eq!(pcb.generator_version.as_deref(), Some("8.0"));
+    assert_eq!(pcb.footprints[0].properties[0].key, "Reference");
+    assert!(pcb.footprints[0].properties[2].hide);
+
+    let legacy_sexpr = with_write_version(FormatVersion::board(20221018), || pcb.to_sexpr());
+    let legacy = kicad_sexpr::to_string(&legacy_sexpr);
+
+    assert!(legacy.contains("(version 20221018)"));
+    assert!(!legacy.contains("generator_version"));
+    assert!(legacy.contains("(tstamp 2b0c4d2e-6d0f-4b8a-9a1c-54e1b0a7f3c1)"));
+    assert!(!legacy.contains("(uuid"));
+    assert!(legacy.contains("(at 0 0 90 unlocked)"));
+    assert!(legacy.contains(r#"(layer "F.Fab") hide"#));
+    assert!(legacy.contains("(plotreference true)"));
+
+    // Reading the KiCad 7 file back and writing it as KiCad 8 gives the
+    // original file, except for what KiCad 7 has no syntax for
+    let parser = Parser::new(legacy_sexpr.as_list().unwrap().clone());
+    let mut legacy_pcb = PcbFile::from_sexpr(parser).unwrap();
+    assert_eq!(legacy_pcb.version, 20221018);
+    assert_eq!(legacy_pcb.footprints[1].pads[0].remove_unused_layers, None);
+
+    legacy_pcb.generator_version = pcb.generator_version.clone();
+    legacy_pcb.footprints[1].pads[0].remove_unused_layers = Some(false);
+
+    let modern_sexpr = with_write_version(FormatVersion::board(20240108), || legacy_pcb.to_sexpr());
+    assert_sexprs_eq(input_sexpr, modern_sexpr);
+}
+
+#[test]
+fn test_syntax_read_by_file_version() {
+    let input = std::fs::read_to_string("./tests/pcb/Divider v8.kicad_pcb").unwrap();
+
+    // KiCad 8 writes flags as `(hide yes)`, so a bare `hide` is not a flag
+    let bare_flag = input.replacen("\t\t\t(hide yes)\n", "\t\t\thide\n", 1);
+    assert!(matches!(
+        kicad_format::parse_pcb_file(&bare_flag),
+        Err(e) if matches!(e.inner(), KiCadParseError::ExpectedEndOfList { .. })
+    ));
+
+    // KiCad 8 identifies items with a `uuid`, so a `tstamp` is only kept as
+    // an unknown list
+    let tstamp = input.replacen(
+        r#"(uuid "1d2e3f40-5a6b-4c7d-8e9f-0a1b2c3d4e5f")"#,
+        r#"(tstamp "1d2e3f40-5a6b-4c7d-8e9f-0a1b2c3d4e5f")"#,
+        1,
+    );
+    let pcb = kicad_format::parse_pcb_file(&tstamp).unwrap();
+    assert_eq!(pcb.footprints[0].properties[2].key, "Footprint");
+    assert_eq!(pcb.footprints[0].properties[2].tstamp, None);
+
+    // Symbol properties are numbered up to KiCad 6 only
+    let input = std::fs::read_to_string("./tests/schematic/sallen_key.kicad_sch").unwrap();
+    let numbered = input.replacen(
+        r#"(property "Reference" "C" "#,
+        r#"(property "Reference" "C" (id 0) "#,
+        1,
+    );
+    let schematic = kicad_format::parse_schematic_file(&numbered).unwrap();
+    assert_eq!(schematic.lib_symbols[0].properties[0].legacy_id, None);
+
+    let input = std::fs::read_to_string("./tests/schematic/symbol_instances_v6.kicad_sch").unwrap();
+    let schematic = kicad_format::parse_schematic_file(&input).unwrap();
+    assert_eq!(schematic.lib_symbols[0].properties[0].legacy_id, Some(0));
+}
+
+#[test]
+fn test_kicad_9_files() {
+    let input = std::fs::read_to_string("./tests/pcb/Divider v9.kicad_pcb").unwrap();
+    let input_sexpr = kicad_sexpr::from_str(&input).unwrap();
+    let pcb = PcbFile::from_sexpr(Parser::new(input_sexpr.as_list().unwrap().clone())).unwrap();
+
+    assert!(pcb.extra.is_empty());
+    let tenting = pcb.setup.tenting.as_ref().unwrap();
+    assert!(tenting.front && tenting.back);
+
+    let resistor = &pcb.footprints[0];
+    assert!(resistor.locked);
+    assert_eq!(
+        resistor.component_classes,
+        Some(vec!["Passives".to_string()])
+    );
+    assert_eq!(resistor.embedded_fonts, Some(false));
+
+    let padstack = pcb.footprints[1].pads[0].padstack.as_ref().unwrap();
+    assert_eq!(padstack.mode, PadstackMode::FrontInnerBack);
+    assert_eq!(padstack.layers[0].layer, PadstackLayerId::Inner);
+    assert_eq!(
+        padstack.layers[1].layer,
+        PadstackLayerId::Layer(LayerId::BCu)
+    );
+
+    let Track::Via(via) = &pcb.tracks[1] else {
+        panic!("expected a via");
+    };
+    assert_eq!(via.padstack.as_ref().unwrap().layers.len(), 2);
+    let tenting = via.tenting.as_ref().unwrap();
+    assert_eq!((tenting.front, tenting.back), (Some(true), Some(false)));
+
+    let Some(PcbGraphicsItem::Table(table)) = pcb.graphics_items.last() else {
+        panic!("expected a table");
+    };
+    assert_eq!(table.cells.len(), 4);
+    assert_eq!(table.cells[2].span, (2, 1));
+
+    let file = &pcb.embedded_files.as_ref().unwrap()[0];
+    assert_eq!(file.name, "notes.txt");
+    // The bars and line breaks around the data are not part of it
+    let data = file.data.as_deref().unwrap();
+    assert!(data.starts_with("KLUv") && data.ends_with("Mwo="), "{data}");
+    assert_eq!(data.len(), 224);
+    assert_eq!(
+        file.checksum.as_deref(),
+        Some("88C075D245DF5A3517FBE3D263146C73")
+    );
+
+    // KiCad 8 marks locked items with a bare symbol, and has none of the
+    // tokens KiCad 9 added
+    let v8 = with_write_version(FormatVersion::board(20240108), || pcb.to_sexpr()).to_string();
+    assert!(v8.contains(r#"(footprint "Resistor_SMD:R_0603_1608Metric" locked"#));
+    for token in [
+        "(padstack",
+        "(tenting",
+        "(table",
+        "(component_classes",
+        "(embedded_fonts",
+        "(embedded_files",
+    ] {
+        assert!(!v8.contains(token), "{token}");
+    }
+
+    let input =
+        std::fs::read_to_string("./tests/footprint_library/Jumper_2_THT_Padstack.kicad_mod")
+            .unwrap();
+    let sexpr = kicad_sexpr::from_str(&input).unwrap();
+    let footprint =
+        FootprintLibraryFile::from_sexpr(Parser::new(sexpr.as_list().unwrap().clone())).unwrap();
+
+    assert_eq!(footprint.duplicate_pad_numbers_are_jumpers, Some(false));
+    assert_eq!(
+        footprint.jumper_pad_groups,
+        Some(vec![vec!["1".to_string(), "2".to_string()]])
+    );
+    let padstack = footprint.pads[1].padstack.as_ref().unwrap();
+    assert_eq!(padstack.mode, PadstackMode::Custom);
+    assert_eq!(padstack.layers.len(), 3);
+
+    let input = std::fs::read_to_string("./tests/schematic/table_v9.kicad_sch").unwrap();
+    let sexpr = kicad_sexpr::from_str(&input).unwrap();
+    let schematic =
+        SchematicFile::from_sexpr(Parser::new(sexpr.as_list().unwrap().clone())).unwrap();
+
+    assert!(schematic.extra.is_empty());
+    assert_eq!(schematic.generator_version.as_deref(), Some("9.0"));
+    assert_eq!(schematic.tables[0].cells[3].text, "2.5 V");
+    assert_eq!(schematic.sheets[0].dnp, Some(false));
+    assert_eq!(schematic.embedded_fonts, Some(false));
+}
+
 #[test]
 fn test_kicad_symbol_dir_parsing() {
     // Check if KICAD_SYMBOL_DIR environment variable is set
@@ -356,3 +590,351 @@ fn test_kicad_symbol_dir_parsing() {
     // The test doesn't fail on parsing errors - we just report them
     // This allows us to see what needs to be fixed without failing the test suite
 }
+
+fn assert_file_round_trips(path: &str, round_trip: impl Fn(&str) -> String) {
+    let input = std::fs::read_to_string(path).unwrap();
+    let output = round_trip(&input);
+
+    if input == output {
+        return;
+    }
+
+    let mut diff_output = String::new();
+
+    for diff in diff::lines(&input, &output) {
+        match diff {
+            diff::Result::Left(l) => diff_output.push_str(&format!("-{}\n", l)),
+            diff::Result::Right(r) => diff_output.push_str(&format!("+{}\n", r)),
+            diff::Result::Both(..) => {}
+        }
+    }
+
+    panic!("{path} was not serialized byte-identically:\n{diff_output}");
+}
+
+#[test]
+fn test_byte_exact_round_trip() {
+    let footprint_library = |input: &str| {
+        kicad_format::serialize_footprint_library_file(
+            kicad_format::parse_footprint_library_file(input).unwrap(),
+        )
+    };
+    let symbol_library = |input: &str| {
+        kicad_format::serialize_symbol_library_file(
+            kicad_format::parse_symbol_library_file(input).unwrap(),
+        )
+    };
+    let schematic = |input: &str| {
+        kicad_format::serialize_schematic_file(kicad_format::parse_schematic_file(input).unwrap())
+    };
+    let pcb = |input: &str| {
+        kicad_format::serialize_pcb_file(kicad_format::parse_pcb_file(input).unwrap())
+    };
+
+    // KiCad 6
+    assert_file_round_trips("./tests/schematic/symbol_instances_v6.kicad_sch", schematic);
+
+    // KiCad 7
+    assert_file_round_trips(
+        "./tests/footprint_library/LED_SK6812_EC15_1.5x1.5mm.kicad_mod",
+        footprint_library,
+    );
+    assert_file_round_trips(
+        "./tests/symbol_library/MCU_Espressif.kicad_sym",
+        symbol_library,
+    );
+    assert_file_round_trips("./tests/schematic/sallen_key.kicad_sch", schematic);
+    assert_file_round_trips("./tests/pcb/TMC2209 Dev Board v1.kicad_pcb", pcb);
+
+    // KiCad 8 and later
+    assert_file_round_trips(
+        "./tests/newer_symbols/Regulator_Current.kicad_sym",
+        symbol_library,
+    );
+    assert_file_round_trips(
+        "./tests/newer_symbols/MCU_NXP_NTAG.kicad_sym",
+        symbol_library,
+    );
+    assert_file_round_trips("./tests/pcb/Divider v8.kicad_pcb", pcb);
+    assert_file_round_trips("./tests/pcb/Outline fonts v8.kicad_pcb", pcb);
+    assert_file_round_trips("./tests/pcb/Dimensions v8.kicad_pcb", pcb);
+
+    // KiCad 9
+    assert_file_round_trips("./tests/pcb/Divider v9.kicad_pcb", pcb);
+}
+
+#[test]
+fn test_streamed_output_matches_serialized_output() {
+    fn assert_streams_identically<T: Clone>(
+        path: &str,
+        parse: impl Fn(&str) -> T,
+        serialize: impl Fn(T) -> String,
+        write: impl Fn(&T, &mut Vec<u8>) -> std::io::Result<()>,
+    ) {
+        let file = parse(&std::fs::read_to_string(path).unwrap());
+
+        let mut streamed = Vec::new();
+        write(&file, &mut streamed).unwrap();
+
+        assert_eq!(
+            String::from_utf8(streamed).unwrap(),
+            serialize(file),
+            "{path} was streamed differently"
+        );
+    }
+
+    assert_streams_identically(
+        "./tests/footprint_library/LED_SK6812_EC15_1.5x1.5mm.kicad_mod",
+        |input| kicad_format::parse_footprint_library_file(input).unwrap(),
+        kicad_format::serialize_footprint_library_file,
+        |file, output| kicad_format::write_footprint_library_file(file, output),
+    );
+    assert_streams_identically(
+        "./tests/newer_symbols/Regulator_Current.kicad_sym",
+        |input| kicad_format::parse_symbol_library_file(input).unwrap(),
+        kicad_format::serialize_symbol_library_file,
+        |file, output| kicad_format::write_symbol_library_file(file, output),
+    );
+    assert_streams_identically(
+        "./tests/schematic/sallen_key.kicad_sch",
+        |input| kicad_format::parse_schematic_file(input).unwrap(),
+        kicad_format::serialize_schematic_file,
+        |file, output| kicad_format::write_schematic_file(file, output),
+    );
+    assert_streams_identically(
+        "./tests/pcb/TMC2209 Dev Board v1.kicad_pcb",
+        |input| kicad_format::parse_pcb_file(input).unwrap(),
+        kicad_format::serialize_pcb_file,
+        |file, output| kicad_format::write_pcb_file(file, output),
+    );
+}
+
+#[test]
+fn test_footprint_field_order() {
+    let pad = r#"(pad "1" thru_hole circle (at -2 0) (size 1.26 1.26) (drill 0.68) (layers "*.Cu" "*.Mask") (net 20 "OA1") (pintype "passive") (tstamp 970b37bf-53bc-4354-b42f-23b927b5fff4))"#;
+    let line = r#"(fp_line (start -3 -1) (end 3 -1) (stroke (width 0.1) (type default)) (layer "F.SilkS") (tstamp 4c5b9498-e50c-43b6-a64b-9d975f5f095c))"#;
+
+    let canonical = format!(
+        r#"(footprint "Lib:C" locked (layer "F.Cu") (tstamp 1c66da6c-dea2-4b2d-8b3c-070af73307ef) (at 18 -18.6) (property "Sheetname" "") (path "/59e8b883") (attr through_hole) {line} {pad})"#
+    );
+    let shuffled = format!(
+        r#"(footprint "Lib:C" (attr through_hole) (layer "F.Cu") {pad} (path "/59e8b883") locked (at 18 -18.6) (tstamp 1c66da6c-dea2-4b2d-8b3c-070af73307ef) {line} (property "Sheetname" ""))"#
+    );
+
+    let parse = |input: &str| {
+        let sexpr = kicad_sexpr::from_str(input).unwrap();
+        let footprint = FootprintInlined::from_sexpr(Parser::new(sexpr.as_list().unwrap().clone()))
+            .unwrap_or_else(|e| panic!("Failed to parse footprint: {e}"));
+
+        (sexpr, footprint)
+    };
+
+    let (canonical_sexpr, canonical) = parse(&canonical);
+    let (shuffled_sexpr, shuffled) = parse(&shuffled);
+
+    // Both orders describe the same footprint
+    assert_eq!(
+        FootprintInlined {
+            field_order: Default::default(),
+            ..canonical.clone()
+        },
+        FootprintInlined {
+            field_order: Default::default(),
+            ..shuffled.clone()
+        }
+    );
+
+    // And each is written back in the order it was read in
+    assert_sexprs_eq(canonical_sexpr.clone(), to_kicad_7_sexpr(&canonical));
+    assert_sexprs_eq(shuffled_sexpr, to_kicad_7_sexpr(&shuffled));
+
+    // Footprints that were not parsed use the canonical order
+    let created = FootprintInlined {
+        field_order: Default::default(),
+        ..shuffled
+    };
+    assert_sexprs_eq(canonical_sexpr, to_kicad_7_sexpr(&created));
+}
+
+#[test]
+fn test_board_item_field_order() {
+    let shuffled = r#"(pad "1" thru_hole circle (at -2 0) (layers "*.Cu" "*.Mask") (size 1.26 1.26) (drill 0.68) (tstamp 970b37bf-53bc-4354-b42f-23b927b5fff4) (net 20 "OA1"))"#;
+    let canonical = r#"(pad "1" thru_hole circle (at -2 0) (size 1.26 1.26) (drill 0.68) (layers "*.Cu" "*.Mask") (net 20 "OA1") (tstamp 970b37bf-53bc-4354-b42f-23b927b5fff4))"#;
+
+    let sexpr = kicad_sexpr::from_str(shuffled).unwrap();
+    let pad = Pad::from_sexpr(Parser::new(sexpr.as_list().unwrap().clone())).unwrap();
+    assert_sexprs_eq(sexpr, to_kicad_7_sexpr(&pad));
+
+    // Pads that were not parsed use the canonical order
+    let created = Pad {
+        field_order: Default::default(),
+        ..pad
+    };
+    assert_sexprs_eq(
+        kicad_sexpr::from_str(canonical).unwrap(),
+        to_kicad_7_sexpr(&created),
+    );
+
+    let items = [
+        r#"(segment (end 3 -1) (start -3 -1) (width 0.25) (net 1) (layer "F.Cu") (tstamp 4c5b9498-e50c-43b6-a64b-9d975f5f095c))"#,
+        r#"(via (at 1 2) (size 0.8) (layers "F.Cu" "B.Cu") (drill 0.4) (net 1) (tstamp 4c5b9498-e50c-43b6-a64b-9d975f5f095c))"#,
+    ];
+
+    for item in items {
+        let sexpr = kicad_sexpr::from_str(item).unwrap();
+        let track = Track::from_sexpr(Parser::new(sexpr.as_list().unwrap().clone())).unwrap();
+        assert_sexprs_eq(sexpr, to_kicad_7_sexpr(&track));
+    }
+
+    let line = r#"(gr_line (start -3 -1) (end 3 -1) (layer "Edge.Cuts") (stroke (width 0.1) (type default)) (tstamp 4c5b9498-e50c-43b6-a64b-9d975f5f095c))"#;
+    let sexpr = kicad_sexpr::from_str(line).unwrap();
+    let item = PcbGraphicsItem::from_sexpr(Parser::new(sexpr.as_list().unwrap().clone())).unwrap();
+    assert_sexprs_eq(sexpr, to_kicad_7_sexpr(&item));
+}
+
+#[test]
+fn test_unknown_lists_round_trip() {
+    let input = r#"(footprint "Lib:C" (layer "F.Cu") (tstamp 1c66da6c-dea2-4b2d-8b3c-070af73307ef) (at 18 -18.6) (unknown_list "a.kicad_sch") (path "/59e8b883") (attr through_hole) (fp_line (start -3 -1) (end 3 -1) (stroke (width 0.1) (type default)) (layer "F.SilkS") (solder_mask_margin 0.1) (tstamp 4c5b9498-e50c-43b6-a64b-9d975f5f095c)) (unknown_setting no))"#;
+
+    let sexpr = kicad_sexpr::from_str(input).unwrap();
+    let footprint = FootprintInlined::from_sexpr(Parser::new(sexpr.as_list().unwrap().clone()))
+        .unwrap_or_else(|e| panic!("Failed to parse footprint: {e}"));
+
+    assert_eq!(footprint.extra.len(), 2);
+
+    // Unknown lists are written back where they were found
+    assert_sexprs_eq(sexpr.clone(), to_kicad_7_sexpr(&footprint));
+
+    // Unknown atoms are still rejected
+    let input = input.replace("(unknown_setting no)", "unknown_setting");
+    let sexpr = kicad_sexpr::from_str(&input).unwrap();
+    assert!(FootprintInlined::from_sexpr(Parser::new(sexpr.as_list().unwrap().clone())).is_err());
+}
+
+#[test]
+fn test_unknown_child_lists_round_trip() {
+    let input = r#"(footprint "Lib:C" (layer "F.Cu") (tstamp 1c66da6c-dea2-4b2d-8b3c-070af73307ef) (at 18 -18.6) (attr smd (unknown_attr 1)) (fp_poly (pts (xy -2 -1) (unknown_point 1) (xy 2 -1) (xy 2 1)) (stroke (width 0) (type solid)) (fill solid) (layer "F.Cu") (tstamp 128c9e52-3b12-4089-887d-2e1c1ffe20bc)) (model "C.wrl" (offset (xyz 0 0 0) (unknown_offset 1)) (scale (xyz 1 1 1)) (rotate (xyz 0 0 0))))"#;
+
+    let sexpr = kicad_sexpr::from_str(input).unwrap();
+    let footprint = FootprintInlined::from_sexpr(Parser::new(sexpr.as_list().unwrap().clone()))
+        .unwrap_or_else(|e| panic!("Failed to parse footprint: {e}"));
+
+    assert_eq!(footprint.attributes.as_ref().unwrap().extra.len(), 1);
+    assert_sexprs_eq(sexpr, to_kicad_7_sexpr(&footprint));
+
+    // Lists read inline by their parent, like the layers of a board
+    let path = Path::new("tests/pcb/Divider v8.kicad_pcb");
+    let input = std::fs::read_to_string(path).unwrap();
+    let input = input
+        .replacen("(layers\n", "(layers (unknown_layer 1)\n", 1)
+        .replacen(
+            "(xy 0 0) (xy 30 0)",
+            "(xy 0 0) (unknown_point 1) (xy 30 0)",
+            1,
+        );
+
+    let pcb = PcbFile::from_sexpr(Parser::new(
+        kicad_sexpr::from_str(&input)
+            .unwrap()
+            .as_list()
+            .unwrap()
+            .clone(),
+    ))
+    .unwrap();
+    assert_eq!(pcb.extra.len(), 1);
+    assert_eq!(pcb.zones[0].extra.len(), 1);
+    assert_in_out_eq::<PcbFile>(&input, path);
+
+    let path = Path::new("tests/schematic/ampli_ht.kicad_sch");
+    let input = std::fs::read_to_string(path).unwrap();
+    let input = input.replacen(
+        "(fill (type none))",
+        "(fill (type none) (unknown_fill 1))",
+        1,
+    );
+    assert_in_out_eq::<SchematicFile>(&input, path);
+}
+
+#[test]
+fn test_fractional_angles_round_trip() {
+    let input = r#"(footprint "Lib:C" (layer "F.Cu") (tstamp 1c66da6c-dea2-4b2d-8b3c-070af73307ef) (at 10 20 45.5) (attr smd) (model "C.wrl" (offset (xyz 0 0 0)) (scale (xyz 1 1 1)) (rotate (xyz -90 0 22.5))))"#;
+
+    let sexpr = kicad_sexpr::from_str(input).unwrap();
+    let mut footprint =
+        FootprintInlined::from_sexpr(Parser::new(sexpr.as_list().unwrap().clone())).unwrap();
+
+    assert_eq!(footprint.position.angle, Some(Angle::from_degrees(45.5)));
+    assert_eq!(footprint.models[0].rotate.z, Angle::from_degrees(22.5));
+    assert_sexprs_eq(sexpr, to_kicad_7_sexpr(&footprint));
+
+    // Angles are written with at most ten significant digits, like KiCad does
+    footprint.position.angle = Some(Angle::from_degrees(100.0 / 3.0));
+    let output = to_kicad_7_sexpr(&footprint).to_string();
+    assert!(output.contains("(at 10 20 33.33333333)"), "{output}");
+}
+
+#[test]
+fn test_text_render_cache() {
+    let path = "./tests/pcb/Outline fonts v8.kicad_pcb";
+    let pcb = kicad_format::parse_pcb_file(&std::fs::read_to_string(path).unwrap()).unwrap();
+
+    let Some(PcbGraphicsItem::Text(text)) = pcb.graphics_items.first() else {
+        panic!("expected a text");
+    };
+    assert!(text.extra.is_empty());
+    let render_cache = text.render_cache.as_ref().unwrap();
+    assert_eq!(render_cache.text, "IO");
+    assert_eq!(render_cache.polygons.len(), 2);
+    assert_eq!(render_cache.polygons[0].outline.len(), 4);
+    assert!(render_cache.polygons[0].holes.is_empty());
+    assert_eq!(render_cache.polygons[1].holes.len(), 1);
+
+    let Some(PcbGraphicsItem::TextBox(text_box)) = pcb.graphics_items.get(1) else {
+        panic!("expected a text box");
+    };
+    assert_eq!(text_box.layer, LayerId::FSilkS);
+    assert_eq!(
+        text_box.render_cache.as_ref().unwrap().angle,
+        Angle::from_degrees(90.0)
+    );
+
+    let footprint = &pcb.footprints[0];
+    let reference = &footprint.properties[0];
+    assert!(reference.extra.is_empty());
+    assert_eq!(reference.render_cache.as_ref().unwrap().text, "L1");
+
+    let Some(FootprintGraphicsItem::Text(text)) = footprint.graphics_items.first() else {
+        panic!("expected a footprint text");
+    };
+    assert!(text.extra.is_empty());
+    assert_eq!(text.render_cache.as_ref().unwrap().polygons.len(), 1);
+
+    let Some(FootprintGraphicsItem::TextBox(text_box)) = footprint.graphics_items.get(1) else {
+        panic!("expected a footprint text box");
+    };
+    assert!(text_box.extra.is_empty());
+    assert!(text_box.render_cache.is_some());
+
+    // The cached outlines are written back as they were read
+    let output = kicad_format::serialize_pcb_file(pcb.clone());
+    assert_eq!(kicad_format::parse_pcb_file(&output).unwrap(), pcb);
+    assert!(output.contains("(render_cache \"IO\" 90"));
+}
+
+#[test]
+fn test_color_alpha() {
+    let sexpr = kicad_sexpr::from_str("(color 255 0 0 0.5)").unwrap();
+    let color = Color::from_sexpr(Parser::new(sexpr.as_list().unwrap().clone())).unwrap();
+
+    assert_eq!(color.alpha.to_f64(), 0.5);
+    assert_sexprs_eq(sexpr, color.to_sexpr());
+
+    // Alphas written with a fixed number of decimals are written back as is
+    let sexpr = kicad_sexpr::from_str("(color 0 0 0 0.0000)").unwrap();
+    let color = Color::from_sexpr(Parser::new(sexpr.as_list().unwrap().clone())).unwrap();
+
+    assert_eq!(
+        kicad_sexpr::to_string(&color.to_sexpr()),
+        "(color 0 0 0 0.0000)"
+    );
+}
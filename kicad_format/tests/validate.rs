@@ -0,0 +1,118 @@
+use kicad_format::{
+    common::{Group, LayerId, Uuid},
+    parse_footprint_library_file, parse_pcb_file, parse_schematic_file, parse_symbol_library_file,
+    validate::ValidationIssueKind,
+};
+
+#[test]
+fn test_valid_files_have_no_issues() {
+    let read = |path: &str| std::fs::read_to_string(path).unwrap();
+
+    let pcb = parse_pcb_file(&read("tests/pcb/TMC2209 Dev Board v1.kicad_pcb")).unwrap();
+    assert_eq!(pcb.validate(), []);
+
+    let pcb = parse_pcb_file(&read("tests/pcb/Divider v8.kicad_pcb")).unwrap();
+    assert_eq!(pcb.validate(), []);
+
+    let pcb = parse_pcb_file(&read("tests/pcb/Divider v9.kicad_pcb")).unwrap();
+    assert_eq!(pcb.validate(), []);
+
+    let schematic = parse_schematic_file(&read("tests/schematic/sallen_key.kicad_sch")).unwrap();
+    assert_eq!(schematic.validate(), []);
+
+    let symbol_library =
+        parse_symbol_library_file(&read("tests/symbol_library/Analog.kicad_sym")).unwrap();
+    assert_eq!(symbol_library.validate(), []);
+
+    // The pad numbers of net tie groups are written with spaces, like "1, 2"
+    let footprint_library = parse_footprint_library_file(&read(
+        "tests/footprint_library/NetTie-3_THT_Pad1.0mm.kicad_mod",
+    ))
+    .unwrap();
+    assert_eq!(footprint_library.validate(), []);
+}
+
+#[test]
+fn test_pcb_references() {
+    let input = std::fs::read_to_string("tests/pcb/TMC2209 Dev Board v1.kicad_pcb").unwrap();
+    let input = input
+        .replacen(
+            r#"(layer "F.Cu") (net 5) (tstamp ceaba846"#,
+            r#"(layer "In5.Cu") (net 500) (tstamp ceaba846"#,
+            1,
+        )
+        .replacen(
+            r#"(net 1 "unconnected-(X1-MECH_PIN-Pad1)") (pinfunction"#,
+            r#"(net 1 "MECH") (pinfunction"#,
+            1,
+        );
+
+    let mut pcb = parse_pcb_file(&input).unwrap();
+
+    let missing = Uuid::new();
+    pcb.groups.push(Group {
+        name: "Power".to_string(),
+        locked: false,
+        id: Uuid::new(),
+        members: vec![pcb.footprints[0].tstamp.clone(), missing.clone()],
+        extra: Default::default(),
+    });
+
+    let issues = pcb.validate();
+    let kinds = issues.iter().map(|i| i.kind.clone()).collect::<Vec<_>>();
+
+    assert_eq!(
+        kinds,
+        [
+            ValidationIssueKind::MismatchedNetName {
+                code: 1,
+                found: "MECH".to_string(),
+                declared: "unconnected-(X1-MECH_PIN-Pad1)".to_string(),
+            },
+            ValidationIssueKind::UndeclaredLayer(LayerId::In5Cu),
+            ValidationIssueKind::UndeclaredNet { code: 500 },
+            ValidationIssueKind::UnknownGroupMember(missing),
+        ]
+    );
+
+    let path = issues[0]
+        .path
+        .iter()
+        .map(|item| item.name)
+        .collect::<Vec<_>>();
+    assert_eq!(path, ["footprint", "pad"]);
+
+    assert_eq!(
+        issues[2].to_string(),
+        "segment ceaba846-3c20-4179-9c9a-b031917042db: Net 500 is not declared in the board"
+    );
+}
+
+#[test]
+fn test_library_symbol_references() {
+    let input = std::fs::read_to_string("tests/schematic/sallen_key.kicad_sch").unwrap();
+    let mut schematic = parse_schematic_file(&input).unwrap();
+
+    schematic
+        .lib_symbols
+        .retain(|s| s.id.to_string() != "sallen_key_schlib:VSOURCE");
+
+    let issues = schematic.validate();
+
+    assert!(!issues.is_empty());
+    assert!(issues.iter().all(|i| i.kind
+        == ValidationIssueKind::MissingLibSymbol("sallen_key_schlib:VSOURCE".to_string())));
+
+    let input = std::fs::read_to_string("tests/symbol_library/Analog.kicad_sym").unwrap();
+    let input = input.replacen(r#"(extends "PGA112")"#, r#"(extends "PGA111")"#, 1);
+    let symbol_library = parse_symbol_library_file(&input).unwrap();
+
+    assert_eq!(
+        symbol_library
+            .validate()
+            .into_iter()
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>(),
+        [r#"symbol "PGA113": Parent symbol `PGA111` does not exist in the library"#]
+    );
+}
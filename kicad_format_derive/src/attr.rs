@@ -0,0 +1,402 @@
+//! Parsing of the `#[sexpr(...)]` attributes into a description of how each
+//! field is laid out in the S-expression.
+
+use syn::{
+    spanned::Spanned, Data, DeriveInput, Fields, GenericArgument, Ident, LitStr, Path,
+    PathArguments, Type,
+};
+
+/// Options that apply to the whole struct.
+pub(crate) struct Container {
+    /// The symbol at the head of the list, such as `pcbplotparams`.
+    pub name: LitStr,
+    pub fields: Vec<Field>,
+}
+
+impl Container {
+    pub fn from_input(input: &DeriveInput) -> syn::Result<Self> {
+        let mut name = None;
+
+        for attr in input.attrs.iter().filter(|a| a.path().is_ident("sexpr")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("name") {
+                    name = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown container attribute"))
+                }
+            })?;
+        }
+
+        let name = name.ok_or_else(|| {
+            syn::Error::new_spanned(&input.ident, "missing `#[sexpr(name = \"...\")]` attribute")
+        })?;
+
+        let Data::Struct(data) = &input.data else {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "only structs can be derived",
+            ));
+        };
+
+        let Fields::Named(fields) = &data.fields else {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "only structs with named fields can be derived",
+            ));
+        };
+
+        let fields = fields
+            .named
+            .iter()
+            .map(Field::from_field)
+            .collect::<syn::Result<_>>()?;
+
+        Ok(Self { name, fields })
+    }
+}
+
+/// The kind of token a field is stored as.
+pub(crate) enum Kind {
+    /// A number, read with `expect_number`
+    Number,
+    /// A whole number, read with `expect_integer`
+    Integer,
+    /// A number of millimetres, read with `expect_length`
+    Length,
+    /// A number of degrees, read with `expect_angle`
+    Angle,
+    /// A quoted string
+    String,
+    /// A bare symbol
+    Symbol,
+    /// `yes` or `no`
+    Bool,
+    /// `true` or `false`
+    AltBool,
+    /// A bare symbol that is present when the value is true
+    Flag,
+    /// A flag that is a bare symbol in older versions and `(name yes)` in
+    /// newer ones, like `hide`
+    VersionedFlag,
+    /// The `locked` flag of board items, which stayed a bare symbol for longer
+    /// than the other flags
+    Locked,
+    /// An optional flag that is an empty list in older versions and
+    /// `(name yes)` or `(name no)` in newer ones, like `remove_unused_layers`
+    ListFlag,
+    /// The `tstamp` or `uuid` of a board item
+    Id,
+    /// A type that implements `FromSexpr` and `ToSexpr`, and names itself
+    Child,
+    /// A type that implements `FromSexprWithName` and `ToSexprWithName`
+    WithName,
+    /// A module with `parse` and `serialize` functions that handles the field
+    With(Path),
+    /// The `Extra` bucket for lists the struct does not know about
+    Extra,
+    /// The `FieldOrder` the children of the list were read in
+    FieldOrder,
+}
+
+/// How the token is converted into the type of the field.
+pub(crate) enum Conversion {
+    /// The token already has the type of the field
+    None,
+    /// Strings and symbols are converted with `FromStr` and `Display`
+    FromStr,
+    /// The token is read as the given type and converted with `TryFrom`, and
+    /// converted back with `From`
+    TryFrom(Box<Type>),
+}
+
+pub(crate) struct Field {
+    pub ident: Ident,
+    /// The name of the `(name value)` list the field is stored in
+    pub name: LitStr,
+    pub kind: Kind,
+    pub conversion: Conversion,
+    /// The type of a single value, without any `Option` or `Vec` around it
+    pub ty: Type,
+    /// The field is an `Option` and can be missing
+    pub optional: bool,
+    /// The field is a `Vec` of values that are repeated in the list
+    pub repeated: bool,
+    /// The value is stored directly in the list, without a `(name ...)` around
+    /// it
+    pub positional: bool,
+    /// The values are stored together in a single `(name ...)` list
+    pub list: bool,
+    /// The `Syntax` of the versions that have the field
+    pub since: Option<Ident>,
+}
+
+#[derive(Default)]
+struct FieldOptions {
+    name: Option<LitStr>,
+    positional: bool,
+    symbol: bool,
+    string: bool,
+    true_false: bool,
+    flag: bool,
+    versioned_flag: bool,
+    locked: bool,
+    list_flag: bool,
+    id: bool,
+    with_name: bool,
+    try_from: Option<Type>,
+    with: Option<Path>,
+    extra: bool,
+    field_order: bool,
+    since: Option<Ident>,
+}
+
+impl Field {
+    fn from_field(field: &syn::Field) -> syn::Result<Self> {
+        let ident = field.ident.clone().unwrap();
+        let mut options = FieldOptions::default();
+
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("sexpr")) {
+            attr.parse_nested_meta(|meta| {
+                let path = &meta.path;
+
+                if path.is_ident("name") {
+                    options.name = Some(meta.value()?.parse()?);
+                } else if path.is_ident("positional") {
+                    options.positional = true;
+                } else if path.is_ident("symbol") {
+                    options.symbol = true;
+                } else if path.is_ident("string") {
+                    options.string = true;
+                } else if path.is_ident("true_false") {
+                    options.true_false = true;
+                } else if path.is_ident("flag") {
+                    options.flag = true;
+                } else if path.is_ident("versioned_flag") {
+                    options.versioned_flag = true;
+                } else if path.is_ident("locked") {
+                    options.locked = true;
+                } else if path.is_ident("list_flag") {
+                    options.list_flag = true;
+                } else if path.is_ident("id") {
+                    options.id = true;
+                } else if path.is_ident("with_name") {
+                    options.with_name = true;
+                } else if path.is_ident("try_from") {
+                    options.try_from = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+                } else if path.is_ident("with") {
+                    options.with = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+                } else if path.is_ident("extra") {
+                    options.extra = true;
+                } else if path.is_ident("field_order") {
+                    options.field_order = true;
+                } else if path.is_ident("since") {
+                    options.since = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+                } else {
+                    return Err(meta.error("unknown field attribute"));
+                }
+
+                Ok(())
+            })?;
+        }
+
+        let name = options
+            .name
+            .take()
+            .unwrap_or_else(|| LitStr::new(&ident.to_string(), ident.span()));
+
+        let (ty, optional, repeated) = match (
+            unwrap_type(&field.ty, "Option"),
+            unwrap_type(&field.ty, "Vec"),
+        ) {
+            (Some(ty), _) => match unwrap_type(ty, "Vec") {
+                Some(ty) => (ty.clone(), true, true),
+                None => (ty.clone(), true, false),
+            },
+            (_, Some(ty)) => (ty.clone(), false, true),
+            _ => (field.ty.clone(), false, false),
+        };
+
+        let error = |message: &str| Err(syn::Error::new(field.ty.span(), message));
+
+        if options.since.is_some() && !optional {
+            return error("fields of newer versions must be optional");
+        }
+
+        let mut conversion = Conversion::None;
+
+        let kind = if options.extra {
+            Kind::Extra
+        } else if options.field_order {
+            Kind::FieldOrder
+        } else if let Some(with) = options.with.take() {
+            Kind::With(with)
+        } else if options.flag || options.versioned_flag || options.locked {
+            if optional || repeated || !is_type(&ty, &["bool"]) {
+                return error("flags must be `bool` fields");
+            }
+
+            if options.locked {
+                Kind::Locked
+            } else if options.versioned_flag {
+                Kind::VersionedFlag
+            } else {
+                Kind::Flag
+            }
+        } else if options.list_flag {
+            if !optional || repeated || !is_type(&ty, &["bool"]) {
+                return error("list flags must be `Option<bool>` fields");
+            }
+
+            Kind::ListFlag
+        } else if options.id {
+            if optional || repeated {
+                return error("ids cannot be optional or repeated");
+            }
+
+            Kind::Id
+        } else if let Some(raw) = options.try_from.take() {
+            let kind = atom_kind(&raw, &options).unwrap_or(Kind::Integer);
+            conversion = Conversion::TryFrom(Box::new(raw));
+            kind
+        } else if let Some(kind) = atom_kind(&ty, &options) {
+            kind
+        } else if options.symbol || options.string {
+            conversion = Conversion::FromStr;
+
+            if options.symbol {
+                Kind::Symbol
+            } else {
+                Kind::String
+            }
+        } else if options.with_name {
+            Kind::WithName
+        } else {
+            Kind::Child
+        };
+
+        match kind {
+            Kind::Bool | Kind::AltBool if options.positional => {
+                return error("booleans cannot be positional");
+            }
+            Kind::Child
+            | Kind::WithName
+            | Kind::Flag
+            | Kind::VersionedFlag
+            | Kind::Locked
+            | Kind::ListFlag
+            | Kind::Id
+                if options.positional =>
+            {
+                return error("lists and flags are always positional");
+            }
+            Kind::String | Kind::Symbol if optional && repeated && options.positional => {
+                return error("optional lists of values cannot be positional");
+            }
+            Kind::Number
+            | Kind::Integer
+            | Kind::Length
+            | Kind::Angle
+            | Kind::Bool
+            | Kind::AltBool
+            | Kind::WithName
+                if repeated =>
+            {
+                return error("only lists, strings and symbols can be repeated");
+            }
+            _ => {}
+        }
+
+        // Strings and symbols are repeated directly in the list when they are
+        // positional, so a `(name ...)` list around them needs a name. Lists
+        // are always repeated directly unless the field is an `Option<Vec<_>>`
+        let list = match kind {
+            Kind::String | Kind::Symbol => repeated && !options.positional,
+            Kind::Child => optional && repeated,
+            _ => false,
+        };
+
+        Ok(Self {
+            ident,
+            name,
+            kind,
+            conversion,
+            ty,
+            optional,
+            repeated,
+            positional: options.positional,
+            list,
+            since: options.since,
+        })
+    }
+
+    /// The type the token is read as before any conversion
+    pub fn raw_type(&self) -> &Type {
+        match &self.conversion {
+            Conversion::TryFrom(raw) => raw,
+            _ => &self.ty,
+        }
+    }
+}
+
+/// Picks the kind of token primitive types are stored as.
+fn atom_kind(ty: &Type, options: &FieldOptions) -> Option<Kind> {
+    if is_type(ty, &["bool"]) {
+        Some(if options.true_false {
+            Kind::AltBool
+        } else {
+            Kind::Bool
+        })
+    } else if is_type(ty, &["f32", "f64"]) {
+        Some(Kind::Number)
+    } else if is_type(ty, &["Length"]) {
+        Some(Kind::Length)
+    } else if is_type(ty, &["Angle"]) {
+        Some(Kind::Angle)
+    } else if is_type(
+        ty,
+        &[
+            "u8", "u16", "u32", "u64", "usize", "i8", "i16", "i32", "i64", "isize",
+        ],
+    ) {
+        Some(Kind::Integer)
+    } else if is_type(ty, &["String"]) {
+        Some(if options.symbol {
+            Kind::Symbol
+        } else {
+            Kind::String
+        })
+    } else {
+        None
+    }
+}
+
+fn is_type(ty: &Type, names: &[&str]) -> bool {
+    let Type::Path(path) = ty else {
+        return false;
+    };
+
+    path.qself.is_none() && names.iter().any(|name| path.path.is_ident(name))
+}
+
+/// Returns `T` if the type is `wrapper<T>`.
+fn unwrap_type<'a>(ty: &'a Type, wrapper: &str) -> Option<&'a Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+
+    let segment = path.path.segments.last()?;
+
+    if segment.ident != wrapper {
+        return None;
+    }
+
+    let PathArguments::AngleBracketed(arguments) = &segment.arguments else {
+        return None;
+    };
+
+    match arguments.args.first()? {
+        GenericArgument::Type(ty) => Some(ty),
+        _ => None,
+    }
+}
@@ -0,0 +1,495 @@
+//! Derive macros for the `FromSexpr` and `ToSexpr` traits of `kicad_format`.
+//!
+//! The generated code reads the fields in declaration order using the
+//! `Parser` helpers, the same way the hand-written implementations do, and
+//! writes them back out in the same order.
+//!
+//! ```ignore
+//! #[derive(FromSexpr, ToSexpr)]
+//! #[sexpr(name = "layer")]
+//! pub struct StackupLayer {
+//!     #[sexpr(positional, string)]
+//!     pub id: StackupLayerId,
+//!     #[sexpr(name = "type")]
+//!     pub kind: String,
+//!     pub thickness: Option<f64>,
+//!     #[sexpr(flag)]
+//!     pub locked: bool,
+//! }
+//! ```
+//!
+//! The struct needs `#[sexpr(name = "...")]` for the symbol at the head of
+//! its list. Deriving `FromSexpr` also implements `MaybeFromSexpr` for it.
+//!
+//! Each field is stored as `(field_name value)` unless stated otherwise. The
+//! token is picked from the type of the field: numbers for `f64`, integers
+//! for integer types, millimetres for `Length`, degrees for `Angle`, quoted
+//! strings for `String` and `yes`/`no` for `bool`.
+//! Any other type is a list that implements `FromSexpr` and `ToSexpr`.
+//! `Option` fields are left out when missing, and `Vec` fields are repeated.
+//! A `Vec` of strings that is not positional is stored as a single
+//! `(field_name "a" "b")` list, and so is an `Option<Vec<_>>` of strings or
+//! lists.
+//!
+//! Fields can be adjusted with these attributes:
+//!
+//! - `name = "..."` stores the field under another name.
+//! - `positional` stores the value directly in the list, without a
+//!   `(name ...)` around it.
+//! - `symbol` stores a string as a bare symbol. `symbol` and `string` can
+//!   also be used on other types, which are then converted with `FromStr`
+//!   and `Display`.
+//! - `true_false` stores a `bool` as `true` or `false` instead of `yes` or
+//!   `no`, in the versions before KiCad 8 settled on `yes` and `no`.
+//! - `flag` stores a `bool` as a bare symbol that is present when the value
+//!   is true.
+//! - `versioned_flag` stores a `bool` like `flag` in the versions before
+//!   KiCad 8, and as `(field_name yes)` after, like `hide`.
+//! - `locked` stores the `locked` flag of a board item, which KiCad kept as a
+//!   bare symbol for longer than the other flags.
+//! - `list_flag` stores an `Option<bool>` as an empty list in the versions
+//!   before KiCad 8, and as `(field_name yes)` after, like
+//!   `remove_unused_layers`.
+//! - `id` stores the `Uuid` of a board item as its `tstamp` or `uuid`,
+//!   depending on the version.
+//! - `with_name` reads the field with `FromSexprWithName` and writes it with
+//!   `ToSexprWithName`, passing the name of the field.
+//! - `try_from = "u8"` reads the token as the given type and converts it with
+//!   `TryFrom`, and converts it back with `From` when writing.
+//! - `with = "module"` hands the field to `module::parse(&mut Parser, name)`
+//!   and `module::write(&mut ListBuilder, name, &value)`.
+//! - `extra` marks an `Extra` field that keeps the lists the struct does not
+//!   know about. Structs with such a field are parsed in keyed mode, so their
+//!   fields can appear in any order, and a missing list is reported as a
+//!   missing field.
+//! - `field_order` marks a `FieldOrder` field that records the order the
+//!   fields were read in, so that they are written back the same way. It
+//!   also makes the struct keyed.
+//! - `since = "Syntax"` marks an optional field that only the versions with
+//!   the given `Syntax` have, like `embedded_files`. Reading it from an older
+//!   file is an error, or a diagnostic in lenient mode, and it is left out
+//!   when writing an older version.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, DeriveInput};
+
+use crate::attr::{Container, Conversion, Field, Kind};
+
+mod attr;
+
+#[proc_macro_derive(FromSexpr, attributes(sexpr))]
+pub fn derive_from_sexpr(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand_from_sexpr(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[proc_macro_derive(ToSexpr, attributes(sexpr))]
+pub fn derive_to_sexpr(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand_to_sexpr(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand_from_sexpr(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let container = Container::from_input(input)?;
+    let ident = &input.ident;
+    let name = &container.name;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let extra = single_field(&container, |kind| matches!(kind, Kind::Extra), "extra")?;
+    let field_order = single_field(
+        &container,
+        |kind| matches!(kind, Kind::FieldOrder),
+        "field_order",
+    )?;
+    let keyed = extra.is_some() || field_order.is_some();
+
+    let fields = container
+        .fields
+        .iter()
+        .filter(|f| !matches!(f.kind, Kind::Extra | Kind::FieldOrder))
+        .collect::<Vec<_>>();
+
+    let idents = fields.iter().map(|f| &f.ident).collect::<Vec<_>>();
+    let values = fields.iter().map(|f| parse_versioned_field(f, keyed));
+
+    let keyed = match (keyed, field_order) {
+        (false, _) => quote!(),
+        (true, None) => quote!(let mut parser = parser.keyed();),
+        (true, Some(field)) => {
+            let ident = &field.ident;
+
+            quote! {
+                let mut parser = parser.keyed();
+                let #ident = parser.field_order();
+            }
+        }
+    };
+    let end = match extra {
+        Some(field) => {
+            let ident = &field.ident;
+            quote!(let #ident = parser.expect_end_with_extra()?;)
+        }
+        None => quote!(parser.expect_end()?;),
+    };
+    let extra = field_order.iter().chain(&extra).map(|f| &f.ident);
+
+    Ok(quote! {
+        #[automatically_derived]
+        #[allow(deprecated)]
+        impl #impl_generics ::kicad_format::convert::FromSexpr for #ident #ty_generics #where_clause {
+            fn from_sexpr(
+                mut parser: ::kicad_format::convert::Parser,
+            ) -> ::std::result::Result<Self, ::kicad_format::KiCadParseError> {
+                parser.expect_symbol_matching(#name)?;
+
+                #keyed
+
+                #(
+                    let #idents = #values;
+                )*
+
+                #end
+
+                ::std::result::Result::Ok(Self { #(#idents,)* #(#extra,)* })
+            }
+        }
+
+        #[automatically_derived]
+        impl #impl_generics ::kicad_format::convert::MaybeFromSexpr for #ident #ty_generics #where_clause {
+            fn is_present(sexpr: &::kicad_sexpr::SexprList) -> bool {
+                ::kicad_format::convert::SexprListExt::first_symbol(sexpr)
+                    .is_some_and(|s| s == #name)
+            }
+        }
+    })
+}
+
+fn expand_to_sexpr(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let container = Container::from_input(input)?;
+    let ident = &input.ident;
+    let name = &container.name;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let extra = single_field(&container, |kind| matches!(kind, Kind::Extra), "extra")?;
+    let field_order = single_field(
+        &container,
+        |kind| matches!(kind, Kind::FieldOrder),
+        "field_order",
+    )?;
+
+    let items = container
+        .fields
+        .iter()
+        .filter(|f| !matches!(f.kind, Kind::Extra | Kind::FieldOrder))
+        .map(serialize_versioned_field);
+
+    let extra = extra.map(|field| {
+        let ident = &field.ident;
+        quote!(&self.#ident)
+    });
+
+    let list = match (field_order, extra) {
+        (Some(field), extra) => {
+            let ident = &field.ident;
+            let extra = extra.unwrap_or_else(|| quote!(&::kicad_format::convert::Extra::default()));
+
+            quote! {
+                s.append_list_in_order(#name, &self.#ident, #extra, |s| { #( #items )* });
+            }
+        }
+        (None, Some(extra)) => {
+            quote!(s.append_list_with_extra(#name, #extra, |s| { #( #items )* });)
+        }
+        (None, None) => quote!(s.append_list(#name, |s| { #( #items )* });),
+    };
+
+    Ok(quote! {
+        #[automatically_derived]
+        #[allow(deprecated)]
+        impl #impl_generics ::kicad_format::convert::ToSexpr for #ident #ty_generics #where_clause {
+            fn write_sexpr(&self, s: &mut ::kicad_format::convert::ListBuilder) {
+                #list
+            }
+        }
+    })
+}
+
+/// Finds the field of the given kind, of which a struct can have only one.
+fn single_field<'a>(
+    container: &'a Container,
+    is_kind: impl Fn(&Kind) -> bool,
+    attribute: &str,
+) -> syn::Result<Option<&'a Field>> {
+    let mut fields = container.fields.iter().filter(|f| is_kind(&f.kind));
+    let first = fields.next();
+
+    match fields.next() {
+        Some(field) => Err(syn::Error::new_spanned(
+            &field.ident,
+            format!("only one field can be marked `{attribute}`"),
+        )),
+        None => Ok(first),
+    }
+}
+
+/// Builds the closure that converts a token into the type of the field, if it
+/// needs converting.
+fn converter(field: &Field) -> Option<TokenStream2> {
+    let ty = &field.ty;
+
+    match &field.conversion {
+        Conversion::None => None,
+        Conversion::FromStr => Some(quote!(|v: ::std::string::String| v.parse::<#ty>())),
+        Conversion::TryFrom(_) => Some(quote!(<#ty as ::std::convert::TryFrom<_>>::try_from)),
+    }
+}
+
+/// Builds the expression that reads a field from `parser`, checking the
+/// version of the file for fields that are marked `since`.
+fn parse_versioned_field(field: &Field, keyed: bool) -> TokenStream2 {
+    let value = parse_field(field, keyed);
+
+    let Some(since) = &field.since else {
+        return value;
+    };
+
+    let name = &field.name;
+
+    quote!({
+        let value = #value;
+
+        if ::std::option::Option::is_some(&value) {
+            parser.check_syntax(::kicad_format::version::Syntax::#since, #name)?;
+        }
+
+        value
+    })
+}
+
+/// Builds the expression that reads a field from `parser`.
+///
+/// Keyed structs report a missing list as a missing field, as they cannot
+/// tell which of the remaining lists was meant to be the field.
+fn parse_field(field: &Field, keyed: bool) -> TokenStream2 {
+    let name = &field.name;
+    let ty = &field.ty;
+    let raw_ty = field.raw_type();
+
+    let (optional, repeated) = (field.optional, field.repeated);
+
+    if field.list {
+        return parse_list(field);
+    }
+
+    let raw = match (&field.kind, field.positional) {
+        (Kind::Extra | Kind::FieldOrder, _) => unreachable!(),
+        (Kind::With(with), _) => return quote!(#with::parse(&mut parser, #name)?),
+        (Kind::Flag, _) => return quote!(parser.maybe_symbol_matching(#name)),
+        (Kind::VersionedFlag, _) => return quote!(parser.maybe_flag(#name)?),
+        (Kind::Locked, _) => return quote!(parser.maybe_locked()?),
+        (Kind::ListFlag, _) => return quote!(parser.maybe_list_flag(#name)?),
+        (Kind::Id, _) => return quote!(parser.expect_id()?),
+        (Kind::Child, _) if repeated => return quote!(parser.expect_many::<#ty>()?),
+        (Kind::Child, _) if optional => return quote!(parser.maybe::<#ty>()?),
+        (Kind::Child, _) if keyed => {
+            return quote!(parser.maybe::<#ty>()?.ok_or_else(|| {
+                ::kicad_format::KiCadParseError::MissingField(
+                    ::std::string::ToString::to_string(#name),
+                )
+            })?)
+        }
+        (Kind::Child, _) => return quote!(parser.expect::<#ty>()?),
+        (Kind::WithName, _) if optional => return quote!(parser.maybe_with_name::<#ty>(#name)?),
+        (Kind::WithName, _) => return quote!(parser.expect_with_name::<#ty>(#name)?),
+
+        (Kind::Number, true) if optional => quote!(parser.maybe_number()),
+        (Kind::Number, true) => quote!(parser.expect_number()?),
+        (Kind::Number, false) if optional => quote!(parser.maybe_number_with_name(#name)?),
+        (Kind::Number, false) => quote!(parser.expect_number_with_name(#name)?),
+
+        (Kind::Integer, true) if optional => quote!(parser.maybe_integer::<#raw_ty>()?),
+        (Kind::Integer, true) => quote!(parser.expect_integer::<#raw_ty>()?),
+        (Kind::Integer, false) if optional => {
+            quote!(parser.maybe_integer_with_name::<#raw_ty>(#name)?)
+        }
+        (Kind::Integer, false) => quote!(parser.expect_integer_with_name::<#raw_ty>(#name)?),
+
+        (Kind::Length, true) if optional => quote!(parser.maybe_length()),
+        (Kind::Length, true) => quote!(parser.expect_length()?),
+        (Kind::Length, false) if optional => quote!(parser.maybe_length_with_name(#name)?),
+        (Kind::Length, false) => quote!(parser.expect_length_with_name(#name)?),
+
+        (Kind::Angle, true) if optional => quote!(parser.maybe_angle()),
+        (Kind::Angle, true) => quote!(parser.expect_angle()?),
+        (Kind::Angle, false) if optional => quote!(parser.maybe_angle_with_name(#name)?),
+        (Kind::Angle, false) => quote!(parser.expect_angle_with_name(#name)?),
+
+        (Kind::String, true) if repeated => quote!(parser.expect_many_strings()?),
+        (Kind::String, true) if optional => quote!(parser.maybe_string()),
+        (Kind::String, true) => quote!(parser.expect_string()?),
+        (Kind::String, false) if optional => quote!(parser.maybe_string_with_name(#name)?),
+        (Kind::String, false) => quote!(parser.expect_string_with_name(#name)?),
+
+        (Kind::Symbol, true) if repeated => quote!(parser.expect_many_symbols()?),
+        (Kind::Symbol, true) if optional => quote!(parser.maybe_symbol()),
+        (Kind::Symbol, true) => quote!(parser.expect_symbol()?),
+        (Kind::Symbol, false) if optional => quote!(parser.maybe_symbol_with_name(#name)?),
+        (Kind::Symbol, false) => quote!(parser.expect_symbol_with_name(#name)?),
+
+        (Kind::Bool, _) if optional => quote!(parser.maybe_bool_with_name(#name)?),
+        (Kind::Bool, _) => quote!(parser.expect_bool_with_name(#name)?),
+        (Kind::AltBool, _) if optional => quote!(parser.maybe_alt_bool_with_name(#name)?),
+        (Kind::AltBool, _) => quote!(parser.expect_alt_bool_with_name(#name)?),
+    };
+
+    let Some(convert) = converter(field) else {
+        return raw;
+    };
+
+    if repeated {
+        quote!(#raw
+            .into_iter()
+            .map(#convert)
+            .collect::<::std::result::Result<::std::vec::Vec<_>, _>>()?)
+    } else if optional {
+        quote!(#raw.map(#convert).transpose()?)
+    } else {
+        quote!((#convert)(#raw)?)
+    }
+}
+
+/// Builds the expression that reads a field whose values are stored together
+/// in a `(name ...)` list.
+fn parse_list(field: &Field) -> TokenStream2 {
+    let name = &field.name;
+    let ty = &field.ty;
+
+    let raw = match field.kind {
+        Kind::Child => quote!(list.expect_many::<#ty>()),
+        Kind::String => quote!(list.expect_many_strings()),
+        Kind::Symbol => quote!(list.expect_many_symbols()),
+        _ => unreachable!(),
+    };
+
+    let values = match converter(field) {
+        Some(convert) => quote!({
+            let values = #raw?
+                .into_iter()
+                .map(#convert)
+                .collect::<::std::result::Result<::std::vec::Vec<_>, _>>()?;
+
+            ::std::result::Result::Ok(values)
+        }),
+        None => raw,
+    };
+
+    if field.optional {
+        quote!(parser.maybe_list_with_name(#name, |list| #values)?)
+    } else {
+        quote!(parser.expect_list_with_name(#name, |list| #values)?)
+    }
+}
+
+/// Builds the statement that writes a field into the builder `s`, leaving out
+/// fields marked `since` in the versions that do not have them.
+fn serialize_versioned_field(field: &Field) -> TokenStream2 {
+    let statement = serialize_field(field);
+
+    match &field.since {
+        Some(since) => quote! {
+            if ::kicad_format::convert::writes(::kicad_format::version::Syntax::#since) {
+                #statement
+            }
+        },
+        None => statement,
+    }
+}
+
+/// Builds the statement that writes a field into the builder `s`.
+fn serialize_field(field: &Field) -> TokenStream2 {
+    let ident = &field.ident;
+    let name = &field.name;
+    let raw_ty = field.raw_type();
+
+    let value = match &field.kind {
+        Kind::With(with) => return quote!(#with::write(s, #name, &self.#ident);),
+        Kind::Flag => return quote!(s.append_symbol_if(#name, self.#ident);),
+        Kind::VersionedFlag => return quote!(s.append_flag(#name, self.#ident);),
+        Kind::Locked => return quote!(s.append_locked(self.#ident);),
+        Kind::ListFlag => return quote!(s.append_maybe_list_flag(#name, self.#ident);),
+        Kind::Id => return quote!(s.append_id(&self.#ident);),
+        _ => match &field.conversion {
+            Conversion::None => match field.kind {
+                Kind::String | Kind::Symbol => quote!(v),
+                _ => quote!(*v),
+            },
+            Conversion::FromStr => quote!(::std::string::ToString::to_string(v)),
+            Conversion::TryFrom(_) => {
+                quote!(<#raw_ty as ::std::convert::From<_>>::from(::std::clone::Clone::clone(v)))
+            }
+        },
+    };
+
+    let append = match (&field.kind, field.positional || field.list) {
+        (Kind::Number | Kind::Integer | Kind::Length | Kind::Angle, true) => {
+            quote!(s.append_number(#value))
+        }
+        (Kind::Number | Kind::Integer | Kind::Length | Kind::Angle, false) => {
+            quote!(s.append_number_with_name(#name, #value))
+        }
+        (Kind::String, true) => quote!(s.append_string(#value)),
+        (Kind::String, false) => quote!(s.append_string_with_name(#name, #value)),
+        (Kind::Symbol, true) => quote!(s.append_symbol(#value)),
+        (Kind::Symbol, false) => quote!(s.append_symbol_with_name(#name, #value)),
+        (Kind::Bool, _) => quote!(s.append_bool_with_name(#name, #value)),
+        (Kind::AltBool, _) => quote!(s.append_alt_bool_with_name(#name, #value)),
+        (Kind::Child, _) => quote!(s.append(v)),
+        (Kind::WithName, _) => quote!(s.append_with_name(#name, v)),
+        (
+            Kind::Flag
+            | Kind::VersionedFlag
+            | Kind::Locked
+            | Kind::ListFlag
+            | Kind::Id
+            | Kind::With(_)
+            | Kind::Extra
+            | Kind::FieldOrder,
+            _,
+        ) => unreachable!(),
+    };
+
+    let append = match field.list {
+        true => quote!(s.append_list(#name, |s| {
+            for v in values.iter() {
+                #append;
+            }
+        })),
+        false => append,
+    };
+
+    if field.list && field.optional {
+        quote!(if let ::std::option::Option::Some(values) = self.#ident.as_ref() { #append; })
+    } else if field.list {
+        quote!({
+            let values = &self.#ident;
+            #append;
+        })
+    } else if field.repeated {
+        quote!(for v in self.#ident.iter() { #append; })
+    } else if field.optional {
+        quote!(if let ::std::option::Option::Some(v) = self.#ident.as_ref() { #append; })
+    } else {
+        quote!({
+            let v = &self.#ident;
+            #append;
+        })
+    }
+}
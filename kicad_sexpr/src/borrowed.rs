@@ -0,0 +1,172 @@
+//! Zero-copy S-expression trees.
+//!
+//! [`from_str`](crate::from_str) allocates a new `String` for every symbol
+//! and string in the input. For batch jobs over large boards and libraries,
+//! [`from_str_borrowed`] instead produces a [`BorrowedSexpr`] tree whose
+//! symbols and strings point into the input buffer. Only strings containing
+//! escape sequences need to allocate. The tree can be converted into an owned
+//! [`Sexpr`] once it has to outlive the input.
+
+use std::borrow::Cow;
+
+use crate::{
+    sexpr::parse_complete,
+    span::{Atom, ParsedNode, Span},
+    Number, Sexpr, SexprParseError,
+};
+
+pub type BorrowedSexprList<'a> = Vec<BorrowedSexpr<'a>>;
+
+/// An S-expression that borrows its symbols and strings from the input it was
+/// parsed from. Mirrors the structure of [`Sexpr`].
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[cfg_attr(feature = "serde", serde(tag = "type"))]
+#[derive(Debug, PartialEq, Clone)]
+pub enum BorrowedSexpr<'a> {
+    List(BorrowedSexprList<'a>),
+    Number(Number),
+    String(Cow<'a, str>),
+    Symbol(&'a str),
+}
+
+impl<'a> BorrowedSexpr<'a> {
+    pub fn as_list(&self) -> Option<&BorrowedSexprList<'a>> {
+        if let Self::List(list) = self {
+            Some(list)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_number(&self) -> Option<&Number> {
+        if let Self::Number(number) = self {
+            Some(number)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_string(&self) -> Option<&str> {
+        if let Self::String(string) = self {
+            Some(string)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_symbol(&self) -> Option<&'a str> {
+        if let Self::Symbol(symbol) = self {
+            Some(symbol)
+        } else {
+            None
+        }
+    }
+
+    /// Copies this tree into an owned [`Sexpr`], leaving the borrowed tree
+    /// intact.
+    pub fn to_owned_sexpr(&self) -> Sexpr {
+        match self {
+            Self::List(list) => Sexpr::List(list.iter().map(Self::to_owned_sexpr).collect()),
+            Self::Number(number) => Sexpr::Number(*number),
+            Self::String(string) => Sexpr::String(string.to_string()),
+            Self::Symbol(symbol) => Sexpr::Symbol(symbol.to_string()),
+        }
+    }
+
+    /// Converts this tree into an owned [`Sexpr`], reusing the buffers of
+    /// strings that already had to be unescaped.
+    pub fn into_owned(self) -> Sexpr {
+        match self {
+            Self::List(list) => Sexpr::List(list.into_iter().map(Self::into_owned).collect()),
+            Self::Number(number) => Sexpr::Number(number),
+            Self::String(string) => Sexpr::String(string.into_owned()),
+            Self::Symbol(symbol) => Sexpr::Symbol(symbol.to_string()),
+        }
+    }
+}
+
+impl From<BorrowedSexpr<'_>> for Sexpr {
+    fn from(value: BorrowedSexpr<'_>) -> Self {
+        value.into_owned()
+    }
+}
+
+impl<'a> ParsedNode<'a> for BorrowedSexpr<'a> {
+    fn from_list(children: Vec<Self>, _span: Span) -> Self {
+        Self::List(children)
+    }
+
+    fn from_atom(atom: Atom<'a>, _span: Span) -> Self {
+        match atom {
+            Atom::Number(number) => Self::Number(number),
+            Atom::String(string) => Self::String(string),
+            Atom::Symbol(symbol) => Self::Symbol(symbol),
+        }
+    }
+}
+
+/// Parses the input like [`from_str`](crate::from_str), but borrows symbols
+/// and strings from `input` instead of copying them.
+pub fn from_str_borrowed(input: &str) -> Result<BorrowedSexpr<'_>, SexprParseError> {
+    parse_complete(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points_into(input: &str, value: &str) -> bool {
+        input.as_bytes().as_ptr_range().contains(&value.as_ptr())
+    }
+
+    #[test]
+    fn test_borrows_from_input() {
+        let input = r#"(property "Reference" "U1" (at 1.27 -2.54 0))"#;
+        let sexpr = from_str_borrowed(input).unwrap();
+        let list = sexpr.as_list().unwrap();
+
+        assert!(points_into(input, list[0].as_symbol().unwrap()));
+        assert!(matches!(
+            &list[1],
+            BorrowedSexpr::String(Cow::Borrowed("Reference"))
+        ));
+        assert!(matches!(
+            &list[2],
+            BorrowedSexpr::String(Cow::Borrowed("U1"))
+        ));
+        assert_eq!(
+            list[3].as_list().unwrap()[2].as_number(),
+            Some(&Number::Float(-2.54, None))
+        );
+    }
+
+    #[test]
+    fn test_allocates_for_escapes() {
+        let input = r#"(text "line one\nline two")"#;
+        let sexpr = from_str_borrowed(input).unwrap();
+
+        assert_eq!(
+            sexpr.as_list().unwrap()[1],
+            BorrowedSexpr::String(Cow::Owned("line one\nline two".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_into_owned_matches_owned_parser() {
+        let input =
+            r#"(symbol "Device:R" (pin_numbers hide) (property "Value" "R\\1" (at 0 0 90)))"#;
+        let borrowed = from_str_borrowed(input).unwrap();
+        let owned = crate::from_str(input).unwrap();
+
+        assert_eq!(borrowed.to_owned_sexpr(), owned);
+        assert_eq!(borrowed.into_owned(), owned);
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let error = from_str_borrowed("(a (b c)").unwrap_err();
+
+        assert_eq!(error.offset, 8);
+    }
+}
@@ -0,0 +1,484 @@
+//! Concrete syntax trees that preserve the original formatting.
+//!
+//! [`Sexpr`] only keeps the meaning of the input: whitespace and `#` comments
+//! are thrown away and numbers are normalized. [`from_str_cst`] instead keeps
+//! that trivia attached to the nodes of a [`Cst`], along with the original
+//! text of every atom. Writing an unmodified tree back out reproduces the
+//! input byte for byte, and edits made through the tree only change the bytes
+//! of the nodes they touch, which keeps automated edits to hand-maintained
+//! files reviewable.
+//!
+//! ```
+//! use kicad_sexpr::{from_str_cst, Sexpr};
+//!
+//! let input = "(net_class Default\n  # keep in sync with the fab\n  (clearance 0.20)\n  (trace_width 0.25))";
+//! let mut cst = from_str_cst(input).unwrap();
+//!
+//! let clearance = cst.root.as_list_mut().unwrap().find_mut("clearance").unwrap();
+//! clearance.children[1].set(Sexpr::number(0.15));
+//!
+//! assert_eq!(
+//!     cst.to_string(),
+//!     "(net_class Default\n  # keep in sync with the fab\n  (clearance 0.15)\n  (trace_width 0.25))"
+//! );
+//! ```
+
+use std::fmt::Display;
+
+use crate::{
+    format::format_compact, from_str_spanned, Sexpr, SexprParseError, SpannedSexpr,
+    SpannedSexprKind,
+};
+
+/// A piece of input between two tokens that carries no meaning.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "text"))]
+#[derive(Debug, PartialEq, Clone)]
+pub enum Trivia {
+    /// A run of spaces, tabs and line breaks.
+    Whitespace(String),
+    /// A comment line, from its `#` up to (but not including) the line break.
+    Comment(String),
+}
+
+impl Trivia {
+    pub fn text(&self) -> &str {
+        match self {
+            Self::Whitespace(text) | Self::Comment(text) => text,
+        }
+    }
+
+    /// Splits the text between two tokens into whitespace and comments.
+    fn split(text: &str) -> Vec<Self> {
+        let mut trivia = Vec::new();
+        let mut rest = text;
+
+        while !rest.is_empty() {
+            let length = if rest.starts_with('#') {
+                rest.find('\n').unwrap_or(rest.len())
+            } else {
+                rest.find('#').unwrap_or(rest.len())
+            };
+            let (piece, remainder) = rest.split_at(length);
+
+            trivia.push(if piece.starts_with('#') {
+                Self::Comment(piece.to_string())
+            } else {
+                Self::Whitespace(piece.to_string())
+            });
+            rest = remainder;
+        }
+
+        trivia
+    }
+}
+
+/// A parsed document: the root node plus whatever trivia follows it.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct Cst {
+    pub root: CstNode,
+    pub trailing: Vec<Trivia>,
+}
+
+impl Cst {
+    /// Strips the trivia, producing a regular [`Sexpr`] tree.
+    pub fn to_sexpr(&self) -> Sexpr {
+        self.root.to_sexpr()
+    }
+}
+
+impl Display for Cst {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.root.fmt(f)?;
+        write_trivia(f, &self.trailing)
+    }
+}
+
+/// A node of a [`Cst`] along with the trivia that precedes it.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct CstNode {
+    pub leading: Vec<Trivia>,
+    pub kind: CstKind,
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[cfg_attr(feature = "serde", serde(tag = "type"))]
+#[derive(Debug, PartialEq, Clone)]
+pub enum CstKind {
+    List(CstList),
+    Atom(CstAtom),
+}
+
+impl CstNode {
+    /// Builds a node without leading trivia from an S-expression. Lists are
+    /// laid out on a single line.
+    pub fn from_sexpr(sexpr: &Sexpr) -> Self {
+        let kind = match sexpr {
+            Sexpr::List(list) => CstKind::List(CstList {
+                children: list
+                    .iter()
+                    .enumerate()
+                    .map(|(i, child)| {
+                        let mut node = Self::from_sexpr(child);
+                        if i > 0 {
+                            node.leading = vec![Trivia::Whitespace(" ".to_string())];
+                        }
+                        node
+                    })
+                    .collect(),
+                trailing: Vec::new(),
+            }),
+            atom => CstKind::Atom(CstAtom {
+                value: atom.clone(),
+                text: format_compact(atom),
+            }),
+        };
+
+        Self {
+            leading: Vec::new(),
+            kind,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&CstList> {
+        if let CstKind::List(list) = &self.kind {
+            Some(list)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_list_mut(&mut self) -> Option<&mut CstList> {
+        if let CstKind::List(list) = &mut self.kind {
+            Some(list)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_atom(&self) -> Option<&CstAtom> {
+        if let CstKind::Atom(atom) = &self.kind {
+            Some(atom)
+        } else {
+            None
+        }
+    }
+
+    /// Replaces the contents of this node, keeping its leading trivia.
+    pub fn set(&mut self, value: Sexpr) {
+        self.kind = Self::from_sexpr(&value).kind;
+    }
+
+    /// Strips the trivia, producing a regular [`Sexpr`] tree.
+    pub fn to_sexpr(&self) -> Sexpr {
+        match &self.kind {
+            CstKind::List(list) => Sexpr::List(list.children.iter().map(Self::to_sexpr).collect()),
+            CstKind::Atom(atom) => atom.value.clone(),
+        }
+    }
+
+    fn from_spanned(input: &str, node: SpannedSexpr, leading: Vec<Trivia>) -> Self {
+        let kind = match node.kind {
+            SpannedSexprKind::List(list) => {
+                let mut children = Vec::with_capacity(list.len());
+                // Skip the opening paren
+                let mut position = node.span.start + 1;
+
+                for child in list {
+                    let leading = Trivia::split(&input[position..child.span.start]);
+                    position = child.span.end;
+                    children.push(Self::from_spanned(input, child, leading));
+                }
+
+                CstKind::List(CstList {
+                    children,
+                    trailing: Trivia::split(&input[position..node.span.end - 1]),
+                })
+            }
+            atom => {
+                let text = node.span.slice(input).to_string();
+                let value = SpannedSexpr {
+                    span: node.span,
+                    kind: atom,
+                }
+                .into_sexpr();
+
+                CstKind::Atom(CstAtom { value, text })
+            }
+        };
+
+        Self { leading, kind }
+    }
+}
+
+impl Display for CstNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_trivia(f, &self.leading)?;
+
+        match &self.kind {
+            CstKind::List(list) => {
+                write!(f, "(")?;
+                for child in &list.children {
+                    child.fmt(f)?;
+                }
+                write_trivia(f, &list.trailing)?;
+                write!(f, ")")
+            }
+            CstKind::Atom(atom) => write!(f, "{}", atom.text),
+        }
+    }
+}
+
+/// The children of a list node, plus the trivia before its closing paren.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct CstList {
+    pub children: Vec<CstNode>,
+    pub trailing: Vec<Trivia>,
+}
+
+impl CstList {
+    /// Returns the first child if it is a symbol, which by convention names
+    /// the list.
+    pub fn name(&self) -> Option<&str> {
+        self.children
+            .first()
+            .and_then(CstNode::as_atom)
+            .and_then(|atom| atom.value.as_symbol())
+            .map(String::as_str)
+    }
+
+    /// Finds the first child list with the given name.
+    pub fn find(&self, name: &str) -> Option<&CstList> {
+        self.children
+            .iter()
+            .filter_map(CstNode::as_list)
+            .find(|list| list.name() == Some(name))
+    }
+
+    /// Finds the first child list with the given name.
+    pub fn find_mut(&mut self, name: &str) -> Option<&mut CstList> {
+        self.children
+            .iter_mut()
+            .filter_map(CstNode::as_list_mut)
+            .find(|list| list.name() == Some(name))
+    }
+
+    /// Appends a child, indenting it like the current last child.
+    pub fn push(&mut self, value: Sexpr) {
+        let mut node = CstNode::from_sexpr(&value);
+        node.leading = self
+            .children
+            .last()
+            .map_or_else(Vec::new, |last| separator(&last.leading));
+
+        self.children.push(node);
+    }
+
+    /// Inserts a child at `index`, indenting it like the child currently at
+    /// that position.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
+    pub fn insert(&mut self, index: usize, value: Sexpr) {
+        if index == self.children.len() {
+            return self.push(value);
+        }
+
+        let mut node = CstNode::from_sexpr(&value);
+
+        if index == 0 {
+            // The new node takes over the position right after the paren
+            let next = &mut self.children[0];
+            node.leading = std::mem::take(&mut next.leading);
+            next.leading = vec![Trivia::Whitespace(" ".to_string())];
+        } else {
+            node.leading = separator(&self.children[index].leading);
+        }
+
+        self.children.insert(index, node);
+    }
+
+    /// Removes the child at `index` along with its leading trivia.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= len`.
+    pub fn remove(&mut self, index: usize) -> CstNode {
+        let mut node = self.children.remove(index);
+
+        if index == 0 {
+            // The next child moves up right after the paren
+            if let Some(next) = self.children.first_mut() {
+                next.leading = std::mem::take(&mut node.leading);
+            }
+        }
+
+        node
+    }
+}
+
+/// A number, string or symbol along with the text it was written as.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct CstAtom {
+    value: Sexpr,
+    text: String,
+}
+
+impl CstAtom {
+    /// The parsed value. Never a [`Sexpr::List`].
+    pub fn value(&self) -> &Sexpr {
+        &self.value
+    }
+
+    /// The text of the atom as it appears in the output, such as a number
+    /// with its original trailing zeros or a string with its quotes and
+    /// escapes.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+/// Parses the input into a [`Cst`] that remembers its whitespace, comments and
+/// the original text of every atom.
+pub fn from_str_cst(input: &str) -> Result<Cst, SexprParseError> {
+    let root = from_str_spanned(input)?;
+    let leading = Trivia::split(&input[..root.span.start]);
+    let end = root.span.end;
+
+    Ok(Cst {
+        root: CstNode::from_spanned(input, root, leading),
+        trailing: Trivia::split(&input[end..]),
+    })
+}
+
+/// The whitespace that separates a node from its previous sibling, without
+/// any comments attached to it.
+fn separator(leading: &[Trivia]) -> Vec<Trivia> {
+    match leading.last() {
+        Some(Trivia::Whitespace(text)) => {
+            // Only keep the indentation of the line the node starts on
+            let text = text.rfind('\n').map_or(text.as_str(), |i| &text[i..]);
+            vec![Trivia::Whitespace(text.to_string())]
+        }
+        _ => vec![Trivia::Whitespace(" ".to_string())],
+    }
+}
+
+fn write_trivia(f: &mut std::fmt::Formatter<'_>, trivia: &[Trivia]) -> std::fmt::Result {
+    for piece in trivia {
+        write!(f, "{}", piece.text())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = "# Generated by a template\n(kicad_sch (version 20231120)\n\n  # Sheet metadata\n  (paper \"A4\")\n  (lib_symbols)\n  (junction (at 100.330 50.8000) (diameter 0))\t\n  )\n# end\n";
+
+    #[test]
+    fn test_round_trip() {
+        let cst = from_str_cst(INPUT).unwrap();
+
+        assert_eq!(cst.to_string(), INPUT);
+        assert_eq!(cst.to_sexpr(), crate::from_str(INPUT).unwrap());
+    }
+
+    #[test]
+    fn test_trivia() {
+        let cst = from_str_cst(INPUT).unwrap();
+        let root = cst.root.as_list().unwrap();
+
+        assert_eq!(
+            cst.root.leading,
+            [
+                Trivia::Comment("# Generated by a template".to_string()),
+                Trivia::Whitespace("\n".to_string()),
+            ]
+        );
+        assert_eq!(
+            root.children[2].leading,
+            [
+                Trivia::Whitespace("\n\n  ".to_string()),
+                Trivia::Comment("# Sheet metadata".to_string()),
+                Trivia::Whitespace("\n  ".to_string()),
+            ]
+        );
+        assert_eq!(root.trailing, [Trivia::Whitespace("\t\n  ".to_string())]);
+        assert_eq!(
+            cst.trailing,
+            [
+                Trivia::Whitespace("\n".to_string()),
+                Trivia::Comment("# end".to_string()),
+                Trivia::Whitespace("\n".to_string()),
+            ]
+        );
+
+        let at = root.find("junction").unwrap().find("at").unwrap();
+        assert_eq!(at.children[1].as_atom().unwrap().text(), "100.330");
+        assert_eq!(at.children[2].as_atom().unwrap().text(), "50.8000");
+    }
+
+    #[test]
+    fn test_edit_only_touches_node() {
+        let mut cst = from_str_cst(INPUT).unwrap();
+        let root = cst.root.as_list_mut().unwrap();
+
+        root.find_mut("paper").unwrap().children[1].set(Sexpr::string("A3"));
+        root.find_mut("junction").unwrap().children[1]
+            .as_list_mut()
+            .unwrap()
+            .children[2]
+            .set(Sexpr::number(60.0));
+
+        assert_eq!(
+            cst.to_string(),
+            INPUT.replace("\"A4\"", "\"A3\"").replace("50.8000", "60")
+        );
+    }
+
+    #[test]
+    fn test_insert_and_remove() {
+        let mut cst = from_str_cst("(a\n  (b 1)\n  (c 2))").unwrap();
+        let root = cst.root.as_list_mut().unwrap();
+
+        root.push(Sexpr::list_with_name("d", [Some(Sexpr::number(3))]));
+        root.insert(1, Sexpr::symbol("x"));
+        assert_eq!(cst.to_string(), "(a\n  x\n  (b 1)\n  (c 2)\n  (d 3))");
+
+        let root = cst.root.as_list_mut().unwrap();
+        root.remove(2);
+        root.remove(0);
+        assert_eq!(cst.to_string(), "(x\n  (c 2)\n  (d 3))");
+
+        let root = cst.root.as_list_mut().unwrap();
+        root.insert(0, Sexpr::symbol("a"));
+        assert_eq!(cst.to_string(), "(a x\n  (c 2)\n  (d 3))");
+    }
+
+    #[test]
+    fn test_hash_symbols_are_not_comments() {
+        let cst = from_str_cst("(symbol #PWR01 (value #FF00FF))").unwrap();
+
+        assert_eq!(
+            cst.root.as_list().unwrap().children[1]
+                .as_atom()
+                .unwrap()
+                .value(),
+            &Sexpr::symbol("#PWR01")
+        );
+    }
+}
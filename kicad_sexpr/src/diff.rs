@@ -0,0 +1,650 @@
+//! Structural diffs between S-expression trees.
+//!
+//! [`diff()`] compares two trees and produces a [`Patch`]: an edit script of
+//! insertions, deletions and updates, each addressed by the path of child
+//! indices leading to the node. Children of a list are aligned with a minimal
+//! edit script, and lists that carry a `(uuid ...)` or `(tstamp ...)` child
+//! are matched by that key so that reordered or edited objects are recognised
+//! as the same object.
+//!
+//! A patch renders as a unified-style diff and can be applied back onto a
+//! tree. Applying checks that the nodes being removed or updated still look
+//! the way they did when the patch was made, so it fails cleanly instead of
+//! silently merging conflicting changes.
+//!
+//! ```
+//! use kicad_sexpr::{diff, from_str};
+//!
+//! let old = from_str(r#"(footprint "R_0603" (at 10 20) (layer "F.Cu"))"#).unwrap();
+//! let new = from_str(r#"(footprint "R_0603" (at 10 25) (layer "F.Cu") (locked))"#).unwrap();
+//!
+//! let patch = diff(&old, &new);
+//! assert_eq!(
+//!     patch.to_string(),
+//!     "@@ /2/2 @@ at/2\n-20\n+25\n@@ /4 @@ locked\n+(locked)\n"
+//! );
+//!
+//! let mut tree = old.clone();
+//! patch.apply(&mut tree).unwrap();
+//! assert_eq!(tree, new);
+//! ```
+
+use std::fmt::Display;
+
+use thiserror::Error;
+
+use crate::{
+    format::{format, format_compact},
+    FormatStyle, Sexpr,
+};
+
+/// A single change to a tree.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct Edit {
+    /// The child indices leading from the root to the node, as they are at
+    /// the time the edit is applied.
+    pub path: Vec<usize>,
+    /// A readable description of where the edit happens, such as
+    /// `footprint[uuid="..."]/at/2`.
+    pub context: String,
+    pub kind: EditKind,
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Clone)]
+pub enum EditKind {
+    /// Inserts a node so that it ends up at the path.
+    Insert(Sexpr),
+    /// Removes the node at the path, which must be equal to the given one.
+    Delete(Sexpr),
+    /// Replaces the node at the path, which must be equal to `old`.
+    Update { old: Sexpr, new: Sexpr },
+}
+
+/// Error returned when a [`Patch`] does not fit the tree it is applied to.
+#[derive(Debug, Error, PartialEq, Clone)]
+pub enum PatchError {
+    #[error("no node at path {0:?}")]
+    MissingNode(Vec<usize>),
+    #[error("the node at path {0:?} does not match the patch")]
+    Conflict(Vec<usize>),
+}
+
+/// An edit script that turns one tree into another. Edits are applied in
+/// order.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Patch {
+    pub edits: Vec<Edit>,
+}
+
+impl Patch {
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+
+    /// Applies every edit to `tree`. If an edit does not fit, the error is
+    /// returned and the edits before it stay applied.
+    pub fn apply(&self, tree: &mut Sexpr) -> Result<(), PatchError> {
+        self.edits.iter().try_for_each(|edit| edit.apply(tree))
+    }
+}
+
+impl Edit {
+    pub fn apply(&self, tree: &mut Sexpr) -> Result<(), PatchError> {
+        let missing = || PatchError::MissingNode(self.path.clone());
+        let conflict = || PatchError::Conflict(self.path.clone());
+
+        if let EditKind::Update { old, new } = &self.kind {
+            let node = node_mut(tree, &self.path).ok_or_else(missing)?;
+
+            if node != old {
+                return Err(conflict());
+            }
+
+            *node = new.clone();
+            return Ok(());
+        }
+
+        let (index, parent) = self.path.split_last().ok_or_else(missing)?;
+        let list = node_mut(tree, parent)
+            .and_then(Sexpr::as_list_mut)
+            .ok_or_else(missing)?;
+
+        match &self.kind {
+            EditKind::Insert(value) if *index <= list.len() => {
+                list.insert(*index, value.clone());
+            }
+            EditKind::Delete(value) => match list.get(*index) {
+                Some(node) if node == value => {
+                    list.remove(*index);
+                }
+                Some(_) => return Err(conflict()),
+                None => return Err(missing()),
+            },
+            _ => return Err(missing()),
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for Patch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for edit in &self.edits {
+            let path: String = edit.path.iter().map(|i| format!("/{i}")).collect();
+            writeln!(
+                f,
+                "@@ {} @@ {}",
+                if path.is_empty() { "/" } else { &path },
+                edit.context
+            )?;
+
+            let (old, new) = match &edit.kind {
+                EditKind::Insert(value) => (None, Some(value)),
+                EditKind::Delete(value) => (Some(value), None),
+                EditKind::Update { old, new } => (Some(old), Some(new)),
+            };
+
+            for (prefix, value) in [("-", old), ("+", new)] {
+                let Some(value) = value else {
+                    continue;
+                };
+
+                for line in format(value, FormatStyle::default()).lines() {
+                    writeln!(f, "{prefix}{line}")?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Computes the edits that turn `old` into `new`.
+pub fn diff(old: &Sexpr, new: &Sexpr) -> Patch {
+    let mut patch = Patch::default();
+    diff_node(old, new, &mut Vec::new(), &mut Vec::new(), &mut patch.edits);
+    patch
+}
+
+/// How a child of the old list relates to the children of the new list.
+enum Step {
+    /// Equal or keyed by the same uuid
+    Match(usize, usize),
+    /// Different, but similar enough to diff recursively
+    Pair(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+fn diff_node(
+    old: &Sexpr,
+    new: &Sexpr,
+    path: &mut Vec<usize>,
+    context: &mut Vec<String>,
+    edits: &mut Vec<Edit>,
+) {
+    if old == new {
+        return;
+    }
+
+    let (Some(old_list), Some(new_list)) = (old.as_list(), new.as_list()) else {
+        edits.push(Edit {
+            path: path.clone(),
+            context: context.join("/"),
+            kind: EditKind::Update {
+                old: old.clone(),
+                new: new.clone(),
+            },
+        });
+        return;
+    };
+
+    // `position` tracks where each child currently lives while the edits
+    // before it are applied
+    let mut position = 0;
+
+    for step in align(old_list, new_list) {
+        match step {
+            Step::Match(i, j) | Step::Pair(i, j) => {
+                path.push(position);
+                context.push(segment(&new_list[j], j));
+                diff_node(&old_list[i], &new_list[j], path, context, edits);
+                context.pop();
+                path.pop();
+                position += 1;
+            }
+            Step::Delete(i) => {
+                path.push(position);
+                context.push(segment(&old_list[i], i));
+                edits.push(Edit {
+                    path: path.clone(),
+                    context: context.join("/"),
+                    kind: EditKind::Delete(old_list[i].clone()),
+                });
+                context.pop();
+                path.pop();
+            }
+            Step::Insert(j) => {
+                path.push(position);
+                context.push(segment(&new_list[j], j));
+                edits.push(Edit {
+                    path: path.clone(),
+                    context: context.join("/"),
+                    kind: EditKind::Insert(new_list[j].clone()),
+                });
+                context.pop();
+                path.pop();
+                position += 1;
+            }
+        }
+    }
+}
+
+/// Aligns the children of two lists. Matching children are found with
+/// Myers' algorithm, and the unmatched runs in between are paired up where
+/// they can be diffed recursively.
+fn align(old: &[Sexpr], new: &[Sexpr]) -> Vec<Step> {
+    let mut steps = Vec::new();
+    let (mut i, mut j) = (0, 0);
+
+    let matches = common_subsequence(old, new);
+
+    for (next_i, next_j) in matches.into_iter().chain([(old.len(), new.len())]) {
+        // Pair up the unmatched run in order, inserting any new children that
+        // have to be skipped to find a partner
+        for (old_index, old_child) in old.iter().enumerate().take(next_i).skip(i) {
+            match (j..next_j).find(|&new_index| similar(old_child, &new[new_index])) {
+                Some(new_index) => {
+                    steps.extend((j..new_index).map(Step::Insert));
+                    steps.push(Step::Pair(old_index, new_index));
+                    j = new_index + 1;
+                }
+                None => steps.push(Step::Delete(old_index)),
+            }
+        }
+
+        steps.extend((j..next_j).map(Step::Insert));
+
+        if next_i < old.len() {
+            steps.push(Step::Match(next_i, next_j));
+        }
+
+        (i, j) = (next_i + 1, next_j + 1);
+    }
+
+    steps
+}
+
+/// Finds a longest common subsequence of `old` and `new` under [`same`] using
+/// the linear space variant of Myers' O(ND) algorithm, returned as pairs of
+/// indices.
+fn common_subsequence(old: &[Sexpr], new: &[Sexpr]) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    collect_matches(old, new, (0, 0), &mut pairs);
+    pairs
+}
+
+/// Adds the matches between `old` and `new` to `pairs`, offsetting their
+/// indices by `offset`. The middle snake of the edit script splits the lists
+/// in two halves that are solved the same way.
+fn collect_matches(
+    old: &[Sexpr],
+    new: &[Sexpr],
+    offset: (usize, usize),
+    pairs: &mut Vec<(usize, usize)>,
+) {
+    let prefix = old
+        .iter()
+        .zip(new)
+        .take_while(|(old, new)| same(old, new))
+        .count();
+    let (old, new) = (&old[prefix..], &new[prefix..]);
+    let suffix = old
+        .iter()
+        .rev()
+        .zip(new.iter().rev())
+        .take_while(|(old, new)| same(old, new))
+        .count();
+    let (old, new) = (&old[..old.len() - suffix], &new[..new.len() - suffix]);
+
+    pairs.extend((0..prefix).map(|i| (offset.0 + i, offset.1 + i)));
+
+    // With the common ends stripped, one side being empty means there is
+    // nothing left to match
+    if !old.is_empty() && !new.is_empty() {
+        let offset = (offset.0 + prefix, offset.1 + prefix);
+        let ((x, y), (u, v)) = middle_snake(old, new);
+
+        collect_matches(&old[..x], &new[..y], offset, pairs);
+        pairs.extend((0..u - x).map(|i| (offset.0 + x + i, offset.1 + y + i)));
+        collect_matches(&old[u..], &new[v..], (offset.0 + u, offset.1 + v), pairs);
+    }
+
+    let end = (offset.0 + prefix + old.len(), offset.1 + prefix + new.len());
+    pairs.extend((0..suffix).map(|i| (end.0 + i, end.1 + i)));
+}
+
+/// Finds the middle snake of a shortest edit script from `old` to `new`,
+/// which must not be empty, by searching from both ends at once until the
+/// paths overlap. Returns the start and end of the snake.
+fn middle_snake(old: &[Sexpr], new: &[Sexpr]) -> ((usize, usize), (usize, usize)) {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let delta = n - m;
+    let odd = delta % 2 != 0;
+    let max = (n + m + 1) / 2;
+    let index = |k: isize| (k + max + 1) as usize;
+
+    // The furthest `x` reached on each diagonal `k = x - y`, from the start
+    // and, on the reversed lists, from the end
+    let mut forward = vec![0isize; 2 * max as usize + 3];
+    let mut backward = vec![0isize; 2 * max as usize + 3];
+
+    for d in 0..=max {
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && forward[index(k - 1)] < forward[index(k + 1)]) {
+                forward[index(k + 1)]
+            } else {
+                forward[index(k - 1)] + 1
+            };
+            let mut y = x - k;
+            let start = (x, y);
+
+            while x < n && y < m && same(&old[x as usize], &new[y as usize]) {
+                x += 1;
+                y += 1;
+            }
+
+            forward[index(k)] = x;
+
+            // The backward search has taken d - 1 steps
+            if odd && (delta - k).abs() < d && x + backward[index(delta - k)] >= n {
+                return (
+                    (start.0 as usize, start.1 as usize),
+                    (x as usize, y as usize),
+                );
+            }
+        }
+
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && backward[index(k - 1)] < backward[index(k + 1)]) {
+                backward[index(k + 1)]
+            } else {
+                backward[index(k - 1)] + 1
+            };
+            let mut y = x - k;
+            let start = (x, y);
+
+            while x < n && y < m && same(&old[(n - x - 1) as usize], &new[(m - y - 1) as usize]) {
+                x += 1;
+                y += 1;
+            }
+
+            backward[index(k)] = x;
+
+            // The forward search has taken d steps
+            if !odd && (delta - k).abs() <= d && x + forward[index(delta - k)] >= n {
+                return (
+                    ((n - x) as usize, (m - y) as usize),
+                    ((n - start.0) as usize, (m - start.1) as usize),
+                );
+            }
+        }
+    }
+
+    unreachable!("the searches meet after at most (n + m) / 2 steps")
+}
+
+/// Two children are the same object if they share a uuid, or if neither has
+/// one and they are equal.
+fn same(old: &Sexpr, new: &Sexpr) -> bool {
+    match (key(old), key(new)) {
+        (Some(old_key), Some(new_key)) => old_key == new_key && name(old) == name(new),
+        (None, None) => old == new,
+        _ => false,
+    }
+}
+
+/// Two children are worth diffing recursively if they are both atoms, or
+/// lists of the same kind without a uuid.
+fn similar(old: &Sexpr, new: &Sexpr) -> bool {
+    match (old, new) {
+        (Sexpr::List(_), Sexpr::List(_)) => {
+            key(old).is_none() && key(new).is_none() && name(old) == name(new)
+        }
+        (Sexpr::List(_), _) | (_, Sexpr::List(_)) => false,
+        _ => true,
+    }
+}
+
+fn name(node: &Sexpr) -> Option<&String> {
+    node.as_list()?.first()?.as_symbol()
+}
+
+/// The `(uuid ...)` or `(tstamp ...)` child identifying a KiCad object.
+fn key(node: &Sexpr) -> Option<&Sexpr> {
+    node.as_list()?.iter().skip(1).find(|child| {
+        child.as_list().is_some_and(|list| {
+            list.len() == 2 && matches!(name(child).map(String::as_str), Some("uuid" | "tstamp"))
+        })
+    })
+}
+
+/// Describes a child in an edit's context.
+fn segment(node: &Sexpr, index: usize) -> String {
+    let Some(name) = name(node) else {
+        return index.to_string();
+    };
+
+    match key(node).and_then(Sexpr::as_list) {
+        Some(key) => format!(
+            "{name}[{}={}]",
+            format_compact(&key[0]),
+            format_compact(&key[1])
+        ),
+        None => name.clone(),
+    }
+}
+
+fn node_mut<'a>(tree: &'a mut Sexpr, path: &[usize]) -> Option<&'a mut Sexpr> {
+    path.iter()
+        .try_fold(tree, |node, index| node.as_list_mut()?.get_mut(*index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_str;
+
+    fn assert_applies(old: &Sexpr, new: &Sexpr) -> Patch {
+        let patch = diff(old, new);
+        let mut tree = old.clone();
+
+        patch.apply(&mut tree).unwrap();
+        assert_eq!(&tree, new, "{patch}");
+
+        patch
+    }
+
+    #[test]
+    fn test_equal_trees() {
+        let tree = from_str("(a (b 1) c)").unwrap();
+
+        assert!(diff(&tree, &tree).is_empty());
+    }
+
+    #[test]
+    fn test_matches_by_uuid() {
+        let old = from_str(
+            r#"(kicad_sch
+                (wire (pts (xy 0 0) (xy 10 0)) (uuid "a"))
+                (wire (pts (xy 0 5) (xy 10 5)) (uuid "b"))
+                (junction (at 10 0) (uuid "c")))"#,
+        )
+        .unwrap();
+        let new = from_str(
+            r#"(kicad_sch
+                (wire (pts (xy 0 5) (xy 10 5)) (uuid "b"))
+                (wire (pts (xy 0 0) (xy 20 0)) (uuid "a"))
+                (junction (at 10 0) (uuid "d")))"#,
+        )
+        .unwrap();
+
+        let patch = assert_applies(&old, &new);
+
+        assert_eq!(
+            patch.to_string(),
+            [
+                r#"@@ /1 @@ wire[uuid="a"]"#,
+                "-(wire",
+                "-\t(pts",
+                "-\t\t(xy 0 0) (xy 10 0)",
+                "-\t)",
+                "-\t(uuid \"a\")",
+                "-)",
+                r#"@@ /2 @@ junction[uuid="c"]"#,
+                "-(junction",
+                "-\t(at 10 0)",
+                "-\t(uuid \"c\")",
+                "-)",
+                r#"@@ /2 @@ wire[uuid="a"]"#,
+                "+(wire",
+                "+\t(pts",
+                "+\t\t(xy 0 0) (xy 20 0)",
+                "+\t)",
+                "+\t(uuid \"a\")",
+                "+)",
+                r#"@@ /3 @@ junction[uuid="d"]"#,
+                "+(junction",
+                "+\t(at 10 0)",
+                "+\t(uuid \"d\")",
+                "+)",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_keyed_list_diffs_recursively() {
+        let old =
+            from_str(r#"(a (symbol (at 1 2) (tstamp 1234)) (symbol (at 3 4) (tstamp 5678)))"#)
+                .unwrap();
+        let new =
+            from_str(r#"(a (symbol (at 1 2) (tstamp 1234)) (symbol (at 3 5) (tstamp 5678)))"#)
+                .unwrap();
+
+        let patch = assert_applies(&old, &new);
+
+        assert_eq!(
+            patch.edits,
+            [Edit {
+                path: vec![2, 1, 2],
+                context: "symbol[tstamp=5678]/at/2".to_string(),
+                kind: EditKind::Update {
+                    old: Sexpr::number(4),
+                    new: Sexpr::number(5),
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn test_mixed_edits_apply() {
+        let cases = [
+            ("(a b c d e)", "(a x c e f)"),
+            ("(a (b 1) (c 2) (d 3))", "(a (d 3) (b 1) (c 2))"),
+            ("(a (b 1 2) 3)", "(a 3 (b 1 2))"),
+            ("(a (b (c (d 1))))", "(a (b (c (d 2) (e 3))))"),
+            ("(a b)", "(x (y z) w v)"),
+            ("(a (b 1))", "(a)"),
+            ("(a)", "(a (b 1) (b 2) (b 3))"),
+        ];
+
+        for (old, new) in cases {
+            assert_applies(&from_str(old).unwrap(), &from_str(new).unwrap());
+        }
+
+        assert_applies(&Sexpr::symbol("a"), &from_str("(a)").unwrap());
+    }
+
+    #[test]
+    fn test_common_subsequence_is_longest() {
+        // Compare with the quadratic dynamic programming solution on small
+        // pseudo-random lists of few distinct symbols
+        let mut seed = 0x2545_f491_u32;
+        let mut list = |len: u32| {
+            (0..len)
+                .map(|_| {
+                    seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                    Sexpr::symbol(["a", "b", "c"][(seed >> 16) as usize % 3])
+                })
+                .collect::<Vec<_>>()
+        };
+
+        for round in 0..200 {
+            let old = list(round % 13);
+            let new = list(round % 7 + round % 5);
+
+            let mut lengths = vec![vec![0; new.len() + 1]; old.len() + 1];
+            for i in (0..old.len()).rev() {
+                for j in (0..new.len()).rev() {
+                    lengths[i][j] = if old[i] == new[j] {
+                        lengths[i + 1][j + 1] + 1
+                    } else {
+                        lengths[i + 1][j].max(lengths[i][j + 1])
+                    };
+                }
+            }
+
+            let pairs = common_subsequence(&old, &new);
+
+            assert_eq!(pairs.len(), lengths[0][0], "{old:?} {new:?}");
+            assert!(pairs.windows(2).all(|w| w[0].0 < w[1].0 && w[0].1 < w[1].1));
+            assert!(pairs.iter().all(|&(i, j)| old[i] == new[j]));
+        }
+    }
+
+    #[test]
+    fn test_diff_long_lists() {
+        let item = |i: usize| Sexpr::List(vec![Sexpr::symbol("item"), Sexpr::number(i as i64)]);
+
+        // Every third child changes and every seventh is new
+        let old = Sexpr::List((0..5000).map(item).collect());
+        let new = Sexpr::List(
+            (0..5000)
+                .flat_map(|i| match (i % 3, i % 7) {
+                    (0, 0) => vec![item(i + 100_000), item(i)],
+                    (0, _) => vec![item(i + 100_000)],
+                    (_, 0) => vec![item(i + 200_000), item(i)],
+                    _ => vec![item(i)],
+                })
+                .collect(),
+        );
+
+        let patch = assert_applies(&old, &new);
+
+        assert!(!patch.is_empty());
+    }
+
+    #[test]
+    fn test_apply_conflict() {
+        let old = from_str("(a (b 1) (c 2))").unwrap();
+        let new = from_str("(a (b 1))").unwrap();
+        let patch = diff(&old, &new);
+
+        let mut other = from_str("(a (b 1) (c 3))").unwrap();
+        assert_eq!(patch.apply(&mut other), Err(PatchError::Conflict(vec![2])));
+
+        let mut other = from_str("(a (b 1))").unwrap();
+        assert_eq!(
+            patch.apply(&mut other),
+            Err(PatchError::MissingNode(vec![2]))
+        );
+    }
+}
@@ -0,0 +1,739 @@
+//! KiCad compatible pretty printing.
+//!
+//! KiCad has changed how it lays out its files between major versions. The
+//! [`FormatStyle`] presets in this module reproduce those layouts so that
+//! re-serializing a parsed file leaves untouched regions byte-identical:
+//!
+//! - [`FormatStyle::KiCad8`] is a port of the `KICAD_FORMAT::Prettify` pass
+//!   that KiCad 8 (and later) runs over every file it writes: tab
+//!   indentation, every list on its own line, runs of `(xy ..)` points packed
+//!   onto shared lines.
+//! - [`FormatStyle::KiCad7`] follows the hand-written layout of the KiCad 7
+//!   `Format()` functions: two space indentation, with short attribute lists
+//!   kept on the same line as their parent. KiCad 7 decides this per token,
+//!   so this style is driven by a table of the tokens known to this crate.
+//! - [`FormatStyle::Tree`] is the original layout of this crate, which puts
+//!   every nested list on a new line.
+//!
+//! None of the styles append a trailing newline; KiCad always ends its files
+//! with one, so file level serializers should add it themselves.
+//!
+//! Numbers are written through their [`Display`](std::fmt::Display)
+//! implementation. KiCad writes a few values with a fixed number of decimals
+//! (for example `(color 0 0 0 0.0000)`); parsed numbers remember that count
+//! and are written back unchanged, while new values use the shortest form.
+
+use std::io::{self, Write};
+
+use crate::{stream::Writer, Number, Sexpr};
+
+/// Layout presets for [`format()`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum FormatStyle {
+    /// Every nested list on a new line, indented by two spaces per level.
+    Tree,
+    /// The layout written by KiCad 7.
+    KiCad7,
+    /// The layout written by KiCad 8 and later.
+    #[default]
+    KiCad8,
+    /// KiCad 8 layout with the "compact save" advanced option enabled, which
+    /// keeps `font`, `stroke`, `fill`, `offset`, `rotate` and `scale` lists on
+    /// a single line.
+    KiCad8Compact,
+}
+
+/// Formats an S-expression tree according to the given [`FormatStyle`].
+pub fn format(sexpr: &Sexpr, style: FormatStyle) -> String {
+    let mut writer = Writer::with_style(Vec::new(), style);
+
+    writer
+        .write_sexpr(sexpr)
+        .expect("writing to a Vec never fails");
+
+    let output = writer.finish().expect("writing to a Vec never fails");
+
+    String::from_utf8(output).expect("the formatter only writes valid UTF-8")
+}
+
+/// Formats an atom the way KiCad writes it. Lists are formatted on a single
+/// line.
+pub fn format_compact(sexpr: &Sexpr) -> String {
+    let mut output = String::new();
+    write_compact(&mut output, sexpr);
+    output
+}
+
+fn write_compact(output: &mut String, sexpr: &Sexpr) {
+    match sexpr {
+        Sexpr::List(list) => {
+            output.push('(');
+
+            for (i, child) in list.iter().enumerate() {
+                if i != 0 {
+                    output.push(' ');
+                }
+
+                write_compact(output, child);
+            }
+
+            output.push(')');
+        }
+        Sexpr::Number(number) => write_number(output, number),
+        Sexpr::String(string) => write_quoted(output, string),
+        Sexpr::Symbol(symbol) => output.push_str(symbol),
+    }
+}
+
+fn write_number(output: &mut String, number: &Number) {
+    output.push_str(&number.to_string());
+}
+
+/// Quotes a string the way KiCad's `OUTPUTFORMATTER::Quotes` does: only
+/// backslashes, double quotes and line breaks are escaped.
+fn write_quoted(output: &mut String, string: &str) {
+    output.push('"');
+
+    for c in string.chars() {
+        match c {
+            '\\' => output.push_str("\\\\"),
+            '"' => output.push_str("\\\""),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            c => output.push(c),
+        }
+    }
+
+    output.push('"');
+}
+
+/// The layout engine behind [`format()`] and [`Writer`].
+///
+/// The engines are driven one token at a time so that output can be streamed.
+/// Every style decides where a list goes based on its first token, so a list
+/// is only opened once its head is known.
+pub(crate) enum Layout {
+    Tree(tree::Layout),
+    KiCad7(kicad7::Layout),
+    KiCad8(kicad8::Layout),
+}
+
+impl Layout {
+    pub(crate) fn new(style: FormatStyle) -> Self {
+        match style {
+            FormatStyle::Tree => Self::Tree(tree::Layout::default()),
+            FormatStyle::KiCad7 => Self::KiCad7(kicad7::Layout::default()),
+            FormatStyle::KiCad8 => Self::KiCad8(kicad8::Layout::new(false)),
+            FormatStyle::KiCad8Compact => Self::KiCad8(kicad8::Layout::new(true)),
+        }
+    }
+
+    /// Opens a list. `head` is the list's first token if it is a symbol; the
+    /// token itself is written afterwards with [`Layout::atom`].
+    pub(crate) fn open_list(
+        &mut self,
+        output: &mut impl Write,
+        head: Option<&str>,
+    ) -> io::Result<()> {
+        match self {
+            Self::Tree(layout) => layout.open_list(output),
+            Self::KiCad7(layout) => layout.open_list(output, head),
+            Self::KiCad8(layout) => layout.open_list(output, head),
+        }
+    }
+
+    /// Writes an atom that has already been formatted with
+    /// [`format_compact`].
+    pub(crate) fn atom(&mut self, output: &mut impl Write, text: &str) -> io::Result<()> {
+        match self {
+            Self::Tree(layout) => layout.atom(output, text),
+            Self::KiCad7(layout) => layout.atom(output, text),
+            Self::KiCad8(layout) => layout.atom(output, text),
+        }
+    }
+
+    pub(crate) fn close_list(&mut self, output: &mut impl Write) -> io::Result<()> {
+        match self {
+            Self::Tree(layout) => layout.close_list(output),
+            Self::KiCad7(layout) => layout.close_list(output),
+            Self::KiCad8(layout) => layout.close_list(output),
+        }
+    }
+}
+
+/// The original layout of this crate.
+mod tree {
+    use std::io::{self, Write};
+
+    #[derive(Default)]
+    pub(crate) struct Layout {
+        /// The number of children written so far in each open list.
+        children: Vec<usize>,
+    }
+
+    impl Layout {
+        fn separate(&mut self, output: &mut impl Write) -> io::Result<()> {
+            if let Some(children) = self.children.last_mut() {
+                if *children != 0 {
+                    output.write_all(b" ")?;
+                }
+
+                *children += 1;
+            }
+
+            Ok(())
+        }
+
+        pub(super) fn open_list(&mut self, output: &mut impl Write) -> io::Result<()> {
+            self.separate(output)?;
+
+            let depth = self.children.len();
+
+            if depth != 0 {
+                write!(output, "\n{}", "  ".repeat(depth))?;
+            }
+
+            self.children.push(0);
+
+            output.write_all(b"(")
+        }
+
+        pub(super) fn atom(&mut self, output: &mut impl Write, text: &str) -> io::Result<()> {
+            self.separate(output)?;
+
+            output.write_all(text.as_bytes())
+        }
+
+        pub(super) fn close_list(&mut self, output: &mut impl Write) -> io::Result<()> {
+            self.children.pop();
+
+            output.write_all(b")")
+        }
+    }
+}
+
+/// Port of `KICAD_FORMAT::Prettify` from KiCad 8.
+///
+/// Upstream runs as a second pass over the text of a file written on a single
+/// line. Here the same state machine is fed tokens instead of characters,
+/// which gives identical output without buffering the whole file.
+mod kicad8 {
+    use std::io::{self, Write};
+
+    const INDENT_CHAR: char = '\t';
+    const INDENT_SIZE: usize = 1;
+
+    // Runs of (xy ..) points are kept on a single line until this column
+    const XY_SPECIAL_CASE_COLUMN_LIMIT: usize = 99;
+
+    // Whitespace inside a list past this column becomes a newline
+    const CONSECUTIVE_TOKEN_WRAP_THRESHOLD: usize = 72;
+
+    /// Lists that the "compact save" option keeps on a single line. Upstream
+    /// compares only the alphabetic prefix of the token.
+    const SHORT_FORM_TOKENS: &[&str] = &["font", "stroke", "fill", "offset", "rotate", "scale"];
+
+    #[derive(PartialEq, Eq, Clone, Copy)]
+    enum Token {
+        None,
+        Open,
+        Close,
+        Atom,
+    }
+
+    pub(crate) struct Layout {
+        compact_save: bool,
+        list_depth: usize,
+        last_token: Token,
+        in_multi_line_list: bool,
+        in_xy: bool,
+        in_short_form: bool,
+        short_form_depth: usize,
+        column: usize,
+    }
+
+    fn indent(depth: usize) -> String {
+        INDENT_CHAR.to_string().repeat(depth * INDENT_SIZE)
+    }
+
+    impl Layout {
+        pub(super) fn new(compact_save: bool) -> Self {
+            Self {
+                compact_save,
+                list_depth: 0,
+                last_token: Token::None,
+                in_multi_line_list: false,
+                in_xy: false,
+                in_short_form: false,
+                short_form_depth: 0,
+                column: 0,
+            }
+        }
+
+        pub(super) fn open_list(
+            &mut self,
+            output: &mut impl Write,
+            head: Option<&str>,
+        ) -> io::Result<()> {
+            let current_is_xy = head == Some("xy");
+            let current_is_short_form = self.compact_save
+                && head.is_some_and(|head| {
+                    let token = head.split(|c: char| !c.is_ascii_alphabetic()).next();
+                    token.is_some_and(|token| SHORT_FORM_TOKENS.contains(&token))
+                });
+
+            if self.last_token == Token::None {
+                output.write_all(b"(")?;
+                self.column += 1;
+            } else if (self.in_xy && current_is_xy && self.column < XY_SPECIAL_CASE_COLUMN_LIMIT)
+                || self.in_short_form
+            {
+                output.write_all(b" (")?;
+                self.column += 2;
+            } else {
+                write!(output, "\n{}(", indent(self.list_depth))?;
+                self.column = self.list_depth * INDENT_SIZE + 1;
+            }
+
+            self.in_xy = current_is_xy;
+
+            if current_is_short_form {
+                self.in_short_form = true;
+                self.short_form_depth = self.list_depth;
+            }
+
+            self.list_depth += 1;
+            self.last_token = Token::Open;
+
+            Ok(())
+        }
+
+        pub(super) fn atom(&mut self, output: &mut impl Write, text: &str) -> io::Result<()> {
+            // The whitespace between two tokens of a list
+            if self.list_depth > 0 && matches!(self.last_token, Token::Close | Token::Atom) {
+                if self.in_xy || self.column < CONSECUTIVE_TOKEN_WRAP_THRESHOLD {
+                    output.write_all(b" ")?;
+                    self.column += 1;
+                } else if self.in_short_form {
+                    output.write_all(b" ")?;
+                } else {
+                    write!(output, "\n{}", indent(self.list_depth))?;
+                    self.column = self.list_depth * INDENT_SIZE;
+                    self.in_multi_line_list = true;
+                }
+            }
+
+            output.write_all(text.as_bytes())?;
+
+            // Columns are counted in bytes, like the upstream implementation
+            self.column += text.len();
+            self.last_token = Token::Atom;
+
+            Ok(())
+        }
+
+        pub(super) fn close_list(&mut self, output: &mut impl Write) -> io::Result<()> {
+            self.list_depth = self.list_depth.saturating_sub(1);
+
+            if self.in_short_form {
+                output.write_all(b")")?;
+                self.column += 1;
+            } else if self.last_token == Token::Close || self.in_multi_line_list {
+                write!(output, "\n{})", indent(self.list_depth))?;
+                self.column = self.list_depth * INDENT_SIZE + 1;
+                self.in_multi_line_list = false;
+            } else {
+                output.write_all(b")")?;
+                self.column += 1;
+            }
+
+            if self.short_form_depth == self.list_depth {
+                self.in_short_form = false;
+                self.short_form_depth = 0;
+            }
+
+            self.last_token = Token::Close;
+
+            Ok(())
+        }
+    }
+}
+
+/// Table driven reproduction of the KiCad 7 layout.
+mod kicad7 {
+    use std::io::{self, Write};
+
+    const INDENT: &str = "  ";
+
+    /// Where a child list is placed relative to its parent.
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    enum Placement {
+        /// On the same line as the previous token.
+        Inline,
+        /// On a new line one level deeper. The parent's closing paren goes on
+        /// its own line.
+        Block,
+        /// On a new line one level deeper, but the following tokens and the
+        /// parent's closing paren continue on that line.
+        Wrap,
+        /// Like [`Placement::Block`] but two levels deeper (used for the
+        /// effects of footprint text).
+        DeepBlock,
+    }
+
+    /// Top level sections of a board that KiCad 7 separates with an empty
+    /// line when a new section starts.
+    fn pcb_section(head: &str) -> Option<&str> {
+        match head {
+            "general" | "paper" | "title_block" | "setup" | "property" | "net" | "footprint"
+            | "zone" | "group" => Some(head),
+            "gr_line" | "gr_rect" | "gr_circle" | "gr_arc" | "gr_poly" | "gr_curve" | "gr_text"
+            | "gr_text_box" | "image" | "dimension" | "target" => Some("drawings"),
+            "segment" | "arc" | "via" => Some("tracks"),
+            _ => None,
+        }
+    }
+
+    /// The number of empty lines written before a child list that is not
+    /// placed inline.
+    fn blank_lines(parent: &str, child: &str, previous: Option<&str>) -> usize {
+        match (parent, child) {
+            // Every footprint gets its own empty line
+            ("kicad_pcb", c) if pcb_section(c).is_some() => {
+                usize::from(c == "footprint" || previous.and_then(pcb_section) != pcb_section(c))
+            }
+
+            // Schematic items are grouped by kind, with an empty line before
+            // each group. Symbols and sheets always get their own empty line,
+            // and the library symbols section is always followed by one.
+            ("kicad_sch", c) => {
+                // Buses and graphic lines continue a group of wires, but not
+                // the other way around
+                let new_group = match c {
+                    "bus" | "polyline" => !matches!(previous, Some("wire")) && previous != Some(c),
+                    c => previous != Some(c),
+                };
+
+                match previous {
+                    Some("lib_symbols") => 1 + usize::from(c == "symbol" || c == "sheet"),
+                    _ => usize::from(new_group || c == "symbol" || c == "sheet"),
+                }
+            }
+
+            ("fp_poly" | "gr_poly", "stroke") => 1,
+
+            _ => 0,
+        }
+    }
+
+    fn placement(
+        parent: &str,
+        grandparent: Option<&str>,
+        child: &str,
+        previous: Option<&str>,
+    ) -> Placement {
+        use Placement::*;
+
+        match (parent, child) {
+            // File headers keep the version and generator on the first line
+            (_, "version" | "generator") if grandparent.is_none() => Inline,
+
+            ("kicad_pcb" | "kicad_sch" | "kicad_symbol_lib" | "lib_symbols", _) => Block,
+
+            // Footprints in a board start with their layer inline, while
+            // footprint files put it on its own line
+            ("footprint", "layer") => {
+                if grandparent.is_none() {
+                    Block
+                } else {
+                    Inline
+                }
+            }
+            ("footprint", _) => Block,
+
+            ("fp_text", "effects") => DeepBlock,
+            ("fp_text", "tstamp" | "uuid") => Block,
+            ("gr_text" | "fp_text_box" | "gr_text_box", "effects" | "render_cache") => Block,
+
+            (
+                "fp_line" | "fp_rect" | "fp_circle" | "fp_arc" | "gr_line" | "gr_rect"
+                | "gr_circle" | "gr_arc",
+                "stroke",
+            ) => Wrap,
+            ("fp_poly" | "gr_poly", "pts") => Block,
+            ("fp_poly" | "gr_poly", "stroke") => Wrap,
+
+            ("pad", "net") => Wrap,
+            ("pad", "primitives" | "options") => Block,
+
+            ("zone", c) => match c {
+                "net" | "net_name" | "layer" | "layers" | "tstamp" | "uuid" | "hatch" | "name" => {
+                    Inline
+                }
+                "filled_areas_thickness" if previous == Some("min_thickness") => Inline,
+                _ => Block,
+            },
+
+            ("placed_symbol", c) => match c {
+                "lib_name" | "lib_id" | "at" | "unit" | "mirror" | "convert" | "on_board"
+                | "dnp" | "fields_autoplaced" => Inline,
+                _ => Block,
+            },
+            ("symbol", c) => match c {
+                "pin_numbers" | "pin_names" | "in_bom" | "on_board" | "power" | "extends"
+                | "exclude_from_sim" => Inline,
+                _ => Block,
+            },
+            ("sheet", "at" | "size") => Inline,
+            ("path", "reference")
+                if matches!(grandparent, Some("project" | "symbol_instances")) =>
+            {
+                Block
+            }
+            ("no_connect", "uuid") => Inline,
+            ("pin", "effects" | "uuid") if grandparent == Some("sheet") => Block,
+            ("text_box", "at") if grandparent == Some("kicad_sch") => Block,
+            ("polyline", "pts") if grandparent == Some("kicad_sch") => Inline,
+
+            ("property", "effects") => Block,
+            ("pin", "name" | "number" | "alternate") => Block,
+            ("name" | "number", "effects") => Inline,
+
+            (
+                "layers" | "setup" | "stackup" | "pcbplotparams" | "general" | "title_block"
+                | "model" | "polygon" | "filled_polygon" | "pts" | "instances" | "project"
+                | "sheet_instances" | "symbol_instances" | "sheet" | "fill_segments",
+                _,
+            ) => Block,
+
+            (
+                "polyline" | "rectangle" | "circle" | "arc" | "bezier" | "text" | "text_box",
+                "stroke" | "fill" | "effects" | "pts" | "uuid",
+            ) if grandparent != Some("kicad_pcb") => Block,
+
+            (
+                "wire" | "bus" | "polyline" | "label" | "global_label" | "hierarchical_label"
+                | "junction" | "no_connect" | "bus_entry",
+                "stroke" | "effects" | "uuid",
+            ) => Block,
+
+            _ => Inline,
+        }
+    }
+
+    /// An open list.
+    struct Frame {
+        /// The list's token, used to look up where its children go.
+        token: String,
+        /// The number of tokens written so far, including the head.
+        children: usize,
+        /// The token of the previous child list.
+        previous: Option<String>,
+        /// Whether the closing paren goes on its own line.
+        multi_line: bool,
+        depth: usize,
+        /// Everything below an inline list stays on the same line.
+        inline: bool,
+    }
+
+    impl Frame {
+        fn new(token: &str, depth: usize, inline: bool) -> Self {
+            Self {
+                token: token.to_string(),
+                children: 0,
+                previous: None,
+                // Section lists that are written with their closing paren on
+                // its own line even when empty
+                multi_line: token == "lib_symbols",
+                depth,
+                inline,
+            }
+        }
+    }
+
+    #[derive(Default)]
+    pub(crate) struct Layout {
+        frames: Vec<Frame>,
+    }
+
+    impl Layout {
+        pub(super) fn open_list(
+            &mut self,
+            output: &mut impl Write,
+            head: Option<&str>,
+        ) -> io::Result<()> {
+            // Lists that do not start with a symbol (like the numbered
+            // entries of `layers`) are looked up with an empty token
+            let token = head.unwrap_or_default();
+
+            let Some((parent, ancestors)) = self.frames.split_last_mut() else {
+                self.frames.push(Frame::new(token, 0, false));
+                return output.write_all(b"(");
+            };
+
+            if parent.inline {
+                if parent.children != 0 {
+                    output.write_all(b" ")?;
+                }
+
+                parent.children += 1;
+
+                let frame = Frame::new(token, parent.depth, true);
+                self.frames.push(frame);
+
+                return output.write_all(b"(");
+            }
+
+            // Placed schematic symbols share their token with library symbols
+            // but are laid out differently
+            if parent.token == "symbol"
+                && parent.children == 1
+                && matches!(token, "lib_id" | "lib_name")
+            {
+                parent.token = "placed_symbol".to_string();
+            }
+
+            let grandparent = ancestors.last().map(|frame| frame.token.as_str());
+            let placement = placement(
+                &parent.token,
+                grandparent,
+                token,
+                parent.previous.as_deref(),
+            );
+
+            let depth = match placement {
+                Placement::Inline => {
+                    if parent.children != 0 {
+                        output.write_all(b" ")?;
+                    }
+
+                    parent.depth
+                }
+                Placement::Block | Placement::Wrap => {
+                    for _ in 0..blank_lines(&parent.token, token, parent.previous.as_deref()) {
+                        output.write_all(b"\n")?;
+                    }
+
+                    write!(output, "\n{}", INDENT.repeat(parent.depth + 1))?;
+
+                    // Only the last line break decides whether the closing
+                    // paren gets its own line
+                    parent.multi_line = placement == Placement::Block;
+
+                    parent.depth + 1
+                }
+                Placement::DeepBlock => {
+                    write!(output, "\n{}", INDENT.repeat(parent.depth + 2))?;
+
+                    parent.multi_line = true;
+
+                    parent.depth + 2
+                }
+            };
+
+            parent.children += 1;
+            parent.previous = Some(token.to_string());
+
+            self.frames
+                .push(Frame::new(token, depth, placement == Placement::Inline));
+
+            output.write_all(b"(")
+        }
+
+        pub(super) fn atom(&mut self, output: &mut impl Write, text: &str) -> io::Result<()> {
+            if let Some(frame) = self.frames.last_mut() {
+                if frame.children != 0 {
+                    output.write_all(b" ")?;
+                }
+
+                frame.children += 1;
+            }
+
+            output.write_all(text.as_bytes())
+        }
+
+        pub(super) fn close_list(&mut self, output: &mut impl Write) -> io::Result<()> {
+            if let Some(frame) = self.frames.pop() {
+                if !frame.inline && frame.multi_line {
+                    write!(output, "\n{}", INDENT.repeat(frame.depth))?;
+                }
+            }
+
+            output.write_all(b")")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_str;
+
+    fn assert_formatted(input: &str, style: FormatStyle, expected: &str) {
+        let sexpr = from_str(input).unwrap();
+
+        assert_eq!(format(&sexpr, style), expected);
+    }
+
+    #[test]
+    fn test_kicad8_packs_xy_points() {
+        assert_formatted(
+            "(polyline (pts (xy 0 0) (xy 1.5 -1)) (stroke (width 0) (type default)))",
+            FormatStyle::KiCad8,
+            "(polyline\n\t(pts\n\t\t(xy 0 0) (xy 1.5 -1)\n\t)\n\t(stroke\n\t\t(width 0)\n\t\t(type default)\n\t)\n)",
+        );
+    }
+
+    #[test]
+    fn test_kicad8_wraps_long_lists() {
+        assert_formatted(
+            "(layers \"F.Cu\" \"B.Cu\" \"F.Paste\" \"B.Paste\" \"F.Mask\" \"B.Mask\" \"F.SilkS\" \"B.SilkS\" \"Edge.Cuts\")",
+            FormatStyle::KiCad8,
+            "(layers \"F.Cu\" \"B.Cu\" \"F.Paste\" \"B.Paste\" \"F.Mask\" \"B.Mask\" \"F.SilkS\" \"B.SilkS\"\n\t\"Edge.Cuts\"\n)",
+        );
+    }
+
+    #[test]
+    fn test_kicad8_compact_save() {
+        assert_formatted(
+            "(effects (font (size 1.27 1.27)) (justify left))",
+            FormatStyle::KiCad8Compact,
+            "(effects\n\t(font (size 1.27 1.27))\n\t(justify left)\n)",
+        );
+    }
+
+    #[test]
+    fn test_kicad7_footprint() {
+        assert_formatted(
+            "(footprint \"R\" (layer \"F.Cu\") (fp_line (start 0 0) (end 1 1) (stroke (width 0.1) (type solid)) (layer \"F.SilkS\") (tstamp 1234)))",
+            FormatStyle::KiCad7,
+            "(footprint \"R\"\n  (layer \"F.Cu\")\n  (fp_line (start 0 0) (end 1 1)\n    (stroke (width 0.1) (type solid)) (layer \"F.SilkS\") (tstamp 1234))\n)",
+        );
+    }
+
+    #[test]
+    fn test_kicad7_schematic_groups() {
+        assert_formatted(
+            "(kicad_sch (version 20230121) (generator eeschema) (no_connect (at 0 0) (uuid 1)) (wire (pts (xy 0 0) (xy 1 0)) (stroke (width 0) (type default)) (uuid 2)) (bus (pts (xy 0 0) (xy 0 1)) (stroke (width 0) (type default)) (uuid 3)))",
+            FormatStyle::KiCad7,
+            "(kicad_sch (version 20230121) (generator eeschema)\n\n  (no_connect (at 0 0) (uuid 1))\n\n  (wire (pts (xy 0 0) (xy 1 0))\n    (stroke (width 0) (type default))\n    (uuid 2)\n  )\n  (bus (pts (xy 0 0) (xy 0 1))\n    (stroke (width 0) (type default))\n    (uuid 3)\n  )\n)",
+        );
+    }
+
+    #[test]
+    fn test_tree() {
+        assert_formatted(
+            "(a b (c 1) \"d\")",
+            FormatStyle::Tree,
+            "(a b \n  (c 1) \"d\")",
+        );
+    }
+
+    #[test]
+    fn test_quoting() {
+        assert_eq!(
+            format_compact(&Sexpr::string("say \"hi\"\\\nbye")),
+            r#""say \"hi\"\\\nbye""#
+        );
+    }
+}
@@ -1,5 +1,29 @@
 pub(crate) mod number;
+pub use number::Number;
 pub(crate) mod string;
 
+pub mod borrowed;
+pub use borrowed::*;
+
+pub mod cst;
+pub use cst::*;
+
+pub mod diff;
+pub use diff::{diff, Patch};
+
+pub mod format;
+pub use format::{format, FormatStyle};
+
+pub mod select;
+pub use select::{Selector, SelectorParseError};
+
+#[cfg(feature = "serde")]
+pub mod serde;
+
 pub mod sexpr;
 pub use sexpr::*;
+
+pub mod span;
+pub use span::*;
+
+pub mod stream;
@@ -1,3 +1,12 @@
+//! Lossless numeric atoms.
+//!
+//! KiCad writes integers (file versions, net codes, enum ordinals) and decimal
+//! millimetre values side by side. Both are kept in a [`Number`] without going
+//! through `f32`, so that re-serializing a parsed file reproduces the original
+//! text.
+
+use std::fmt::Display;
+
 use nom::{
     branch::alt,
     bytes::complete::tag,
@@ -12,7 +21,141 @@ fn is_digit(c: char) -> bool {
     c.is_ascii_digit()
 }
 
-fn parse_float(input: &str) -> IResult<&str, f32> {
+/// A number parsed from (or destined for) an S-expression.
+///
+/// Values written without a decimal point are stored as [`Number::Integer`],
+/// everything else as [`Number::Float`]. Equality compares the numeric value,
+/// so `Integer(1) == Float(1.0, None)`.
+#[derive(Debug, Clone, Copy)]
+pub enum Number {
+    Integer(i64),
+    /// A decimal value, along with the number of digits after the decimal
+    /// point it was written with. KiCad writes some values with a fixed
+    /// number of decimals, like `12.000000` or `0.0000`, and keeping the
+    /// count writes them back the same way. Without a count, the value is
+    /// written in its shortest form.
+    Float(f64, Option<u8>),
+}
+
+impl Number {
+    pub fn to_f64(self) -> f64 {
+        match self {
+            Number::Integer(i) => i as f64,
+            Number::Float(f, _) => f,
+        }
+    }
+
+    pub fn to_f32(self) -> f32 {
+        self.to_f64() as f32
+    }
+
+    /// Returns the value as an integer if it has no fractional part.
+    pub fn to_integer(self) -> Option<i64> {
+        match self {
+            Number::Integer(i) => Some(i),
+            Number::Float(f, _) if f.fract() == 0.0 && f.abs() < i64::MAX as f64 => Some(f as i64),
+            Number::Float(..) => None,
+        }
+    }
+}
+
+impl PartialEq for Number {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Number::Integer(a), Number::Integer(b)) => a == b,
+            _ => self.to_f64() == other.to_f64(),
+        }
+    }
+}
+
+impl Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Number::Integer(i) => write!(f, "{i}"),
+            Number::Float(n, Some(decimals)) => write!(f, "{n:.*}", *decimals as usize),
+            // KiCad never writes a trailing `.0` on its own, so whole floats
+            // are printed as integers. `f64`'s `Display` already produces the
+            // shortest representation that round-trips, without an exponent.
+            Number::Float(n, None) if n.fract() == 0.0 && n.abs() < 1e15 => {
+                write!(f, "{}", *n as i64)
+            }
+            Number::Float(n, None) => write!(f, "{n}"),
+        }
+    }
+}
+
+impl From<f32> for Number {
+    /// Converts through the shortest decimal representation of the `f32`, so
+    /// that e.g. `0.1f32` becomes `0.1` rather than `0.10000000149011612`.
+    fn from(value: f32) -> Self {
+        Number::Float(value.to_string().parse().unwrap_or(value as f64), None)
+    }
+}
+
+impl From<f64> for Number {
+    fn from(value: f64) -> Self {
+        Number::Float(value, None)
+    }
+}
+
+// Numbers are serialized as plain numbers. The decimal count is a detail of
+// the S-expression text, so it is not kept.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Number {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            Number::Integer(i) => serializer.serialize_i64(i),
+            Number::Float(f, _) => serializer.serialize_f64(f),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Number {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct NumberVisitor;
+
+        impl serde::de::Visitor<'_> for NumberVisitor {
+            type Value = Number;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a number")
+            }
+
+            fn visit_i64<E>(self, value: i64) -> Result<Number, E> {
+                Ok(Number::Integer(value))
+            }
+
+            fn visit_u64<E>(self, value: u64) -> Result<Number, E> {
+                Ok(i64::try_from(value)
+                    .map(Number::Integer)
+                    .unwrap_or(Number::Float(value as f64, None)))
+            }
+
+            fn visit_f64<E>(self, value: f64) -> Result<Number, E> {
+                Ok(Number::Float(value, None))
+            }
+        }
+
+        deserializer.deserialize_any(NumberVisitor)
+    }
+}
+
+macro_rules! number_from_integer {
+    ($($ty:ty),+) => {
+        $(
+            impl From<$ty> for Number {
+                fn from(value: $ty) -> Self {
+                    Number::Integer(value.into())
+                }
+            }
+        )+
+    };
+}
+
+number_from_integer!(i8, i16, i32, i64, u8, u16, u32);
+
+fn parse_float(input: &str) -> IResult<&str, Number> {
     let (input, float) = recognize(tuple((
         opt(tag("-")),
         many1(satisfy(is_digit)),
@@ -20,25 +163,36 @@ fn parse_float(input: &str) -> IResult<&str, f32> {
         many1(satisfy(is_digit)),
     )))(input)?;
 
-    Ok((
-        input,
-        float
-            .parse()
-            .unwrap_or_else(|_| panic!("Failed to parse float {float}!")),
-    ))
+    let value = float
+        .parse()
+        .unwrap_or_else(|_| panic!("Failed to parse float {float}!"));
+
+    // The decimal count is only kept if it writes the value back the way it
+    // was read, which is not the case for digits beyond what an f64 holds
+    let decimals = float
+        .split_once('.')
+        .and_then(|(_, fraction)| u8::try_from(fraction.len()).ok())
+        .filter(|&decimals| format!("{value:.*}", decimals as usize) == float);
+
+    Ok((input, Number::Float(value, decimals)))
 }
 
-fn parse_int(input: &str) -> IResult<&str, f32> {
+fn parse_int(input: &str) -> IResult<&str, Number> {
     let (input, int) = recognize(tuple((opt(tag("-")), many1(satisfy(is_digit)))))(input)?;
 
-    Ok((
-        input,
-        int.parse()
-            .unwrap_or_else(|_| panic!("Failed to parse int {int}!")),
-    ))
+    // Integers too large for an i64 fall back to a float rather than failing
+    let number = int.parse().map(Number::Integer).unwrap_or_else(|_| {
+        Number::Float(
+            int.parse()
+                .unwrap_or_else(|_| panic!("Failed to parse int {int}!")),
+            None,
+        )
+    });
+
+    Ok((input, number))
 }
 
-pub fn parse_number(input: &str) -> IResult<&str, f32> {
+pub fn parse_number(input: &str) -> IResult<&str, Number> {
     let (input, number) = alt((parse_float, parse_int))(input)?;
 
     Ok((input, number))
@@ -50,9 +204,69 @@ mod tests {
 
     #[test]
     fn test_parse_number() {
-        assert_eq!(parse_number("123"), Ok(("", 123.0)));
-        assert_eq!(parse_number("-123"), Ok(("", -123.0)));
-        assert_eq!(parse_number("123.456"), Ok(("", 123.456)));
-        assert_eq!(parse_number("-123.456"), Ok(("", -123.456)));
+        assert_eq!(parse_number("123"), Ok(("", Number::Integer(123))));
+        assert_eq!(parse_number("-123"), Ok(("", Number::Integer(-123))));
+        assert_eq!(
+            parse_number("123.456"),
+            Ok(("", Number::Float(123.456, Some(3))))
+        );
+        assert_eq!(
+            parse_number("-123.456"),
+            Ok(("", Number::Float(-123.456, Some(3))))
+        );
+    }
+
+    #[test]
+    fn test_number_display_round_trips() {
+        for input in [
+            "20230121",
+            "148.5875",
+            "-0.000001",
+            "0.1",
+            "-42",
+            "1234567.123456",
+            "12.000000",
+            "0.0000",
+            "-0.0000",
+            "1.50",
+        ] {
+            let (_, number) = parse_number(input).unwrap();
+
+            assert_eq!(number.to_string(), input);
+        }
+    }
+
+    #[test]
+    fn test_number_from_f32() {
+        assert_eq!(Number::from(148.5875f32).to_string(), "148.5875");
+        assert_eq!(Number::from(0.1f32).to_string(), "0.1");
+        assert_eq!(Number::from(3.0f32).to_string(), "3");
+    }
+
+    #[test]
+    fn test_number_equality() {
+        assert_eq!(Number::Integer(1), Number::Float(1.0, None));
+        assert_ne!(Number::Integer(1), Number::Float(1.5, None));
+        assert_eq!(Number::Float(12.0, Some(6)), Number::Float(12.0, None));
+        assert_eq!(Number::Integer(20230121).to_integer(), Some(20230121));
+        assert_eq!(Number::Float(1.5, Some(1)).to_integer(), None);
+    }
+
+    #[test]
+    fn test_fixed_decimals() {
+        let (_, number) = parse_number("12.000000").unwrap();
+        assert_eq!(number, Number::Float(12.0, Some(6)));
+        assert_eq!(number.to_string(), "12.000000");
+
+        let (_, number) = parse_number("0.0000").unwrap();
+        assert_eq!(number.to_string(), "0.0000");
+
+        // A value set from code is written in its shortest form
+        assert_eq!(Number::from(12.0).to_string(), "12");
+        assert_eq!(Number::from(1.5f64).to_string(), "1.5");
+
+        // Digits an f64 cannot hold are not kept
+        let (_, number) = parse_number("0.12345678901234567890").unwrap();
+        assert_eq!(number, Number::Float(0.12345678901234568, None));
     }
 }
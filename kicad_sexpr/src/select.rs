@@ -0,0 +1,420 @@
+//! Path-based queries over [`Sexpr`] trees.
+//!
+//! A [`Selector`] describes a path from a list down into its descendants, one
+//! step per level, separated by `/`. Each step is one of:
+//!
+//! - a name such as `pad`, matching every child list that starts with that
+//!   symbol,
+//! - `*`, matching every child list,
+//! - an index such as `2`, matching the child at that position (including
+//!   atoms, so `net/2` is the name of a net).
+//!
+//! A step can be narrowed down with any number of predicates in brackets:
+//!
+//! - `[layer]` keeps nodes that have a `(layer ...)` child list,
+//! - `[layer="F.Cu"]` keeps nodes with a `(layer "F.Cu" ...)` child list,
+//!   and `[property="Reference"="U1"]` checks the following values too,
+//! - `[1="R1"]` keeps nodes whose child at index 1 is `"R1"`.
+//!
+//! Values are compared by text, so `"F.Cu"` and `F.Cu` match both the string
+//! and the symbol. Numbers are compared numerically.
+//!
+//! ```
+//! use kicad_sexpr::{from_str, Sexpr};
+//!
+//! let mut pcb = from_str(
+//!     r#"(kicad_pcb
+//!         (footprint "R_0603" (property "Reference" "R1") (pad "1" smd (net 1 "VCC")))
+//!         (footprint "SOT-23" (property "Reference" "U1") (pad "1" smd (net 2 "GND"))))"#,
+//! )
+//! .unwrap();
+//!
+//! let nets = pcb
+//!     .select(r#"footprint[property="Reference"="U1"]/pad/net/2"#)
+//!     .unwrap();
+//! assert_eq!(nets, [&Sexpr::string("GND")]);
+//!
+//! pcb.replace("footprint/pad[1=1]/net/2", Sexpr::string("+3V3"))
+//!     .unwrap();
+//! assert_eq!(pcb.select("*/pad/net/2").unwrap().len(), 2);
+//! ```
+
+use std::str::FromStr;
+
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_while1},
+    character::complete::char,
+    combinator::{cut, eof, map},
+    multi::{many0, separated_list1},
+    sequence::{pair, preceded, terminated},
+    IResult,
+};
+use thiserror::Error;
+
+use crate::{string::parse_string, Sexpr};
+
+/// Error returned when a selector string is malformed.
+#[derive(Debug, Error, PartialEq, Clone)]
+#[error("invalid selector {selector:?} at offset {offset}")]
+pub struct SelectorParseError {
+    pub selector: String,
+    pub offset: usize,
+}
+
+/// A parsed selector path. See the [module documentation](self) for the
+/// syntax.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Selector {
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct Step {
+    target: Target,
+    predicates: Vec<Predicate>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Target {
+    Any,
+    Index(usize),
+    Name(String),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct Predicate {
+    target: Target,
+    values: Vec<String>,
+}
+
+impl Target {
+    fn parse(token: &str) -> Self {
+        if token == "*" {
+            Self::Any
+        } else if let Ok(index) = token.parse() {
+            Self::Index(index)
+        } else {
+            Self::Name(token.to_string())
+        }
+    }
+
+    fn matches(&self, index: usize, node: &Sexpr) -> bool {
+        match self {
+            Self::Any => node.as_list().is_some(),
+            Self::Index(i) => *i == index,
+            Self::Name(name) => list_name(node) == Some(name),
+        }
+    }
+}
+
+impl Predicate {
+    fn matches(&self, node: &Sexpr) -> bool {
+        let Some(list) = node.as_list() else {
+            return false;
+        };
+
+        match &self.target {
+            Target::Index(index) => list
+                .get(*index..)
+                .is_some_and(|rest| !rest.is_empty() && starts_with(rest, &self.values)),
+            target => list.iter().enumerate().skip(1).any(|(index, child)| {
+                target.matches(index, child)
+                    && child
+                        .as_list()
+                        .is_some_and(|child| starts_with(&child[1..], &self.values))
+            }),
+        }
+    }
+}
+
+impl Step {
+    fn matches(&self, index: usize, node: &Sexpr) -> bool {
+        self.target.matches(index, node)
+            && self
+                .predicates
+                .iter()
+                .all(|predicate| predicate.matches(node))
+    }
+}
+
+impl Selector {
+    pub fn new(selector: &str) -> Result<Self, SelectorParseError> {
+        let error = |rest: &str| SelectorParseError {
+            selector: selector.to_string(),
+            offset: selector.len() - rest.len(),
+        };
+
+        match terminated(parse_steps, eof)(selector) {
+            Ok((_, steps)) => Ok(Self { steps }),
+            Err(nom::Err::Error(e) | nom::Err::Failure(e)) => Err(error(e.input)),
+            // Only an unterminated string can run out of input
+            Err(nom::Err::Incomplete(_)) => Err(error("")),
+        }
+    }
+
+    /// Returns every node under `root` that the selector matches, in document
+    /// order.
+    pub fn select<'a>(&self, root: &'a Sexpr) -> Vec<&'a Sexpr> {
+        let mut matches = vec![root];
+
+        for step in &self.steps {
+            matches = matches
+                .into_iter()
+                .filter_map(Sexpr::as_list)
+                .flat_map(|list| {
+                    list.iter()
+                        .enumerate()
+                        .filter(|(index, child)| step.matches(*index, child))
+                        .map(|(_, child)| child)
+                })
+                .collect();
+        }
+
+        matches
+    }
+
+    /// Like [`Selector::select`], but returns mutable references.
+    pub fn select_mut<'a>(&self, root: &'a mut Sexpr) -> Vec<&'a mut Sexpr> {
+        let mut matches = vec![root];
+
+        for step in &self.steps {
+            matches = matches
+                .into_iter()
+                .filter_map(Sexpr::as_list_mut)
+                .flat_map(|list| {
+                    list.iter_mut()
+                        .enumerate()
+                        .filter(|(index, child)| step.matches(*index, child))
+                        .map(|(_, child)| child)
+                })
+                .collect();
+        }
+
+        matches
+    }
+
+    /// Removes every node the selector matches from its parent list and
+    /// returns them in document order.
+    pub fn remove(&self, root: &mut Sexpr) -> Vec<Sexpr> {
+        let Some((last, parents)) = self.steps.split_last() else {
+            return Vec::new();
+        };
+
+        let parents = Selector {
+            steps: parents.to_vec(),
+        };
+        let mut removed = Vec::new();
+
+        for parent in parents.select_mut(root) {
+            let Some(list) = parent.as_list_mut() else {
+                continue;
+            };
+
+            for (index, child) in std::mem::take(list).into_iter().enumerate() {
+                if last.matches(index, &child) {
+                    removed.push(child);
+                } else {
+                    list.push(child);
+                }
+            }
+        }
+
+        removed
+    }
+}
+
+impl FromStr for Selector {
+    type Err = SelectorParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+impl Sexpr {
+    /// Returns every node matching the [`Selector`] path, relative to this
+    /// list.
+    pub fn select(&self, selector: &str) -> Result<Vec<&Sexpr>, SelectorParseError> {
+        Ok(Selector::new(selector)?.select(self))
+    }
+
+    /// Returns mutable references to every node matching the [`Selector`]
+    /// path, relative to this list.
+    pub fn select_mut(&mut self, selector: &str) -> Result<Vec<&mut Sexpr>, SelectorParseError> {
+        Ok(Selector::new(selector)?.select_mut(self))
+    }
+
+    /// Replaces every node matching the [`Selector`] path with `value` and
+    /// returns how many were replaced.
+    pub fn replace(&mut self, selector: &str, value: Sexpr) -> Result<usize, SelectorParseError> {
+        let matches = self.select_mut(selector)?;
+        let count = matches.len();
+
+        for node in matches {
+            *node = value.clone();
+        }
+
+        Ok(count)
+    }
+
+    /// Removes every node matching the [`Selector`] path and returns them.
+    pub fn remove(&mut self, selector: &str) -> Result<Vec<Sexpr>, SelectorParseError> {
+        Ok(Selector::new(selector)?.remove(self))
+    }
+}
+
+fn list_name(node: &Sexpr) -> Option<&String> {
+    node.as_list()?.first()?.as_symbol()
+}
+
+/// Checks whether `values` match the first nodes of `nodes`.
+fn starts_with(nodes: &[Sexpr], values: &[String]) -> bool {
+    nodes.len() >= values.len()
+        && values
+            .iter()
+            .zip(nodes)
+            .all(|(value, node)| value_matches(value, node))
+}
+
+fn value_matches(value: &str, node: &Sexpr) -> bool {
+    match node {
+        Sexpr::String(text) | Sexpr::Symbol(text) => text == value,
+        Sexpr::Number(number) => value
+            .parse::<f64>()
+            .is_ok_and(|value| number.to_f64() == value),
+        Sexpr::List(_) => false,
+    }
+}
+
+fn is_token_char(c: char) -> bool {
+    !matches!(c, '/' | '[' | ']' | '=' | '"') && !c.is_whitespace()
+}
+
+fn parse_token(input: &str) -> IResult<&str, &str> {
+    take_while1(is_token_char)(input)
+}
+
+fn parse_value(input: &str) -> IResult<&str, String> {
+    alt((
+        map(parse_string, |string| string.into_owned()),
+        map(parse_token, str::to_string),
+    ))(input)
+}
+
+fn parse_predicate(input: &str) -> IResult<&str, Predicate> {
+    // Everything after a `[` must form a predicate, so errors are reported
+    // where they occur instead of at the bracket
+    map(
+        preceded(
+            tag("["),
+            cut(terminated(
+                pair(parse_token, many0(preceded(char('='), parse_value))),
+                tag("]"),
+            )),
+        ),
+        |(target, values)| Predicate {
+            target: Target::parse(target),
+            values,
+        },
+    )(input)
+}
+
+fn parse_steps(input: &str) -> IResult<&str, Vec<Step>> {
+    separated_list1(
+        char('/'),
+        cut(map(
+            pair(parse_token, many0(parse_predicate)),
+            |(target, predicates)| Step {
+                target: Target::parse(target),
+                predicates,
+            },
+        )),
+    )(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_str;
+
+    const INPUT: &str = r#"(kicad_pcb
+        (setup (pad_to_mask_clearance 0) (pcbplotparams (layerselection 0x00010fc) (mirror no)))
+        (footprint "R_0603" (layer "F.Cu") (property "Reference" "R1")
+            (pad "1" smd (net 1 "VCC")) (pad "2" smd (net 0 "")))
+        (footprint "SOT-23" (layer B.Cu) (property "Reference" "U1")
+            (pad "1" smd (net 2 "GND")) (pad "2" smd (net 1 "VCC"))))"#;
+
+    #[test]
+    fn test_select() {
+        let pcb = from_str(INPUT).unwrap();
+
+        assert_eq!(
+            pcb.select(r#"footprint[property="Reference"="U1"]/pad/net"#)
+                .unwrap(),
+            [
+                &from_str(r#"(net 2 "GND")"#).unwrap(),
+                &from_str(r#"(net 1 "VCC")"#).unwrap(),
+            ]
+        );
+        assert_eq!(pcb.select("setup/pcbplotparams/*").unwrap().len(), 2);
+        assert_eq!(
+            pcb.select("footprint[layer=B.Cu]/1").unwrap(),
+            [&Sexpr::string("SOT-23")]
+        );
+        assert_eq!(
+            pcb.select(r#"footprint[layer="F.Cu"]/pad[1="2"]/net/2"#)
+                .unwrap(),
+            [&Sexpr::string("")]
+        );
+        assert_eq!(pcb.select("*/pad/net[1=1]").unwrap().len(), 2);
+        assert_eq!(pcb.select("footprint[2]").unwrap().len(), 2);
+        assert_eq!(pcb.select("footprint[9]").unwrap().len(), 0);
+        assert_eq!(pcb.select("footprint[missing]").unwrap().len(), 0);
+        assert_eq!(pcb.select("via").unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_replace_and_remove() {
+        let mut pcb = from_str(INPUT).unwrap();
+
+        assert_eq!(
+            pcb.replace("*/pad/net[2=VCC]", from_str(r#"(net 3 "+3V3")"#).unwrap()),
+            Ok(2)
+        );
+        assert_eq!(pcb.select("*/pad/net[2=+3V3]").unwrap().len(), 2);
+
+        for net in pcb.select_mut("footprint/pad/net/1").unwrap() {
+            *net = Sexpr::number(0);
+        }
+        assert_eq!(pcb.select("*/pad/net[1=0]").unwrap().len(), 4);
+
+        let removed = pcb.remove(r#"footprint/pad[1="2"]"#).unwrap();
+        assert_eq!(removed.len(), 2);
+        assert_eq!(pcb.select("footprint/pad").unwrap().len(), 2);
+        assert_eq!(pcb.remove("setup").unwrap().len(), 1);
+        assert!(pcb.select("setup").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for (selector, offset) in [
+            ("", 0),
+            ("footprint/", 10),
+            ("footprint[", 10),
+            ("footprint[layer", 15),
+            ("footprint[layer=\"F.Cu]", 22),
+            ("a//b", 2),
+            ("a b", 1),
+        ] {
+            assert_eq!(
+                Selector::new(selector),
+                Err(SelectorParseError {
+                    selector: selector.to_string(),
+                    offset,
+                }),
+                "{selector:?}"
+            );
+        }
+    }
+}
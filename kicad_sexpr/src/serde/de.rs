@@ -0,0 +1,710 @@
+//! Deserializing Rust values from S-expressions.
+
+use std::cell::Cell;
+
+use ::serde::{
+    de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor},
+    forward_to_deserialize_any, Deserialize,
+};
+
+use super::{Error, FLAG, POSITIONAL};
+use crate::{Number, Sexpr};
+
+/// Deserializes a value from a list, ignoring the list's name.
+pub fn from_sexpr<'a, T: Deserialize<'a>>(sexpr: &'a Sexpr) -> Result<T, Error> {
+    match sexpr.as_list().map(Vec::as_slice) {
+        Some([Sexpr::Symbol(_), items @ ..]) => T::deserialize(Items(items)),
+        _ => Err(de::Error::custom("expected a list starting with a symbol")),
+    }
+}
+
+/// Parses the input and deserializes a value from its root list.
+pub fn from_str<T: DeserializeOwned>(input: &str) -> Result<T, Error> {
+    from_sexpr(&crate::from_str(input)?)
+}
+
+fn unexpected(sexpr: &Sexpr) -> de::Unexpected<'_> {
+    match sexpr {
+        Sexpr::List(_) => de::Unexpected::Other("a list"),
+        Sexpr::Number(number) => de::Unexpected::Float(number.to_f64()),
+        Sexpr::String(string) => de::Unexpected::Str(string),
+        Sexpr::Symbol(symbol) => de::Unexpected::Other(symbol),
+    }
+}
+
+/// The items of a list after its name, which make up one value.
+#[derive(Clone, Copy)]
+struct Items<'a>(&'a [Sexpr]);
+
+impl<'a> Items<'a> {
+    fn atom(self) -> Result<&'a Sexpr, Error> {
+        match self.0 {
+            [atom] if atom.as_list().is_none() => Ok(atom),
+            _ => Err(de::Error::invalid_length(self.0.len(), &"a single atom")),
+        }
+    }
+
+    /// A list item is one element with its children as items, and an atom is
+    /// an element by itself.
+    fn element(item: &'a Sexpr) -> Self {
+        match item {
+            Sexpr::List(list) => Items(list),
+            atom => Items(std::slice::from_ref(atom)),
+        }
+    }
+}
+
+/// Forwards the sized number methods to the 64-bit ones, which report the
+/// value through the visitor's widest method.
+macro_rules! forward_to_deserialize_numbers {
+    ($($($method:ident)* => $target:ident),*) => {
+        $($(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                self.$target(visitor)
+            }
+        )*)*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Items<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            [] => visitor.visit_unit(),
+            [Sexpr::Number(Number::Integer(integer))] => visitor.visit_i64(*integer),
+            [Sexpr::Number(Number::Float(float, _))] => visitor.visit_f64(*float),
+            [Sexpr::String(string) | Sexpr::Symbol(string)] => visitor.visit_borrowed_str(string),
+            _ => self.deserialize_seq(visitor),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let atom = self.atom()?;
+
+        match atom {
+            Sexpr::String(text) | Sexpr::Symbol(text) if text == "yes" || text == "true" => {
+                visitor.visit_bool(true)
+            }
+            Sexpr::String(text) | Sexpr::Symbol(text) if text == "no" || text == "false" => {
+                visitor.visit_bool(false)
+            }
+            _ => Err(de::Error::invalid_value(unexpected(atom), &"a boolean")),
+        }
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let atom = self.atom()?;
+
+        match atom.as_number().and_then(|number| number.to_integer()) {
+            Some(integer) => visitor.visit_i64(integer),
+            None => Err(de::Error::invalid_type(unexpected(atom), &"an integer")),
+        }
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let atom = self.atom()?;
+
+        match atom.as_number() {
+            Some(number) => visitor.visit_f64(number.to_f64()),
+            None => Err(de::Error::invalid_type(unexpected(atom), &"a number")),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.atom()? {
+            Sexpr::String(text) | Sexpr::Symbol(text) => visitor.visit_borrowed_str(text),
+            // Unquoted tokens such as pad numbers may have been read as numbers
+            Sexpr::Number(number) => visitor.visit_string(number.to_string()),
+            Sexpr::List(_) => unreachable!("atom() never returns a list"),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.0.is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match name {
+            FLAG => self.deserialize_bool(visitor),
+            _ => visitor.visit_newtype_struct(self),
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_seq(de::value::SeqDeserializer::new(
+            self.0.iter().map(Items::element),
+        ))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_map(de::value::MapDeserializer::new(self.0.iter().filter_map(
+            |item| match item.as_list()?.split_first()? {
+                (Sexpr::Symbol(key), rest) => Some((key.as_str(), Items(rest))),
+                _ => None,
+            },
+        )))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        // Atoms that are not flags of the struct are positional values
+        let positional = self
+            .0
+            .iter()
+            .filter(|item| match item {
+                Sexpr::List(_) => false,
+                Sexpr::Symbol(symbol) => !fields.contains(&symbol.as_str()),
+                _ => true,
+            })
+            .collect();
+
+        visitor.visit_map(StructAccess {
+            items: self.0,
+            fields: fields.iter(),
+            positional: Positional(positional, 0),
+            value: None,
+        })
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.0 {
+            [Sexpr::String(variant) | Sexpr::Symbol(variant), rest @ ..] => {
+                visitor.visit_enum(EnumAccess(variant, Items(rest)))
+            }
+            _ => Err(de::Error::custom("expected an enum variant")),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        char string bytes byte_buf identifier
+    }
+
+    forward_to_deserialize_numbers! {
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i128
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_u128 => deserialize_i64,
+        deserialize_f32 => deserialize_f64
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Items<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+struct EnumAccess<'a>(&'a str, Items<'a>);
+
+impl<'de> de::EnumAccess<'de> for EnumAccess<'de> {
+    type Error = Error;
+    type Variant = Items<'de>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Items<'de>), Error> {
+        let variant = seed.deserialize(de::value::BorrowedStrDeserializer::<Error>::new(self.0))?;
+        Ok((variant, self.1))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Items<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        match self.0 {
+            [] => Ok(()),
+            _ => Err(de::Error::custom("unexpected values after a unit variant")),
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_struct(self, "", fields, visitor)
+    }
+}
+
+/// The positional atoms of a struct and how many have been used.
+struct Positional<'a>(Vec<&'a Sexpr>, usize);
+
+impl<'a> Positional<'a> {
+    fn remaining(&self) -> bool {
+        self.1 < self.0.len()
+    }
+
+    fn next(&mut self) -> Option<&'a Sexpr> {
+        let atom = self.0.get(self.1).copied();
+        self.1 += 1;
+        atom
+    }
+}
+
+struct StructAccess<'a, I> {
+    items: &'a [Sexpr],
+    fields: I,
+    positional: Positional<'a>,
+    value: Option<Field<'a>>,
+}
+
+/// Where the value of a struct field comes from.
+enum Field<'a> {
+    /// One or more `(name ...)` lists
+    Named(Vec<Items<'a>>),
+    /// A bare symbol with the field's name
+    Flag,
+    /// Nothing with the field's name, so it is either positional or missing
+    Pending(&'static str),
+}
+
+impl<'de, I: Iterator<Item = &'static &'static str>> de::MapAccess<'de> for StructAccess<'de, I> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        for &field in self.fields.by_ref() {
+            let named: Vec<_> = self
+                .items
+                .iter()
+                .filter_map(|item| match item.as_list()?.split_first()? {
+                    (Sexpr::Symbol(name), rest) if name == field => Some(Items(rest)),
+                    _ => None,
+                })
+                .collect();
+
+            let value = if !named.is_empty() {
+                Field::Named(named)
+            } else if self
+                .items
+                .iter()
+                .any(|item| item.as_symbol().is_some_and(|s| s == field))
+            {
+                Field::Flag
+            } else if self.positional.remaining() {
+                Field::Pending(field)
+            } else {
+                // Let serde fill in `None` or the default
+                continue;
+            };
+
+            self.value = Some(value);
+            return seed
+                .deserialize(de::value::BorrowedStrDeserializer::<Error>::new(field))
+                .map(Some);
+        }
+
+        Ok(None)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        match self.value.take() {
+            Some(Field::Named(named)) => seed.deserialize(NamedDeserializer(named)),
+            Some(Field::Flag) => seed.deserialize(FlagDeserializer),
+            Some(Field::Pending(field)) => seed.deserialize(PendingDeserializer {
+                field,
+                positional: &mut self.positional,
+            }),
+            None => Err(de::Error::custom("value requested before key")),
+        }
+    }
+}
+
+/// A field given by one or more `(name ...)` lists. Anything but a sequence
+/// expects exactly one.
+struct NamedDeserializer<'a>(Vec<Items<'a>>);
+
+impl<'a> NamedDeserializer<'a> {
+    fn single(self) -> Result<Items<'a>, Error> {
+        match self.0.as_slice() {
+            [items] => Ok(*items),
+            _ => Err(de::Error::custom("duplicate field")),
+        }
+    }
+}
+
+macro_rules! forward_to_single {
+    ($($method:ident($($arg:ident: $ty:ty),*)),*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value, Error> {
+                self.single()?.$method($($arg,)* visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for NamedDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match name {
+            FLAG => self.single()?.deserialize_bool(visitor),
+            _ => visitor.visit_newtype_struct(self),
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0.as_slice() {
+            // A single list of atoms is either an inline sequence of atoms or
+            // one compound element, depending on what the element type asks
+            // for
+            [Items(items)] if items.iter().all(|item| item.as_list().is_none()) => visitor
+                .visit_seq(ElementSeq {
+                    items,
+                    index: 0,
+                    mode: Cell::new(Mode::Undecided),
+                }),
+            _ => visitor.visit_seq(de::value::SeqDeserializer::new(self.0.into_iter())),
+        }
+    }
+
+    forward_to_single!(
+        deserialize_any(),
+        deserialize_bool(),
+        deserialize_i8(),
+        deserialize_i16(),
+        deserialize_i32(),
+        deserialize_i64(),
+        deserialize_u8(),
+        deserialize_u16(),
+        deserialize_u32(),
+        deserialize_u64(),
+        deserialize_f32(),
+        deserialize_f64(),
+        deserialize_char(),
+        deserialize_str(),
+        deserialize_string(),
+        deserialize_bytes(),
+        deserialize_byte_buf(),
+        deserialize_unit(),
+        deserialize_unit_struct(name: &'static str),
+        deserialize_tuple(len: usize),
+        deserialize_tuple_struct(name: &'static str, len: usize),
+        deserialize_map(),
+        deserialize_struct(name: &'static str, fields: &'static [&'static str]),
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]),
+        deserialize_identifier()
+    );
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Undecided,
+    /// Every atom is an element
+    Inline,
+    /// The whole list is a single element
+    Compound,
+}
+
+struct ElementSeq<'a> {
+    items: &'a [Sexpr],
+    index: usize,
+    mode: Cell<Mode>,
+}
+
+impl<'de> de::SeqAccess<'de> for ElementSeq<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        let done = match self.mode.get() {
+            Mode::Undecided => self.items.is_empty(),
+            Mode::Inline => self.index >= self.items.len(),
+            Mode::Compound => true,
+        };
+
+        if done {
+            return Ok(None);
+        }
+
+        let value = seed.deserialize(ElementDeserializer {
+            items: self.items,
+            index: self.index,
+            mode: &self.mode,
+        })?;
+        self.index += 1;
+
+        Ok(Some(value))
+    }
+}
+
+/// The next element of an [`ElementSeq`]. Its first use settles the mode.
+struct ElementDeserializer<'a, 'm> {
+    items: &'a [Sexpr],
+    index: usize,
+    mode: &'m Cell<Mode>,
+}
+
+impl<'a> ElementDeserializer<'a, '_> {
+    fn resolve(&self, mode: Mode) -> Items<'a> {
+        if self.mode.get() == Mode::Undecided {
+            self.mode.set(mode);
+        }
+
+        match self.mode.get() {
+            Mode::Compound => Items(self.items),
+            _ => Items(std::slice::from_ref(&self.items[self.index])),
+        }
+    }
+}
+
+macro_rules! forward_with_mode {
+    ($mode:expr => $($method:ident($($arg:ident: $ty:ty),*)),*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value, Error> {
+                self.resolve($mode).$method($($arg,)* visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for ElementDeserializer<'de, '_> {
+    type Error = Error;
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match name {
+            FLAG => self.resolve(Mode::Inline).deserialize_bool(visitor),
+            _ => visitor.visit_newtype_struct(self),
+        }
+    }
+
+    forward_with_mode!(Mode::Inline =>
+        deserialize_any(),
+        deserialize_bool(),
+        deserialize_i8(),
+        deserialize_i16(),
+        deserialize_i32(),
+        deserialize_i64(),
+        deserialize_u8(),
+        deserialize_u16(),
+        deserialize_u32(),
+        deserialize_u64(),
+        deserialize_f32(),
+        deserialize_f64(),
+        deserialize_char(),
+        deserialize_str(),
+        deserialize_string(),
+        deserialize_bytes(),
+        deserialize_byte_buf(),
+        deserialize_unit(),
+        deserialize_unit_struct(name: &'static str),
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]),
+        deserialize_identifier(),
+        deserialize_ignored_any()
+    );
+
+    forward_with_mode!(Mode::Compound =>
+        deserialize_seq(),
+        deserialize_tuple(len: usize),
+        deserialize_tuple_struct(name: &'static str, len: usize),
+        deserialize_map(),
+        deserialize_struct(name: &'static str, fields: &'static [&'static str])
+    );
+}
+
+/// A field set by a bare symbol.
+struct FlagDeserializer;
+
+impl<'de> de::Deserializer<'de> for FlagDeserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_bool(true)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+/// A field without a `(name ...)` list or flag. It takes the next positional
+/// atom if it is a positional field, and is missing otherwise.
+struct PendingDeserializer<'a, 'p> {
+    field: &'static str,
+    positional: &'p mut Positional<'a>,
+}
+
+impl<'de> de::Deserializer<'de> for PendingDeserializer<'de, '_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Error> {
+        Err(de::Error::missing_field(self.field))
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_none()
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_seq(de::value::SeqDeserializer::new(std::iter::empty::<Items>()))
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match name {
+            POSITIONAL => visitor.visit_newtype_struct(PositionalDeserializer(self.positional)),
+            FLAG => visitor.visit_bool(false),
+            _ => Err(de::Error::missing_field(self.field)),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+/// Reads a positional field from the struct's remaining atoms. A sequence
+/// takes all of them.
+struct PositionalDeserializer<'a, 'p>(&'p mut Positional<'a>);
+
+impl<'a> PositionalDeserializer<'a, '_> {
+    fn single(self) -> Result<Items<'a>, Error> {
+        self.0
+            .next()
+            .map(|atom| Items(std::slice::from_ref(atom)))
+            .ok_or_else(|| de::Error::custom("missing positional value"))
+    }
+}
+
+impl<'de> de::Deserializer<'de> for PositionalDeserializer<'de, '_> {
+    type Error = Error;
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.0.remaining() {
+            visitor.visit_some(self)
+        } else {
+            visitor.visit_none()
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let rest = std::iter::from_fn(|| self.0.next()).map(Items::element);
+        visitor.visit_seq(de::value::SeqDeserializer::new(rest))
+    }
+
+    forward_to_single!(
+        deserialize_any(),
+        deserialize_bool(),
+        deserialize_i8(),
+        deserialize_i16(),
+        deserialize_i32(),
+        deserialize_i64(),
+        deserialize_u8(),
+        deserialize_u16(),
+        deserialize_u32(),
+        deserialize_u64(),
+        deserialize_f32(),
+        deserialize_f64(),
+        deserialize_char(),
+        deserialize_str(),
+        deserialize_string(),
+        deserialize_bytes(),
+        deserialize_byte_buf(),
+        deserialize_unit(),
+        deserialize_unit_struct(name: &'static str),
+        deserialize_newtype_struct(name: &'static str),
+        deserialize_tuple(len: usize),
+        deserialize_tuple_struct(name: &'static str, len: usize),
+        deserialize_map(),
+        deserialize_struct(name: &'static str, fields: &'static [&'static str]),
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]),
+        deserialize_identifier(),
+        deserialize_ignored_any()
+    );
+}
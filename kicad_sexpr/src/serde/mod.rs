@@ -0,0 +1,353 @@
+//! A serde data format for KiCad-style S-expressions.
+//!
+//! Unlike the `Serialize`/`Deserialize` derives on [`Sexpr`](crate::Sexpr)
+//! itself, which describe the tree, this module maps arbitrary Rust types to
+//! the shape KiCad uses in its files:
+//!
+//! - A struct is a list of its fields, each written as `(name value)`. The
+//!   top-level struct is named by the caller.
+//! - Numbers and strings are atoms, and `bool`s are written as `true` or
+//!   `false`. Unit enum variants are bare symbols (use
+//!   `#[serde(rename_all = "snake_case")]` for KiCad-style names).
+//! - `None` fields are left out.
+//! - A sequence of atoms is written inline as `(name 1 2 3)`. A sequence of
+//!   structs repeats the field, as in `(property ...) (property ...)`.
+//! - Tuples are written inline, so `(f64, f64)` becomes `(name 1 2)`.
+//!
+//! Fields can be adjusted with `#[serde(with = "...")]` using the modules
+//! below:
+//!
+//! - [`symbol`] writes a string as a bare symbol instead of a quoted string.
+//! - [`yes_no`] writes a `bool` as `yes` or `no`.
+//! - [`flag`] writes a `bool` as a bare symbol that is only present when the
+//!   value is true, like `hide`. Flags also need `#[serde(default)]`.
+//! - [`positional`] writes a value without a `(name ...)` wrapper, directly in
+//!   the parent list. Positional fields are read from the atoms of the list in
+//!   order, so they have to be declared before any field that may be missing.
+//!
+//! Unknown lists in the input are ignored, as are sequences that are missing
+//! entirely unless the field has `#[serde(default)]`.
+//!
+//! ```
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Debug, PartialEq, Serialize, Deserialize)]
+//! #[serde(rename_all = "snake_case")]
+//! enum Unit {
+//!     Mm,
+//!     Mils,
+//! }
+//!
+//! #[derive(Debug, PartialEq, Serialize, Deserialize)]
+//! struct NetClass {
+//!     #[serde(with = "kicad_sexpr::serde::positional")]
+//!     name: String,
+//!     clearance: f64,
+//!     unit: Unit,
+//!     #[serde(with = "kicad_sexpr::serde::yes_no")]
+//!     diff_pair: bool,
+//!     #[serde(default, with = "kicad_sexpr::serde::flag")]
+//!     locked: bool,
+//!     #[serde(default)]
+//!     add_net: Vec<String>,
+//! }
+//!
+//! let class = NetClass {
+//!     name: "Power".to_string(),
+//!     clearance: 0.3,
+//!     unit: Unit::Mm,
+//!     diff_pair: false,
+//!     locked: true,
+//!     add_net: vec!["GND".to_string(), "VCC".to_string()],
+//! };
+//!
+//! let text = kicad_sexpr::serde::to_string("net_class", &class).unwrap();
+//! assert_eq!(
+//!     text,
+//!     "(net_class \"Power\"\n\t(clearance 0.3)\n\t(unit mm)\n\t(diff_pair no) locked\n\t(add_net \"GND\" \"VCC\")\n)"
+//! );
+//!
+//! let parsed: NetClass = kicad_sexpr::serde::from_str(&text).unwrap();
+//! assert_eq!(parsed, class);
+//! ```
+
+use thiserror::Error;
+
+use crate::SexprParseError;
+
+pub mod de;
+pub mod ser;
+
+pub use de::{from_sexpr, from_str};
+pub use ser::{to_sexpr, to_string};
+
+/// Newtype struct names the serializer and deserializer recognize, used to
+/// pass the field attributes through serde.
+const SYMBOL: &str = "$kicad_sexpr::symbol";
+const YES_NO: &str = "$kicad_sexpr::yes_no";
+const FLAG: &str = "$kicad_sexpr::flag";
+const POSITIONAL: &str = "$kicad_sexpr::positional";
+
+/// Error returned when a value cannot be converted to or from an
+/// S-expression.
+#[derive(Debug, Error, PartialEq, Clone)]
+pub enum Error {
+    #[error("{0}")]
+    Message(String),
+    #[error(transparent)]
+    Parse(#[from] SexprParseError),
+}
+
+impl ::serde::ser::Error for Error {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Self::Message(msg.to_string())
+    }
+}
+
+impl ::serde::de::Error for Error {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Self::Message(msg.to_string())
+    }
+}
+
+/// Writes a string as a bare symbol, such as `(layer F.Cu)`.
+pub mod symbol {
+    use ::serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &str, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(super::SYMBOL, value)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+        String::deserialize(deserializer)
+    }
+}
+
+/// Writes a `bool` as `yes` or `no`.
+pub mod yes_no {
+    use ::serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &bool, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(super::YES_NO, value)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+        bool::deserialize(deserializer)
+    }
+}
+
+/// Writes a `bool` as a bare symbol named after the field, present only when
+/// the value is true.
+pub mod flag {
+    use ::serde::{
+        de::{self, Visitor},
+        Deserialize, Deserializer, Serializer,
+    };
+
+    pub fn serialize<S: Serializer>(value: &bool, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(super::FLAG, value)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+        struct FlagVisitor;
+
+        impl<'de> Visitor<'de> for FlagVisitor {
+            type Value = bool;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a flag")
+            }
+
+            fn visit_bool<E: de::Error>(self, value: bool) -> Result<bool, E> {
+                Ok(value)
+            }
+
+            fn visit_newtype_struct<D: Deserializer<'de>>(
+                self,
+                deserializer: D,
+            ) -> Result<bool, D::Error> {
+                bool::deserialize(deserializer)
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(super::FLAG, FlagVisitor)
+    }
+}
+
+/// Writes a value directly into the parent list, without a `(name ...)`
+/// wrapper.
+pub mod positional {
+    use std::marker::PhantomData;
+
+    use ::serde::{de::Visitor, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<T: Serialize, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(super::POSITIONAL, value)
+    }
+
+    pub fn deserialize<'de, T: Deserialize<'de>, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        struct PositionalVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de>> Visitor<'de> for PositionalVisitor<T> {
+            type Value = T;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a positional value")
+            }
+
+            fn visit_newtype_struct<D: Deserializer<'de>>(
+                self,
+                deserializer: D,
+            ) -> Result<T, D::Error> {
+                T::deserialize(deserializer)
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(super::POSITIONAL, PositionalVisitor(PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::from_str as parse;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Point {
+        #[serde(with = "positional")]
+        x: f64,
+        #[serde(with = "positional")]
+        y: f64,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    enum Shape {
+        Circle,
+        Rect,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Property {
+        #[serde(with = "positional")]
+        key: String,
+        #[serde(with = "positional")]
+        value: String,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Pad {
+        #[serde(with = "positional")]
+        number: String,
+        #[serde(with = "positional")]
+        shape: Shape,
+        at: Point,
+        size: (f64, f64),
+        #[serde(with = "symbol")]
+        layer: String,
+        #[serde(default)]
+        layers: Vec<String>,
+        #[serde(default)]
+        property: Vec<Property>,
+        #[serde(default)]
+        pts: Vec<Point>,
+        drill: Option<f64>,
+        #[serde(with = "yes_no")]
+        plated: bool,
+        #[serde(default, with = "flag")]
+        locked: bool,
+        net: Option<(i32, String)>,
+    }
+
+    fn pad() -> Pad {
+        Pad {
+            number: "1".to_string(),
+            shape: Shape::Rect,
+            at: Point { x: 1.27, y: -2.5 },
+            size: (1.0, 0.5),
+            layer: "F.Cu".to_string(),
+            layers: vec!["F.Cu".to_string(), "F.Mask".to_string()],
+            property: vec![
+                Property {
+                    key: "a".to_string(),
+                    value: "1".to_string(),
+                },
+                Property {
+                    key: "b".to_string(),
+                    value: "2".to_string(),
+                },
+            ],
+            pts: vec![Point { x: 0.0, y: 1.0 }],
+            drill: None,
+            plated: true,
+            locked: true,
+            net: Some((3, "GND".to_string())),
+        }
+    }
+
+    const PAD: &str = r#"(pad "1" rect (at 1.27 -2.5) (size 1 0.5) (layer F.Cu) (layers "F.Cu" "F.Mask") (property "a" "1") (property "b" "2") (pts 0 1) (plated yes) locked (net 3 "GND"))"#;
+
+    #[test]
+    fn test_serialize() {
+        assert_eq!(to_sexpr("pad", &pad()), Ok(parse(PAD).unwrap()));
+    }
+
+    #[test]
+    fn test_deserialize() {
+        assert_eq!(from_str::<Pad>(PAD), Ok(pad()));
+    }
+
+    #[test]
+    fn test_deserialize_is_lenient() {
+        // Unknown lists are skipped, flags and positional values can appear
+        // in any order, and missing optional values are left out
+        let input = r#"(pad "2" locked circle (uuid "x") (at 0 0) (size 1 1) (layer B.Cu) (plated no) (drill 0.8))"#;
+
+        let pad: Pad = from_str(input).unwrap();
+
+        assert_eq!(pad.number, "2");
+        assert_eq!(pad.shape, Shape::Circle);
+        assert_eq!(pad.drill, Some(0.8));
+        assert!(pad.locked);
+        assert!(!pad.plated);
+        assert!(pad.layers.is_empty());
+        assert_eq!(pad.net, None);
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = from_str::<Pad>(r#"(pad "1" rect (at 0 0) (size 1 1) (plated yes))"#);
+        assert_eq!(
+            error,
+            Err(Error::Message("missing field `layer`".to_string()))
+        );
+
+        let error = from_str::<Point>("(xy 1)");
+        assert_eq!(error, Err(Error::Message("missing field `y`".to_string())));
+
+        assert!(matches!(from_str::<Point>("(xy 1"), Err(Error::Parse(_))));
+        assert!(to_sexpr("x", &1).is_err());
+    }
+
+    #[test]
+    fn test_maps() {
+        let mut map = std::collections::BTreeMap::new();
+        map.insert("clearance".to_string(), 0.2);
+        map.insert("track_width".to_string(), 0.25);
+
+        let sexpr = to_sexpr("rules", &map).unwrap();
+
+        assert_eq!(
+            sexpr,
+            parse("(rules (clearance 0.2) (track_width 0.25))").unwrap()
+        );
+        assert_eq!(from_sexpr(&sexpr), Ok(map));
+    }
+}
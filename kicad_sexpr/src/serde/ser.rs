@@ -0,0 +1,528 @@
+//! Serializing Rust values into S-expressions.
+
+use ::serde::{
+    ser::{self, Impossible},
+    Serialize,
+};
+
+use super::{Error, FLAG, POSITIONAL, SYMBOL, YES_NO};
+use crate::{format, FormatStyle, Number, Sexpr, SexprList};
+
+/// Serializes `value` into a list named `name`. The value must be a struct or
+/// a map.
+pub fn to_sexpr<T: Serialize + ?Sized>(name: &str, value: &T) -> Result<Sexpr, Error> {
+    match value.serialize(ValueSerializer)? {
+        Value::Compound(items) => {
+            let mut list = vec![Sexpr::symbol(name)];
+            list.extend(items);
+            Ok(Sexpr::List(list))
+        }
+        _ => Err(ser::Error::custom(
+            "the top-level value must be a struct or map",
+        )),
+    }
+}
+
+/// Serializes `value` into a list named `name` and formats it like KiCad 8.
+pub fn to_string<T: Serialize + ?Sized>(name: &str, value: &T) -> Result<String, Error> {
+    Ok(format(&to_sexpr(name, value)?, FormatStyle::default()))
+}
+
+/// The serialized form of a value, before it is placed in its parent list.
+enum Value {
+    /// `None` and `()`, which are left out
+    Absent,
+    Atom(Sexpr),
+    /// The children of a struct, map or tuple
+    Compound(SexprList),
+    Seq(Vec<Value>),
+    Positional(Box<Value>),
+    Flag(bool),
+}
+
+impl Value {
+    /// The items this value contributes to a list.
+    fn into_items(self) -> SexprList {
+        match self {
+            Value::Absent | Value::Flag(_) => Vec::new(),
+            Value::Atom(atom) => vec![atom],
+            Value::Compound(items) => items,
+            Value::Seq(elements) => elements
+                .into_iter()
+                .flat_map(|element| match element {
+                    Value::Compound(items) => vec![Sexpr::List(items)],
+                    element => element.into_items(),
+                })
+                .collect(),
+            Value::Positional(value) => value.into_items(),
+        }
+    }
+
+    /// Appends this value to `list` as the field `key`.
+    fn push_field(self, key: &str, list: &mut SexprList) {
+        let named = |items: SexprList| {
+            let mut list = vec![Sexpr::symbol(key)];
+            list.extend(items);
+            Sexpr::List(list)
+        };
+
+        match self {
+            Value::Absent | Value::Flag(false) => {}
+            Value::Flag(true) => list.push(Sexpr::symbol(key)),
+            Value::Positional(value) => list.extend(value.into_items()),
+            Value::Seq(elements) if !elements.iter().all(|e| matches!(e, Value::Atom(_))) => {
+                list.extend(
+                    elements
+                        .into_iter()
+                        .filter(|element| !matches!(element, Value::Absent))
+                        .map(|element| named(element.into_items())),
+                );
+            }
+            value => list.push(named(value.into_items())),
+        }
+    }
+}
+
+struct ValueSerializer;
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = SeqSerializer;
+    type SerializeMap = StructSerializer;
+    type SerializeStruct = StructSerializer;
+    type SerializeStructVariant = StructSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, Error> {
+        Ok(Value::Atom(Sexpr::symbol(if v { "true" } else { "false" })))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value, Error> {
+        Ok(Value::Atom(Sexpr::Number(Number::Integer(v))))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value, Error> {
+        let v =
+            i64::try_from(v).map_err(|_| <Error as ser::Error>::custom("integer out of range"))?;
+        self.serialize_i64(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value, Error> {
+        Ok(Value::Atom(Sexpr::number(v)))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value, Error> {
+        Ok(Value::Atom(Sexpr::number(v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value, Error> {
+        Ok(Value::Atom(Sexpr::string(v)))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value, Error> {
+        Ok(Value::Atom(Sexpr::string(v)))
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Value, Error> {
+        Err(ser::Error::custom("byte arrays are not supported"))
+    }
+
+    fn serialize_none(self) -> Result<Value, Error> {
+        Ok(Value::Absent)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value, Error> {
+        Ok(Value::Absent)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, Error> {
+        Ok(Value::Absent)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Value, Error> {
+        Ok(Value::Atom(Sexpr::symbol(variant)))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Value, Error> {
+        let value = value.serialize(self)?;
+
+        Ok(match (name, value) {
+            (SYMBOL, Value::Atom(Sexpr::String(string))) => Value::Atom(Sexpr::Symbol(string)),
+            (YES_NO, Value::Atom(Sexpr::Symbol(symbol))) => {
+                Value::Atom(Sexpr::symbol(if symbol == "true" { "yes" } else { "no" }))
+            }
+            (FLAG, Value::Atom(Sexpr::Symbol(symbol))) => Value::Flag(symbol == "true"),
+            (POSITIONAL, value) => Value::Positional(Box::new(value)),
+            (_, value) => value,
+        })
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value, Error> {
+        let mut items = vec![Sexpr::symbol(variant)];
+        items.extend(value.serialize(self)?.into_items());
+        Ok(Value::Compound(items))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer, Error> {
+        Ok(SeqSerializer {
+            variant: None,
+            tuple: false,
+            elements: Vec::with_capacity(len.unwrap_or_default()),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer, Error> {
+        Ok(SeqSerializer {
+            variant: None,
+            tuple: true,
+            elements: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer, Error> {
+        self.serialize_tuple(len)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer, Error> {
+        Ok(SeqSerializer {
+            variant: Some(variant),
+            tuple: true,
+            elements: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<StructSerializer, Error> {
+        Ok(StructSerializer {
+            items: Vec::new(),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<StructSerializer, Error> {
+        self.serialize_map(None)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<StructSerializer, Error> {
+        Ok(StructSerializer {
+            items: vec![Sexpr::symbol(variant)],
+            key: None,
+        })
+    }
+}
+
+struct SeqSerializer {
+    variant: Option<&'static str>,
+    tuple: bool,
+    elements: Vec<Value>,
+}
+
+impl SeqSerializer {
+    fn finish(self) -> Value {
+        if !self.tuple {
+            return Value::Seq(self.elements);
+        }
+
+        let mut items: SexprList = self.variant.map(Sexpr::symbol).into_iter().collect();
+        items.extend(Value::Seq(self.elements).into_items());
+        Value::Compound(items)
+    }
+}
+
+macro_rules! impl_seq_serializer {
+    ($($trait:ident::$method:ident),*) => {
+        $(
+            impl ser::$trait for SeqSerializer {
+                type Ok = Value;
+                type Error = Error;
+
+                fn $method<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+                    self.elements.push(value.serialize(ValueSerializer)?);
+                    Ok(())
+                }
+
+                fn end(self) -> Result<Value, Error> {
+                    Ok(self.finish())
+                }
+            }
+        )*
+    };
+}
+
+impl_seq_serializer!(
+    SerializeSeq::serialize_element,
+    SerializeTuple::serialize_element,
+    SerializeTupleStruct::serialize_field,
+    SerializeTupleVariant::serialize_field
+);
+
+struct StructSerializer {
+    items: SexprList,
+    key: Option<String>,
+}
+
+impl ser::SerializeStruct for StructSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value
+            .serialize(ValueSerializer)?
+            .push_field(key, &mut self.items);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(Value::Compound(self.items))
+    }
+}
+
+impl ser::SerializeStructVariant for StructSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        ser::SerializeStruct::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(Value::Compound(self.items))
+    }
+}
+
+impl ser::SerializeMap for StructSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| <Error as ser::Error>::custom("map value without a key"))?;
+
+        value
+            .serialize(ValueSerializer)?
+            .push_field(&key, &mut self.items);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(Value::Compound(self.items))
+    }
+}
+
+/// Serializes map keys, which become the names of the entries.
+struct KeySerializer;
+
+macro_rules! serialize_key_with_display {
+    ($($method:ident: $ty:ty),*) => {
+        $(
+            fn $method(self, v: $ty) -> Result<String, Error> {
+                Ok(v.to_string())
+            }
+        )*
+    };
+}
+
+impl ser::Serializer for KeySerializer {
+    type Ok = String;
+    type Error = Error;
+
+    type SerializeSeq = Impossible<String, Error>;
+    type SerializeTuple = Impossible<String, Error>;
+    type SerializeTupleStruct = Impossible<String, Error>;
+    type SerializeTupleVariant = Impossible<String, Error>;
+    type SerializeMap = Impossible<String, Error>;
+    type SerializeStruct = Impossible<String, Error>;
+    type SerializeStructVariant = Impossible<String, Error>;
+
+    serialize_key_with_display!(
+        serialize_bool: bool,
+        serialize_i8: i8,
+        serialize_i16: i16,
+        serialize_i32: i32,
+        serialize_i64: i64,
+        serialize_u8: u8,
+        serialize_u16: u16,
+        serialize_u32: u32,
+        serialize_u64: u64,
+        serialize_f32: f32,
+        serialize_f64: f64,
+        serialize_char: char,
+        serialize_str: &str
+    );
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<String, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_none(self) -> Result<String, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<String, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<String, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<String, Error> {
+        Ok(variant.to_string())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<String, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(key_error())
+    }
+}
+
+fn key_error() -> Error {
+    ser::Error::custom("map keys must be strings, numbers or unit variants")
+}
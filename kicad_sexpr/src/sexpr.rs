@@ -4,16 +4,19 @@ use std::fmt::Display;
 
 use nom::{
     branch::alt,
-    bytes::complete::tag,
-    character::complete::{multispace0, multispace1, satisfy},
-    combinator::{eof, recognize},
-    multi::{many1, separated_list1},
+    bytes::complete::{tag, take_while1},
+    character::complete::multispace1,
+    combinator::{cut, eof, recognize},
+    error::{Error, ErrorKind},
+    multi::separated_list1,
     sequence::tuple,
     IResult,
 };
 use thiserror::Error;
 
-use super::number::parse_number;
+use super::format::{format, FormatStyle};
+use super::number::{parse_number, Number};
+use super::span::{Atom, Location, ParsedNode, Span, SpannedSexpr};
 use super::string::parse_string;
 
 pub type SexprList = Vec<Sexpr>;
@@ -24,7 +27,7 @@ pub type SexprList = Vec<Sexpr>;
 #[derive(Debug, PartialEq, Clone)]
 pub enum Sexpr {
     List(SexprList),
-    Number(f32),
+    Number(Number),
     String(String),
     Symbol(String),
 }
@@ -93,17 +96,17 @@ macro_rules! sexpr_construct_with_name {
 #[allow(unused)]
 impl Sexpr {
     sexpr_as!(as_list, SexprList, List);
-    sexpr_as!(as_number, f32, Number);
+    sexpr_as!(as_number, Number, Number);
     sexpr_as!(as_string, String, String);
     sexpr_as!(as_symbol, String, Symbol);
 
     sexpr_as_mut!(as_list_mut, SexprList, List);
-    sexpr_as_mut!(as_number_mut, f32, Number);
+    sexpr_as_mut!(as_number_mut, Number, Number);
     sexpr_as_mut!(as_string_mut, String, String);
     sexpr_as_mut!(as_symbol_mut, String, Symbol);
 
     sexpr_take!(take_list, SexprList, List);
-    sexpr_take!(take_number, f32, Number);
+    sexpr_take!(take_number, Number, Number);
     sexpr_take!(take_string, String, String);
     sexpr_take!(take_symbol, String, Symbol);
 
@@ -115,7 +118,7 @@ impl Sexpr {
     /// # Example
     ///
     /// ```
-    /// use kicad_sexpr::Sexpr;
+    /// use kicad_sexpr::{Number, Sexpr};
     ///
     /// struct Test {
     ///     a: f32,
@@ -137,8 +140,8 @@ impl Sexpr {
     ///     ]),
     ///     Sexpr::List(
     ///         vec![
-    ///             Sexpr::Number(1.0),
-    ///             Sexpr::Number(2.0),
+    ///             Sexpr::Number(Number::Float(1.0, None)),
+    ///             Sexpr::Number(Number::Float(2.0, None)),
     ///         ]    
     ///     )
     /// )
@@ -148,7 +151,7 @@ impl Sexpr {
         Self::List(value.into().into_iter().flatten().collect::<Vec<_>>())
     }
 
-    sexpr_construct!(number, f32, Number);
+    sexpr_construct!(number, Number, Number);
     sexpr_construct!(string, String, String);
     sexpr_construct!(symbol, String, Symbol);
 
@@ -158,7 +161,7 @@ impl Sexpr {
     /// # Example
     ///
     /// ```
-    /// use kicad_sexpr::Sexpr;
+    /// use kicad_sexpr::{Number, Sexpr};
     ///
     /// assert_eq!(
     ///     Sexpr::list_with_name("test", [
@@ -168,7 +171,7 @@ impl Sexpr {
     ///     Sexpr::List(
     ///         vec![
     ///             Sexpr::Symbol("test".to_string()),
-    ///             Sexpr::Number(1.0),
+    ///             Sexpr::Number(Number::Float(1.0, None)),
     ///             Sexpr::String("hello, world".to_string()),
     ///         ]    
     ///     )
@@ -181,7 +184,7 @@ impl Sexpr {
         Self::list(list)
     }
 
-    sexpr_construct_with_name!(number_with_name, f32, Number);
+    sexpr_construct_with_name!(number_with_name, Number, Number);
     sexpr_construct_with_name!(string_with_name, String, String);
     sexpr_construct_with_name!(symbol_with_name, String, Symbol);
 
@@ -194,113 +197,209 @@ impl Sexpr {
     }
 }
 
+/// Error returned when the input is not a well-formed S-expression.
+///
+/// Points at the first byte the parser could not make sense of, both as a
+/// byte offset and as a 1-based line/column pair, along with a short excerpt
+/// of the input starting at that position.
 #[derive(Debug, Error, PartialEq, Clone)]
-#[error("S-expression parse error")]
-pub struct SexprParseError;
-
-pub fn from_str(input: &str) -> Result<Sexpr, SexprParseError> {
-    let (input, sexpr) = parse_sexpr(input.trim()).map_err(|_| SexprParseError)?;
+#[error("S-expression parse error at line {line}, column {column} (near {snippet:?})")]
+pub struct SexprParseError {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+    pub snippet: String,
+}
 
-    if !input.is_empty() {
-        return Err(SexprParseError);
+impl SexprParseError {
+    pub(crate) const SNIPPET_LENGTH: usize = 32;
+
+    fn new(input: &str, offset: usize) -> Self {
+        let Location { line, column } = Location::from_offset(input, offset);
+
+        let snippet = input[offset..]
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .chars()
+            .take(Self::SNIPPET_LENGTH)
+            .collect();
+
+        Self {
+            offset,
+            line,
+            column,
+            snippet,
+        }
     }
-
-    Ok(sexpr)
 }
 
-pub fn to_string(sexpr: &Sexpr) -> String {
-    to_string_recursive(sexpr, 0)
+pub fn from_str(input: &str) -> Result<Sexpr, SexprParseError> {
+    parse_complete(input)
 }
 
-fn to_string_recursive(sexpr: &Sexpr, depth: usize) -> String {
-    let mut result = String::new();
+/// Parses the input like [`from_str`], but keeps the [`Span`] of every node
+/// so that later processing can refer back to the original input.
+pub fn from_str_spanned(input: &str) -> Result<SpannedSexpr, SexprParseError> {
+    parse_complete(input)
+}
 
-    match sexpr {
-        Sexpr::List(list) => {
-            if depth != 0 {
-                result += "\n";
-            }
+pub(crate) fn parse_complete<'a, N: ParsedNode<'a>>(input: &'a str) -> Result<N, SexprParseError> {
+    let total = input.len();
 
-            result.push_str(&" ".repeat(depth * 2));
+    let start = trivia_len(input, true);
 
-            result += "(";
+    let (rest, sexpr) = parse_sexpr::<N>(total, &input[start..]).map_err(|e| match e {
+        // Only the streaming string parser can run out of input, which means
+        // a string was never terminated
+        nom::Err::Incomplete(_) => SexprParseError::new(input, total),
+        nom::Err::Error(e) | nom::Err::Failure(e) => {
+            SexprParseError::new(input, total - e.input.len())
+        }
+    })?;
 
-            for (i, attribute) in list.iter().enumerate() {
-                result += &to_string_recursive(attribute, depth + 1);
+    let rest = &rest[trivia_len(rest, false)..];
 
-                if i != list.len() - 1 {
-                    result += " ";
-                }
-            }
-
-            result += ")";
-        }
-        Sexpr::Number(number) => {
-            if number.fract() == 0.0 {
-                result += &(*number as i64).to_string();
-            } else {
-                result += number.to_string().trim_end_matches('0');
-            }
-        }
-        Sexpr::String(string) => {
-            result += &format!("{:?}", string);
-        }
-        Sexpr::Symbol(symbol) => {
-            result += symbol;
-        }
+    if !rest.is_empty() {
+        return Err(SexprParseError::new(input, total - rest.len()));
     }
 
-    result
+    Ok(sexpr)
+}
+
+/// Formats an S-expression the way KiCad 8 writes it. Use
+/// [`format`](crate::format()) to pick a different [`FormatStyle`].
+pub fn to_string(sexpr: &Sexpr) -> String {
+    format(sexpr, FormatStyle::default())
 }
 
-fn parse_sexpr(input: &str) -> IResult<&str, Sexpr> {
+/// Parses a single node. `total` is the length of the complete input, which
+/// is used to turn the remaining input into byte offsets for spans.
+fn parse_sexpr<'a, N: ParsedNode<'a>>(total: usize, input: &'a str) -> IResult<&'a str, N> {
+    let start = total - input.len();
+
     let (input, sexpr) = alt((
-        parse_sexpr_list,
-        parse_sexpr_number,
-        parse_sexpr_string,
-        parse_sexpr_symbol,
+        |i| parse_sexpr_list(total, i),
+        |i| parse_sexpr_atom(start, total, i),
     ))(input)?;
 
     Ok((input, sexpr))
 }
 
-fn parse_sexpr_list(input: &str) -> IResult<&str, Sexpr> {
+fn parse_sexpr_list<'a, N: ParsedNode<'a>>(total: usize, input: &'a str) -> IResult<&'a str, N> {
+    let start = total - input.len();
+
     // Parse opening tag
     let (input, _) = tag("(")(input)?;
-    let (input, _) = multispace0(input)?;
+    let (input, _) = parse_trivia(total, input)?;
+
+    // Once an opening paren has been seen this can only be a list, so errors
+    // past this point are reported where they occur instead of backtracking
+    // to the start of the list.
 
     // Parse list children
-    let (input, children) = separated_list1(multispace1, parse_sexpr)(input)?;
+    let (input, children) = cut(separated_list1(
+        |i| parse_separator(total, i),
+        |i| parse_sexpr(total, i),
+    ))(input)?;
 
     // Parse closing tag
-    let (input, _) = multispace0(input)?;
-    let (input, _) = tag(")")(input)?;
+    let (input, _) = parse_trivia(total, input)?;
+    let (input, _) = cut(tag(")"))(input)?;
+
+    Ok((
+        input,
+        N::from_list(children, Span::new(start, total - input.len())),
+    ))
+}
+
+/// Returns the length of the whitespace and comments at the start of `input`.
+///
+/// Like KiCad's `DSNLEXER`, a `#` only starts a comment when it is the first
+/// non-blank character on a line. Anywhere else it is part of a symbol such as
+/// `#PWR01`. `line_start` tells whether `input` begins at the start of a line.
+pub(crate) fn trivia_len(input: &str, mut line_start: bool) -> usize {
+    let bytes = input.as_bytes();
+    let mut length = 0;
+
+    while let Some(byte) = bytes.get(length) {
+        match byte {
+            b'\n' => {
+                line_start = true;
+                length += 1;
+            }
+            b' ' | b'\t' | b'\r' => length += 1,
+            b'#' if line_start => {
+                length += bytes[length..]
+                    .iter()
+                    .position(|byte| *byte == b'\n')
+                    .unwrap_or(bytes.len() - length);
+            }
+            _ => break,
+        }
+    }
 
-    Ok((input, Sexpr::List(children)))
+    length
 }
 
-fn parse_sexpr_number(input: &str) -> IResult<&str, Sexpr> {
+/// Parses optional whitespace and comments. Trivia always follows a token on
+/// the same line unless it starts the input, so past the start a comment can
+/// only follow a line break within the trivia itself.
+fn parse_trivia(total: usize, input: &str) -> IResult<&str, &str> {
+    let length = trivia_len(input, input.len() == total);
+
+    Ok((&input[length..], &input[..length]))
+}
+
+/// Parses the whitespace and comments separating two list children.
+fn parse_separator(total: usize, input: &str) -> IResult<&str, &str> {
+    match parse_trivia(total, input)? {
+        (_, "") => Err(nom::Err::Error(Error::new(input, ErrorKind::MultiSpace))),
+        result => Ok(result),
+    }
+}
+
+fn parse_sexpr_atom<'a, N: ParsedNode<'a>>(
+    start: usize,
+    total: usize,
+    input: &'a str,
+) -> IResult<&'a str, N> {
+    let (input, atom) = alt((parse_sexpr_number, parse_sexpr_string, parse_sexpr_symbol))(input)?;
+
+    Ok((
+        input,
+        N::from_atom(atom, Span::new(start, total - input.len())),
+    ))
+}
+
+fn parse_sexpr_number(input: &str) -> IResult<&str, Atom<'_>> {
     // Look ahead to see if the number is terminated by a space or a closing paren
-    // If it's not, then it could be a value like a UUID
+    // If it's not, then it is the start of a symbol like a UUID or `1.5mm`
     let _ = recognize(tuple((parse_number, alt((multispace1, tag(")"), eof)))))(input)?;
 
     let (input, number) = parse_number(input)?;
 
-    Ok((input, Sexpr::Number(number)))
+    Ok((input, Atom::Number(number)))
 }
 
-fn parse_sexpr_string(input: &str) -> IResult<&str, Sexpr> {
+fn parse_sexpr_string(input: &str) -> IResult<&str, Atom<'_>> {
     let (input, string) = parse_string(input)?;
 
-    Ok((input, Sexpr::String(string)))
+    Ok((input, Atom::String(string)))
 }
 
-fn parse_sexpr_symbol(input: &str) -> IResult<&str, Sexpr> {
-    let (input, chars) = many1(satisfy(|c| {
-        c.is_ascii_alphanumeric() || c == '_' || c == '-'
-    }))(input)?;
+/// Returns true for the characters that end an unquoted token.
+pub(crate) fn is_token_delimiter(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '\r' | '(' | ')' | '"')
+}
 
-    Ok((input, Sexpr::Symbol(chars.iter().collect())))
+/// Parses an unquoted token. Like KiCad's `DSNLEXER`, a symbol is any run of
+/// characters other than whitespace, parens and double quotes, so values such
+/// as `*.Cu`, `+3V3` and `#FF00FF` are read as a single symbol.
+fn parse_sexpr_symbol(input: &str) -> IResult<&str, Atom<'_>> {
+    let (input, symbol) = take_while1(|c: char| !is_token_delimiter(c))(input)?;
+
+    Ok((input, Atom::Symbol(symbol)))
 }
 
 #[cfg(test)]
@@ -308,7 +407,7 @@ mod tests {
     use super::*;
 
     fn assert_parsed(input: &str, expected: Sexpr) {
-        let (input, sexpr) = parse_sexpr(input).unwrap();
+        let (input, sexpr) = parse_sexpr::<Sexpr>(input.len(), input).unwrap();
 
         assert_eq!(input, "");
         assert_eq!(sexpr, expected);
@@ -332,10 +431,10 @@ mod tests {
 
     #[test]
     fn test_parse_number() {
-        assert_parsed("123", Sexpr::Number(123.0));
-        assert_parsed("-123", Sexpr::Number(-123.0));
-        assert_parsed("123.456", Sexpr::Number(123.456));
-        assert_parsed("-123.456", Sexpr::Number(-123.456));
+        assert_parsed("123", Sexpr::Number(Number::Integer(123)));
+        assert_parsed("-123", Sexpr::Number(Number::Integer(-123)));
+        assert_parsed("123.456", Sexpr::Number(Number::Float(123.456, None)));
+        assert_parsed("-123.456", Sexpr::Number(Number::Float(-123.456, None)));
     }
 
     #[test]
@@ -395,8 +494,8 @@ mod tests {
             "(test 0 0.1)",
             Sexpr::List(vec![
                 Sexpr::Symbol("test".to_string()),
-                Sexpr::Number(0.0),
-                Sexpr::Number(0.1),
+                Sexpr::Number(Number::Integer(0)),
+                Sexpr::Number(Number::Float(0.1, None)),
             ]),
         );
     }
@@ -435,4 +534,158 @@ mod tests {
             ]),
         );
     }
+
+    #[test]
+    fn test_token_conformance() {
+        let symbols = [
+            "F.Cu",
+            "*.Cu",
+            "*.Mask",
+            "+3V3",
+            "-5V",
+            "1.5mm",
+            "#FF00FF",
+            "#PWR01",
+            "~",
+            "${REFERENCE}",
+            "µm",
+            "Ω",
+            "-",
+            "+",
+            "1.",
+            ".5",
+            "1e5",
+            "0x1F",
+            "a'b",
+            "a;b",
+            "a\\b",
+            "2349f563-989d-4999-a369-9f24d984ce74",
+            "00000000-0000-0000-0000-000000000000",
+            "12345678-0000",
+        ];
+
+        for symbol in symbols {
+            assert_parsed(symbol, Sexpr::Symbol(symbol.to_string()));
+            assert_parsed(
+                &format!("(a {symbol})"),
+                Sexpr::List(vec![
+                    Sexpr::Symbol("a".to_string()),
+                    Sexpr::Symbol(symbol.to_string()),
+                ]),
+            );
+        }
+
+        let numbers = [
+            ("0", Number::Integer(0)),
+            ("-1", Number::Integer(-1)),
+            ("12345678", Number::Integer(12345678)),
+            ("1.5", Number::Float(1.5, None)),
+            ("-0.5", Number::Float(-0.5, None)),
+        ];
+
+        for (input, number) in numbers {
+            assert_parsed(input, Sexpr::Number(number));
+            assert_parsed(
+                &format!("(a {input})"),
+                Sexpr::List(vec![Sexpr::Symbol("a".to_string()), Sexpr::Number(number)]),
+            );
+        }
+
+        assert_parsed(
+            "(layers *.Cu *.Mask F.SilkS)",
+            Sexpr::List(vec![
+                Sexpr::Symbol("layers".to_string()),
+                Sexpr::Symbol("*.Cu".to_string()),
+                Sexpr::Symbol("*.Mask".to_string()),
+                Sexpr::Symbol("F.SilkS".to_string()),
+            ]),
+        );
+    }
+
+    #[test]
+    fn test_fixed_decimals_round_trip() {
+        for input in [
+            "(a 12.000000)",
+            "(color 0 0 0 0.0000)",
+            "(a 1.50)",
+            "(at 148.5875 -0.1 90)",
+        ] {
+            assert_eq!(to_string(&from_str(input).unwrap()), input);
+        }
+    }
+
+    #[test]
+    fn test_token_delimiters() {
+        for input in ["(a b\"c\")", "(a 1(b))", "(a \"b\"c)", "(a(b))"] {
+            assert!(from_str(input).is_err(), "{input} should not parse");
+        }
+    }
+
+    #[test]
+    fn test_parse_comments() {
+        assert_eq!(
+            from_str("# header\n(a\n  # note (b)\n  #c\n\t# tab\n  d # e\n  )\n# footer"),
+            Ok(Sexpr::List(vec![
+                Sexpr::Symbol("a".to_string()),
+                Sexpr::Symbol("d".to_string()),
+                Sexpr::Symbol("#".to_string()),
+                Sexpr::Symbol("e".to_string()),
+            ]))
+        );
+
+        assert!(from_str("(a) # trailing").is_err());
+        assert!(from_str("(a\n# unclosed)").is_err());
+    }
+
+    #[test]
+    fn test_parse_error_location() {
+        let error = from_str("(test\n  (nested 1 2)\n  (bad x\"y\"))").unwrap_err();
+
+        assert_eq!(
+            error,
+            SexprParseError {
+                offset: 29,
+                line: 3,
+                column: 9,
+                snippet: "\"y\"))".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_error_unclosed_list() {
+        let error = from_str("(test (nested 1 2)").unwrap_err();
+
+        assert_eq!(error.offset, 18);
+        assert_eq!((error.line, error.column), (1, 19));
+        assert_eq!(error.snippet, "");
+    }
+
+    #[test]
+    fn test_parse_error_trailing_input() {
+        let error = from_str("  (test)\n)").unwrap_err();
+
+        assert_eq!(error.offset, 9);
+        assert_eq!((error.line, error.column), (2, 1));
+        assert_eq!(error.snippet, ")");
+    }
+
+    #[test]
+    fn test_parse_spanned() {
+        let input = " (test (nested \"str\") 1.5)";
+        let sexpr = from_str_spanned(input).unwrap();
+
+        assert_eq!(sexpr.span, Span::new(1, input.len()));
+
+        let children = sexpr.as_list().unwrap();
+        assert_eq!(children[0].span.slice(input), "test");
+        assert_eq!(children[1].span.slice(input), "(nested \"str\")");
+        assert_eq!(
+            children[1].as_list().unwrap()[1].span.slice(input),
+            "\"str\""
+        );
+        assert_eq!(children[2].span.slice(input), "1.5");
+
+        assert_eq!(sexpr.into_sexpr(), from_str(input).unwrap());
+    }
 }
@@ -0,0 +1,192 @@
+//! Source location tracking for parsed S-expressions.
+//!
+//! The regular [`Sexpr`] tree does not remember where in the input each node
+//! came from. When that information is needed (for example to report where a
+//! semantically invalid token lives in a large board file) the input can be
+//! parsed with [`from_str_spanned`](crate::from_str_spanned) instead, which
+//! produces a [`SpannedSexpr`] tree where every node carries its [`Span`].
+
+use std::borrow::Cow;
+
+use crate::{Number, Sexpr};
+
+/// A half-open range of byte offsets (`start..end`) into the original input.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// Resolves the start of this span to a line and column in `input`.
+    ///
+    /// `input` must be the same string the span was produced from.
+    pub fn location(&self, input: &str) -> Location {
+        Location::from_offset(input, self.start)
+    }
+
+    /// Returns the slice of `input` covered by this span.
+    pub fn slice<'a>(&self, input: &'a str) -> &'a str {
+        &input[self.start..self.end]
+    }
+}
+
+/// A human readable position in the input. Both fields are 1-based, and the
+/// column is counted in characters rather than bytes.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Location {
+    pub fn from_offset(input: &str, offset: usize) -> Self {
+        let before = &input[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+
+        Self {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+/// An S-expression node annotated with the [`Span`] of input it was parsed
+/// from. Mirrors the structure of [`Sexpr`].
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct SpannedSexpr {
+    pub span: Span,
+    pub kind: SpannedSexprKind,
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[cfg_attr(feature = "serde", serde(tag = "type"))]
+#[derive(Debug, PartialEq, Clone)]
+pub enum SpannedSexprKind {
+    List(Vec<SpannedSexpr>),
+    Number(Number),
+    String(String),
+    Symbol(String),
+}
+
+impl SpannedSexpr {
+    /// Returns the child nodes if this node is a list.
+    pub fn as_list(&self) -> Option<&Vec<SpannedSexpr>> {
+        if let SpannedSexprKind::List(list) = &self.kind {
+            Some(list)
+        } else {
+            None
+        }
+    }
+
+    /// Strips the span information, producing a regular [`Sexpr`] tree.
+    pub fn into_sexpr(self) -> Sexpr {
+        match self.kind {
+            SpannedSexprKind::List(list) => {
+                Sexpr::List(list.into_iter().map(Self::into_sexpr).collect())
+            }
+            SpannedSexprKind::Number(number) => Sexpr::Number(number),
+            SpannedSexprKind::String(string) => Sexpr::String(string),
+            SpannedSexprKind::Symbol(symbol) => Sexpr::Symbol(symbol),
+        }
+    }
+}
+
+impl From<SpannedSexpr> for Sexpr {
+    fn from(value: SpannedSexpr) -> Self {
+        value.into_sexpr()
+    }
+}
+
+/// A leaf of the tree as produced by the parser, borrowing from the input
+/// wherever possible.
+pub(crate) enum Atom<'a> {
+    Number(Number),
+    String(Cow<'a, str>),
+    Symbol(&'a str),
+}
+
+/// Abstraction over the node types the parser can produce, so the same
+/// grammar builds [`Sexpr`], [`SpannedSexpr`] and
+/// [`BorrowedSexpr`](crate::BorrowedSexpr) trees.
+pub(crate) trait ParsedNode<'a>: Sized {
+    fn from_list(children: Vec<Self>, span: Span) -> Self;
+    fn from_atom(atom: Atom<'a>, span: Span) -> Self;
+}
+
+impl<'a> ParsedNode<'a> for Sexpr {
+    fn from_list(children: Vec<Self>, _span: Span) -> Self {
+        Sexpr::List(children)
+    }
+
+    fn from_atom(atom: Atom<'a>, _span: Span) -> Self {
+        match atom {
+            Atom::Number(number) => Sexpr::Number(number),
+            Atom::String(string) => Sexpr::String(string.into_owned()),
+            Atom::Symbol(symbol) => Sexpr::Symbol(symbol.to_string()),
+        }
+    }
+}
+
+impl<'a> ParsedNode<'a> for SpannedSexpr {
+    fn from_list(children: Vec<Self>, span: Span) -> Self {
+        Self {
+            span,
+            kind: SpannedSexprKind::List(children),
+        }
+    }
+
+    fn from_atom(atom: Atom<'a>, span: Span) -> Self {
+        let kind = match atom {
+            Atom::Number(number) => SpannedSexprKind::Number(number),
+            Atom::String(string) => SpannedSexprKind::String(string.into_owned()),
+            Atom::Symbol(symbol) => SpannedSexprKind::Symbol(symbol.to_string()),
+        };
+
+        Self { span, kind }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_location_from_offset() {
+        let input = "(a\n  (b c)\n  d)";
+
+        assert_eq!(
+            Location::from_offset(input, 0),
+            Location { line: 1, column: 1 }
+        );
+        assert_eq!(
+            Location::from_offset(input, 5),
+            Location { line: 2, column: 3 }
+        );
+        assert_eq!(
+            Location::from_offset(input, input.len()),
+            Location { line: 3, column: 5 }
+        );
+    }
+
+    #[test]
+    fn test_location_counts_characters() {
+        let input = "(\"µm\" x)";
+
+        assert_eq!(
+            Location::from_offset(input, input.find('x').unwrap()),
+            Location { line: 1, column: 7 }
+        );
+    }
+}
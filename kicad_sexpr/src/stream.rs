@@ -0,0 +1,682 @@
+//! Streaming S-expression reading and writing.
+//!
+//! [`Reader`] pulls [`Event`]s out of any [`io::Read`] without building the
+//! tree, so a tool can scan a large board for the lists it cares about and
+//! skip everything else. [`Writer`] is its counterpart, emitting formatted
+//! output to an [`io::Write`] as events arrive.
+//!
+//! ```
+//! use kicad_sexpr::{stream::{Event, Reader}, Sexpr};
+//!
+//! let input = r#"(kicad_pcb (net 0 "") (net 1 "GND") (footprint "R_0603" (layer "F.Cu")))"#;
+//! let mut reader = Reader::new(input.as_bytes());
+//! let mut nets = Vec::new();
+//!
+//! while let Some(event) = reader.next_event().unwrap() {
+//!     if event != Event::ListStart || reader.depth() != 2 {
+//!         continue;
+//!     }
+//!
+//!     match reader.next_event().unwrap() {
+//!         Some(Event::Atom(Sexpr::Symbol(token))) if token == "net" => {
+//!             nets.push(reader.read_list_rest().unwrap());
+//!         }
+//!         _ => reader.skip_list_rest().unwrap(),
+//!     }
+//! }
+//!
+//! assert_eq!(nets.len(), 2);
+//! assert_eq!(nets[1][1], Sexpr::string("GND"));
+//! ```
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+use thiserror::Error;
+
+use crate::{
+    format::{format_compact, Layout},
+    number::parse_number,
+    sexpr::is_token_delimiter,
+    FormatStyle, Sexpr, SexprList, SexprParseError,
+};
+
+/// A single token of an S-expression.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Event {
+    /// An opening paren.
+    ListStart,
+    /// A number, string or symbol. Never a [`Sexpr::List`].
+    Atom(Sexpr),
+    /// A closing paren.
+    ListEnd,
+}
+
+/// Error returned by [`Reader`].
+#[derive(Debug, Error)]
+pub enum ReadError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Parse(#[from] SexprParseError),
+}
+
+/// Pulls [`Event`]s out of an [`io::Read`].
+///
+/// The reader accepts exactly the same input as [`from_str`](crate::from_str)
+/// and reports errors at the same offsets.
+pub struct Reader<R> {
+    input: BufReader<R>,
+    offset: usize,
+    line: usize,
+    column: usize,
+    depth: usize,
+    /// Set after a token that must be followed by whitespace or a closing
+    /// paren.
+    needs_separator: bool,
+    /// Set right after an opening paren, since empty lists are not allowed.
+    list_opened: bool,
+    /// Set while the current line holds nothing but whitespace, which is
+    /// where a `#` starts a comment.
+    line_blank: bool,
+    root_read: bool,
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(input: R) -> Self {
+        Self {
+            input: BufReader::new(input),
+            offset: 0,
+            line: 1,
+            column: 1,
+            depth: 0,
+            needs_separator: false,
+            list_opened: false,
+            line_blank: true,
+            root_read: false,
+        }
+    }
+
+    /// The number of lists that are currently open.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The byte offset of the next unread byte.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Reads the next event, or `None` once the input has been read
+    /// completely.
+    pub fn next_event(&mut self) -> Result<Option<Event>, ReadError> {
+        let separated = self.skip_trivia()?;
+
+        let Some(byte) = self.peek()? else {
+            if self.depth > 0 || !self.root_read {
+                return Err(self.error()?.into());
+            }
+
+            return Ok(None);
+        };
+
+        if self.root_read && self.depth == 0 {
+            return Err(self.error()?.into());
+        }
+
+        if byte == b')' {
+            if self.depth == 0 || self.list_opened {
+                return Err(self.error()?.into());
+            }
+
+            self.bump()?;
+            self.close_element();
+            self.depth -= 1;
+
+            if self.depth == 0 {
+                self.root_read = true;
+            }
+
+            return Ok(Some(Event::ListEnd));
+        }
+
+        if self.needs_separator && !separated {
+            return Err(self.error()?.into());
+        }
+
+        if byte == b'(' {
+            self.bump()?;
+            self.depth += 1;
+            self.needs_separator = false;
+            self.list_opened = true;
+
+            return Ok(Some(Event::ListStart));
+        }
+
+        let atom = if byte == b'"' {
+            self.read_string()?
+        } else {
+            self.read_bare_token()?
+        };
+
+        self.close_element();
+
+        if self.depth == 0 {
+            self.root_read = true;
+        }
+
+        Ok(Some(Event::Atom(atom)))
+    }
+
+    /// Reads the remaining children of the innermost open list, including its
+    /// closing paren. Call this after [`Event::ListStart`] (and possibly a few
+    /// more events) to materialize just that list.
+    pub fn read_list_rest(&mut self) -> Result<SexprList, ReadError> {
+        if self.depth == 0 {
+            return Err(not_in_list().into());
+        }
+
+        let mut stack = vec![SexprList::new()];
+
+        while let Some(event) = self.next_event()? {
+            match event {
+                Event::ListStart => stack.push(SexprList::new()),
+                Event::Atom(atom) => stack.last_mut().unwrap().push(atom),
+                Event::ListEnd => {
+                    let list = stack.pop().unwrap();
+
+                    match stack.last_mut() {
+                        Some(parent) => parent.push(Sexpr::List(list)),
+                        None => return Ok(list),
+                    }
+                }
+            }
+        }
+
+        // `next_event` only returns `None` once every list has been closed
+        unreachable!("the input ended inside a list")
+    }
+
+    /// Skips the remaining children of the innermost open list, including its
+    /// closing paren, without building them.
+    pub fn skip_list_rest(&mut self) -> Result<(), ReadError> {
+        if self.depth == 0 {
+            return Err(not_in_list().into());
+        }
+
+        let target = self.depth - 1;
+
+        while self.depth > target {
+            self.next_event()?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads the whole input into a tree.
+    ///
+    /// Fails if some of the input has already been read, either because the
+    /// root was read or because the reader is inside a list.
+    pub fn read_sexpr(&mut self) -> Result<Sexpr, ReadError> {
+        if self.depth > 0 {
+            return Err(inside_list().into());
+        }
+
+        let root = match self.next_event()? {
+            Some(Event::ListStart) => Sexpr::List(self.read_list_rest()?),
+            Some(Event::Atom(atom)) => atom,
+            // Outside of a list, this only happens once the root was read
+            Some(Event::ListEnd) | None => return Err(no_root_left().into()),
+        };
+
+        // Reject trailing input
+        self.next_event()?;
+
+        Ok(root)
+    }
+
+    fn close_element(&mut self) {
+        self.needs_separator = true;
+        self.list_opened = false;
+    }
+
+    fn peek(&mut self) -> io::Result<Option<u8>> {
+        Ok(self.input.fill_buf()?.first().copied())
+    }
+
+    fn bump(&mut self) -> io::Result<Option<u8>> {
+        let byte = self.peek()?;
+
+        if let Some(byte) = byte {
+            self.input.consume(1);
+            self.offset += 1;
+
+            if byte == b'\n' {
+                self.line += 1;
+                self.column = 1;
+                self.line_blank = true;
+            } else if byte & 0xC0 != 0x80 {
+                // Columns are counted in characters, so UTF-8 continuation
+                // bytes do not advance them
+                self.column += 1;
+            }
+
+            if !is_whitespace(byte) {
+                self.line_blank = false;
+            }
+        }
+
+        Ok(byte)
+    }
+
+    fn skip_whitespace(&mut self) -> io::Result<bool> {
+        let mut skipped = false;
+
+        while let Some(byte) = self.peek()? {
+            if !is_whitespace(byte) {
+                break;
+            }
+
+            self.bump()?;
+            skipped = true;
+        }
+
+        Ok(skipped)
+    }
+
+    /// Skips whitespace and `#` comment lines, following the same rules as
+    /// the tree parser.
+    fn skip_trivia(&mut self) -> io::Result<bool> {
+        let mut skipped = self.skip_whitespace()?;
+
+        while self.line_blank && self.peek()? == Some(b'#') {
+            while !matches!(self.peek()?, None | Some(b'\n')) {
+                self.bump()?;
+            }
+
+            self.skip_whitespace()?;
+            skipped = true;
+        }
+
+        Ok(skipped)
+    }
+
+    /// The buffered input up to the end of the current line.
+    fn rest_of_line(&mut self) -> io::Result<Vec<u8>> {
+        let buffer = self.input.fill_buf()?;
+
+        Ok(buffer
+            .split(|byte| *byte == b'\n')
+            .next()
+            .unwrap_or_default()
+            .to_vec())
+    }
+
+    /// Builds an error pointing at the next unread byte.
+    fn error(&mut self) -> io::Result<SexprParseError> {
+        let snippet = self.rest_of_line()?;
+
+        Ok(SexprParseError {
+            offset: self.offset,
+            line: self.line,
+            column: self.column,
+            snippet: String::from_utf8_lossy(&snippet)
+                .chars()
+                .take(SexprParseError::SNIPPET_LENGTH)
+                .collect(),
+        })
+    }
+
+    fn read_string(&mut self) -> Result<Sexpr, ReadError> {
+        // Invalid strings are reported at their opening quote, like the tree
+        // parser does
+        let error = self.error()?;
+
+        self.bump()?;
+
+        let mut bytes = Vec::new();
+
+        loop {
+            match self.bump()? {
+                None => return Err(self.error()?.into()),
+                Some(b'"') => break,
+                Some(b'\\') => match self.bump()? {
+                    Some(b'n') => bytes.push(b'\n'),
+                    Some(b'r') => bytes.push(b'\r'),
+                    Some(b't') => bytes.push(b'\t'),
+                    Some(b'b') => bytes.push(0x08),
+                    Some(b'f') => bytes.push(0x0C),
+                    Some(b'\\') => bytes.push(b'\\'),
+                    Some(b'/') => bytes.push(b'/'),
+                    Some(b'"') => bytes.push(b'"'),
+                    // An escaped line break swallows the whitespace after it
+                    Some(byte) if is_whitespace(byte) => {
+                        self.skip_whitespace()?;
+                    }
+                    None => return Err(self.error()?.into()),
+                    Some(_) => return Err(error.into()),
+                },
+                Some(byte) => bytes.push(byte),
+            }
+        }
+
+        match String::from_utf8(bytes) {
+            Ok(string) => Ok(Sexpr::String(string)),
+            Err(_) => Err(error.into()),
+        }
+    }
+
+    /// Reads a number or symbol, which both run until the next delimiter.
+    fn read_bare_token(&mut self) -> Result<Sexpr, ReadError> {
+        let error = self.error()?;
+        let mut token = Vec::new();
+
+        while let Some(byte) = self.peek()? {
+            if byte.is_ascii() && is_token_delimiter(byte as char) {
+                break;
+            }
+
+            token.push(byte);
+            self.bump()?;
+        }
+
+        // Only reachable with invalid UTF-8, since `from_str` takes a `&str`
+        let Ok(text) = String::from_utf8(token) else {
+            return Err(error.into());
+        };
+
+        // Like the tree parser, a number has to be followed by whitespace, a
+        // closing paren or the end of the input to not be read as a symbol
+        let terminated = !matches!(self.peek()?, Some(b'(' | b'"'));
+
+        if let (true, Ok(("", number))) = (terminated, parse_number(&text)) {
+            return Ok(Sexpr::Number(number));
+        }
+
+        Ok(Sexpr::Symbol(text))
+    }
+}
+
+impl<R: Read> Iterator for Reader<R> {
+    type Item = Result<Event, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_event().transpose()
+    }
+}
+
+fn not_in_list() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "no list is open")
+}
+
+fn inside_list() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "the reader is inside a list")
+}
+
+fn no_root_left() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "no root element left")
+}
+
+fn is_whitespace(byte: u8) -> bool {
+    matches!(byte, b' ' | b'\t' | b'\n' | b'\r')
+}
+
+/// Reads a complete S-expression from an [`io::Read`].
+pub fn from_reader(input: impl Read) -> Result<Sexpr, ReadError> {
+    Reader::new(input).read_sexpr()
+}
+
+/// Writes formatted S-expressions to an [`io::Write`] as they are produced,
+/// using the same layouts as [`format`](crate::format()).
+pub struct Writer<W: Write> {
+    output: W,
+    layout: Layout,
+    depth: usize,
+    /// Set after an opening paren whose head has not been written yet.
+    pending_list: bool,
+}
+
+impl<W: Write> Writer<W> {
+    /// Creates a writer that uses the default [`FormatStyle`].
+    pub fn new(output: W) -> Self {
+        Self::with_style(output, FormatStyle::default())
+    }
+
+    pub fn with_style(output: W, style: FormatStyle) -> Self {
+        Self {
+            output,
+            layout: Layout::new(style),
+            depth: 0,
+            pending_list: false,
+        }
+    }
+
+    pub fn start_list(&mut self) -> io::Result<()> {
+        self.open_pending_list(None)?;
+
+        self.depth += 1;
+        self.pending_list = true;
+
+        Ok(())
+    }
+
+    pub fn end_list(&mut self) -> io::Result<()> {
+        if self.depth == 0 {
+            return Err(not_in_list());
+        }
+
+        self.open_pending_list(None)?;
+
+        self.depth -= 1;
+        self.layout.close_list(&mut self.output)
+    }
+
+    /// Writes a number, string or symbol. Lists are written as a whole, like
+    /// [`Writer::write_sexpr`].
+    pub fn write_atom(&mut self, atom: &Sexpr) -> io::Result<()> {
+        if let Sexpr::List(_) = atom {
+            return self.write_sexpr(atom);
+        }
+
+        self.open_pending_list(atom.as_symbol().map(String::as_str))?;
+        self.layout.atom(&mut self.output, &format_compact(atom))
+    }
+
+    pub fn write_event(&mut self, event: &Event) -> io::Result<()> {
+        match event {
+            Event::ListStart => self.start_list(),
+            Event::Atom(atom) => self.write_atom(atom),
+            Event::ListEnd => self.end_list(),
+        }
+    }
+
+    pub fn write_sexpr(&mut self, sexpr: &Sexpr) -> io::Result<()> {
+        match sexpr {
+            Sexpr::List(list) => {
+                self.start_list()?;
+
+                for child in list {
+                    self.write_sexpr(child)?;
+                }
+
+                self.end_list()
+            }
+            atom => self.write_atom(atom),
+        }
+    }
+
+    /// Flushes the output and returns it. Fails if a list is still open.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.depth != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "finish called with unclosed lists",
+            ));
+        }
+
+        self.output.flush()?;
+
+        Ok(self.output)
+    }
+
+    fn open_pending_list(&mut self, head: Option<&str>) -> io::Result<()> {
+        if self.pending_list {
+            self.pending_list = false;
+            self.layout.open_list(&mut self.output, head)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{from_str, Number};
+
+    const INPUT: &str = r#"(kicad_pcb (version 20221018)
+  (net 1 "GND")
+  (gr_text "a \"quoted\"\nline" (at -1.5 2))
+)"#;
+
+    fn read_events(input: &str) -> Result<Vec<Event>, ReadError> {
+        Reader::new(input.as_bytes()).collect()
+    }
+
+    #[test]
+    fn test_read_events() {
+        let events = read_events("(net 1 \"GND\")").unwrap();
+
+        assert_eq!(
+            events,
+            vec![
+                Event::ListStart,
+                Event::Atom(Sexpr::symbol("net")),
+                Event::Atom(Sexpr::Number(Number::Integer(1))),
+                Event::Atom(Sexpr::string("GND")),
+                Event::ListEnd,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_matches_from_str() {
+        assert_eq!(
+            from_reader(INPUT.as_bytes()).unwrap(),
+            from_str(INPUT).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_read_tokens_match_from_str() {
+        let input = "# header\n(tokens\n  # comment\n  F.Cu *.Cu +3V3 1.5mm #FF00FF ~ ${REFERENCE} µm 1. .5 - -1 0.5 \
+                     12345678 2349f563-989d-4999-a369-9f24d984ce74)";
+
+        assert_eq!(
+            from_reader(input.as_bytes()).unwrap(),
+            from_str(input).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_read_errors_match_from_str() {
+        for input in [
+            "",
+            "(a (b c)",
+            "(a (b c)) d",
+            "()",
+            "(a(b))",
+            "(a 1.5(b))",
+            "(a b\"c\")",
+            "(a µ\"c\")",
+            "(a \"b)",
+            "(a \"\\q\")",
+            "(a\n  \"b\"\"c\")",
+            "(a) # trailing",
+            "(a\n# unclosed)",
+        ] {
+            let expected = from_str(input).unwrap_err();
+
+            match from_reader(input.as_bytes()) {
+                Err(ReadError::Parse(error)) => assert_eq!(error, expected, "{input:?}"),
+                result => panic!("expected a parse error for {input:?}, got {result:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_skip_list_rest() {
+        let mut reader = Reader::new(INPUT.as_bytes());
+
+        assert_eq!(reader.next_event().unwrap(), Some(Event::ListStart));
+        assert_eq!(
+            reader.next_event().unwrap(),
+            Some(Event::Atom(Sexpr::symbol("kicad_pcb")))
+        );
+        assert_eq!(reader.next_event().unwrap(), Some(Event::ListStart));
+
+        reader.skip_list_rest().unwrap();
+
+        assert_eq!(reader.depth(), 1);
+        assert_eq!(reader.next_event().unwrap(), Some(Event::ListStart));
+        assert_eq!(
+            reader.read_list_rest().unwrap(),
+            vec![
+                Sexpr::symbol("net"),
+                Sexpr::Number(Number::Integer(1)),
+                Sexpr::string("GND"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_sexpr_after_reading() {
+        let message = |result: Result<Sexpr, ReadError>| match result {
+            Err(ReadError::Io(error)) => error.to_string(),
+            result => panic!("expected an error, got {result:?}"),
+        };
+
+        let mut reader = Reader::new(INPUT.as_bytes());
+
+        reader.read_sexpr().unwrap();
+        assert_eq!(message(reader.read_sexpr()), "no root element left");
+
+        let mut reader = Reader::new(INPUT.as_bytes());
+
+        assert_eq!(reader.next_event().unwrap(), Some(Event::ListStart));
+        assert_eq!(message(reader.read_sexpr()), "the reader is inside a list");
+    }
+
+    #[test]
+    fn test_writer_matches_format() {
+        let sexpr = from_str(INPUT).unwrap();
+
+        for style in [
+            FormatStyle::Tree,
+            FormatStyle::KiCad7,
+            FormatStyle::KiCad8,
+            FormatStyle::KiCad8Compact,
+        ] {
+            let mut writer = Writer::with_style(Vec::new(), style);
+
+            for event in Reader::new(INPUT.as_bytes()) {
+                writer.write_event(&event.unwrap()).unwrap();
+            }
+
+            let output = String::from_utf8(writer.finish().unwrap()).unwrap();
+
+            assert_eq!(output, crate::format(&sexpr, style));
+        }
+    }
+
+    #[test]
+    fn test_writer_rejects_unbalanced_lists() {
+        let mut writer = Writer::new(Vec::new());
+
+        assert!(writer.end_list().is_err());
+
+        writer.start_list().unwrap();
+        writer.write_atom(&Sexpr::symbol("a")).unwrap();
+
+        assert!(writer.finish().is_err());
+    }
+}
@@ -6,13 +6,15 @@
 //! - an escape followed by whitespace consumes all whitespace between the
 //!   escape and the next non-whitespace character
 
+use std::borrow::Cow;
+
 use nom::branch::alt;
 use nom::bytes::streaming::is_not;
 use nom::character::streaming::{char, multispace1};
-use nom::combinator::{map, value, verify};
+use nom::combinator::{map, opt, value, verify};
 use nom::error::{FromExternalError, ParseError};
 use nom::multi::fold_many0;
-use nom::sequence::{delimited, preceded};
+use nom::sequence::{preceded, terminated};
 use nom::{IResult, Parser};
 
 /// Parse an escaped character: \n, \t, \r, \u{00AC}, etc.
@@ -83,12 +85,17 @@ where
     .parse(input)
 }
 
-/// Parse a string. Use a loop of parse_fragment and push all of the fragments
-/// into an output string.
-pub fn parse_string<'a, E>(input: &'a str) -> IResult<&'a str, String, E>
+/// Parse a string. Strings without escape sequences are borrowed from the
+/// input; otherwise a loop of parse_fragment pushes all of the fragments into
+/// an output string.
+pub fn parse_string<'a, E>(input: &'a str) -> IResult<&'a str, Cow<'a, str>, E>
 where
     E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
 {
+    // The common case: a literal directly followed by the closing quote
+    let borrowed =
+        terminated(opt(parse_literal), char('"')).map(|s| Cow::Borrowed(s.unwrap_or("")));
+
     // fold is the equivalent of iterator::fold. It runs a parser in a loop,
     // and for each output value, calls a folding function on each output value.
     let build_string = fold_many0(
@@ -112,7 +119,9 @@ where
     // " character, the closing delimiter " would never match. When using
     // `delimited` with a looping parser (like fold), be sure that the
     // loop won't accidentally match your closing delimiter!
-    delimited(char('"'), build_string, char('"')).parse(input)
+    let owned = terminated(build_string, char('"')).map(Cow::Owned);
+
+    preceded(char('"'), alt((borrowed, owned))).parse(input)
 }
 
 #[cfg(test)]
@@ -123,39 +132,54 @@ mod tests {
     fn test_parse_string() {
         assert_eq!(
             parse_string::<()>(r#""Hello, world!""#),
-            Ok(("", "Hello, world!".to_string()))
+            Ok(("", "Hello, world!".into()))
         );
         assert_eq!(
             parse_string::<()>(r#""Hello, \"world\"!""#),
-            Ok(("", "Hello, \"world\"!".to_string()))
+            Ok(("", "Hello, \"world\"!".into()))
         );
         assert_eq!(
             parse_string::<()>(r#""Hello, \nworld!""#),
-            Ok(("", "Hello, \nworld!".to_string()))
+            Ok(("", "Hello, \nworld!".into()))
         );
         assert_eq!(
             parse_string::<()>(r#""Hello, \tworld!""#),
-            Ok(("", "Hello, \tworld!".to_string()))
+            Ok(("", "Hello, \tworld!".into()))
         );
         assert_eq!(
             parse_string::<()>(r#""Hello, \rworld!""#),
-            Ok(("", "Hello, \rworld!".to_string()))
+            Ok(("", "Hello, \rworld!".into()))
         );
         assert_eq!(
             parse_string::<()>(r#""Hello, \fworld!""#),
-            Ok(("", "Hello, \u{0C}world!".to_string()))
+            Ok(("", "Hello, \u{0C}world!".into()))
         );
         assert_eq!(
             parse_string::<()>(r#""Hello, \bworld!""#),
-            Ok(("", "Hello, \u{08}world!".to_string()))
+            Ok(("", "Hello, \u{08}world!".into()))
         );
         assert_eq!(
             parse_string::<()>(r#""Hello, \\world!""#),
-            Ok(("", "Hello, \\world!".to_string()))
+            Ok(("", "Hello, \\world!".into()))
         );
         assert_eq!(
             parse_string::<()>(r#""Hello, \/world!""#),
-            Ok(("", "Hello, /world!".to_string()))
+            Ok(("", "Hello, /world!".into()))
         );
     }
+    #[test]
+    fn test_parse_string_borrows_without_escapes() {
+        assert!(matches!(
+            parse_string::<()>(r#""Hello, world!""#),
+            Ok(("", Cow::Borrowed("Hello, world!")))
+        ));
+        assert!(matches!(
+            parse_string::<()>(r#""""#),
+            Ok(("", Cow::Borrowed("")))
+        ));
+        assert!(matches!(
+            parse_string::<()>(r#""Hello, \"world\"!""#),
+            Ok(("", Cow::Owned(_)))
+        ));
+    }
 }